@@ -0,0 +1,31 @@
+// Host benchmarks for the hot paths of the command processor
+//
+// Run with `cargo bench --features bench`. Criterion reports the time per
+// iteration of each benchmark and how it changed since the previous run, to
+// compare with the host guidelines documented in the README. On target
+// hardware, see `run_benchmarks` and `DwtCycleCounter`.
+use command_processor::{benchmark_processor, BENCHMARKS};
+use core::fmt::Write;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A writer dropping what is written
+struct Sink;
+
+impl Write for Sink {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        black_box(s);
+        Ok(())
+    }
+}
+
+fn hot_paths(c: &mut Criterion) {
+    let mut command_processor = benchmark_processor();
+    for benchmark in &BENCHMARKS {
+        c.bench_function(benchmark.name, |b| {
+            b.iter(|| command_processor.process_command(black_box(benchmark.line), Some(&mut Sink)))
+        });
+    }
+}
+
+criterion_group!(benches, hot_paths);
+criterion_main!(benches);
@@ -0,0 +1,353 @@
+// This module contains the threshold alarms on metrics
+use crate::tokenizer::Tokens;
+use crate::{parse_int, CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::{self, Write};
+use heapless::String;
+
+/// The maximum number of alarms
+pub const MAX_ALARMS: usize = 8;
+
+/// The maximum size of the name of a metric with an alarm
+const ALARM_METRIC_SIZE: usize = 16;
+
+/// The storage key of the alarms, which script names can't start with
+const ALARM_KEY: &str = ".alarms";
+
+/// The size of the alarms once saved, a metric, a direction and a limit per line
+const SAVED_SIZE: usize = MAX_ALARMS * (ALARM_METRIC_SIZE + 28);
+
+/// Alarm callback type, called when a metric crosses a threshold or returns within it
+pub type AlarmCallback = fn(&AlarmEvent);
+
+/// The limit a metric is expected to stay within
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    /// The alarm is raised when the metric goes above the limit
+    Above(i64),
+    /// The alarm is raised when the metric goes below the limit
+    Below(i64),
+}
+
+impl Threshold {
+    /// Returns whether a value is beyond the threshold
+    fn crossed(self, value: i64) -> bool {
+        match self {
+            Threshold::Above(limit) => value > limit,
+            Threshold::Below(limit) => value < limit,
+        }
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::Above(limit) => write!(f, "above {}", limit),
+            Threshold::Below(limit) => write!(f, "below {}", limit),
+        }
+    }
+}
+
+/// A metric crossing a threshold, or returning within it
+///
+/// Displays as an event line, such as `ALARM rx_errors above 10: 11` or
+/// `CLEAR rx_errors above 10: 0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmEvent<'e> {
+    /// The name of the metric
+    pub metric: &'e str,
+    /// The value of the metric, counters beyond `i64::MAX` being saturated
+    pub value: i64,
+    /// The threshold crossed
+    pub threshold: Threshold,
+    /// Whether the alarm was raised, or cleared
+    pub raised: bool,
+}
+
+impl fmt::Display for AlarmEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self.raised {
+            true => "ALARM",
+            false => "CLEAR",
+        };
+        write!(
+            f,
+            "{} {} {}: {}",
+            state, self.metric, self.threshold, self.value
+        )
+    }
+}
+
+/// A threshold set on a metric
+pub(crate) struct Alarm {
+    metric: String<ALARM_METRIC_SIZE>,
+    threshold: Threshold,
+    /// Whether the metric was last beyond the threshold
+    raised: bool,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets the callback called when a metric crosses a threshold
+    ///
+    /// Thresholds are set from the console with `metrics alarm <name> above
+    /// <limit>` or `metrics alarm <name> below <limit>`, removed with
+    /// `metrics alarm <name> off` and listed with `metrics alarm`. They're
+    /// saved to the storage if one is set, see `load_alarms`.
+    ///
+    /// The callback is called with the event when a counter or gauge update
+    /// takes the metric beyond a threshold, and again when an update brings
+    /// it back, so the application can notify the console and record it.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The alarm callback
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicBool, Ordering};
+    /// use command_processor::{AlarmEvent, CommandProcessor};
+    ///
+    /// static OVERHEATED: AtomicBool = AtomicBool::new(false);
+    ///
+    /// fn alarm(event: &AlarmEvent) {
+    ///     // Queue `event` for the console and the event log here
+    ///     OVERHEATED.store(event.raised, Ordering::Relaxed);
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_alarm_callback(alarm);
+    /// command_processor.process_command("metrics alarm temp above 80", None).unwrap();
+    ///
+    /// command_processor.set_gauge("temp", 85).unwrap();
+    /// assert!(OVERHEATED.load(Ordering::Relaxed));
+    /// command_processor.set_gauge("temp", 70).unwrap();
+    /// assert!(!OVERHEATED.load(Ordering::Relaxed));
+    /// ```
+    ///
+    pub fn set_alarm_callback(&mut self, callback: AlarmCallback) {
+        self.alarm_callback = Some(callback);
+    }
+
+    /// Loads the alarms saved by `metrics alarm`, usually at startup
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the alarms were loaded, or none were saved
+    /// * `Err(CommandProcessorError::StorageUnavailable)` - If no storage was set
+    /// * `Err(CommandProcessorError::StorageError)` - If the storage failed or holds invalid alarms
+    ///
+    pub fn load_alarms(&mut self) -> Result<(), CommandProcessorError> {
+        let mut buffer = [0u8; SAVED_SIZE];
+        let loaded = match self.storage.as_deref() {
+            Some(storage) => storage.load(ALARM_KEY, &mut buffer),
+            None => return Err(CommandProcessorError::StorageUnavailable),
+        };
+        let len = loaded.map_err(|_| CommandProcessorError::StorageError)?;
+        let saved = buffer
+            .get(..len.unwrap_or(0))
+            .and_then(|saved| core::str::from_utf8(saved).ok())
+            .ok_or(CommandProcessorError::StorageError)?;
+
+        self.alarms.clear();
+        for line in saved.lines() {
+            let mut fields = line.split(' ');
+            let alarm = match (fields.next(), fields.next(), fields.next()) {
+                (Some(metric), Some(direction), Some(limit)) => alarm(metric, direction, limit),
+                _ => None,
+            };
+            self.alarms
+                .push(alarm.ok_or(CommandProcessorError::StorageError)?)
+                .map_err(|_| CommandProcessorError::StorageError)?;
+        }
+        Ok(())
+    }
+
+    /// Raises or clears the alarms of a metric that was updated
+    pub(crate) fn check_alarms(&mut self, metric: &str, value: i64) {
+        for alarm in self
+            .alarms
+            .iter_mut()
+            .filter(|alarm| alarm.metric == metric)
+        {
+            let crossed = alarm.threshold.crossed(value);
+            if crossed == alarm.raised {
+                continue;
+            }
+            alarm.raised = crossed;
+            if let Some(callback) = self.alarm_callback {
+                callback(&AlarmEvent {
+                    metric,
+                    value,
+                    threshold: alarm.threshold,
+                    raised: crossed,
+                });
+            }
+        }
+    }
+
+    /// Runs `metrics alarm`, see `set_alarm_callback`
+    pub(crate) fn alarm(
+        &mut self,
+        mut tokens: Tokens,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let metric = tokens.next();
+        let direction = tokens.next();
+        let limit = tokens.next();
+        if tokens.next().is_some() {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        tokens.check()?;
+
+        match (metric, direction, limit) {
+            (None, _, _) => {
+                let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+                for alarm in &self.alarms {
+                    let state = match alarm.raised {
+                        true => " raised",
+                        false => "",
+                    };
+                    self.output.write(
+                        writer,
+                        format_args!("{} {}{}\n", alarm.metric, alarm.threshold, state),
+                    )?;
+                }
+                return Ok(ReturnCode::Success);
+            }
+            (Some(metric), Some("off"), None) => {
+                self.alarms.retain(|alarm| alarm.metric != metric);
+            }
+            (Some(_), None, _) | (Some(_), Some("above" | "below"), None) => {
+                return Err(CommandProcessorError::MissingArgument)
+            }
+            (Some(metric), Some(direction), Some(limit)) => {
+                let alarm = alarm(metric, direction, limit)
+                    .ok_or(CommandProcessorError::InvalidArguments)?;
+                let same = |existing: &&mut Alarm| {
+                    existing.metric == alarm.metric
+                        && core::mem::discriminant(&existing.threshold)
+                            == core::mem::discriminant(&alarm.threshold)
+                };
+                match self.alarms.iter_mut().find(same) {
+                    Some(existing) => *existing = alarm,
+                    None => self
+                        .alarms
+                        .push(alarm)
+                        .map_err(|_| CommandProcessorError::TooManyAlarms)?,
+                }
+            }
+            _ => return Err(CommandProcessorError::InvalidArguments),
+        }
+        self.save_alarms()?;
+        Ok(ReturnCode::Success)
+    }
+
+    /// Saves the alarms to the storage, if one is set
+    fn save_alarms(&mut self) -> Result<(), CommandProcessorError> {
+        let mut saved: String<SAVED_SIZE> = String::new();
+        for alarm in &self.alarms {
+            writeln!(saved, "{} {}", alarm.metric, alarm.threshold)
+                .map_err(|_| CommandProcessorError::StorageError)?;
+        }
+
+        let Some(storage) = self.storage.as_deref_mut() else {
+            return Ok(());
+        };
+        let result = match saved.is_empty() {
+            true => storage.remove(ALARM_KEY).map(|_| ()),
+            false => storage.store(ALARM_KEY, saved.as_bytes()),
+        };
+        result.map_err(|_| CommandProcessorError::StorageError)
+    }
+}
+
+/// Parses an alarm, as typed after `metrics alarm` or saved
+fn alarm(metric: &str, direction: &str, limit: &str) -> Option<Alarm> {
+    let limit = parse_int(limit)?;
+    let threshold = match direction {
+        "above" => Threshold::Above(limit),
+        "below" => Threshold::Below(limit),
+        _ => return None,
+    };
+    let mut copy = String::new();
+    copy.push_str(metric).ok()?;
+    Some(Alarm {
+        metric: copy,
+        threshold,
+        raised: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stored::tests::Memory;
+
+    use core::sync::atomic::{AtomicI64, Ordering};
+
+    static RAISED: AtomicI64 = AtomicI64::new(0);
+
+    fn alarm(event: &AlarmEvent) {
+        let step = match event.raised {
+            true => 1,
+            false => -1,
+        };
+        RAISED.fetch_add(step, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_alarms() {
+        let mut storage = Memory::default();
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_storage(&mut storage);
+        command_processor.set_alarm_callback(alarm);
+
+        for line in [
+            "metrics alarm errors above 2",
+            "metrics alarm depth below 0",
+        ] {
+            assert!(command_processor.process_command(line, None).is_ok());
+        }
+        assert!(matches!(
+            command_processor.process_command("metrics alarm depth sideways 1", None),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+
+        // Raised once when crossing, cleared by the reset
+        for _ in 0..4 {
+            assert!(command_processor.increment_counter("errors", 1).is_ok());
+        }
+        assert!(command_processor.set_gauge("depth", 3).is_ok());
+        assert_eq!(RAISED.load(Ordering::Relaxed), 1);
+
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .process_command("metrics alarm", Some(&mut writer))
+            .is_ok());
+        assert_eq!(writer, "errors above 2 raised\ndepth below 0\n");
+
+        command_processor.reset_metrics();
+        assert_eq!(RAISED.load(Ordering::Relaxed), 0);
+
+        // The alarms survive a reload
+        assert!(command_processor
+            .process_command("metrics alarm depth off", None)
+            .is_ok());
+        assert!(command_processor.load_alarms().is_ok());
+        writer.clear();
+        assert!(command_processor
+            .process_command("metrics alarm", Some(&mut writer))
+            .is_ok());
+        assert_eq!(writer, "errors above 2\n");
+    }
+}
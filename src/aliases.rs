@@ -0,0 +1,231 @@
+// This module contains the alternative names of commands
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+#[cfg(feature = "help")]
+use core::fmt;
+
+/// The maximum number of aliases
+pub const MAX_ALIASES: usize = 8;
+
+/// A command name followed by its aliases, such as `reset (rst, r)`
+#[cfg(feature = "help")]
+pub(crate) struct Aliased<'n> {
+    pub(crate) name: &'n str,
+    pub(crate) aliases: &'n [(&'n str, &'n str)],
+}
+
+#[cfg(feature = "help")]
+impl Aliased<'_> {
+    /// Returns the number of characters written
+    pub(crate) fn width(&self) -> usize {
+        let mut width = Width(0);
+        let _ = fmt::write(&mut width, format_args!("{}", self));
+        width.0
+    }
+}
+
+#[cfg(feature = "help")]
+impl fmt::Display for Aliased<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        let mut separator = " (";
+        for (alias, _) in self
+            .aliases
+            .iter()
+            .filter(|(_, command)| *command == self.name)
+        {
+            write!(f, "{}{}", separator, alias)?;
+            separator = ", ";
+        }
+        match separator {
+            ", " => f.write_str(")"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Counts the characters written to it
+#[cfg(feature = "help")]
+struct Width(usize);
+
+#[cfg(feature = "help")]
+impl fmt::Write for Width {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 = self.0.saturating_add(s.chars().count());
+        Ok(())
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Adds an alternative name for a command, such as `rst` for `reset`
+    ///
+    /// The alias runs the command with the arguments given to it, without
+    /// taking a command slot or a help string. `help -s` and `help -v` list
+    /// the aliases next to the command's name, and `help <alias>` writes the
+    /// command's help. An alias of a command that is later removed isn't
+    /// found anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `alias` - The alternative name
+    /// * `command` - The name of the registered command it runs
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the alias was added successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the alias is already a command or an alias
+    /// * `Err(CommandProcessorError::TooManyAliases)` - If `MAX_ALIASES` aliases already exist
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// fn reset<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     if let Some(writer) = writer {
+    ///         let _ = writeln!(writer, "resetting {}", args.remaining().join(" "));
+    ///     }
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("reset", reset, None).unwrap();
+    /// command_processor.add_alias("rst", "reset").unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.process_command("rst radio", Some(&mut writer)).unwrap();
+    /// assert_eq!(writer, "resetting radio\n");
+    ///
+    /// // `help -s` lists the aliases next to their command
+    /// #[cfg(feature = "help")]
+    /// {
+    ///     writer.clear();
+    ///     command_processor.process_command("help -s", Some(&mut writer)).unwrap();
+    ///     assert_eq!(writer, "reset (rst)\n");
+    /// }
+    /// ```
+    ///
+    pub fn add_alias(
+        &mut self,
+        alias: &'a str,
+        command: &'a str,
+    ) -> Result<(), CommandProcessorError> {
+        if !self.commands.iter().any(|cmd| cmd.command == command) {
+            return Err(CommandProcessorError::CommandNotFound);
+        }
+        if self.commands.iter().any(|cmd| cmd.command == alias)
+            || self.aliases.iter().any(|(existing, _)| *existing == alias)
+        {
+            return Err(CommandProcessorError::CommandAlreadyExists);
+        }
+
+        self.aliases
+            .push((alias, command))
+            .map_err(|_| CommandProcessorError::TooManyAliases)
+    }
+
+    /// Removes an alias, see `add_alias`
+    ///
+    /// # Arguments
+    ///
+    /// * `alias` - The alias to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the alias was removed successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the alias was not found
+    ///
+    pub fn remove_alias(&mut self, alias: &str) -> Result<(), CommandProcessorError> {
+        let index = self
+            .aliases
+            .iter()
+            .position(|(existing, _)| *existing == alias)
+            .ok_or(CommandProcessorError::CommandNotFound)?;
+        self.aliases.remove(index);
+        Ok(())
+    }
+
+    /// Returns the name of the command a name runs, the name itself if it isn't an alias
+    ///
+    /// A command registered after an alias of the same name takes precedence.
+    pub(crate) fn resolve_alias<'n>(&self, name: &'n str) -> &'n str
+    where
+        'a: 'n,
+    {
+        if self.commands.iter().any(|cmd| cmd.command == name) {
+            return name;
+        }
+        self.aliases
+            .iter()
+            .find(|(alias, _)| *alias == name)
+            .map_or(name, |(_, command)| command)
+    }
+
+    /// Returns a command's name followed by its aliases, for help listings
+    #[cfg(feature = "help")]
+    pub(crate) fn aliased<'n>(&'n self, name: &'n str) -> Aliased<'n> {
+        Aliased {
+            name,
+            aliases: &self.aliases,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, ReturnCode};
+
+    use core::fmt::Write;
+
+    fn echo<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "{:?}", args.remaining())
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("reset", echo, None).is_ok());
+        assert!(command_processor
+            .add_command("net ping", echo, None)
+            .is_ok());
+        assert!(command_processor.add_alias("rst", "reset").is_ok());
+        assert!(command_processor.add_alias("p", "net ping").is_ok());
+        assert!(matches!(
+            command_processor.add_alias("rst", "reset"),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+        assert!(matches!(
+            command_processor.add_alias("r", "reboot"),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        let mut writer = std::string::String::new();
+        for line in ["rst now", "p host"] {
+            assert!(command_processor
+                .process_command(line, Some(&mut writer))
+                .is_ok());
+        }
+        assert_eq!(writer, "[\"now\"]\n[\"host\"]\n");
+
+        assert!(command_processor.remove_alias("rst").is_ok());
+        assert!(matches!(
+            command_processor.process_command("rst", None),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+    }
+}
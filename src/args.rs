@@ -0,0 +1,631 @@
+// This module contains the arguments handed to command callbacks
+use crate::{CommandProcessorError, SessionInfo};
+
+use core::str::FromStr;
+use core::time::Duration;
+
+/// The problem with an argument, and its position among the arguments
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgError {
+    /// The argument at this position is missing
+    Missing(usize),
+    /// The argument at this position failed to parse
+    Invalid(usize),
+    /// The argument at this position is one too many
+    Unexpected(usize),
+}
+
+impl From<ArgError> for CommandProcessorError {
+    fn from(error: ArgError) -> Self {
+        match error {
+            ArgError::Missing(_) => CommandProcessorError::MissingArgument,
+            ArgError::Invalid(_) => CommandProcessorError::InvalidArguments,
+            ArgError::Unexpected(_) => CommandProcessorError::UnexpectedArgument,
+        }
+    }
+}
+
+/// The arguments of a command, as received by its callback
+///
+/// A cursor over the arguments following the command name, taking them in
+/// order. Errors convert into `CommandProcessorError`, so callbacks can use
+/// `?` on them.
+///
+/// Options can be queried with `flag` and `opt_value`, in the GNU style:
+/// `--verbose`, `--count 5` or `--count=5` for long names, and `-v`, `-vq`,
+/// `-n 5` or `-vn5` for single character names. Options are recognized
+/// anywhere before a `--` argument, and the arguments they use are skipped
+/// when taking the others, so options should be queried first.
+///
+/// Keyword arguments such as `baud=115200` are queried the same way with
+/// `keyword` and `keyword_parse`, and can be mixed with the others.
+///
+/// # Example
+///
+/// ```
+/// use core::fmt::Write;
+/// use command_processor::{Args, CommandCallbackReturn, CommandProcessorError, ReturnCode};
+///
+/// fn set_baud<'a>(mut args: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     let baud: u32 = args.next_parse()?;
+///     args.finish()?;
+///
+///     assert_eq!(baud, 115200);
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// assert!(set_baud(Args::new(&["115200"]), None).is_ok());
+/// assert!(matches!(
+///     set_baud(Args::new(&["fast"]), None),
+///     Err(CommandProcessorError::InvalidArguments)
+/// ));
+/// ```
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Args<'s> {
+    args: &'s [&'s str],
+    position: usize,
+    consumed: u64,
+    session: Option<&'s SessionInfo>,
+    dry_run: bool,
+    /// The postcard encoded arguments of a binary request, for binary handlers
+    #[cfg(feature = "binary")]
+    payload: &'s [u8],
+}
+
+impl<'s> Args<'s> {
+    /// Creates a cursor at the first of the given arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments
+    ///
+    pub fn new(args: &'s [&'s str]) -> Self {
+        Self {
+            args,
+            position: 0,
+            consumed: 0,
+            session: None,
+            dry_run: false,
+            #[cfg(feature = "binary")]
+            payload: &[],
+        }
+    }
+
+    /// Attaches the session the command runs in
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session, if any
+    ///
+    pub fn with_session(mut self, session: Option<&'s SessionInfo>) -> Self {
+        self.session = session;
+        self
+    }
+
+    /// Returns the session the command runs in, see `set_session_info`
+    pub fn session(&self) -> Option<&'s SessionInfo> {
+        self.session
+    }
+
+    /// Marks the command as run in dry-run mode
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - Whether the command runs in dry-run mode
+    ///
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Attaches the postcard encoded arguments of a binary request
+    #[cfg(feature = "binary")]
+    pub(crate) fn with_payload(mut self, payload: &'s [u8]) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Returns the postcard encoded arguments of a binary request, see `set_binary_handler`
+    #[cfg(feature = "binary")]
+    pub(crate) fn payload(&self) -> &'s [u8] {
+        self.payload
+    }
+
+    /// Returns whether the command runs in dry-run mode, see `set_dry_run`
+    ///
+    /// Callbacks shared by a command and its dry-run callback can use it to
+    /// skip their side effects.
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Checks for a flag
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the flag, a single character for a short flag
+    ///
+    /// # Returns
+    ///
+    /// * `true` - If the flag was given
+    ///
+    pub fn flag(&mut self, name: &str) -> bool {
+        let letter = short_name(name);
+        let position = self.find_option(|arg| match (long(arg), short(arg)) {
+            (Some((key, None)), _) => key == name,
+            (_, Some(cluster)) => letter.is_some_and(|c| cluster.contains(c)),
+            _ => false,
+        });
+
+        if let Some(position) = position {
+            self.consume(position);
+        }
+        position.is_some()
+    }
+
+    /// Takes the value of an option and parses it
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the option, a single character for a short option
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(T))` - The parsed value, if the option was given
+    /// * `Ok(None)` - If the option wasn't given
+    /// * `Err(ArgError::Missing)` - If the option was given without a value
+    /// * `Err(ArgError::Invalid)` - If the value failed to parse
+    ///
+    pub fn opt_value<T: FromStr>(&mut self, name: &str) -> Result<Option<T>, ArgError> {
+        let letter = short_name(name);
+        let attached = |arg: &'s str| match (long(arg), short(arg)) {
+            (Some((key, value)), _) if key == name => Some(value),
+            (_, Some(cluster)) => letter
+                .and_then(|c| cluster.split_once(c))
+                .map(|(_, value)| Some(value).filter(|value| !value.is_empty())),
+            _ => None,
+        };
+
+        let Some(position) = self.find_option(|arg| attached(arg).is_some()) else {
+            return Ok(None);
+        };
+        self.consume(position);
+
+        let (position, value) = match self.args.get(position).and_then(|arg| attached(arg)) {
+            Some(Some(value)) => (position, value),
+            _ => {
+                let position = position.saturating_add(1);
+                let value = self
+                    .args
+                    .get(position)
+                    .filter(|value| **value != "--")
+                    .ok_or(ArgError::Missing(position))?;
+                self.consume(position);
+                (position, *value)
+            }
+        };
+
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| ArgError::Invalid(position))
+    }
+
+    /// Takes the value of a `key=value` argument
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the argument
+    ///
+    /// # Returns
+    ///
+    /// * `Some(&str)` - The value, if the key was given
+    ///
+    pub fn keyword(&mut self, key: &str) -> Option<&'s str> {
+        self.take_keyword(key).map(|(_, value)| value)
+    }
+
+    /// Takes the value of a `key=value` argument and parses it
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the argument
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(T))` - The parsed value, if the key was given
+    /// * `Ok(None)` - If the key wasn't given
+    /// * `Err(ArgError::Invalid)` - If the value failed to parse
+    ///
+    pub fn keyword_parse<T: FromStr>(&mut self, key: &str) -> Result<Option<T>, ArgError> {
+        match self.take_keyword(key) {
+            Some((position, value)) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| ArgError::Invalid(position)),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks that every `key=value` argument has one of the known keys
+    ///
+    /// # Arguments
+    ///
+    /// * `known` - The keys the command accepts
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every key is known
+    /// * `Err(ArgError::Unexpected)` - If a key is unknown
+    ///
+    pub fn check_keywords(&self, known: &[&str]) -> Result<(), ArgError> {
+        let unknown = self
+            .args
+            .iter()
+            .take_while(|arg| **arg != "--")
+            .position(|arg| keyword(arg).is_some_and(|(key, _)| !known.contains(&key)));
+
+        match unknown {
+            Some(position) => Err(ArgError::Unexpected(position)),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the next argument
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(&str)` - The argument
+    /// * `Err(ArgError::Missing)` - If every argument was taken
+    ///
+    pub fn next_str(&mut self) -> Result<&'s str, ArgError> {
+        while self.is_consumed(self.position) {
+            self.position = self.position.saturating_add(1);
+        }
+
+        let arg = self
+            .args
+            .get(self.position)
+            .ok_or(ArgError::Missing(self.position))?;
+        self.position = self.position.saturating_add(1);
+        Ok(arg)
+    }
+
+    /// Takes the next argument and parses it
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(T)` - The parsed argument
+    /// * `Err(ArgError::Missing)` - If every argument was taken
+    /// * `Err(ArgError::Invalid)` - If the argument failed to parse
+    ///
+    pub fn next_parse<T: FromStr>(&mut self) -> Result<T, ArgError> {
+        let position = self.position;
+        self.next_str()?
+            .parse()
+            .map_err(|_| ArgError::Invalid(position))
+    }
+
+    /// Takes the next argument and parses it as an integer literal, see `parse_int`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(T)` - The parsed argument
+    /// * `Err(ArgError::Missing)` - If every argument was taken
+    /// * `Err(ArgError::Invalid)` - If the argument isn't an integer literal fitting `T`
+    ///
+    pub fn next_int<T: TryFrom<i128>>(&mut self) -> Result<T, ArgError> {
+        let position = self.position;
+        parse_int(self.next_str()?).ok_or(ArgError::Invalid(position))
+    }
+
+    /// Takes the next argument and parses it as a duration, see `parse_duration`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Duration)` - The parsed argument
+    /// * `Err(ArgError::Missing)` - If every argument was taken
+    /// * `Err(ArgError::Invalid)` - If the argument isn't a duration
+    ///
+    pub fn next_duration(&mut self) -> Result<Duration, ArgError> {
+        let position = self.position;
+        parse_duration(self.next_str()?).ok_or(ArgError::Invalid(position))
+    }
+
+    /// Returns the arguments that weren't taken yet, options included
+    pub fn remaining(&self) -> &'s [&'s str] {
+        self.args.get(self.position..).unwrap_or(&[])
+    }
+
+    /// Checks that every argument was taken
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If no argument is left
+    /// * `Err(ArgError::Unexpected)` - If arguments are left
+    ///
+    pub fn finish(&self) -> Result<(), ArgError> {
+        match (self.position..self.args.len()).find(|&position| !self.is_consumed(position)) {
+            None => Ok(()),
+            Some(position) => Err(ArgError::Unexpected(position)),
+        }
+    }
+
+    /// Returns the position of the first option matching, before any `--`
+    ///
+    /// The `--` itself is consumed, as it only separates options from the
+    /// other arguments.
+    fn find_option(&mut self, mut matches: impl FnMut(&'s str) -> bool) -> Option<usize> {
+        let args = self.args;
+        for (position, arg) in args.iter().enumerate() {
+            if *arg == "--" {
+                self.consume(position);
+                return None;
+            }
+            if matches(arg) {
+                return Some(position);
+            }
+        }
+        None
+    }
+
+    /// Takes a `key=value` argument, returning its position and value
+    fn take_keyword(&mut self, key: &str) -> Option<(usize, &'s str)> {
+        let position = self.find_option(|arg| keyword(arg).is_some_and(|(k, _)| k == key))?;
+        self.consume(position);
+        let (_, value) = keyword(self.args.get(position)?)?;
+        Some((position, value))
+    }
+
+    /// Marks an argument as used by an option, past the first 64 it is ignored
+    fn consume(&mut self, position: usize) {
+        let bit = u32::try_from(position)
+            .ok()
+            .and_then(|position| 1u64.checked_shl(position))
+            .unwrap_or(0);
+        self.consumed |= bit;
+    }
+
+    fn is_consumed(&self, position: usize) -> bool {
+        u32::try_from(position)
+            .ok()
+            .and_then(|position| self.consumed.checked_shr(position))
+            .is_some_and(|consumed| consumed & 1 == 1)
+    }
+}
+
+/// Parses an integer literal
+///
+/// Literals are decimal, or hexadecimal, binary or octal with a `0x`, `0b`
+/// or `0o` prefix, can be negative, and can have `_` separators between
+/// digits. A `k`, `M` or `G` suffix multiplies the value by a thousand, a
+/// million or a billion.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::parse_int;
+///
+/// assert_eq!(parse_int::<u32>("0x2000_0000"), Some(0x2000_0000));
+/// assert_eq!(parse_int::<u8>("0b1010"), Some(10));
+/// assert_eq!(parse_int::<u32>("16k"), Some(16_000));
+/// assert_eq!(parse_int::<i8>("-0x80"), Some(-128));
+/// assert_eq!(parse_int::<u8>("256"), None);
+/// ```
+///
+pub fn parse_int<T: TryFrom<i128>>(literal: &str) -> Option<T> {
+    let (negative, literal) = match literal.strip_prefix('-') {
+        Some(literal) => (true, literal),
+        None => (false, literal),
+    };
+
+    let (digits, multiplier) = [("k", 1_000), ("M", 1_000_000), ("G", 1_000_000_000)]
+        .into_iter()
+        .find_map(|(suffix, multiplier)| Some((literal.strip_suffix(suffix)?, multiplier)))
+        .unwrap_or((literal, 1));
+
+    let magnitude = i128::try_from(parse_digits(digits)?.checked_mul(multiplier)?).ok()?;
+    let value = match negative {
+        true => magnitude.checked_neg()?,
+        false => magnitude,
+    };
+    T::try_from(value).ok()
+}
+
+/// Parses a duration
+///
+/// Durations are an integer literal, see `parse_int`, followed by a unit:
+/// `ns`, `us`, `ms`, `s`, `min` or `h`.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::parse_duration;
+/// use core::time::Duration;
+///
+/// assert_eq!(parse_duration("10ms"), Some(Duration::from_millis(10)));
+/// assert_eq!(parse_duration("2min"), Some(Duration::from_secs(120)));
+/// assert_eq!(parse_duration("10"), None);
+/// ```
+///
+pub fn parse_duration(literal: &str) -> Option<Duration> {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+    const UNITS: [(&str, u128); 6] = [
+        ("ns", 1),
+        ("us", 1_000),
+        ("ms", 1_000_000),
+        ("min", 60 * NANOS_PER_SEC),
+        ("s", NANOS_PER_SEC),
+        ("h", 3_600 * NANOS_PER_SEC),
+    ];
+
+    let nanos = UNITS.into_iter().find_map(|(unit, nanos)| {
+        let value = literal.strip_suffix(unit)?;
+        // An `s` suffix would otherwise take the end of `ns`, `us` or `ms`
+        if value.ends_with(['n', 'u', 'm']) {
+            return None;
+        }
+        parse_int::<u128>(value)?.checked_mul(nanos)
+    })?;
+
+    Some(Duration::new(
+        u64::try_from(nanos.checked_div(NANOS_PER_SEC)?).ok()?,
+        u32::try_from(nanos.checked_rem(NANOS_PER_SEC)?).ok()?,
+    ))
+}
+
+/// Parses the digits of an unsigned literal, with its radix prefix if any
+fn parse_digits(literal: &str) -> Option<u128> {
+    let (radix, digits) = [
+        ("0x", 16),
+        ("0X", 16),
+        ("0b", 2),
+        ("0B", 2),
+        ("0o", 8),
+        ("0O", 8),
+    ]
+    .into_iter()
+    .find_map(|(prefix, radix)| Some((radix, literal.strip_prefix(prefix)?)))
+    .unwrap_or((10, literal));
+
+    // Separators only go between digits
+    if digits.starts_with('_') || digits.ends_with('_') || digits.is_empty() {
+        return None;
+    }
+
+    digits
+        .chars()
+        .filter(|c| *c != '_')
+        .try_fold(0u128, |value, c| {
+            value
+                .checked_mul(u128::from(radix))?
+                .checked_add(u128::from(c.to_digit(radix)?))
+        })
+}
+
+/// Splits a `key=value` argument, keys don't start with `-` like options
+fn keyword(arg: &str) -> Option<(&str, &str)> {
+    arg.split_once('=').filter(|(key, _)| {
+        key.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// Splits a long option into its name and attached value
+fn long(arg: &str) -> Option<(&str, Option<&str>)> {
+    let option = arg.strip_prefix("--").filter(|option| !option.is_empty())?;
+    match option.split_once('=') {
+        Some((key, value)) => Some((key, Some(value))),
+        None => Some((option, None)),
+    }
+}
+
+/// Returns the characters of a short option, negative numbers aren't options
+fn short(arg: &str) -> Option<&str> {
+    arg.strip_prefix('-')
+        .filter(|cluster| cluster.starts_with(|c: char| c != '-' && !c.is_ascii_digit()))
+}
+
+/// Returns the character of a single character name
+fn short_name(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let mut args = Args::new(&["led", "3", "x"]);
+
+        assert_eq!(args.next_str(), Ok("led"));
+        assert_eq!(args.next_parse::<u8>(), Ok(3));
+        assert_eq!(args.remaining(), ["x"]);
+        assert_eq!(args.finish(), Err(ArgError::Unexpected(2)));
+        assert_eq!(args.next_parse::<u8>(), Err(ArgError::Invalid(2)));
+        assert_eq!(args.next_str(), Err(ArgError::Missing(3)));
+        assert!(args.finish().is_ok());
+
+        assert!(matches!(
+            CommandProcessorError::from(ArgError::Missing(0)),
+            CommandProcessorError::MissingArgument
+        ));
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        assert_eq!(parse_int::<u64>("1_000_000"), Some(1_000_000));
+        assert_eq!(parse_int::<u32>("0xFFFF_FFFF"), Some(u32::MAX));
+        assert_eq!(parse_int::<u32>("0o17"), Some(15));
+        assert_eq!(parse_int::<u64>("2G"), Some(2_000_000_000));
+        assert_eq!(parse_int::<i32>("-5k"), Some(-5_000));
+        assert_eq!(parse_int::<u32>("-1"), None);
+        assert_eq!(parse_int::<u32>("0x"), None);
+        assert_eq!(parse_int::<u32>("_1"), None);
+        assert_eq!(parse_int::<u32>("0b102"), None);
+        assert_eq!(parse_int::<u32>("1.5"), None);
+
+        assert_eq!(parse_duration("250us"), Some(Duration::from_micros(250)));
+        assert_eq!(parse_duration("0x10s"), Some(Duration::from_secs(16)));
+        assert_eq!(parse_duration("1h"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("5ks"), Some(Duration::from_secs(5000)));
+        assert_eq!(parse_duration("-1s"), None);
+        assert_eq!(parse_duration("1mss"), None);
+
+        let mut args = Args::new(&["0x2000_0000", "10ms", "fast"]);
+        assert_eq!(args.next_int::<u32>(), Ok(0x2000_0000));
+        assert_eq!(args.next_duration(), Ok(Duration::from_millis(10)));
+        assert_eq!(args.next_int::<u32>(), Err(ArgError::Invalid(2)));
+    }
+
+    #[test]
+    fn test_keywords() {
+        let mut args = Args::new(&["configure", "baud=115200", "uart1", "parity=none", "x=1"]);
+
+        assert_eq!(
+            args.check_keywords(&["baud", "parity"]),
+            Err(ArgError::Unexpected(4))
+        );
+        assert!(args.check_keywords(&["baud", "parity", "x"]).is_ok());
+        assert_eq!(args.keyword_parse::<u32>("baud"), Ok(Some(115200)));
+        assert_eq!(args.keyword("parity"), Some("none"));
+        assert_eq!(args.keyword("stop"), None);
+        assert_eq!(args.keyword_parse::<u8>("x"), Ok(Some(1)));
+        assert_eq!(args.next_str(), Ok("configure"));
+        assert_eq!(args.next_str(), Ok("uart1"));
+        assert!(args.finish().is_ok());
+
+        let mut args = Args::new(&["--mode=fast", "baud=fast"]);
+        assert_eq!(args.keyword("--mode"), None);
+        assert_eq!(args.keyword_parse::<u32>("baud"), Err(ArgError::Invalid(1)));
+    }
+
+    #[test]
+    fn test_options() {
+        let mut args = Args::new(&["-vn", "5", "led", "--level=3", "-x", "--", "-q"]);
+
+        assert!(args.flag("v"));
+        assert!(!args.flag("q"));
+        assert_eq!(args.opt_value::<u8>("n"), Ok(Some(5)));
+        assert_eq!(args.opt_value::<u8>("level"), Ok(Some(3)));
+        assert_eq!(args.opt_value::<u8>("count"), Ok(None));
+        assert_eq!(args.next_str(), Ok("led"));
+        assert_eq!(args.finish(), Err(ArgError::Unexpected(4)));
+        assert_eq!(args.opt_value::<u8>("x"), Err(ArgError::Missing(5)));
+        assert_eq!(args.next_str(), Ok("-q"));
+        assert!(args.finish().is_ok());
+
+        let mut args = Args::new(&["--count", "many", "-5", "-c7"]);
+        assert_eq!(args.opt_value::<u8>("count"), Err(ArgError::Invalid(1)));
+        assert_eq!(args.opt_value::<u8>("c"), Ok(Some(7)));
+        assert_eq!(args.next_parse::<i8>(), Ok(-5));
+    }
+}
@@ -0,0 +1,626 @@
+// This module contains the async variant of the processor, for executors such as Embassy
+#[cfg(feature = "builtins")]
+use crate::variables::EXPANSION_BUFFER_SIZE;
+use crate::{Args, CommandProcessor, CommandProcessorError, ReturnCode, Status, Tokens, MAX_ARGS};
+
+use core::fmt::Write;
+use core::future::Future;
+use heapless::Vec;
+
+/// The commands of an `AsyncCommandProcessor` that await
+///
+/// Without an allocator, a table of callbacks can't hold futures of
+/// different types, so the application implements this trait once, usually
+/// on its board type, and matches the command names itself. The returned
+/// future is then a single concrete type the executor can poll.
+///
+/// # Arguments
+///
+/// * `C` - The type of the context shared by the commands, as for the processor
+/// * `R` - The status returned by the commands, as for the processor
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{Args, AsyncCommands, CommandProcessorError, ReturnCode};
+/// use core::fmt::Write;
+///
+/// struct Board;
+///
+/// impl Board {
+///     async fn read_sensor(&mut self) -> u16 {
+///         // Await the I2C transfer here
+///         42
+///     }
+/// }
+///
+/// impl AsyncCommands for Board {
+///     fn handles(&self, name: &str) -> bool {
+///         name == "sensor"
+///     }
+///
+///     async fn call(
+///         &mut self,
+///         _: &str,
+///         _: Args<'_>,
+///         _: Option<&mut ()>,
+///         writer: Option<&mut dyn Write>,
+///     ) -> Result<ReturnCode, CommandProcessorError> {
+///         let value = self.read_sensor().await;
+///         let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+///         writeln!(writer, "{}", value).map_err(|_| CommandProcessorError::WriteError)?;
+///         Ok(ReturnCode::Success)
+///     }
+/// }
+/// ```
+///
+pub trait AsyncCommands<C = (), R = ReturnCode> {
+    /// Returns whether the command is one of these
+    fn handles(&self, name: &str) -> bool;
+
+    /// Returns whether the command only reads, so it runs while another session holds the lock
+    ///
+    /// None are by default, see `CommandProcessor::set_safe`.
+    fn is_safe(&self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+
+    /// Returns whether the command is an operation other sessions must not interleave with
+    ///
+    /// None are by default. While an exclusive async command is awaited,
+    /// exclusive commands of other sessions fail with
+    /// `CommandProcessorError::Busy`, see `CommandProcessor::set_exclusive`.
+    fn is_exclusive(&self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+
+    /// Runs a command
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the command, one `handles` accepted
+    /// * `args` - The arguments of the command
+    /// * `context` - The context given to `process_command_with`, if any
+    /// * `writer` - The writer the command can write with
+    ///
+    fn call(
+        &mut self,
+        name: &str,
+        args: Args<'_>,
+        context: Option<&mut C>,
+        writer: Option<&mut dyn Write>,
+    ) -> impl Future<Output = Result<R, CommandProcessorError>>;
+}
+
+/// A command processor whose commands can await
+///
+/// Commands the `AsyncCommands` handle are awaited, any other line is
+/// processed by the wrapped processor, so sync commands and built-ins keep
+/// working unchanged. Async command lines go through the same checks as
+/// sync ones: their length, aliases, variables, the console lock and
+/// exclusive operations, and they're traced. In dry-run mode they fail with
+/// `DryRunUnsupported`, as they have no dry-run callback. Strict mode,
+/// schemas and hooks don't apply to them, and they aren't listed by `help`.
+///
+/// # Arguments
+///
+/// * `H` - The async commands
+/// * `NUM_COMMANDS` - The maximum number of sync commands
+/// * `HELP_STR_SIZE` - The maximum size of a help string
+/// * `NAME_SIZE` - The maximum size of a sync command name built at runtime
+/// * `LINE_SIZE` - The maximum size of a command line
+/// * `C` - The type of the context shared by the commands
+/// * `R` - The status returned by the commands
+///
+/// # Example
+///
+/// ```
+/// # use command_processor::{Args, AsyncCommands, CommandProcessorError, ReturnCode};
+/// # use core::fmt::Write;
+/// # struct Board;
+/// # impl AsyncCommands for Board {
+/// #     fn handles(&self, name: &str) -> bool { name == "sensor" }
+/// #     async fn call(&mut self, _: &str, _: Args<'_>, _: Option<&mut ()>, _: Option<&mut dyn Write>) -> Result<ReturnCode, CommandProcessorError> {
+/// #         Ok(ReturnCode::Success)
+/// #     }
+/// # }
+/// use command_processor::{AsyncCommandProcessor, CommandProcessor};
+///
+/// async fn shell(board: Board) {
+///     let mut command_processor: AsyncCommandProcessor<Board, 8, 32> =
+///         AsyncCommandProcessor::new(CommandProcessor::new(), board);
+///
+///     let result = command_processor.process_command("sensor", None).await;
+///     assert!(result.is_ok());
+/// }
+/// ```
+///
+pub struct AsyncCommandProcessor<
+    'a,
+    H,
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize = 32,
+    const LINE_SIZE: usize = 32,
+    C = (),
+    R = ReturnCode,
+> {
+    processor: CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+    commands: H,
+}
+
+impl<
+        'a,
+        H: AsyncCommands<C, R>,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > AsyncCommandProcessor<'a, H, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Creates an async processor
+    ///
+    /// # Arguments
+    ///
+    /// * `processor` - The processor running the sync commands and built-ins
+    /// * `commands` - The async commands
+    ///
+    pub fn new(
+        processor: CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+        commands: H,
+    ) -> Self {
+        Self {
+            processor,
+            commands,
+        }
+    }
+
+    /// Returns the processor running the sync commands, to register them
+    pub fn processor(
+        &mut self,
+    ) -> &mut CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R> {
+        &mut self.processor
+    }
+
+    /// Returns the async commands
+    pub fn commands(&mut self) -> &mut H {
+        &mut self.commands
+    }
+
+    /// Processes a command, awaiting it if it is async
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to process
+    /// * `writer` - The writer the command can write with
+    ///
+    /// # Returns
+    ///
+    /// * The result of the async command
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If an async command has more than `MAX_ARGS` arguments
+    /// * `Err(CommandProcessorError::ShuttingDown)` - If `shutdown` was called
+    /// * `Err(CommandProcessorError::LineTooLong)` - If the line is longer than `LINE_SIZE`
+    /// * `Err(CommandProcessorError::Busy)` - If an exclusive async command runs during another session's exclusive operation
+    /// * `Err(CommandProcessorError::Locked)` - If another session locked the console and the async command isn't safe
+    /// * `Err(CommandProcessorError::DryRunUnsupported)` - If an async command runs in dry-run mode
+    /// * The result of the wrapped processor's `process_command` for any other line
+    ///
+    pub async fn process_command(
+        &mut self,
+        command: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.process_line(command, None, writer).await
+    }
+
+    /// Processes a command, handing the shared context to it, see `process_command`
+    ///
+    /// Async commands get the context in `AsyncCommands::call`, sync ones as
+    /// with `CommandProcessor::process_command_with`.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context passed to the commands
+    /// * `command` - The command to process
+    /// * `writer` - The writer the command can write with
+    ///
+    /// # Returns
+    ///
+    /// The same results as `process_command`
+    ///
+    pub async fn process_command_with(
+        &mut self,
+        context: &mut C,
+        command: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.process_line(command, Some(context), writer).await
+    }
+
+    async fn process_line(
+        &mut self,
+        command: &str,
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.processor.check_accepting(command)?;
+
+        let line = self.processor.strip_comment(command);
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(line, self.processor.delimiters, &mut buffer);
+        let name = self.processor.resolve_alias(tokens.next().unwrap_or(""));
+
+        #[cfg(feature = "builtins")]
+        let recording = self.processor.is_recording();
+        #[cfg(not(feature = "builtins"))]
+        let recording = false;
+
+        if recording || !self.commands.handles(name) {
+            return match context {
+                Some(context) => self
+                    .processor
+                    .process_command_with(context, command, writer),
+                None => self.processor.process_command(command, writer),
+            };
+        }
+
+        #[cfg(feature = "builtins")]
+        let started_ms = self.processor.clock.map(|clock| clock.now_ms());
+        let result = self.run(name, tokens, context, writer).await;
+        #[cfg(feature = "builtins")]
+        self.processor.record_trace(command, started_ms, &result);
+        result
+    }
+
+    /// Awaits an async command, once the checks of sync commands passed
+    async fn run(
+        &mut self,
+        name: &str,
+        mut tokens: Tokens<'_>,
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        #[cfg(feature = "builtins")]
+        if !self.commands.is_safe(name) && !self.processor.may_run_unsafe() {
+            return Err(CommandProcessorError::Locked);
+        }
+
+        #[cfg(feature = "builtins")]
+        let mut expansions = [0u8; EXPANSION_BUFFER_SIZE];
+        #[cfg(feature = "builtins")]
+        let mut expansions = expansions.as_mut_slice();
+
+        let mut args: Vec<&str, MAX_ARGS> = Vec::new();
+        for arg in tokens.by_ref() {
+            #[cfg(feature = "builtins")]
+            let arg = self.processor.variables.expand(arg, &mut expansions)?;
+            args.push(arg)
+                .map_err(|_| CommandProcessorError::InvalidArguments)?;
+        }
+        tokens.check()?;
+
+        if self.processor.dry_run {
+            return Err(CommandProcessorError::DryRunUnsupported);
+        }
+
+        let info = self.processor.session;
+        let session = info.map(|session| session.id);
+        let exclusive = self.commands.is_exclusive(name);
+        if exclusive && self.processor.is_busy_for(session) {
+            return Err(CommandProcessorError::Busy);
+        }
+        // The operation is the command itself, so the claim ends with it,
+        // even when the future is dropped, by a timeout for instance
+        let _claim = exclusive.then(|| Claim {
+            previous: self.processor.busy.replace(session),
+            busy: &mut self.processor.busy,
+        });
+
+        let args = Args::new(&args).with_session(info.as_ref());
+        match writer {
+            Some(writer) => self.commands.call(name, args, context, Some(writer)).await,
+            None => self.commands.call(name, args, context, None).await,
+        }
+    }
+}
+
+/// The claim of an exclusive async command, given back when dropped
+struct Claim<'p> {
+    busy: &'p mut Option<Option<u8>>,
+    previous: Option<Option<u8>>,
+}
+
+impl Drop for Claim<'_> {
+    fn drop(&mut self) {
+        *self.busy = self.previous;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{block_on, Transfer};
+    use crate::{CommandCallbackReturn, Link, SessionInfo};
+    use core::pin::pin;
+    use core::task::{Context, Waker};
+    use heapless::String;
+
+    struct Bus {
+        transfers: u32,
+    }
+
+    impl AsyncCommands for Bus {
+        fn handles(&self, name: &str) -> bool {
+            name == "i2c-read"
+        }
+
+        fn is_exclusive(&self, _: &str) -> bool {
+            true
+        }
+
+        async fn call(
+            &mut self,
+            _: &str,
+            mut args: Args<'_>,
+            _: Option<&mut ()>,
+            writer: Option<&mut dyn Write>,
+        ) -> Result<ReturnCode, CommandProcessorError> {
+            let address: u8 = args.next_parse()?;
+            Transfer(false).await;
+            self.transfers += 1;
+
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writeln!(writer, "{:#04x}", address).map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+    }
+
+    fn ping<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "pong").map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_async_commands() {
+        let mut command_processor: AsyncCommandProcessor<Bus, 8, 32> =
+            AsyncCommandProcessor::new(CommandProcessor::new(), Bus { transfers: 0 });
+        assert!(command_processor
+            .processor()
+            .add_command("ping", ping, None)
+            .is_ok());
+
+        let mut buffer: String<32> = String::new();
+        for line in ["i2c-read 80", "ping", "i2c-read 80 # eeprom"] {
+            let result = block_on(command_processor.process_command(line, Some(&mut buffer)));
+            assert!(result.is_ok());
+        }
+        assert!(matches!(
+            block_on(command_processor.process_command("i2c-read", None)),
+            Err(CommandProcessorError::MissingArgument)
+        ));
+        assert!(matches!(
+            block_on(command_processor.process_command("nothing", None)),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        assert_eq!(buffer, "0x50\npong\n0x50\n");
+        assert_eq!(command_processor.commands().transfers, 2);
+
+        assert!(command_processor.processor().shutdown().is_ok());
+        assert!(matches!(
+            block_on(command_processor.process_command("i2c-read 80", None)),
+            Err(CommandProcessorError::ShuttingDown)
+        ));
+        assert_eq!(command_processor.commands().transfers, 2);
+    }
+
+    #[test]
+    fn test_async_checks() {
+        let mut command_processor: AsyncCommandProcessor<Bus, 8, 32> =
+            AsyncCommandProcessor::new(CommandProcessor::new(), Bus { transfers: 0 });
+        let processor = command_processor.processor();
+        assert!(processor.add_command("erase", ping, None).is_ok());
+        assert!(processor.set_exclusive("erase", true).is_ok());
+        // Registered for its help, the async command runs instead
+        assert!(processor.add_command("i2c-read", ping, None).is_ok());
+        assert!(processor.add_alias("rd", "i2c-read").is_ok());
+
+        let mut buffer: String<128> = String::new();
+        assert!(matches!(
+            block_on(command_processor.process_command(&"i2c-read 1 ".repeat(4), None)),
+            Err(CommandProcessorError::LineTooLong)
+        ));
+        assert!(block_on(command_processor.process_command("rd 80", Some(&mut buffer))).is_ok());
+        assert_eq!(buffer, "0x50\n");
+
+        // Exclusive async commands wait for other sessions' operations
+        let tool = Some(SessionInfo::new(0, Link::Serial));
+        let app = Some(SessionInfo::new(1, Link::Ble));
+        command_processor.processor().set_session_info(tool);
+        assert!(block_on(command_processor.process_command("erase", Some(&mut buffer))).is_ok());
+        command_processor.processor().set_session_info(app);
+        assert!(matches!(
+            block_on(command_processor.process_command("i2c-read 80", None)),
+            Err(CommandProcessorError::Busy)
+        ));
+        command_processor.processor().set_session_info(tool);
+        assert!(block_on(command_processor.process_command("rd 80", Some(&mut buffer))).is_ok());
+        assert!(command_processor.processor().is_busy());
+
+        // And only claim the processor while they're awaited
+        command_processor.processor().release_exclusive();
+        command_processor.processor().set_session_info(app);
+        assert!(block_on(command_processor.process_command("rd 80", Some(&mut buffer))).is_ok());
+        assert!(!command_processor.processor().is_busy());
+        assert_eq!(command_processor.commands().transfers, 3);
+
+        #[cfg(feature = "builtins")]
+        {
+            assert!(block_on(command_processor.process_command("let addr 16", None)).is_ok());
+            buffer.clear();
+            assert!(
+                block_on(command_processor.process_command("rd $addr", Some(&mut buffer))).is_ok()
+            );
+            assert_eq!(buffer, "0x10\n");
+
+            buffer.clear();
+            let trace = command_processor.processor().write_trace(&mut buffer);
+            assert!(trace.is_ok());
+            assert_eq!(
+                buffer,
+                "rd: OK\nerase: OK\ni2c-read: ERR: busy\nrd: OK\nrd: OK\nlet: OK\nrd: OK\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_async_dropped() {
+        let mut command_processor: AsyncCommandProcessor<Bus, 8, 32> =
+            AsyncCommandProcessor::new(CommandProcessor::new(), Bus { transfers: 0 });
+        let tool = Some(SessionInfo::new(0, Link::Serial));
+        let app = Some(SessionInfo::new(1, Link::Ble));
+
+        // Dropped while the transfer is pending, as on a timeout
+        command_processor.processor().set_session_info(tool);
+        {
+            let mut future = pin!(command_processor.process_command("i2c-read 80", None));
+            let mut context = Context::from_waker(Waker::noop());
+            assert!(future.as_mut().poll(&mut context).is_pending());
+        }
+        assert!(!command_processor.processor().is_busy());
+
+        let mut buffer: String<32> = String::new();
+        command_processor.processor().set_session_info(app);
+        assert!(
+            block_on(command_processor.process_command("i2c-read 80", Some(&mut buffer))).is_ok()
+        );
+        assert_eq!(command_processor.commands().transfers, 1);
+    }
+
+    #[test]
+    fn test_async_dry_run() {
+        let mut command_processor: AsyncCommandProcessor<Bus, 8, 32> =
+            AsyncCommandProcessor::new(CommandProcessor::new(), Bus { transfers: 0 });
+        command_processor.processor().set_dry_run(true);
+        assert!(matches!(
+            block_on(command_processor.process_command("i2c-read 80", None)),
+            Err(CommandProcessorError::DryRunUnsupported)
+        ));
+        assert_eq!(command_processor.commands().transfers, 0);
+
+        #[cfg(feature = "builtins")]
+        {
+            command_processor.processor().set_dry_run(false);
+            assert!(block_on(command_processor.process_command("simulate on", None)).is_ok());
+            assert!(matches!(
+                block_on(command_processor.process_command("i2c-read 80", None)),
+                Err(CommandProcessorError::DryRunUnsupported)
+            ));
+            assert_eq!(command_processor.commands().transfers, 0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "builtins")]
+    fn test_async_lock() {
+        let mut command_processor: AsyncCommandProcessor<Bus, 8, 32> =
+            AsyncCommandProcessor::new(CommandProcessor::new(), Bus { transfers: 0 });
+        let tool = Some(SessionInfo::new(0, Link::Serial));
+        let app = Some(SessionInfo::new(1, Link::Ble));
+        command_processor.processor().set_session_info(tool);
+        assert!(block_on(command_processor.process_command("lock", None)).is_ok());
+
+        // Other sessions can't run async commands either
+        command_processor.processor().set_session_info(app);
+        assert!(matches!(
+            block_on(command_processor.process_command("i2c-read 80", None)),
+            Err(CommandProcessorError::Locked)
+        ));
+        assert_eq!(command_processor.commands().transfers, 0);
+
+        let mut buffer: String<32> = String::new();
+        command_processor.processor().set_session_info(tool);
+        assert!(
+            block_on(command_processor.process_command("i2c-read 80", Some(&mut buffer))).is_ok()
+        );
+        assert_eq!(command_processor.commands().transfers, 1);
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Response {
+        Ack,
+        Nack,
+        Pending,
+    }
+
+    impl From<ReturnCode> for Response {
+        fn from(code: ReturnCode) -> Self {
+            match code {
+                ReturnCode::Success => Response::Ack,
+                ReturnCode::Failure => Response::Nack,
+            }
+        }
+    }
+
+    impl Status for Response {
+        fn is_success(&self) -> bool {
+            *self == Response::Ack
+        }
+    }
+
+    struct Pump;
+
+    impl AsyncCommands<u32, Response> for Pump {
+        fn handles(&self, name: &str) -> bool {
+            name == "prime"
+        }
+
+        async fn call(
+            &mut self,
+            _: &str,
+            _: Args<'_>,
+            strokes: Option<&mut u32>,
+            _: Option<&mut dyn Write>,
+        ) -> Result<Response, CommandProcessorError> {
+            Transfer(false).await;
+            *strokes.ok_or(CommandProcessorError::NoContext)? += 1;
+            Ok(Response::Pending)
+        }
+    }
+
+    fn stroke<'a>(
+        strokes: &mut u32,
+        _: Args,
+        _: Option<&mut (dyn Write + 'a)>,
+    ) -> CommandCallbackReturn<'a, Response> {
+        *strokes += 1;
+        Ok(Response::Ack)
+    }
+
+    #[test]
+    fn test_async_context_and_status() {
+        let mut command_processor: AsyncCommandProcessor<Pump, 8, 32, 32, 32, u32, Response> =
+            AsyncCommandProcessor::new(CommandProcessor::new(), Pump);
+        assert!(command_processor
+            .processor()
+            .add_command_shared("stroke", stroke, None)
+            .is_ok());
+
+        let mut strokes = 0;
+        for (line, expected) in [("prime", Response::Pending), ("stroke", Response::Ack)] {
+            let result = block_on(command_processor.process_command_with(&mut strokes, line, None));
+            assert!(matches!(result, Ok(response) if response == expected));
+        }
+        assert_eq!(strokes, 2);
+
+        assert!(matches!(
+            block_on(command_processor.process_command("prime", None)),
+            Err(CommandProcessorError::NoContext)
+        ));
+    }
+}
@@ -0,0 +1,185 @@
+// This module contains the benchmarks of the hot paths, run on the host or on the target
+use crate::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The number of runs of each workload, whose mean is reported
+pub const BENCH_ITERATIONS: u32 = 1_000;
+
+/// A benchmark of a hot path, see `run_benchmarks`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Benchmark {
+    pub name: &'static str,
+    /// The line processed, over 16 registered commands
+    pub line: &'static str,
+    /// The mean number of cycles a Cortex-M4 should stay below
+    pub target_cycles: u32,
+}
+
+/// The benchmarks run by `run_benchmarks`
+pub const BENCHMARKS: [Benchmark; 3] = [
+    Benchmark {
+        name: "dispatch",
+        line: "version",
+        target_cycles: 5_000,
+    },
+    Benchmark {
+        name: "tokenization",
+        line: "gpio 1 2 3 4 5 6 7 8",
+        target_cycles: 10_000,
+    },
+    Benchmark {
+        name: "help rendering",
+        line: "help",
+        target_cycles: 100_000,
+    },
+];
+
+/// The DWT cycle counter of Cortex-M3 and later cores
+pub struct DwtCycleCounter(());
+
+impl DwtCycleCounter {
+    const DEMCR: usize = 0xE000_EDFC;
+    const DWT_CTRL: usize = 0xE000_1000;
+    const DWT_CYCCNT: usize = 0xE000_1004;
+
+    /// Enables the trace unit and starts the cycle counter
+    ///
+    /// # Safety
+    ///
+    /// Must only be called on a Cortex-M3 or later core with a DWT unit, whose
+    /// trace configuration no debugger or other code relies on.
+    pub unsafe fn enable() -> Self {
+        let demcr = Self::DEMCR as *mut u32;
+        let ctrl = Self::DWT_CTRL as *mut u32;
+        // SAFETY: the caller guarantees the registers exist
+        core::ptr::write_volatile(demcr, core::ptr::read_volatile(demcr) | 1 << 24);
+        core::ptr::write_volatile(ctrl, core::ptr::read_volatile(ctrl) | 1);
+        DwtCycleCounter(())
+    }
+
+    /// Returns the running cycle count, which wraps around
+    pub fn cycles(&self) -> u32 {
+        // SAFETY: `enable` checked the counter exists
+        unsafe { core::ptr::read_volatile(Self::DWT_CYCCNT as *const u32) }
+    }
+}
+
+/// A writer dropping what is written
+struct Sink;
+
+impl Write for Sink {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        core::hint::black_box(s);
+        Ok(())
+    }
+}
+
+fn noop<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    Ok(ReturnCode::Success)
+}
+
+/// Returns the processor the lines of `BENCHMARKS` are run on, with 16 commands
+///
+/// The commands do nothing, so only the processor is measured. Host
+/// benchmarks, such as `cargo bench --features bench`, run the lines on it
+/// with their own harness.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{benchmark_processor, BENCHMARKS};
+///
+/// let mut command_processor = benchmark_processor();
+/// for benchmark in &BENCHMARKS {
+///     assert!(command_processor.process_command(benchmark.line, Some(&mut String::new())).is_ok());
+/// }
+/// ```
+///
+pub fn benchmark_processor() -> CommandProcessor<'static, 16, 64> {
+    let mut command_processor: CommandProcessor<16, 64> = CommandProcessor::new();
+    for name in [
+        "led", "reboot", "status", "gpio", "adc", "dac", "pwm", "uart", "spi", "i2c", "can",
+        "flash", "eeprom", "rtc", "wdt", "version",
+    ] {
+        let _ = command_processor.add_command(name, noop, Some(String::from("Does nothing")));
+    }
+    command_processor
+}
+
+/// Runs the benchmarks of dispatch, tokenization and help rendering
+///
+/// Each line of `BENCHMARKS` is processed `BENCH_ITERATIONS` times by a
+/// processor with 16 commands, and the mean of the counter's increments is
+/// reported. It's meant for the target, counting cycles with
+/// `DwtCycleCounter`, to compare with the targets, while host runs go
+/// through `cargo bench --features bench`:
+///
+/// ```ignore
+/// let counter = unsafe { DwtCycleCounter::enable() };
+/// run_benchmarks(&mut || counter.cycles(), &mut |benchmark, cycles| {
+///     defmt::info!("{}: {} cycles (target {})", benchmark.name, cycles, benchmark.target_cycles);
+/// });
+/// ```
+///
+/// # Arguments
+///
+/// * `counter` - Returns a running count of time units, which may wrap around
+/// * `report` - Called with each benchmark and its mean count
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{run_benchmarks, BENCHMARKS};
+///
+/// let mut ticks = 0u32;
+/// let mut reported = Vec::new();
+/// run_benchmarks(
+///     &mut || {
+///         ticks += 1;
+///         ticks
+///     },
+///     &mut |benchmark, mean| reported.push((benchmark.name, mean)),
+/// );
+///
+/// assert_eq!(reported.len(), BENCHMARKS.len());
+/// ```
+///
+pub fn run_benchmarks(counter: &mut dyn FnMut() -> u32, report: &mut dyn FnMut(&Benchmark, u32)) {
+    let mut command_processor = benchmark_processor();
+    for benchmark in &BENCHMARKS {
+        let start = counter();
+        for _ in 0..BENCH_ITERATIONS {
+            let _ = command_processor
+                .process_command(core::hint::black_box(benchmark.line), Some(&mut Sink));
+        }
+        let elapsed = counter().wrapping_sub(start);
+        report(
+            benchmark,
+            elapsed.checked_div(BENCH_ITERATIONS).unwrap_or(0),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_benchmarks() {
+        let mut ticks = 0u32;
+        let mut reported = std::vec::Vec::new();
+        run_benchmarks(
+            &mut || {
+                ticks = ticks.wrapping_add(BENCH_ITERATIONS);
+                ticks
+            },
+            &mut |benchmark, mean| reported.push((benchmark.name, mean)),
+        );
+        assert_eq!(
+            reported,
+            [("dispatch", 1), ("tokenization", 1), ("help rendering", 1)]
+        );
+    }
+}
@@ -0,0 +1,1099 @@
+// This module contains the binary request mode for machine-driven consoles
+#[cfg(feature = "builtins")]
+use crate::tokenizer::Tokens;
+use crate::{
+    Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, Handler, Input,
+    ReturnCode, SliceWriter, Status, MAX_ARGS,
+};
+
+use core::fmt::Write;
+use heapless::{String, Vec};
+
+/// The size of the buffer capturing a command's output in binary mode
+///
+/// Applies when no scratch buffer is set, otherwise the scratch buffer's size
+/// applies.
+pub const BINARY_OUTPUT_SIZE: usize = 256;
+
+/// The size of the buffer receiving binary frames through `feed`
+#[cfg(feature = "input")]
+pub const BINARY_FRAME_SIZE: usize = 64;
+
+/// The frame a host sends to check the link is alive, answered with itself
+///
+/// No request is this short, as requests hold at least the length of the
+/// command name and the number of arguments.
+#[cfg(feature = "input")]
+pub const BINARY_PING: &[u8] = &[0x00];
+
+/// The ID of the capabilities request, listing the IDs of the commands
+///
+/// `command_id` never returns it, and `set_command_id` rejects it.
+pub const CAPABILITIES_ID: u16 = 0;
+
+/// Returns the default ID of a command, usable in constants
+///
+/// The ID is a 16-bit FNV-1a hash of the command's full name, so host
+/// tooling can compute it from the name without asking the device, and it
+/// stays the same across firmware versions. Adding a command whose name
+/// hashes to the ID of another fails with `CommandAlreadyExists`; give the
+/// other command a fixed ID with `set_command_id` first.
+///
+/// # Arguments
+///
+/// * `name` - The command's name, with its group for subcommands
+///
+/// # Returns
+///
+/// The command's ID, never `CAPABILITIES_ID`
+///
+/// # Example
+///
+/// ```
+/// use command_processor::command_id;
+///
+/// const LED: u16 = command_id("led");
+///
+/// assert_eq!(LED, command_id("led"));
+/// assert_ne!(LED, command_id("net ping"));
+/// ```
+///
+pub const fn command_id(name: &str) -> u16 {
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut bytes = name.as_bytes();
+    while let Some((&byte, rest)) = bytes.split_first() {
+        hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+        bytes = rest;
+    }
+
+    // Folded to 16 bits, as IDs are sent as varints
+    match ((hash >> 16) ^ (hash & 0xffff)) as u16 {
+        CAPABILITIES_ID => 1,
+        id => id,
+    }
+}
+
+/// The size of an encoded response, the output plus the result and lengths
+#[cfg(feature = "input")]
+const BINARY_RESPONSE_SIZE: usize = BINARY_OUTPUT_SIZE + 64;
+
+/// Binary sink callback type
+///
+/// Sends the bytes of a binary response to the transport. Returns `true` if
+/// they were sent.
+#[cfg(feature = "input")]
+pub type BinarySink = fn(&[u8]) -> bool;
+
+/// SLIP framing bytes
+#[cfg(feature = "input")]
+const SLIP_END: u8 = 0xc0;
+#[cfg(feature = "input")]
+const SLIP_ESC: u8 = 0xdb;
+#[cfg(feature = "input")]
+const SLIP_ESC_END: u8 = 0xdc;
+#[cfg(feature = "input")]
+const SLIP_ESC_ESC: u8 = 0xdd;
+
+/// Where `feed` is in the input stream
+#[cfg(feature = "input")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FrameState {
+    /// Receiving text lines
+    Text,
+    /// Receiving a binary frame
+    Frame,
+    /// Receiving a binary frame, after an escape byte
+    Escaped,
+    /// Dropping a binary frame too long for the frame buffer
+    Discarding,
+}
+
+/// Binary handler callback type
+///
+/// Receives the postcard encoded arguments of a binary request, see
+/// `set_binary_handler`, and the writer.
+pub type BinaryCallback<'a, R = ReturnCode> =
+    fn(BinaryArgs, Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a, R>;
+
+/// Reads postcard encoded values from a request
+#[derive(Debug, Clone, Copy)]
+struct Decoder<'r> {
+    bytes: &'r [u8],
+}
+
+impl<'r> Decoder<'r> {
+    /// Reads a LEB128 varint, as postcard encodes lengths and integers
+    fn varint(&mut self) -> Option<u32> {
+        let mut value: u32 = 0;
+
+        for shift in [0, 7, 14, 21, 28] {
+            let (&byte, rest) = self.bytes.split_first()?;
+            self.bytes = rest;
+            value |= u32::from(byte & 0x7f).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+
+    fn str(&mut self) -> Option<&'r str> {
+        let len = usize::try_from(self.varint()?).ok()?;
+        let bytes = self.bytes.get(..len)?;
+        self.bytes = self.bytes.get(len..)?;
+        core::str::from_utf8(bytes).ok()
+    }
+}
+
+/// The arguments of a binary request, as received by a binary handler
+///
+/// A cursor decoding the fields of the request's arguments struct in order,
+/// as postcard encodes them: `u8` and `bool` as a byte, other unsigned
+/// integers as varints, signed ones zigzag encoded, `f32` as 4 little-endian
+/// bytes and `&str` as its length and bytes. Fields that don't decode fail
+/// with `CommandProcessorError::MalformedRequest`, so handlers can use `?`.
+///
+/// # Example
+///
+/// ```
+/// use core::fmt::Write;
+/// use command_processor::{BinaryArgs, CommandCallbackReturn, ReturnCode};
+///
+/// // struct Dim { led: u8, level: u16, fade: bool }
+/// fn dim<'a>(mut args: BinaryArgs, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     let (led, level, fade) = (args.next_u8()?, args.next_u16()?, args.next_bool()?);
+///     args.finish()?;
+///
+///     assert_eq!((led, level, fade), (2, 300, true));
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// assert!(dim(BinaryArgs::new(&[0x02, 0xac, 0x02, 0x01]), None).is_ok());
+/// assert!(dim(BinaryArgs::new(&[0x02, 0xac]), None).is_err());
+/// ```
+///
+#[derive(Debug, Clone, Copy)]
+pub struct BinaryArgs<'r> {
+    decoder: Decoder<'r>,
+}
+
+impl<'r> BinaryArgs<'r> {
+    /// Creates a cursor at the first field of the given arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The postcard encoded arguments
+    ///
+    pub fn new(bytes: &'r [u8]) -> Self {
+        Self {
+            decoder: Decoder { bytes },
+        }
+    }
+
+    /// Decodes a `u8` field
+    pub fn next_u8(&mut self) -> Result<u8, CommandProcessorError> {
+        let (&byte, rest) = self
+            .decoder
+            .bytes
+            .split_first()
+            .ok_or(CommandProcessorError::MalformedRequest)?;
+        self.decoder.bytes = rest;
+        Ok(byte)
+    }
+
+    /// Decodes a `bool` field
+    pub fn next_bool(&mut self) -> Result<bool, CommandProcessorError> {
+        match self.next_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CommandProcessorError::MalformedRequest),
+        }
+    }
+
+    /// Decodes a `u16` field
+    pub fn next_u16(&mut self) -> Result<u16, CommandProcessorError> {
+        u16::try_from(self.next_u32()?).map_err(|_| CommandProcessorError::MalformedRequest)
+    }
+
+    /// Decodes a `u32` field
+    pub fn next_u32(&mut self) -> Result<u32, CommandProcessorError> {
+        self.decoder
+            .varint()
+            .ok_or(CommandProcessorError::MalformedRequest)
+    }
+
+    /// Decodes an `i32` field
+    pub fn next_i32(&mut self) -> Result<i32, CommandProcessorError> {
+        let zigzag = self.next_u32()?;
+        Ok((zigzag >> 1) as i32 ^ ((zigzag & 1) as i32).wrapping_neg())
+    }
+
+    /// Decodes an `f32` field
+    pub fn next_f32(&mut self) -> Result<f32, CommandProcessorError> {
+        let (bytes, rest) = self
+            .decoder
+            .bytes
+            .split_first_chunk::<4>()
+            .ok_or(CommandProcessorError::MalformedRequest)?;
+        self.decoder.bytes = rest;
+        Ok(f32::from_le_bytes(*bytes))
+    }
+
+    /// Decodes a `&str` field
+    pub fn next_str(&mut self) -> Result<&'r str, CommandProcessorError> {
+        self.decoder
+            .str()
+            .ok_or(CommandProcessorError::MalformedRequest)
+    }
+
+    /// Returns the bytes left after the fields decoded so far
+    pub fn remaining(&self) -> &'r [u8] {
+        self.decoder.bytes
+    }
+
+    /// Checks that every field was decoded
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If no bytes are left
+    /// * `Err(CommandProcessorError::MalformedRequest)` - If bytes are left
+    ///
+    pub fn finish(self) -> Result<(), CommandProcessorError> {
+        match self.decoder.bytes.is_empty() {
+            true => Ok(()),
+            false => Err(CommandProcessorError::MalformedRequest),
+        }
+    }
+}
+
+/// Writes postcard encoded values to a response
+struct Encoder<'b> {
+    buffer: &'b mut [u8],
+    len: usize,
+}
+
+impl Encoder<'_> {
+    fn byte(&mut self, byte: u8) -> Result<(), CommandProcessorError> {
+        let slot = self
+            .buffer
+            .get_mut(self.len)
+            .ok_or(CommandProcessorError::WriteError)?;
+        *slot = byte;
+        self.len = self.len.saturating_add(1);
+        Ok(())
+    }
+
+    fn varint(&mut self, mut value: u64) -> Result<(), CommandProcessorError> {
+        while value >= 0x80 {
+            self.byte((value & 0x7f) as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.byte(value as u8)
+    }
+
+    fn str(&mut self, s: &str) -> Result<(), CommandProcessorError> {
+        self.varint(s.len() as u64)?;
+        let end = self.len.saturating_add(s.len());
+        self.buffer
+            .get_mut(self.len..end)
+            .ok_or(CommandProcessorError::WriteError)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Processes a binary request and encodes its response
+    ///
+    /// For products where the console is only driven by machines, requests
+    /// and responses are structs encoded with postcard, so hosts can use
+    /// `postcard` and `serde` to talk to the device. Requests are dispatched
+    /// to the same command table as text lines, with the same checks and
+    /// hooks, but without the built-ins. The request is:
+    ///
+    /// ```ignore
+    /// struct Request<'r> {
+    ///     command: &'r str,
+    ///     args: Vec<&'r str>,
+    /// }
+    /// ```
+    ///
+    /// and the response, whose `Ok` holds the code of the command's status,
+    /// see `Status::code`, and whose `Err` holds the error's message, see
+    /// `set_messages`:
+    ///
+    /// ```ignore
+    /// struct Response<'r> {
+    ///     result: Result<u32, &'r str>,
+    ///     output: &'r str,
+    /// }
+    /// ```
+    ///
+    /// The codes of `ReturnCode` are its variant indexes, so hosts can decode
+    /// them as a `ReturnCode` too.
+    ///
+    /// A request with an empty command is followed by the command's ID, see
+    /// `command_id`, so hosts can send opcodes instead of names:
+    ///
+    /// ```ignore
+    /// struct OpcodeRequest<'r> {
+    ///     command: &'r str, // ""
+    ///     id: u16,
+    ///     args: Vec<&'r str>,
+    /// }
+    /// ```
+    ///
+    /// The `CAPABILITIES_ID` request has no arguments and its output lists
+    /// the IDs, see `write_capabilities`. Requests for commands with a binary
+    /// handler hold the handler's arguments struct instead of `args`, see
+    /// `set_binary_handler`.
+    ///
+    /// The command's output is captured up to `BINARY_OUTPUT_SIZE` bytes,
+    /// output that doesn't fit fails the command with `WriteError`.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The encoded request
+    /// * `response` - The buffer to encode the response into
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The length of the encoded response
+    /// * `Err(CommandProcessorError::WriteError)` - If the response doesn't fit
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::CommandProcessor;
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// let mut response = [0u8; 64];
+    ///
+    /// let len = command_processor
+    ///     .process_binary(b"\x04boot\x00", &mut response)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(&response[..len], b"\x01\x11command not found\x00");
+    /// ```
+    ///
+    pub fn process_binary(
+        &mut self,
+        request: &[u8],
+        response: &mut [u8],
+    ) -> Result<usize, CommandProcessorError> {
+        self.context = None;
+        match self.scratch.take() {
+            Some(scratch) => {
+                let mut capture = SliceWriter::new(scratch);
+                let result = self.execute_binary(request, &mut capture);
+                let len = self.encode_response(result, &capture, response);
+                self.scratch = Some(capture.into_inner());
+                len
+            }
+            None => {
+                let mut capture = SliceWriter::new([0u8; BINARY_OUTPUT_SIZE]);
+                let result = self.execute_binary(request, &mut capture);
+                self.encode_response(result, &capture, response)
+            }
+        }
+    }
+
+    /// Sets the sink for binary responses, enabling binary frames in `feed`
+    ///
+    /// With a sink set, a `0xC0` byte at the start of a line starts a SLIP
+    /// framed binary request instead of a text line, so a single link serves
+    /// both operators and machines without a mode switch. The frame ends at
+    /// the next `0xC0`, its request is processed like with `process_binary`
+    /// and the response is sent SLIP framed to the sink. Text lines keep
+    /// being answered through the writer.
+    ///
+    /// Frames longer than `BINARY_FRAME_SIZE` bytes are answered with
+    /// `LineTooLong`. A `BINARY_PING` frame is answered with itself without
+    /// running anything, so hosts can keep the link alive and detect when it
+    /// is dead, see also `last_activity_ms`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - The sink sending binary responses
+    ///
+    #[cfg(feature = "input")]
+    pub fn set_binary_sink(&mut self, sink: BinarySink) {
+        self.binary_sink = Some(sink);
+    }
+
+    /// Feeds a byte to the binary frame being received
+    ///
+    /// Returns whether the byte belongs to a binary frame.
+    #[cfg(feature = "input")]
+    pub(crate) fn feed_frame(&mut self, byte: u8) -> Result<bool, CommandProcessorError> {
+        let sink = match self.binary_sink {
+            Some(sink) => sink,
+            None => return Ok(false),
+        };
+
+        let pushed = match (self.frame_state, byte) {
+            (FrameState::Text, SLIP_END) if self.line.is_empty() && !self.line_overflowed => {
+                self.frame_state = FrameState::Frame;
+                return Ok(true);
+            }
+            (FrameState::Text, _) => return Ok(false),
+            // Empty frames are allowed between frames, as in SLIP
+            (FrameState::Frame, SLIP_END) if self.frame.is_empty() => return Ok(true),
+            (_, SLIP_END) => {
+                self.end_frame(sink)?;
+                return Ok(true);
+            }
+            (FrameState::Discarding, _) => return Ok(true),
+            (FrameState::Escaped, SLIP_ESC_END) => self.frame.push(SLIP_END),
+            (FrameState::Escaped, SLIP_ESC_ESC) => self.frame.push(SLIP_ESC),
+            (FrameState::Frame, SLIP_ESC) => {
+                self.frame_state = FrameState::Escaped;
+                return Ok(true);
+            }
+            (_, byte) => self.frame.push(byte),
+        };
+
+        self.frame_state = match pushed {
+            Ok(()) => FrameState::Frame,
+            Err(_) => FrameState::Discarding,
+        };
+        Ok(true)
+    }
+
+    #[cfg(feature = "input")]
+    fn end_frame(&mut self, sink: BinarySink) -> Result<(), CommandProcessorError> {
+        let state = core::mem::replace(&mut self.frame_state, FrameState::Text);
+        let frame = core::mem::take(&mut self.frame);
+        let mut response = [0u8; BINARY_RESPONSE_SIZE];
+
+        let len = match state {
+            FrameState::Discarding => self.encode_response(
+                Err(CommandProcessorError::LineTooLong),
+                &SliceWriter::new([0u8; 0]),
+                &mut response,
+            ),
+            _ if frame.as_slice() == BINARY_PING => response
+                .get_mut(..BINARY_PING.len())
+                .map(|pong| {
+                    pong.copy_from_slice(BINARY_PING);
+                    BINARY_PING.len()
+                })
+                .ok_or(CommandProcessorError::WriteError),
+            _ => self.process_binary(&frame, &mut response),
+        };
+
+        self.frame = frame;
+        self.frame.clear();
+
+        let mut rest = response.get(..len?).unwrap_or(&[]);
+        let mut sent = sink(&[SLIP_END]);
+        while let Some(special) = rest.iter().position(|&b| b == SLIP_END || b == SLIP_ESC) {
+            let escape = match rest.get(special) {
+                Some(&SLIP_END) => [SLIP_ESC, SLIP_ESC_END],
+                _ => [SLIP_ESC, SLIP_ESC_ESC],
+            };
+            sent &= sink(rest.get(..special).unwrap_or(&[])) && sink(&escape);
+            rest = rest.get(special.saturating_add(1)..).unwrap_or(&[]);
+        }
+        sent &= sink(rest) && sink(&[SLIP_END]);
+
+        match sent {
+            true => Ok(()),
+            false => Err(CommandProcessorError::WriteError),
+        }
+    }
+
+    /// Sets the ID of a command in binary requests, instead of the hash of its name
+    ///
+    /// For commands whose opcode is fixed by an existing protocol, or whose
+    /// name's hash is the ID of another command. The ID should be a constant
+    /// of the firmware, so it doesn't change with the order commands are
+    /// registered in.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command
+    /// * `id` - The command's ID
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was updated successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If another command has the ID
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the ID is `CAPABILITIES_ID`
+    ///
+    pub fn set_command_id(&mut self, command: &str, id: u16) -> Result<(), CommandProcessorError> {
+        if id == CAPABILITIES_ID {
+            return Err(CommandProcessorError::InvalidArguments);
+        }
+        if self
+            .commands
+            .iter()
+            .any(|cmd| cmd.id == id && cmd.command != command)
+        {
+            return Err(CommandProcessorError::CommandAlreadyExists);
+        }
+        self.find_command_mut(command)?.id = id;
+        Ok(())
+    }
+
+    /// Sets the handler of a command's binary requests, taking typed arguments
+    ///
+    /// Binary requests for the command then hold its arguments struct as
+    /// postcard encodes it, rather than text arguments, so hosts send the
+    /// same struct as for any other postcard protocol:
+    ///
+    /// ```ignore
+    /// struct TypedRequest<'r, T> {
+    ///     command: &'r str,
+    ///     args: T,
+    /// }
+    /// ```
+    ///
+    /// or `""` and the command's ID, followed by the struct. The handler
+    /// decodes the fields with `BinaryArgs`, and text lines keep running the
+    /// command's callback. Requests go through the same checks and hooks as
+    /// the callback's, but for those of text arguments: the schema, the
+    /// validator and the maximum number of arguments. They can't hold an
+    /// arming token, so commands requiring arming fail with `NotArmed`, and
+    /// dry runs fail with `DryRunUnsupported`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the binary handler for
+    /// * `handler` - The handler, decoding the command's arguments struct
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the handler was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{
+    ///     Args, BinaryArgs, CommandCallbackReturn, CommandProcessor, CommandProcessorError, ReturnCode,
+    /// };
+    ///
+    /// fn dim<'a>(mut args: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     let _level: u16 = args.next_parse()?;
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// // struct Dim { level: u16 }
+    /// fn dim_binary<'a>(mut args: BinaryArgs, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     let level = args.next_u16()?;
+    ///     args.finish()?;
+    ///
+    ///     let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+    ///     write!(writer, "{}", level).map_err(|_| CommandProcessorError::WriteError)?;
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("dim", dim, None).unwrap();
+    /// command_processor.set_binary_handler("dim", dim_binary).unwrap();
+    ///
+    /// let mut response = [0u8; 64];
+    /// let len = command_processor
+    ///     .process_binary(b"\x03dim\xac\x02", &mut response)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(&response[..len], b"\x00\x00\x03300");
+    /// ```
+    ///
+    pub fn set_binary_handler(
+        &mut self,
+        command: &str,
+        handler: BinaryCallback<'a, R>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.binary_callback = Some(handler);
+        Ok(())
+    }
+
+    /// Runs a command's binary handler with the postcard encoded arguments
+    pub(crate) fn run_typed(
+        &mut self,
+        handler: Handler<'a, C, R>,
+        payload: &[u8],
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let session = self.session;
+        let args = Args::new(&[])
+            .with_session(session.as_ref())
+            .with_payload(payload);
+        self.run_args(handler, args, context, writer)
+    }
+
+    /// Writes the ID of every command, one `<id> <name>` line per command
+    ///
+    /// Hidden commands are listed too, as the list is meant for host
+    /// tooling mapping names to opcodes. The list is the output of the
+    /// `CAPABILITIES_ID` binary request and of the `capabilities` built-in,
+    /// which a command registered as `capabilities` takes precedence over.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to write the list to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the list was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the list failed to write
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// fn demo<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("led", demo, None).unwrap();
+    /// command_processor.add_command("reset", demo, None).unwrap();
+    /// command_processor.set_command_id("reset", 0x10).unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.write_capabilities(&mut writer).unwrap();
+    ///
+    /// let led = command_processor::command_id("led");
+    /// assert_eq!(writer, format!("{} led\n16 reset\n", led));
+    /// ```
+    ///
+    pub fn write_capabilities(&self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        for cmd in &self.commands {
+            self.output
+                .write(writer, format_args!("{} {}\n", cmd.id, cmd.command))?;
+        }
+        Ok(())
+    }
+
+    /// Runs the capabilities built-in, see `write_capabilities`
+    #[cfg(feature = "builtins")]
+    pub(crate) fn capabilities(
+        &self,
+        mut tokens: Tokens,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        if tokens.next().is_some() {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        tokens.check()?;
+        self.write_capabilities(writer.ok_or(CommandProcessorError::NoWriter)?)?;
+        Ok(ReturnCode::Success)
+    }
+
+    fn execute_binary<B: AsRef<[u8]> + AsMut<[u8]> + 'a>(
+        &mut self,
+        request: &[u8],
+        capture: &mut SliceWriter<B>,
+    ) -> Result<R, CommandProcessorError> {
+        let mut decoder = Decoder { bytes: request };
+        let mut name: String<LINE_SIZE> = String::new();
+        let command = match decoder.str() {
+            Some("") => {
+                let id = decoder
+                    .varint()
+                    .and_then(|id| u16::try_from(id).ok())
+                    .ok_or(CommandProcessorError::MalformedRequest)?;
+                if id == CAPABILITIES_ID {
+                    if decoder.bytes != [0] {
+                        return Err(CommandProcessorError::MalformedRequest);
+                    }
+                    self.write_capabilities(capture)?;
+                    return Ok(R::from(ReturnCode::Success));
+                }
+                let cmd = self
+                    .commands
+                    .iter()
+                    .find(|cmd| cmd.id == id)
+                    .ok_or(CommandProcessorError::CommandNotFound)?;
+                name.push_str(&cmd.command)
+                    .map_err(|_| CommandProcessorError::CommandNotFound)?;
+                name.as_str()
+            }
+            Some(command) => command,
+            None => return Err(CommandProcessorError::MalformedRequest),
+        };
+
+        // The rest of the request is the arguments struct of binary handlers
+        let typed = self
+            .commands
+            .iter()
+            .any(|cmd| cmd.command == command && cmd.binary_callback.is_some());
+        let mut args: Vec<&str, MAX_ARGS> = Vec::new();
+        if !typed {
+            let count = decoder
+                .varint()
+                .ok_or(CommandProcessorError::MalformedRequest)?;
+            for _ in 0..count {
+                let arg = decoder
+                    .str()
+                    .ok_or(CommandProcessorError::MalformedRequest)?;
+                args.push(arg)
+                    .map_err(|_| CommandProcessorError::InvalidArguments)?;
+            }
+
+            if !decoder.bytes.is_empty() {
+                return Err(CommandProcessorError::MalformedRequest);
+            }
+        }
+
+        #[cfg(feature = "builtins")]
+        self.check_lock(command, args.first().copied())?;
+
+        let input = match typed {
+            true => Input::Typed(decoder.bytes),
+            false => Input::Text(&args),
+        };
+        let result = self.execute_input(command, input, self.dry_run, Some(capture));
+        match result {
+            Ok(_) if capture.overflowed() => Err(CommandProcessorError::WriteError),
+            result => result,
+        }
+    }
+
+    fn encode_response<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &self,
+        result: Result<R, CommandProcessorError>,
+        capture: &SliceWriter<B>,
+        response: &mut [u8],
+    ) -> Result<usize, CommandProcessorError> {
+        let mut encoder = Encoder {
+            buffer: response,
+            len: 0,
+        };
+
+        match result {
+            Ok(status) => encoder
+                .varint(0)
+                .and_then(|_| encoder.varint(status.code().into())),
+            Err(e) => encoder
+                .varint(1)
+                .and_then(|_| encoder.str(e.message(self.messages))),
+        }?;
+        encoder.str(capture.as_str())?;
+
+        Ok(encoder.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, ReturnCode};
+    use core::fmt::Write;
+
+    fn add<'a>(mut args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        let mut sum: u32 = 0;
+        while !args.remaining().is_empty() {
+            sum = sum.saturating_add(args.next_parse()?);
+        }
+        write!(writer, "{}", sum).map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    /// Takes `struct Move { axis: &str, steps: i32, speed: f32 }`
+    fn move_binary<'a>(
+        mut args: BinaryArgs,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> CommandCallbackReturn<'a> {
+        let (axis, steps, speed) = (args.next_str()?, args.next_i32()?, args.next_f32()?);
+        args.finish()?;
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        write!(writer, "{} {} {}", axis, steps, speed)
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_binary_handler() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("move", add, None).is_ok());
+        assert!(command_processor
+            .set_binary_handler("move", move_binary)
+            .is_ok());
+        assert!(matches!(
+            command_processor.set_binary_handler("jog", move_binary),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        // By name and by ID, the steps zigzag encoded
+        let mut response = [0u8; 32];
+        let len = command_processor
+            .process_binary(b"\x04move\x01x\x05\x00\x00\x20\x40", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x00\x00\x08x -3 2.5");
+        assert!(command_processor.set_command_id("move", 0x10).is_ok());
+        let len = command_processor
+            .process_binary(b"\x00\x10\x01y\x04\x00\x00\x80\x3f", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x00\x00\x05y 2 1");
+
+        for request in [
+            &b"\x04move\x01x\x05"[..],
+            b"\x04move\x01x\x05\x00\x00\x20\x40\x00",
+        ] {
+            let len = command_processor
+                .process_binary(request, &mut response)
+                .unwrap();
+            assert_eq!(&response[..len], b"\x01\x11malformed request\x00");
+        }
+
+        // Text lines keep running the callback
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .process_command("move 1 2", Some(&mut writer))
+            .is_ok());
+        assert_eq!(writer, "3");
+
+        // Through the same checks as other requests
+        assert!(command_processor.set_enabled("move", false).is_ok());
+        let len = command_processor
+            .process_binary(b"\x04move\x01x\x05\x00\x00\x20\x40", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x01\x10command disabled\x00");
+    }
+
+    #[test]
+    fn test_varint() {
+        let mut buffer = [0u8; 8];
+        let mut encoder = Encoder {
+            buffer: &mut buffer,
+            len: 0,
+        };
+        assert!(encoder.varint(300).is_ok());
+        assert_eq!(&buffer[..2], [0xac, 0x02]);
+
+        let mut decoder = Decoder {
+            bytes: &[0xac, 0x02],
+        };
+        assert_eq!(decoder.varint(), Some(300));
+        assert_eq!(Decoder { bytes: &[0x80] }.varint(), None);
+    }
+
+    #[test]
+    fn test_process_binary() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("add", add, None).is_ok());
+
+        let mut response = [0u8; 32];
+        let len = command_processor
+            .process_binary(b"\x03add\x02\x0240\x012", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x00\x00\x0242");
+
+        let len = command_processor
+            .process_binary(b"\x03add\x01\x01x", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x01\x11invalid arguments\x00");
+
+        let len = command_processor
+            .process_binary(b"\x03add\x02\x01", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x01\x11malformed request\x00");
+
+        assert!(matches!(
+            command_processor.process_binary(b"\x03add\x00", &mut [0u8; 2]),
+            Err(CommandProcessorError::WriteError)
+        ));
+
+        // Binary requests are refused once shutting down, as lines are
+        assert!(command_processor.shutdown().is_ok());
+        let len = command_processor
+            .process_binary(b"\x03add\x02\x0240\x012", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x01\x0dshutting down\x00");
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Response {
+        Ack,
+        Nack,
+        Busy,
+    }
+
+    impl From<ReturnCode> for Response {
+        fn from(code: ReturnCode) -> Self {
+            match code {
+                ReturnCode::Success => Response::Ack,
+                ReturnCode::Failure => Response::Nack,
+            }
+        }
+    }
+
+    impl Status for Response {
+        fn is_success(&self) -> bool {
+            *self == Response::Ack
+        }
+
+        fn code(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    fn busy<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a, Response> {
+        Ok(Response::Busy)
+    }
+
+    #[test]
+    fn test_binary_status() {
+        static GERMAN: crate::Messages = crate::Messages {
+            command_not_found: "Befehl nicht gefunden",
+            ..crate::DEFAULT_MESSAGES
+        };
+
+        let mut command_processor: CommandProcessor<8, 32, 32, 32, (), Response> =
+            CommandProcessor::new();
+        assert!(command_processor.add_command("busy", busy, None).is_ok());
+        command_processor.set_messages(&GERMAN);
+
+        // The status's own code is sent, not only success or failure
+        let mut response = [0u8; 32];
+        let len = command_processor
+            .process_binary(b"\x04busy\x00", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x00\x02\x00");
+
+        let len = command_processor
+            .process_binary(b"\x04boot\x00", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x01\x15Befehl nicht gefunden\x00");
+    }
+
+    #[test]
+    #[cfg(feature = "input")]
+    fn test_binary_frames() {
+        use std::cell::RefCell;
+
+        thread_local! {
+            static SENT: RefCell<std::vec::Vec<u8>> = const { RefCell::new(std::vec::Vec::new()) };
+        }
+
+        fn sink(bytes: &[u8]) -> bool {
+            SENT.with(|sent| sent.borrow_mut().extend_from_slice(bytes));
+            true
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("add", add, None).is_ok());
+        command_processor.set_binary_sink(sink);
+
+        // A text line, then a frame whose argument holds an escaped byte
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .feed(
+                b"add 1 2\n\xc0\xc0\x03add\x01\x02\xdb\xdd\x80\xc0",
+                &mut writer
+            )
+            .is_ok());
+        assert_eq!(writer, "3OK\n");
+        SENT.with(|sent| assert_eq!(*sent.borrow(), b"\xc0\x01\x11invalid arguments\x00\xc0"));
+
+        // Text resumes after the frame, long frames are dropped
+        writer.clear();
+        SENT.with(|sent| sent.borrow_mut().clear());
+        assert!(command_processor.feed(b"add 4\n\xc0", &mut writer).is_ok());
+        assert!(command_processor.feed(&[b'x'; 100], &mut writer).is_ok());
+        assert!(command_processor.feed(b"\xc0", &mut writer).is_ok());
+        assert_eq!(writer, "4OK\n");
+        SENT.with(|sent| assert_eq!(*sent.borrow(), b"\xc0\x01\x0dline too long\x00\xc0"));
+    }
+
+    #[test]
+    #[cfg(feature = "input")]
+    fn test_binary_ping() {
+        use crate::Clock;
+        use std::cell::RefCell;
+
+        thread_local! {
+            static SENT: RefCell<std::vec::Vec<u8>> = const { RefCell::new(std::vec::Vec::new()) };
+        }
+
+        fn sink(bytes: &[u8]) -> bool {
+            SENT.with(|sent| sent.borrow_mut().extend_from_slice(bytes));
+            true
+        }
+
+        struct FixedClock;
+
+        impl Clock for FixedClock {
+            fn now_ms(&self) -> u64 {
+                1_000
+            }
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_binary_sink(sink);
+        assert_eq!(command_processor.last_activity_ms(), None);
+        command_processor.set_clock(&FixedClock);
+
+        let mut writer = std::string::String::new();
+        assert!(command_processor.feed(b"\xc0\x00\xc0", &mut writer).is_ok());
+        assert_eq!(writer, "");
+        SENT.with(|sent| assert_eq!(*sent.borrow(), b"\xc0\x00\xc0"));
+        assert_eq!(command_processor.last_activity_ms(), Some(1_000));
+    }
+
+    #[test]
+    fn test_command_ids() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("add", add, None).is_ok());
+        assert!(command_processor.add_command("sum", add, None).is_ok());
+        assert!(command_processor.set_command_id("sum", 0x81).is_ok());
+        assert!(matches!(
+            command_processor.set_command_id("add", 0x81),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+        assert!(matches!(
+            command_processor.set_command_id("add", CAPABILITIES_ID),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+
+        // Names hashing to the same ID are only added once one has another ID
+        assert_eq!(command_id("cmdaan"), command_id("cmdafp"));
+        let mut colliding: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(colliding.add_command("cmdaan", add, None).is_ok());
+        assert!(matches!(
+            colliding.add_command("cmdafp", add, None),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+        assert!(colliding.set_command_id("cmdaan", 0x82).is_ok());
+        assert!(colliding.add_command("cmdafp", add, None).is_ok());
+
+        // By opcode, the ID being a varint
+        let mut response = [0u8; 64];
+        let len = command_processor
+            .process_binary(b"\x00\x81\x01\x02\x011\x012", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x00\x00\x013");
+        let len = command_processor
+            .process_binary(b"\x00\x05\x00", &mut response)
+            .unwrap();
+        assert_eq!(&response[..len], b"\x01\x11command not found\x00");
+
+        let len = command_processor
+            .process_binary(b"\x00\x00\x00", &mut response)
+            .unwrap();
+        let listing = std::format!("{} add\n129 sum\n", command_id("add"));
+        assert_eq!(response.get(..3), Some(&[0, 0, listing.len() as u8][..]));
+        assert_eq!(response.get(3..len), Some(listing.as_bytes()));
+    }
+}
@@ -0,0 +1,275 @@
+// This module contains the builder for processor configuration
+#[cfg(feature = "framing")]
+use crate::Checksum;
+#[cfg(feature = "builtins")]
+use crate::Storage;
+use crate::{
+    Clock, CommandProcessor, CommandProcessorError, DuplicatePolicy, FlushCallback, LegacyCommand,
+    Messages, ReturnCode, Status, WriteErrorPolicy,
+};
+#[cfg(feature = "input")]
+use crate::{LineEnding, Prompt};
+
+/// A builder configuring a command processor in one place
+///
+/// `CommandProcessor::new` creates a processor with the default
+/// configuration, the builder sets everything up front and checks that the
+/// settings are consistent before producing the processor.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{CommandProcessor, WriteErrorPolicy};
+///
+/// let command_processor: CommandProcessor<8, 32> = CommandProcessor::builder()
+///     .strict(true)
+///     .write_error_policy(WriteErrorPolicy::SkipLine)
+///     .build()
+///     .unwrap();
+///
+/// // Retrying needs a flush callback
+/// assert!(CommandProcessor::<8, 32>::builder()
+///     .write_error_policy(WriteErrorPolicy::Retry)
+///     .build()
+///     .is_err());
+/// ```
+///
+pub struct CommandProcessorBuilder<
+    'a,
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize = 32,
+    const LINE_SIZE: usize = 32,
+    C = (),
+    R = ReturnCode,
+> {
+    command_processor:
+        CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Returns a builder starting from the default configuration
+    pub fn builder(
+    ) -> CommandProcessorBuilder<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R> {
+        CommandProcessorBuilder {
+            command_processor: Self::new(),
+        }
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessorBuilder<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Rejects arguments beyond a command's declared maximum, see `set_strict`
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.command_processor.strict = strict;
+        self
+    }
+
+    /// Stops scripts at their first failing line, see `set_abort_on_error`
+    #[cfg(feature = "builtins")]
+    pub fn abort_on_error(mut self, abort_on_error: bool) -> Self {
+        self.command_processor.abort_on_error = abort_on_error;
+        self
+    }
+
+    /// Starts the session in dry-run mode, see `set_dry_run`
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.command_processor.dry_run = dry_run;
+        self
+    }
+
+    /// Sets the message catalog, see `set_messages`
+    pub fn messages(mut self, messages: &'a Messages) -> Self {
+        self.command_processor.messages = messages;
+        self
+    }
+
+    /// Sets the time source, see `set_clock`
+    pub fn clock(mut self, clock: &'a (dyn Clock + Sync)) -> Self {
+        self.command_processor.clock = Some(clock);
+        self
+    }
+
+    /// Sets the storage of the script built-in, see `set_storage`
+    #[cfg(feature = "builtins")]
+    pub fn storage(mut self, storage: &'a mut (dyn Storage + Send)) -> Self {
+        self.command_processor.storage = Some(storage);
+        self
+    }
+
+    /// Sets how long an arming stays valid, see `set_arm_window`
+    pub fn arm_window(mut self, window_ms: u64) -> Self {
+        self.command_processor.arm_window_ms = window_ms;
+        self
+    }
+
+    /// Sets the deprecated command translations, see `set_legacy_commands`
+    pub fn legacy_commands(mut self, legacy_commands: &'a [LegacyCommand]) -> Self {
+        self.command_processor.legacy_commands = legacy_commands;
+        self
+    }
+
+    /// Sets the character starting a comment, see `set_comment_char`
+    pub fn comment_char(mut self, comment: Option<char>) -> Self {
+        self.command_processor.comment = comment;
+        self
+    }
+
+    /// Sets the characters separating tokens, see `set_delimiters`
+    pub fn delimiters(mut self, delimiters: Option<&'a [char]>) -> Self {
+        self.command_processor.delimiters = delimiters;
+        self
+    }
+
+    /// Sets what adding an already registered command does, see `set_duplicate_policy`
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.command_processor.duplicate_policy = policy;
+        self
+    }
+
+    /// Sets the terminal width used by `help -s`, see `set_terminal_width`
+    #[cfg(feature = "help")]
+    pub fn terminal_width(mut self, terminal_width: usize) -> Self {
+        self.command_processor.terminal_width = terminal_width;
+        self
+    }
+
+    /// Sets the write error policy, see `set_write_error_policy`
+    pub fn write_error_policy(mut self, policy: WriteErrorPolicy) -> Self {
+        self.command_processor.output.policy = policy;
+        self
+    }
+
+    /// Sets the flush callback, see `set_flush_callback`
+    pub fn flush_callback(mut self, flush: FlushCallback) -> Self {
+        self.command_processor.output.flush = Some(flush);
+        self
+    }
+
+    /// Sets the response checksum, see `set_response_checksum`
+    #[cfg(feature = "framing")]
+    pub fn response_checksum(mut self, checksum: Option<Checksum>) -> Self {
+        self.command_processor.response_checksum = checksum;
+        self
+    }
+
+    /// Sets the input checksum, see `set_input_checksum`
+    #[cfg(feature = "framing")]
+    pub fn input_checksum(mut self, checksum: Option<Checksum>) -> Self {
+        self.command_processor.input_checksum = checksum;
+        self
+    }
+
+    /// Sets the prompt of the terminal, see `set_prompt`
+    #[cfg(feature = "input")]
+    pub fn prompt(mut self, prompt: Option<Prompt<'a>>) -> Self {
+        self.command_processor.prompt = prompt;
+        self
+    }
+
+    /// Sets whether the characters received are echoed back, see `set_echo`
+    #[cfg(feature = "input")]
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.command_processor.echo = echo;
+        self
+    }
+
+    /// Sets the bytes ending a line, see `set_line_ending`
+    #[cfg(feature = "input")]
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.command_processor.line_ending = line_ending;
+        self
+    }
+
+    /// Sets the shared scratch buffer, see `set_scratch_buffer`
+    pub fn scratch_buffer(mut self, scratch: &'a mut [u8]) -> Self {
+        self.command_processor.scratch = Some(scratch);
+        self
+    }
+
+    /// Sets the buffer of the cached command output, see `set_cache_buffer`
+    pub fn cache_buffer(mut self, buffer: &'a mut [u8]) -> Self {
+        self.command_processor.set_cache_buffer(buffer);
+        self
+    }
+
+    /// Checks the configuration and produces the processor
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(CommandProcessor)` - The configured processor
+    /// * `Err(CommandProcessorError::InvalidConfiguration)` - If the write
+    ///   error policy is `Retry` without a flush callback, or the terminal
+    ///   width is zero
+    ///
+    pub fn build(
+        self,
+    ) -> Result<
+        CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+        CommandProcessorError,
+    > {
+        let command_processor = self.command_processor;
+
+        if command_processor.output.policy == WriteErrorPolicy::Retry
+            && command_processor.output.flush.is_none()
+        {
+            return Err(CommandProcessorError::InvalidConfiguration);
+        }
+
+        #[cfg(feature = "help")]
+        if command_processor.terminal_width == 0 {
+            return Err(CommandProcessorError::InvalidConfiguration);
+        }
+
+        Ok(command_processor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CommandProcessor, CommandProcessorError, Messages, WriteErrorPolicy, DEFAULT_MESSAGES,
+    };
+
+    #[test]
+    fn test_builder() {
+        static MESSAGES: Messages = Messages {
+            ok: "done",
+            ..DEFAULT_MESSAGES
+        };
+
+        let command_processor: CommandProcessor<8, 32> = CommandProcessor::builder()
+            .dry_run(true)
+            .messages(&MESSAGES)
+            .flush_callback(|| true)
+            .build()
+            .unwrap();
+
+        assert!(command_processor.is_dry_run());
+        assert_eq!(command_processor.messages().ok, "done");
+
+        assert!(matches!(
+            CommandProcessor::<8, 32>::builder()
+                .write_error_policy(WriteErrorPolicy::Retry)
+                .build(),
+            Err(CommandProcessorError::InvalidConfiguration)
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+// This module contains the cache replaying the output of read-only commands
+use crate::{CommandProcessor, CommandProcessorError, Handler, SliceWriter, Status};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The output of the last cacheable command that ran
+pub(crate) struct Cache<'a, const LINE_SIZE: usize, R> {
+    output: SliceWriter<&'a mut [u8]>,
+    entry: Option<Entry<LINE_SIZE, R>>,
+}
+
+/// What the cached output was produced by
+struct Entry<const LINE_SIZE: usize, R> {
+    /// The command name and its arguments, see `command_key`
+    key: String<LINE_SIZE>,
+    /// When the command ran
+    at_ms: u64,
+    result: R,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets the buffer holding the output of the last cacheable command, see `set_cache_ttl`
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer, at least as large as the output of the cacheable commands
+    ///
+    pub fn set_cache_buffer(&mut self, buffer: &'a mut [u8]) {
+        self.cache = Some(Cache {
+            output: SliceWriter::new(buffer),
+            entry: None,
+        });
+    }
+
+    /// Sets how long the output of a command is replayed instead of running it again
+    ///
+    /// Meant for read-only commands that are expensive to run, such as a
+    /// `status` command reading every sensor, when a host polls them faster
+    /// than their output changes. Running the command again with the same
+    /// arguments within the time to live replays its output and result.
+    ///
+    /// Only the last command that ran is cached, in the buffer set with
+    /// `set_cache_buffer`, and caching requires a clock to be set. Output
+    /// that doesn't fit in the buffer fails with
+    /// `CommandProcessorError::WriteError` and isn't cached, nor are failed
+    /// commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to cache
+    /// * `ttl_ms` - The time to live of its output in milliseconds, 0 to disable
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the time to live was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+    /// use command_processor::{Args, Clock, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// static READS: AtomicU32 = AtomicU32::new(0);
+    ///
+    /// fn status<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     // Read every sensor
+    ///     let reads = READS.fetch_add(1, Ordering::Relaxed) + 1;
+    ///     if let Some(writer) = writer {
+    ///         let _ = writeln!(writer, "reads={}", reads);
+    ///     }
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// struct Ticks(AtomicU64);
+    ///
+    /// impl Clock for Ticks {
+    ///     fn now_ms(&self) -> u64 {
+    ///         self.0.load(Ordering::Relaxed)
+    ///     }
+    /// }
+    ///
+    /// let ticks = Ticks(AtomicU64::new(0));
+    /// let mut buffer = [0u8; 64];
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_clock(&ticks);
+    /// command_processor.set_cache_buffer(&mut buffer);
+    /// command_processor.add_command("status", status, None).unwrap();
+    /// command_processor.set_cache_ttl("status", 1_000).unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.process_command("status", Some(&mut writer)).unwrap();
+    /// ticks.0.store(500, Ordering::Relaxed);
+    /// command_processor.process_command("status", Some(&mut writer)).unwrap();
+    /// ticks.0.store(1_000, Ordering::Relaxed);
+    /// command_processor.process_command("status", Some(&mut writer)).unwrap();
+    ///
+    /// assert_eq!(writer, "reads=1\nreads=1\nreads=2\n");
+    /// ```
+    ///
+    pub fn set_cache_ttl(
+        &mut self,
+        command: &str,
+        ttl_ms: u64,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.cache_ttl_ms = ttl_ms;
+        Ok(())
+    }
+
+    /// Forgets the cached output, after a command changed what it reports for instance
+    pub fn clear_cache(&mut self) {
+        if let Some(cache) = self.cache.as_mut() {
+            cache.entry = None;
+        }
+    }
+
+    /// Returns the key and time to live the output of a command run is cached with
+    ///
+    /// `None` if the run isn't cached, or if its arguments don't fit in a key.
+    pub(crate) fn cache_key(
+        &self,
+        name: &str,
+        args: &[&str],
+        ttl_ms: u64,
+    ) -> Option<(String<LINE_SIZE>, u64)> {
+        if ttl_ms == 0 || self.cache.is_none() || self.clock.is_none() {
+            return None;
+        }
+        Some((command_key(name, args)?, ttl_ms))
+    }
+
+    /// Replays the cached output of a command run, or runs it and caches its output
+    ///
+    /// Runs the command as usual without a cache key.
+    pub(crate) fn run_cached(
+        &mut self,
+        key: Option<(String<LINE_SIZE>, u64)>,
+        handler: Handler<'a, C, R>,
+        args: &[&str],
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let Some((key, ttl_ms)) = key else {
+            return self.run(handler, args, context, writer);
+        };
+        let Some(mut cache) = self.cache.take() else {
+            return self.run(handler, args, context, writer);
+        };
+        let now = self.clock.map_or(0, |clock| clock.now_ms());
+
+        let fresh = cache
+            .entry
+            .as_ref()
+            .filter(|entry| entry.key == key && now.saturating_sub(entry.at_ms) < ttl_ms)
+            .map(|entry| entry.result);
+        let result = match fresh {
+            Some(result) => Ok(result),
+            None => {
+                cache.entry = None;
+                cache.output.clear();
+                // Commands may ignore write errors, so check for overflows here
+                let result = match self.run(handler, args, context, Some(&mut cache.output)) {
+                    Ok(_) if cache.output.overflowed() => Err(CommandProcessorError::WriteError),
+                    result => result,
+                };
+                if let Ok(result) = result {
+                    cache.entry = Some(Entry {
+                        key,
+                        at_ms: now,
+                        result,
+                    });
+                }
+                result
+            }
+        };
+
+        let written = match writer {
+            Some(writer) => writer
+                .write_str(cache.output.as_str())
+                .map_err(|_| CommandProcessorError::WriteError),
+            None => Ok(()),
+        };
+        self.cache = Some(cache);
+        written.and(result)
+    }
+}
+
+/// Returns a key telling a command run from runs with other arguments
+///
+/// The command name and its arguments, separated by NUL characters, or
+/// `None` if they don't fit.
+pub(crate) fn command_key<const LINE_SIZE: usize>(
+    name: &str,
+    args: &[&str],
+) -> Option<String<LINE_SIZE>> {
+    let mut key = String::new();
+    key.push_str(name).ok()?;
+    for arg in args {
+        key.push('\0').ok()?;
+        key.push_str(arg).ok()?;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestClock;
+    use crate::{Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, ReturnCode};
+
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static RUNS: AtomicU32 = AtomicU32::new(0);
+
+    fn echo<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        RUNS.fetch_add(1, Ordering::Relaxed);
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        for arg in args.remaining() {
+            write!(writer, "{} ", arg).map_err(|_| CommandProcessorError::WriteError)?;
+        }
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_cache() {
+        let clock = TestClock::new(0);
+        let mut buffer = [0u8; 8];
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_clock(&clock);
+        command_processor.set_cache_buffer(&mut buffer);
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+        assert!(command_processor.set_cache_ttl("echo", 100).is_ok());
+
+        let mut run = |line: &str| {
+            let mut writer = std::string::String::new();
+            let result = command_processor.process_command(line, Some(&mut writer));
+            (result, writer, RUNS.load(Ordering::Relaxed))
+        };
+
+        assert_eq!(run("echo a").1, "a ");
+        assert_eq!(run("echo a").2, 1);
+        // Other arguments aren't replayed
+        assert_eq!(run("echo b").1, "b ");
+        assert_eq!(run("echo b").2, 2);
+        clock.set(100);
+        assert_eq!(run("echo b").2, 3);
+
+        // Output that doesn't fit isn't cached
+        let (result, output, _) = run("echo long output");
+        assert!(matches!(result, Err(CommandProcessorError::WriteError)));
+        assert!(output.starts_with("long "));
+        assert_eq!(run("echo long output").2, 5);
+    }
+}
@@ -0,0 +1,326 @@
+// This module contains the checksums appended to responses in machine mode
+use crate::{CommandProcessor, CommandProcessorError, SliceWriter, Status};
+
+use core::fmt::{self, Write};
+use heapless::String;
+
+/// The size of the buffer capturing a command's output before it's checksummed
+///
+/// Applies when no scratch buffer is set, otherwise the scratch buffer's size
+/// applies.
+pub const CHECKSUM_BUFFER_SIZE: usize = 256;
+
+/// The checksum appended to each response line
+///
+/// The checksum of the line's bytes, excluding its line ending, is appended
+/// as `*` followed by two uppercase hexadecimal digits, as in NMEA 0183.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Checksum {
+    /// The XOR of every byte
+    Xor,
+    /// CRC-8 with polynomial `0x07` and initial value `0`, as used by SMBus
+    Crc8,
+}
+
+impl Checksum {
+    /// Adds a byte to a running checksum
+    fn update(self, checksum: u8, byte: u8) -> u8 {
+        match self {
+            Checksum::Xor => checksum ^ byte,
+            Checksum::Crc8 => (0..8).fold(checksum ^ byte, |crc, _| match crc & 0x80 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x07,
+            }),
+        }
+    }
+
+    /// Computes the checksum of some bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The bytes to checksum
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::Checksum;
+    ///
+    /// assert_eq!(Checksum::Xor.compute(b"OK"), 0x04);
+    /// assert_eq!(Checksum::Crc8.compute(b"123456789"), 0xF4);
+    /// ```
+    ///
+    pub fn compute(self, bytes: &[u8]) -> u8 {
+        bytes
+            .iter()
+            .fold(0, |checksum, &byte| self.update(checksum, byte))
+    }
+}
+
+/// A writer appending a checksum to every line written through it
+pub(crate) struct ChecksumWriter<'w> {
+    writer: &'w mut dyn Write,
+    checksum: Checksum,
+    running: u8,
+}
+
+impl<'w> ChecksumWriter<'w> {
+    pub(crate) fn new(writer: &'w mut dyn Write, checksum: Checksum) -> Self {
+        Self {
+            writer,
+            checksum,
+            running: 0,
+        }
+    }
+}
+
+impl Write for ChecksumWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut lines = s.split('\n');
+
+        if let Some(first) = lines.next() {
+            self.writer.write_str(first)?;
+            self.running = first.bytes().fold(self.running, |running, byte| {
+                self.checksum.update(running, byte)
+            });
+        }
+
+        for line in lines {
+            write!(self.writer, "*{:02X}\n{}", self.running, line)?;
+            self.running = self.checksum.compute(line.as_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets the checksum appended to response lines, or `None` to disable them
+    ///
+    /// Lets host tools detect responses corrupted on noisy links. Applies to
+    /// everything written by `feed`: the command's output, which is captured
+    /// up to `CHECKSUM_BUFFER_SIZE` bytes, and the status report. Output that
+    /// doesn't fit fails the command with `WriteError`.
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum` - The checksum to append
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, Checksum, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// fn reset<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("reset", reset, None).unwrap();
+    /// command_processor.set_response_checksum(Some(Checksum::Xor));
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.feed(b"reset\n", &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "OK*04\n");
+    /// ```
+    ///
+    pub fn set_response_checksum(&mut self, checksum: Option<Checksum>) {
+        self.response_checksum = checksum;
+    }
+
+    /// Sets the checksum lines received by `feed` must end with, or `None` to accept any line
+    ///
+    /// The checksum of the line's bytes before the last `*` follows it as two
+    /// hexadecimal digits, as in responses, see `set_response_checksum`.
+    /// Lines without it or with a wrong one aren't run and fail with
+    /// `ChecksumMismatch`, so a command corrupted on the link can't run with
+    /// other arguments. Empty lines are ignored as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `checksum` - The checksum lines must end with
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, Checksum, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// fn led<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     if let Some(writer) = writer {
+    ///         let _ = writeln!(writer, "led {}", args.remaining().join(" "));
+    ///     }
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("led", led, None).unwrap();
+    /// command_processor.set_input_checksum(Some(Checksum::Xor));
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.feed(b"led 1*7C\n", &mut writer).unwrap();
+    /// command_processor.feed(b"led 2*7C\n", &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "led 1\nOK\nERR: checksum mismatch\n");
+    /// ```
+    ///
+    pub fn set_input_checksum(&mut self, checksum: Option<Checksum>) {
+        self.input_checksum = checksum;
+    }
+
+    /// Verifies the checksum at the end of a received line and removes it, see `set_input_checksum`
+    pub(crate) fn verify_input<const N: usize>(
+        &self,
+        line: &mut String<N>,
+    ) -> Result<(), CommandProcessorError> {
+        let Some(checksum) = self.input_checksum else {
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        let (body, expected) = line
+            .rsplit_once('*')
+            .ok_or(CommandProcessorError::ChecksumMismatch)?;
+        let valid = expected.len() == 2
+            && u8::from_str_radix(expected, 16)
+                .is_ok_and(|expected| expected == checksum.compute(body.as_bytes()));
+        if !valid {
+            return Err(CommandProcessorError::ChecksumMismatch);
+        }
+
+        let len = body.len();
+        line.truncate(len);
+        Ok(())
+    }
+
+    /// Processes a line, appending a checksum to each line of the response
+    pub(crate) fn process_checksummed(
+        &mut self,
+        line: &str,
+        checksum: Checksum,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        let (result, output) = match self.scratch.take() {
+            Some(scratch) => {
+                let mut capture = SliceWriter::new(scratch);
+                let result = self.process_command(line, Some(&mut capture));
+                let written = Self::forward(&mut capture, checksum, writer);
+                self.scratch = Some(capture.into_inner());
+                (result, written)
+            }
+            None => {
+                let mut capture = SliceWriter::new([0u8; CHECKSUM_BUFFER_SIZE]);
+                let result = self.process_command(line, Some(&mut capture));
+                (result, Self::forward(&mut capture, checksum, writer))
+            }
+        };
+
+        let result = match output {
+            Ok(true) => result.and(Err(CommandProcessorError::WriteError)),
+            Ok(false) => result,
+            Err(e) => return Err(e),
+        };
+
+        let mut writer = ChecksumWriter::new(writer, checksum);
+        self.report(&result, &mut writer)
+    }
+
+    /// Writes captured output, returning whether it overflowed the capture
+    fn forward<B: AsRef<[u8]> + AsMut<[u8]>>(
+        capture: &mut SliceWriter<B>,
+        checksum: Checksum,
+        writer: &mut dyn Write,
+    ) -> Result<bool, CommandProcessorError> {
+        let mut writer = ChecksumWriter::new(writer, checksum);
+        // Lines must be complete, or the report's checksum would cover them
+        let output = capture.as_str();
+        let separator = match output.is_empty() || output.ends_with('\n') {
+            true => "",
+            false => "\n",
+        };
+
+        write!(writer, "{}{}", output, separator).map_err(|_| CommandProcessorError::WriteError)?;
+
+        Ok(capture.overflowed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, ReturnCode};
+
+    fn two_lines<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        write!(writer, "hi\nthere").map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_response_checksum() {
+        let mut scratch = [0u8; 4];
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("hello", two_lines, None)
+            .is_ok());
+
+        let mut writer = std::string::String::new();
+        command_processor.set_response_checksum(Some(Checksum::Xor));
+        assert!(command_processor.feed(b"hello\n", &mut writer).is_ok());
+
+        let there = Checksum::Xor.compute(b"there");
+        assert_eq!(writer, format!("hi*01\nthere*{:02X}\nOK*04\n", there));
+
+        writer.clear();
+        command_processor.set_scratch_buffer(&mut scratch);
+        command_processor.set_response_checksum(Some(Checksum::Crc8));
+        assert!(command_processor.feed(b"hello\n", &mut writer).is_ok());
+
+        let hi = Checksum::Crc8.compute(b"hi");
+        let t = Checksum::Crc8.compute(b"t");
+        let report = Checksum::Crc8.compute(b"ERR: write error");
+        let expected = match cfg!(feature = "strict-capacity") {
+            // Output that doesn't fit the scratch buffer is dropped whole
+            true => format!("ERR: write error*{:02X}\n", report),
+            // The output is truncated to the scratch buffer's size
+            false => format!(
+                "hi*{:02X}\nt*{:02X}\nERR: write error*{:02X}\n",
+                hi, t, report
+            ),
+        };
+        assert_eq!(writer, expected);
+    }
+
+    #[test]
+    fn test_input_checksum() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("hello", two_lines, None)
+            .is_ok());
+        command_processor.set_input_checksum(Some(Checksum::Crc8));
+
+        let mut writer = std::string::String::new();
+        let line = format!("hello*{:02X}\n", Checksum::Crc8.compute(b"hello"));
+        assert!(command_processor.feed(line.as_bytes(), &mut writer).is_ok());
+        assert_eq!(writer, "hi\nthereOK\n");
+
+        // Missing, malformed and wrong checksums, blank lines are ignored
+        writer.clear();
+        assert!(command_processor
+            .feed(b"hello\nhello*1\nhello*00\n\n", &mut writer)
+            .is_ok());
+        assert_eq!(writer, "ERR: checksum mismatch\n".repeat(3));
+    }
+}
@@ -0,0 +1,233 @@
+// This module contains the host-side client calling device commands
+use crate::{CommandProcessor, CommandProcessorError, Status, DEFAULT_MESSAGES};
+
+use core::fmt::Write;
+use std::string::String;
+use std::vec::Vec;
+
+/// Errors returned by host-side command calls
+#[derive(Debug, PartialEq)]
+pub enum ClientError {
+    /// The transport failed to exchange the command and its response
+    Transport,
+    /// The response didn't end with a status line
+    MalformedResponse,
+    /// The device reported an error, with its description if it gave one
+    Device(String),
+}
+
+/// The response of a device command
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientResponse {
+    /// The output lines, without the status line
+    pub lines: Vec<String>,
+    /// The `key=value` pairs found in the output, in order
+    pub values: Vec<(String, String)>,
+}
+
+impl ClientResponse {
+    /// Returns the first value with the given key
+    pub fn value(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A connection to a device running a command processor
+///
+/// Implemented by host tools over their link to the device, a serial port
+/// for instance. Calls are made through `call`, or through the functions
+/// generated by `CommandProcessor::write_rust_client`. Responses are parsed
+/// with the default message catalog.
+pub trait Transport {
+    /// Sends a command line and returns the whole response, status line included
+    fn exchange(&mut self, line: &str) -> Result<String, ClientError>;
+
+    /// Calls a device command and parses its response
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The name of the command
+    /// * `args` - The arguments of the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ClientResponse)` - The output of the command, if it succeeded
+    /// * `Err(ClientError::Device)` - If the device reported an error
+    /// * `Err(ClientError::MalformedResponse)` - If there was no status line
+    /// * `Err(ClientError::Transport)` - If the exchange failed
+    ///
+    fn call(&mut self, command: &str, args: &[&str]) -> Result<ClientResponse, ClientError> {
+        let mut line = String::from(command);
+        for arg in args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+
+        parse_response(&self.exchange(&line)?)
+    }
+}
+
+/// Splits a response into its output and status line
+fn parse_response(response: &str) -> Result<ClientResponse, ClientError> {
+    let mut lines: Vec<String> = response.lines().map(String::from).collect();
+    let status = lines.pop().ok_or(ClientError::MalformedResponse)?;
+
+    if status != DEFAULT_MESSAGES.ok {
+        return match status.strip_prefix(DEFAULT_MESSAGES.error) {
+            Some(description) => Err(ClientError::Device(String::from(
+                description.trim_start_matches(':').trim(),
+            ))),
+            None => Err(ClientError::MalformedResponse),
+        };
+    }
+
+    let values = lines
+        .iter()
+        .flat_map(|line| line.split_whitespace())
+        .filter_map(|token| token.split_once('='))
+        .map(|(key, value)| (String::from(key), String::from(value)))
+        .collect();
+
+    Ok(ClientResponse { lines, values })
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Writes Rust source with a function calling each registered command
+    ///
+    /// Like the completion scripts, intended to be run on the host with the
+    /// same command table that is compiled for the device, from a build
+    /// script for instance. Each function is named after its command, with
+    /// characters that aren't valid in identifiers replaced by `_`, is
+    /// documented by the command's help string and calls it through a
+    /// `Transport`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to write the source to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the source was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the source failed to write
+    ///
+    pub fn write_rust_client(&self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        writeln!(writer, "// Generated from the device command table")
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| {
+                writeln!(
+                    writer,
+                    "use command_processor::{{ClientError, ClientResponse, Transport}};"
+                )
+            })
+            .map_err(|_| CommandProcessorError::WriteError)?;
+
+        for cmd in self.sorted_commands() {
+            writeln!(writer).map_err(|_| CommandProcessorError::WriteError)?;
+
+            for line in cmd.help.as_deref().unwrap_or_default().lines() {
+                writeln!(writer, "/// {}", line).map_err(|_| CommandProcessorError::WriteError)?;
+            }
+
+            writeln!(
+                writer,
+                "pub fn {}<T: Transport>(transport: &mut T, args: &[&str]) -> Result<ClientResponse, ClientError> {{",
+                function_name(&cmd.command)
+            )
+            .and_then(|_| writeln!(writer, "    transport.call({:?}, args)", cmd.command.as_str()))
+            .and_then(|_| writeln!(writer, "}}"))
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the Rust function name used for a command
+fn function_name(command: &str) -> String {
+    let mut function: String = command
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '_',
+        })
+        .collect();
+
+    if !function.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        function.insert(0, '_');
+    }
+    function
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::printer_demo;
+    use heapless::String as HString;
+
+    #[test]
+    fn test_parse_response() {
+        let response = parse_response("baud=9600 bits=8\nready\nOK\n").unwrap();
+        assert_eq!(response.lines, ["baud=9600 bits=8", "ready"]);
+        assert_eq!(response.value("bits"), Some("8"));
+        assert_eq!(response.value("ready"), None);
+
+        assert_eq!(
+            parse_response("ERR: command not found\n"),
+            Err(ClientError::Device(String::from("command not found")))
+        );
+        assert_eq!(parse_response(""), Err(ClientError::MalformedResponse));
+    }
+
+    #[test]
+    fn test_transport_call() {
+        struct Loopback;
+
+        impl Transport for Loopback {
+            fn exchange(&mut self, line: &str) -> Result<String, ClientError> {
+                match line {
+                    "baud 9600" => Ok(String::from("baud=9600\nOK\n")),
+                    _ => Err(ClientError::Transport),
+                }
+            }
+        }
+
+        let response = Loopback.call("baud", &["9600"]).unwrap();
+        assert_eq!(response.value("baud"), Some("9600"));
+        assert_eq!(Loopback.call("baud", &[]), Err(ClientError::Transport));
+    }
+
+    #[test]
+    fn test_rust_client() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                "set-baud",
+                printer_demo,
+                Some(HString::from("Sets the baud rate"))
+            )
+            .is_ok());
+        assert!(command_processor
+            .add_command("2fa", printer_demo, None)
+            .is_ok());
+
+        let mut buffer = String::new();
+        assert!(command_processor.write_rust_client(&mut buffer).is_ok());
+        assert!(buffer.contains("\npub fn _2fa<T: Transport>("));
+        assert!(buffer.contains(
+            "/// Sets the baud rate\npub fn set_baud<T: Transport>(transport: &mut T, args: &[&str]) -> Result<ClientResponse, ClientError> {\n    transport.call(\"set-baud\", args)\n}\n"
+        ));
+    }
+}
@@ -0,0 +1,31 @@
+// This module contains the clock abstraction used for time-based features
+
+/// A monotonic time source
+///
+/// Features such as arming and cooldowns need to know the current time. The
+/// application provides it by implementing this trait, usually on top of a
+/// hardware timer or the RTOS tick counter. The processor holds it shared
+/// and stays `Send`, so the clock must be `Sync`: a clock keeping state
+/// keeps it in atomics or behind a critical section.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::Clock;
+///
+/// struct Ticks;
+///
+/// impl Clock for Ticks {
+///     fn now_ms(&self) -> u64 {
+///         // Read the hardware timer here
+///         0
+///     }
+/// }
+/// ```
+///
+pub trait Clock {
+    /// Returns the current time in milliseconds
+    ///
+    /// The value must never decrease, its starting point is irrelevant.
+    fn now_ms(&self) -> u64;
+}
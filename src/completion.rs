@@ -0,0 +1,320 @@
+// This module contains the Tab completion of the line editor
+use crate::editor::BELL;
+use crate::groups::next_word;
+use crate::tokenizer::{is_delimiter, tokenize};
+use crate::{CommandProcessor, CommandProcessorError, Status, MAX_ARGS};
+
+use core::fmt::{self, Write};
+use heapless::{String, Vec};
+
+/// Argument completion callback type
+///
+/// Receives the arguments already typed after the command name, the partial
+/// argument before the cursor, and the collector of the candidates.
+pub type CompletionCallback = fn(&[&str], &str, &mut Completions);
+
+/// The collector of the candidates completing a token
+///
+/// Candidates not starting with the partial token are ignored, so a
+/// completion callback can add all of them.
+pub struct Completions<'c> {
+    partial: &'c str,
+    sink: &'c mut dyn FnMut(&str) -> fmt::Result,
+    result: fmt::Result,
+}
+
+impl<'c> Completions<'c> {
+    fn new(partial: &'c str, sink: &'c mut dyn FnMut(&str) -> fmt::Result) -> Self {
+        Self {
+            partial,
+            sink,
+            result: Ok(()),
+        }
+    }
+
+    /// Returns the partial token being completed
+    pub fn partial(&self) -> &str {
+        self.partial
+    }
+
+    /// Adds a candidate, if it starts with the partial token
+    ///
+    /// # Arguments
+    ///
+    /// * `candidate` - The whole token the partial one could complete to
+    ///
+    pub fn add(&mut self, candidate: &str) {
+        if self.result.is_ok() && candidate.starts_with(self.partial) {
+            self.result = (self.sink)(candidate);
+        }
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets a callback that completes a command's arguments on Tab
+    ///
+    /// Without one, Tab only completes command names. The candidates are
+    /// handled like command names: a single one is completed, several are
+    /// completed up to their common prefix or listed.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to complete the arguments of
+    /// * `completion` - The completion callback
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the completion callback was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, Completions, ReturnCode};
+    ///
+    /// fn gpio<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// fn pins(_: &[&str], _: &str, completions: &mut Completions) {
+    ///     for pin in ["led", "button", "buzzer"] {
+    ///         completions.add(pin);
+    ///     }
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("gpio", gpio, None).unwrap();
+    /// command_processor.set_completion("gpio", pins).unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.feed(b"gpio l\t", &mut writer).unwrap();
+    /// assert_eq!(writer, "ed ");
+    /// ```
+    ///
+    pub fn set_completion(
+        &mut self,
+        command: &str,
+        completion: CompletionCallback,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.completion = Some(completion);
+        Ok(())
+    }
+
+    /// Completes the token before the cursor, for the Tab key
+    ///
+    /// The first token is completed from the command names, and so are the
+    /// subcommands of a group, the others with the command's completion
+    /// callback. A single candidate is completed,
+    /// followed by a space. Several are completed up to their common prefix,
+    /// or listed under the line if they have none beyond what was typed.
+    /// Hidden commands aren't completed, see `set_hidden`.
+    pub(crate) fn complete(&mut self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        match self.completion_text(writer)? {
+            Some(text) => self.insert_str(&text, writer),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the text completing the token before the cursor
+    ///
+    /// `None` if there is nothing to insert, the bell having been rung or
+    /// the candidates listed.
+    fn completion_text(
+        &self,
+        writer: &mut dyn Write,
+    ) -> Result<Option<String<LINE_SIZE>>, CommandProcessorError> {
+        let head = self.line.get(..self.cursor).unwrap_or("");
+        let start = head
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| is_delimiter(self.delimiters, c))
+            .map_or(0, |(index, c)| index.saturating_add(c.len_utf8()));
+        let (before, partial) = head.split_at_checked(start).unwrap_or((head, ""));
+
+        // The words of a group's path are completed like command names
+        let mut path: String<LINE_SIZE> = String::new();
+        let mut tokens = tokenize(before, self.delimiters);
+        let mut first_arg = None;
+        for token in tokens.by_ref() {
+            let mut subcommand = path.clone();
+            let extended = (subcommand.is_empty() || subcommand.push(' ').is_ok())
+                && subcommand.push_str(token).is_ok();
+            let known = self
+                .commands
+                .iter()
+                .any(|cmd| cmd.command == subcommand.as_str());
+            match extended && (known || self.is_group(&subcommand)) {
+                true => path = subcommand,
+                // The token is the first argument of a group that is a command
+                false if self.commands.iter().any(|cmd| cmd.command == path.as_str()) => {
+                    first_arg = Some(token);
+                    break;
+                }
+                false => return Ok(None),
+            }
+            if !self.is_group(&path) {
+                break;
+            }
+        }
+
+        if first_arg.is_some() || !self.is_group(&path) && !path.is_empty() {
+            let Some(completion) = self
+                .commands
+                .iter()
+                .find(|cmd| cmd.command == path.as_str())
+                .and_then(|cmd| cmd.completion)
+            else {
+                return Ok(None);
+            };
+            let args: Vec<&str, MAX_ARGS> =
+                first_arg.into_iter().chain(tokens).take(MAX_ARGS).collect();
+            return self.write_completions(partial, writer, &|completions| {
+                completion(&args, partial, completions)
+            });
+        }
+
+        self.write_completions(partial, writer, &|completions| {
+            let visible = || self.commands.iter().filter(|cmd| !cmd.hidden);
+            for (index, cmd) in visible().enumerate() {
+                let Some(word) = next_word(&cmd.command, &path) else {
+                    continue;
+                };
+                // Groups are listed once, not once per subcommand
+                let mut listed = visible().take(index);
+                if !listed.any(|other| next_word(&other.command, &path) == Some(word)) {
+                    completions.add(word);
+                }
+            }
+        })
+    }
+
+    /// Completes a partial token from the candidates added by `add`, see `completion_text`
+    fn write_completions(
+        &self,
+        partial: &str,
+        writer: &mut dyn Write,
+        add: &dyn Fn(&mut Completions),
+    ) -> Result<Option<String<LINE_SIZE>>, CommandProcessorError> {
+        let collect = |sink: &mut dyn FnMut(&str) -> fmt::Result| {
+            let mut completions = Completions::new(partial, sink);
+            add(&mut completions);
+            completions.result
+        };
+
+        // Candidates longer than a line are cut, they couldn't fit anyway
+        let mut common: String<LINE_SIZE> = String::new();
+        let mut count = 0usize;
+        let _ = collect(&mut |candidate| {
+            let shared = match count {
+                0 => candidate.len(),
+                _ => common
+                    .char_indices()
+                    .zip(candidate.chars())
+                    .find(|&((_, a), b)| a != b)
+                    .map_or(common.len().min(candidate.len()), |((index, _), _)| index),
+            };
+            if count == 0 {
+                let _ = candidate.chars().try_for_each(|c| common.push(c));
+            }
+            common.truncate(shared);
+            count = count.saturating_add(1);
+            Ok(())
+        });
+
+        let mut text: String<LINE_SIZE> = String::new();
+        let _ = text.push_str(common.get(partial.len()..).unwrap_or(""));
+        if count == 1 {
+            let _ = text.push(' ');
+        }
+        let written = match (count, text.is_empty()) {
+            (0, _) => writer.write_str(BELL),
+            (_, true) => {
+                writer
+                    .write_str("\n")
+                    .and_then(|()| collect(&mut |candidate| write!(writer, "{}  ", candidate)))
+                    .and_then(|()| writer.write_str("\n"))
+                    .map_err(|_| CommandProcessorError::WriteError)?;
+                return self.redraw_line(writer).map(|()| None);
+            }
+            _ => return Ok(Some(text)),
+        };
+        written
+            .map(|()| None)
+            .map_err(|_| CommandProcessorError::WriteError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Args, CommandCallbackReturn, CommandProcessor, Completions, ReturnCode};
+
+    use core::fmt::Write;
+
+    fn nothing<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Success)
+    }
+
+    fn gpio(args: &[&str], _: &str, completions: &mut Completions) {
+        match args {
+            [] => ["get", "set"]
+                .iter()
+                .for_each(|action| completions.add(action)),
+            [_] => ["led1", "led2", "button"]
+                .iter()
+                .for_each(|pin| completions.add(pin)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_completion_callback() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("gpio", nothing, None).is_ok());
+        assert!(command_processor.add_command("echo", nothing, None).is_ok());
+        assert!(command_processor.set_completion("gpio", gpio).is_ok());
+
+        let mut writer = std::string::String::new();
+        assert!(command_processor.feed(b"gpio s\tl\t", &mut writer).is_ok());
+        assert_eq!(writer, "et ed");
+
+        // Lists the candidates when there is nothing to add
+        writer.clear();
+        assert!(command_processor.feed(b"\t", &mut writer).is_ok());
+        assert_eq!(writer, "\nled1  led2  \ngpio set led");
+
+        // Commands without a callback don't complete their arguments
+        writer.clear();
+        assert!(command_processor.feed(b"\x18echo \t", &mut writer).is_ok());
+        assert_eq!(writer, "");
+    }
+
+    #[test]
+    fn test_complete_subcommands() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for name in ["net ping", "net status", "gpio"] {
+            assert!(command_processor.add_command(name, nothing, None).is_ok());
+        }
+        assert!(command_processor.set_completion("net ping", gpio).is_ok());
+
+        // Groups complete once, then their subcommands and their arguments
+        let mut writer = std::string::String::new();
+        assert!(command_processor.feed(b"n\tp\tg\t", &mut writer).is_ok());
+        assert_eq!(writer, "et ing et ");
+
+        // Hidden commands aren't candidates
+        assert!(command_processor.set_hidden("net ping", true).is_ok());
+        writer.clear();
+        assert!(command_processor.feed(b"\x18net \t", &mut writer).is_ok());
+        assert_eq!(writer, "status ");
+    }
+}
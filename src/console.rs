@@ -0,0 +1,544 @@
+// This module contains the ready-made console task for async executors such as Embassy
+#[cfg(feature = "async")]
+use crate::{AsyncCommandProcessor, AsyncCommands};
+use crate::{CommandProcessor, Status};
+
+use core::fmt::{self, Display, Write};
+use core::future::{poll_fn, Future};
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use heapless::Vec;
+
+/// The size of the buffer receiving bytes from the link
+pub const CONSOLE_READ_SIZE: usize = 32;
+
+/// The size of the buffer collecting output before it's written to the link
+pub const CONSOLE_OUTPUT_SIZE: usize = 256;
+
+/// The receiving half of a console link
+///
+/// Has the signature of `embedded_io_async::Read::read`, so UART, USB and
+/// TCP drivers implementing it forward to it in one line. The read must be
+/// cancel-safe, as it's dropped when an event arrives first, which Embassy's
+/// buffered UARTs and pipes are.
+pub trait ConsoleRead {
+    type Error;
+
+    /// Reads at least one byte into the buffer, or `0` once the link is closed
+    fn read(&mut self, buffer: &mut [u8]) -> impl Future<Output = Result<usize, Self::Error>>;
+}
+
+/// The sending half of a console link
+///
+/// Has the signature of `embedded_io_async::Write::write_all`.
+pub trait ConsoleWrite {
+    type Error;
+
+    /// Writes the whole buffer
+    fn write_all(&mut self, buffer: &[u8]) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// The events other tasks write to the console, such as alarms or log lines
+///
+/// Usually implemented on the receiving end of a channel, such as an
+/// `embassy_sync::channel::Receiver`, by forwarding to its `receive`.
+pub trait ConsoleEvents {
+    type Event: Display;
+
+    /// Waits for the next event
+    fn receive(&mut self) -> impl Future<Output = Self::Event>;
+}
+
+/// Events for consoles no other task writes to
+pub struct NoEvents;
+
+impl ConsoleEvents for NoEvents {
+    type Event = &'static str;
+
+    async fn receive(&mut self) -> Self::Event {
+        poll_fn(|_| Poll::Pending).await
+    }
+}
+
+/// Why the console task ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleError<R, W> {
+    /// Reading from the link failed
+    Read(R),
+    /// Writing to the link failed
+    Write(W),
+    /// The link was closed
+    Closed,
+}
+
+/// What the console task waits for
+enum Wake<T> {
+    Read(usize),
+    Event(T),
+}
+
+/// Runs the console on a link, the body of an Embassy task
+///
+/// Received bytes are fed to the processor, see `feed`, so lines are
+/// echoed, edited and dispatched as with a blocking front-end. Events other
+/// tasks send are written between the chunks on a line of their own, and
+/// the line being typed is redrawn after them when echo is on. The task only
+/// returns when the link fails or closes.
+///
+/// Output is collected in a buffer of `CONSOLE_OUTPUT_SIZE` bytes, written
+/// to the link once each chunk is processed. Commands run to completion
+/// within `feed`, so when they fill the buffer it's written in place, polling
+/// the write until the link takes it: its driver must make progress without
+/// the executor, as the interrupt-driven buffered UARTs of Embassy do. Use
+/// `run_async_console` to await the commands of an `AsyncCommandProcessor`.
+///
+/// # Arguments
+///
+/// * `processor` - The processor, with its commands registered
+/// * `rx` - The receiving half of the link
+/// * `tx` - The sending half of the link
+/// * `events` - The events to write, or `NoEvents`
+///
+/// # Returns
+///
+/// Why the console ended
+///
+/// # Example
+///
+/// ```ignore
+/// struct Rx(BufferedUartRx<'static, UART0>);
+///
+/// impl ConsoleRead for Rx {
+///     type Error = embassy_rp::uart::Error;
+///
+///     async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+///         embedded_io_async::Read::read(&mut self.0, buffer).await
+///     }
+/// }
+///
+/// #[embassy_executor::task]
+/// async fn console(rx: Rx, tx: Tx, events: Alarms) {
+///     let mut command_processor: CommandProcessor<16, 64> = CommandProcessor::new();
+///     register_commands(&mut command_processor);
+///     let _ = run_console(&mut command_processor, rx, tx, events).await;
+/// }
+/// ```
+///
+pub async fn run_console<
+    'a,
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize,
+    const LINE_SIZE: usize,
+    C,
+    R: Status,
+    RX: ConsoleRead,
+    TX: ConsoleWrite + 'a,
+    E: ConsoleEvents,
+>(
+    processor: &mut CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+    mut rx: RX,
+    tx: TX,
+    mut events: E,
+) -> ConsoleError<RX::Error, TX::Error> {
+    let mut buffer = [0u8; CONSOLE_READ_SIZE];
+    let mut output = Streamed::new(tx);
+    loop {
+        match next_wake(&mut rx, &mut events, &mut buffer).await {
+            Err(e) => return ConsoleError::Read(e),
+            Ok(Wake::Read(0)) => return ConsoleError::Closed,
+            Ok(Wake::Read(len)) => {
+                let received = buffer.get(..len).unwrap_or(&[]);
+                if let Err(e) = processor.feed(received, &mut output) {
+                    let _ = processor.report(&Err(e), &mut output);
+                }
+            }
+            Ok(Wake::Event(event)) => write_event(processor, event, &mut output),
+        }
+
+        if let Err(e) = output.flush().await {
+            return ConsoleError::Write(e);
+        }
+    }
+}
+
+/// Runs the console on a link, awaiting the commands of an async processor, see `run_console`
+///
+/// Lines are edited and dispatched as with `run_console`, except each line
+/// is processed with `AsyncCommandProcessor::process_command`, so its async
+/// commands are awaited. The output so far, with the echoed line, is written
+/// before awaiting the command. Events arriving meanwhile are written once
+/// the command ends. Paged help and lines with a response checksum run their
+/// sync commands only, as with `feed`.
+///
+/// # Arguments
+///
+/// * `processor` - The async processor, with its commands registered
+/// * `rx` - The receiving half of the link
+/// * `tx` - The sending half of the link
+/// * `events` - The events to write, or `NoEvents`
+///
+/// # Returns
+///
+/// Why the console ended
+///
+/// # Example
+///
+/// ```ignore
+/// #[embassy_executor::task]
+/// async fn console(rx: Rx, tx: Tx, board: Board) {
+///     let mut command_processor: AsyncCommandProcessor<Board, 16, 64> =
+///         AsyncCommandProcessor::new(CommandProcessor::new(), board);
+///     register_commands(command_processor.processor());
+///     let _ = run_async_console(&mut command_processor, rx, tx, NoEvents).await;
+/// }
+/// ```
+///
+#[cfg(feature = "async")]
+pub async fn run_async_console<
+    'a,
+    H: AsyncCommands<C, R>,
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize,
+    const LINE_SIZE: usize,
+    C,
+    R: Status,
+    RX: ConsoleRead,
+    TX: ConsoleWrite + 'a,
+    E: ConsoleEvents,
+>(
+    processor: &mut AsyncCommandProcessor<
+        'a,
+        H,
+        NUM_COMMANDS,
+        HELP_STR_SIZE,
+        NAME_SIZE,
+        LINE_SIZE,
+        C,
+        R,
+    >,
+    mut rx: RX,
+    tx: TX,
+    mut events: E,
+) -> ConsoleError<RX::Error, TX::Error> {
+    let mut buffer = [0u8; CONSOLE_READ_SIZE];
+    let mut output = Streamed::new(tx);
+    loop {
+        match next_wake(&mut rx, &mut events, &mut buffer).await {
+            Err(e) => return ConsoleError::Read(e),
+            Ok(Wake::Read(0)) => return ConsoleError::Closed,
+            Ok(Wake::Read(len)) => {
+                let mut received = buffer.get(..len).unwrap_or(&[]);
+                while !received.is_empty() {
+                    let (consumed, line) =
+                        match processor.processor().feed_line(received, &mut output) {
+                            Ok(fed) => fed,
+                            Err(e) => {
+                                let _ = processor.processor().report(&Err(e), &mut output);
+                                break;
+                            }
+                        };
+                    received = received.get(consumed..).unwrap_or(&[]);
+                    let Some(line) = line else {
+                        continue;
+                    };
+
+                    if let Err(e) = output.flush().await {
+                        return ConsoleError::Write(e);
+                    }
+                    let result = processor.process_command(&line, Some(&mut output)).await;
+                    if let Err(e) = processor.processor().end_taken_line(&result, &mut output) {
+                        let _ = processor.processor().report(&Err(e), &mut output);
+                    }
+                }
+            }
+            Ok(Wake::Event(event)) => write_event(processor.processor(), event, &mut output),
+        }
+
+        if let Err(e) = output.flush().await {
+            return ConsoleError::Write(e);
+        }
+    }
+}
+
+/// Waits for received bytes or an event
+async fn next_wake<RX: ConsoleRead, E: ConsoleEvents>(
+    rx: &mut RX,
+    events: &mut E,
+    buffer: &mut [u8],
+) -> Result<Wake<E::Event>, RX::Error> {
+    let mut read = pin!(rx.read(buffer));
+    let mut event = pin!(events.receive());
+    // Events first, as a continuous input stream would hold them back
+    poll_fn(|cx| {
+        if let Poll::Ready(event) = event.as_mut().poll(cx) {
+            return Poll::Ready(Ok(Wake::Event(event)));
+        }
+        read.as_mut().poll(cx).map(|result| result.map(Wake::Read))
+    })
+    .await
+}
+
+/// Writes an event on a line of its own, redrawing the line being typed
+fn write_event<
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize,
+    const LINE_SIZE: usize,
+    C,
+    R: Status,
+>(
+    processor: &CommandProcessor<'_, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+    event: impl Display,
+    output: &mut dyn Write,
+) {
+    let clear = match processor.echo {
+        true => "\r\x1b[K",
+        false => "",
+    };
+    let _ = writeln!(output, "{}{}", clear, event);
+    if processor.echo {
+        let _ = processor.redraw_line(output);
+    }
+}
+
+/// Writes output to the link through a buffer of `CONSOLE_OUTPUT_SIZE` bytes
+///
+/// The buffer is written in place when it fills up, and awaited with
+/// `flush` otherwise. A failed write fails every later write, and is
+/// returned by the next `flush`.
+struct Streamed<TX: ConsoleWrite> {
+    tx: TX,
+    buffer: Vec<u8, CONSOLE_OUTPUT_SIZE>,
+    error: Option<TX::Error>,
+}
+
+impl<TX: ConsoleWrite> Streamed<TX> {
+    fn new(tx: TX) -> Self {
+        Self {
+            tx,
+            buffer: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Writes the buffer, polling the write until it completes as commands can't await
+    fn write_in_place(&mut self) {
+        let result = {
+            let mut write = pin!(self.tx.write_all(&self.buffer));
+            let mut context = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(result) = write.as_mut().poll(&mut context) {
+                    break result;
+                }
+                core::hint::spin_loop();
+            }
+        };
+        self.buffer.clear();
+        self.error = result.err();
+    }
+
+    /// Writes the buffer, or returns the error of an earlier write
+    async fn flush(&mut self) -> Result<(), TX::Error> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let result = self.tx.write_all(&self.buffer).await;
+        self.buffer.clear();
+        result
+    }
+}
+
+impl<TX: ConsoleWrite> Write for Streamed<TX> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s.as_bytes();
+        while !rest.is_empty() {
+            if self.error.is_some() {
+                return Err(fmt::Error);
+            }
+            if self.buffer.is_full() {
+                self.write_in_place();
+                continue;
+            }
+            let room = self.buffer.capacity().saturating_sub(self.buffer.len());
+            let (chunk, tail) = rest.split_at(rest.len().min(room));
+            let _ = self.buffer.extend_from_slice(chunk);
+            rest = tail;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{block_on, hello, Transfer};
+    use crate::{Args, CommandCallbackReturn, CommandProcessorError, Prompt, ReturnCode};
+
+    use core::cell::{Cell, RefCell};
+
+    /// Receives chunks, then closes the link
+    struct Chunks(&'static [&'static [u8]]);
+
+    impl ConsoleRead for Chunks {
+        type Error = ();
+
+        async fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error> {
+            let Some((chunk, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            self.0 = rest;
+            buffer[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    /// Takes each write on the second poll, as a link whose buffer drains
+    #[derive(Default)]
+    struct Link {
+        sent: RefCell<std::string::String>,
+        writes: Cell<usize>,
+    }
+
+    impl ConsoleWrite for &Link {
+        type Error = ();
+
+        async fn write_all(&mut self, buffer: &[u8]) -> Result<(), Self::Error> {
+            Transfer(false).await;
+            self.sent
+                .borrow_mut()
+                .push_str(core::str::from_utf8(buffer).map_err(|_| ())?);
+            self.writes.set(self.writes.get() + 1);
+            Ok(())
+        }
+    }
+
+    /// Has an event ready on the second wait only
+    struct Alarm(u8);
+
+    impl ConsoleEvents for Alarm {
+        type Event = &'static str;
+
+        async fn receive(&mut self) -> Self::Event {
+            self.0 += 1;
+            match self.0 {
+                2 => "overheat",
+                _ => poll_fn(|_| Poll::Pending).await,
+            }
+        }
+    }
+
+    #[test]
+    fn test_run_console() {
+        let link = Link::default();
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("hello", hello, None).is_ok());
+        command_processor.set_prompt(Some(Prompt::Text("> ")));
+
+        let ended = block_on(run_console(
+            &mut command_processor,
+            Chunks(&[b"hel", b"lo\n"]),
+            &link,
+            NoEvents,
+        ));
+        assert_eq!(ended, ConsoleError::Closed);
+        assert_eq!(*link.sent.borrow(), "hi\nOK\n> ");
+
+        // The line being typed is redrawn after an event
+        command_processor.set_echo(true);
+        link.sent.borrow_mut().clear();
+        let ended = block_on(run_console(
+            &mut command_processor,
+            Chunks(&[b"he"]),
+            &link,
+            Alarm(0),
+        ));
+        assert_eq!(ended, ConsoleError::Closed);
+        assert_eq!(*link.sent.borrow(), "he\r\x1b[Koverheat\n> he");
+    }
+
+    fn dump<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        for _ in 0..20 {
+            writeln!(writer, "{:x<31}", "").map_err(|_| CommandProcessorError::WriteError)?;
+        }
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_run_console_long_output() {
+        let link = Link::default();
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("dump", dump, None).is_ok());
+        command_processor.set_prompt(Some(Prompt::Text("> ")));
+
+        let ended = block_on(run_console(
+            &mut command_processor,
+            Chunks(&[b"dump\n"]),
+            &link,
+            NoEvents,
+        ));
+        assert_eq!(ended, ConsoleError::Closed);
+
+        // The 640 bytes go out as the buffer fills, then the rest once the line ran
+        let line = std::format!("{:x<31}\n", "");
+        assert_eq!(*link.sent.borrow(), line.repeat(20) + "OK\n> ");
+        assert_eq!(link.writes.get(), 3);
+    }
+
+    #[cfg(feature = "async")]
+    struct Bus;
+
+    #[cfg(feature = "async")]
+    impl AsyncCommands for Bus {
+        fn handles(&self, name: &str) -> bool {
+            name == "i2c-read"
+        }
+
+        async fn call(
+            &mut self,
+            _: &str,
+            mut args: Args<'_>,
+            _: Option<&mut ()>,
+            writer: Option<&mut dyn Write>,
+        ) -> Result<ReturnCode, CommandProcessorError> {
+            let address: u8 = args
+                .next_parse()
+                .map_err(|_| CommandProcessorError::InvalidArguments)?;
+            // The transfer completes on the second poll
+            Transfer(false).await;
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writeln!(writer, "{:#04x}", address).map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_run_async_console() {
+        let link = Link::default();
+        let mut processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(processor.add_command("hello", hello, None).is_ok());
+        processor.set_prompt(Some(Prompt::Text("> ")));
+        processor.set_echo(true);
+        let mut command_processor = AsyncCommandProcessor::new(processor, Bus);
+
+        // Both lines of a chunk run, the async one awaited
+        let ended = block_on(run_async_console(
+            &mut command_processor,
+            Chunks(&[b"i2c-read 80\nhel", b"lo\n"]),
+            &link,
+            NoEvents,
+        ));
+        assert_eq!(ended, ConsoleError::Closed);
+        assert_eq!(
+            *link.sent.borrow(),
+            "i2c-read 80\n0x50\nOK\n> hello\nhi\nOK\n> "
+        );
+    }
+}
@@ -0,0 +1,675 @@
+// This module contains commands bound to an application context, closure or object
+use crate::table::help_string;
+use crate::{
+    Args, CommandCallback, CommandCallbackReturn, CommandName, CommandProcessor,
+    CommandProcessorError, ReturnCode, Status,
+};
+#[cfg(feature = "binary")]
+use crate::{BinaryArgs, BinaryCallback};
+use heapless::String;
+
+use core::fmt::Write;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+/// Callback type for commands bound to a context
+///
+/// Receives the context given to `add_command_with_ctx` along with the
+/// arguments and the writer.
+pub type ContextCallback<'a, T, R = ReturnCode> =
+    fn(&mut T, Args, Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a, R>;
+
+/// Closure type for commands capturing their state
+///
+/// Registered with `add_command_fn`, from storage owned by the application.
+pub type CommandClosure<'a, R = ReturnCode> =
+    dyn FnMut(Args, Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a, R> + Send + 'a;
+
+/// A command implemented by an object
+///
+/// Commands with a lot of internal state, such as a flash update state
+/// machine, are easier to write as a type than as a function with a context.
+/// Registered with `add_command_object`, `R` is the status it returns.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{Args, Command, CommandProcessorError, ReturnCode};
+/// use core::fmt::Write;
+///
+/// struct Counter {
+///     count: u32,
+/// }
+///
+/// impl Command for Counter {
+///     fn name(&self) -> &'static str {
+///         "count"
+///     }
+///
+///     fn help(&self) -> Option<&'static str> {
+///         Some("Counts its calls")
+///     }
+///
+///     fn execute(&mut self, _: Args, writer: Option<&mut dyn Write>) -> Result<ReturnCode, CommandProcessorError> {
+///         self.count += 1;
+///         if let Some(writer) = writer {
+///             writeln!(writer, "{}", self.count).map_err(|_| CommandProcessorError::WriteError)?;
+///         }
+///         Ok(ReturnCode::Success)
+///     }
+/// }
+/// ```
+///
+pub trait Command<R = ReturnCode> {
+    /// Returns the name the command is invoked with
+    fn name(&self) -> &'static str;
+
+    /// Returns the help string of the command
+    fn help(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Runs the command
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments of the command
+    /// * `writer` - The writer the command can write with
+    ///
+    fn execute(
+        &mut self,
+        args: Args,
+        writer: Option<&mut dyn Write>,
+    ) -> Result<R, CommandProcessorError>;
+}
+
+/// The callback of a command table entry
+pub(crate) enum Callback<'a, C, R> {
+    Plain(CommandCallback<'a, R>),
+    Context(ErasedContext<'a, R>),
+    Closure(BorrowedClosure<'a, R>),
+    Object(BorrowedCommand<'a, R>),
+    Shared(ContextCallback<'a, C, R>),
+    #[cfg(feature = "binary")]
+    Binary(BinaryCallback<'a, R>),
+}
+
+// Derived impls would require `C: Copy`, though only a function taking it is stored
+impl<C, R> Clone for Callback<'_, C, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C, R> Copy for Callback<'_, C, R> {}
+
+/// A context callback with the type of its context erased
+///
+/// Two thin pointers, unlike a `dyn FnMut` which would also need the context
+/// to be stored somewhere.
+pub(crate) struct ErasedContext<'a, R> {
+    context: NonNull<()>,
+    callback: ContextCallback<'a, (), R>,
+    _borrow: PhantomData<&'a mut ()>,
+}
+
+/// A closure borrowed for `'a`
+///
+/// A pointer rather than a `&'a mut`, so that table entries stay `Copy`.
+pub(crate) struct BorrowedClosure<'a, R> {
+    closure: NonNull<CommandClosure<'a, R>>,
+    _borrow: PhantomData<&'a mut ()>,
+}
+
+/// A command object borrowed for `'a`, like `BorrowedClosure`
+pub(crate) struct BorrowedCommand<'a, R> {
+    command: NonNull<dyn Command<R> + Send + 'a>,
+    _borrow: PhantomData<&'a mut ()>,
+}
+
+/// The context `process_command_with` lends the processor for the call
+///
+/// A pointer rather than a `&mut C`, as the call's lifetime can't be named.
+pub(crate) struct LentContext<C>(NonNull<C>);
+
+impl<C> LentContext<C> {
+    pub(crate) fn new(context: &mut C) -> Self {
+        LentContext(NonNull::from(context))
+    }
+
+    pub(crate) fn as_ptr(self) -> *mut C {
+        self.0.as_ptr()
+    }
+}
+
+// The borrowed values are `Send`, as their registrations require, and the
+// pointers only stand for `&'a mut` borrows of them, which are `Send` then.
+// SAFETY: the context of `add_command_with_ctx` is `Send`
+unsafe impl<R> Send for ErasedContext<'_, R> {}
+// SAFETY: `CommandClosure` is `Send`
+unsafe impl<R> Send for BorrowedClosure<'_, R> {}
+// SAFETY: `add_command_object` only takes `Send` objects
+unsafe impl<R> Send for BorrowedCommand<'_, R> {}
+// SAFETY: it stands for the `&mut C` borrowed by the call
+unsafe impl<C: Send> Send for LentContext<C> {}
+
+// Derived impls would require `R: Copy`, though only pointers are stored
+impl<R> Clone for ErasedContext<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for ErasedContext<'_, R> {}
+
+impl<R> Clone for BorrowedClosure<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for BorrowedClosure<'_, R> {}
+
+impl<R> Clone for BorrowedCommand<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R> Copy for BorrowedCommand<'_, R> {}
+
+impl<C> Clone for LentContext<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for LentContext<C> {}
+
+impl<'a, C, R> Callback<'a, C, R> {
+    pub(crate) fn call(
+        &self,
+        args: Args<'_>,
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> CommandCallbackReturn<'a, R> {
+        match self {
+            Callback::Plain(callback) => callback(args, writer),
+            Callback::Context(erased) => {
+                // SAFETY: `context` comes from a `&'a mut T` handed over to the
+                // processor for `'a`, so nothing else can access it, and
+                // callbacks can't reenter the processor. `callback` was a
+                // `ContextCallback<'a, T, R>`, which is ABI compatible with
+                // `ContextCallback<'a, (), R>` as both take a thin reference.
+                let context = unsafe { &mut *erased.context.as_ptr() };
+                (erased.callback)(context, args, writer)
+            }
+            Callback::Closure(borrowed) => {
+                // SAFETY: `closure` comes from a `&'a mut` handed over to the
+                // processor for `'a`, like the context above.
+                let closure = unsafe { &mut *borrowed.closure.as_ptr() };
+                closure(args, writer)
+            }
+            Callback::Object(borrowed) => {
+                // SAFETY: `command` comes from a `&'a mut` handed over to the
+                // processor for `'a`, like the context above.
+                let command = unsafe { &mut *borrowed.command.as_ptr() };
+                match writer {
+                    Some(writer) => command.execute(args, Some(writer)),
+                    None => command.execute(args, None),
+                }
+            }
+            Callback::Shared(callback) => {
+                let context = context.ok_or(CommandProcessorError::NoContext)?;
+                callback(context, args, writer)
+            }
+            #[cfg(feature = "binary")]
+            Callback::Binary(callback) => callback(BinaryArgs::new(args.payload()), writer),
+        }
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Adds a command bound to an application context
+    ///
+    /// The callback receives the context every time the command is processed,
+    /// giving closure-like access to application state with a plain function
+    /// pointer. The processor borrows the context for its whole lifetime, and
+    /// the context must be `Send`, as the processor is.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    /// * `context` - The context passed to the callback
+    /// * `callback` - The callback to call when the command is processed
+    /// * `help` - The help string for the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added successfully
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already exists
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    /// use core::fmt::Write;
+    ///
+    /// fn count<'a>(counter: &mut u32, _: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     *counter += 1;
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut counter = 0;
+    /// {
+    ///     let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    ///     command_processor.add_command_with_ctx("count", &mut counter, count, None).unwrap();
+    ///     command_processor.process_command("count", None).unwrap();
+    /// }
+    ///
+    /// assert_eq!(counter, 1);
+    /// ```
+    ///
+    pub fn add_command_with_ctx<T: Send>(
+        &mut self,
+        command: impl Into<CommandName<NAME_SIZE>>,
+        context: &'a mut T,
+        callback: ContextCallback<'a, T, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+    ) -> Result<(), CommandProcessorError> {
+        let erased = ErasedContext {
+            context: NonNull::from(context).cast(),
+            // SAFETY: only ever called with the context it was registered with
+            callback: unsafe {
+                core::mem::transmute::<ContextCallback<'a, T, R>, ContextCallback<'a, (), R>>(
+                    callback,
+                )
+            },
+            _borrow: PhantomData,
+        };
+
+        self.insert_command(
+            command.into(),
+            Callback::Context(erased),
+            help,
+            self.duplicate_policy,
+            false,
+        )
+    }
+
+    /// Adds a command taking the context shared by the processor's commands
+    ///
+    /// Unlike `add_command_with_ctx`, the processor doesn't borrow the
+    /// context, it is handed over for each line with `process_command_with`.
+    /// Several commands can then share peripherals or state that the
+    /// application keeps using between lines. Processed any other way, the
+    /// command fails with `CommandProcessorError::NoContext`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    /// * `callback` - The callback to call when the command is processed
+    /// * `help` - The help string for the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added successfully
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already exists
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    /// use core::fmt::Write;
+    ///
+    /// struct Board {
+    ///     led: bool,
+    /// }
+    ///
+    /// fn led_on<'a>(board: &mut Board, _: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     board.led = true;
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut board = Board { led: false };
+    /// let mut command_processor: CommandProcessor<8, 32, 32, 32, Board> = CommandProcessor::new();
+    /// command_processor.add_command_shared("led-on", led_on, None).unwrap();
+    ///
+    /// command_processor.process_command_with(&mut board, "led-on", None).unwrap();
+    /// assert!(board.led);
+    /// ```
+    ///
+    pub fn add_command_shared(
+        &mut self,
+        command: impl Into<CommandName<NAME_SIZE>>,
+        callback: ContextCallback<'a, C, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+    ) -> Result<(), CommandProcessorError> {
+        self.insert_command(
+            command.into(),
+            Callback::Shared(callback),
+            help,
+            self.duplicate_policy,
+            false,
+        )
+    }
+
+    /// Processes a command, handing the shared context to its callback
+    ///
+    /// The context is only borrowed for the duration of the call.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The context passed to commands added with `add_command_shared`
+    /// * `command` - The command to process
+    /// * `writer` - The writer the command can write with
+    ///
+    /// # Returns
+    ///
+    /// The same results as `process_command`
+    ///
+    pub fn process_command_with(
+        &mut self,
+        context: &mut C,
+        command: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.context = Some(LentContext::new(context));
+        let result = self.process_line(command, writer);
+        self.context = None;
+        result
+    }
+
+    /// Adds a command implemented by an object
+    ///
+    /// The command is registered with its own name and help string, and the
+    /// processor borrows it for its whole lifetime. Like the processor, it
+    /// must be `Send`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added successfully
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already exists
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    /// * `Err(CommandProcessorError::HelpTooLong)` - If the help string doesn't fit `HELP_STR_SIZE`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{Args, Command, CommandProcessor, CommandProcessorError, ReturnCode};
+    /// use core::fmt::Write;
+    ///
+    /// struct Update {
+    ///     received: usize,
+    /// }
+    ///
+    /// impl Command for Update {
+    ///     fn name(&self) -> &'static str {
+    ///         "update"
+    ///     }
+    ///
+    ///     fn execute(&mut self, mut args: Args, _: Option<&mut dyn Write>) -> Result<ReturnCode, CommandProcessorError> {
+    ///         self.received += args.next_str()?.len() / 2;
+    ///         Ok(ReturnCode::Success)
+    ///     }
+    /// }
+    ///
+    /// let mut update = Update { received: 0 };
+    /// {
+    ///     let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    ///     command_processor.add_command_object(&mut update).unwrap();
+    ///     command_processor.process_command("update 0badcafe", None).unwrap();
+    /// }
+    ///
+    /// assert_eq!(update.received, 4);
+    /// ```
+    ///
+    pub fn add_command_object(
+        &mut self,
+        command: &'a mut (dyn Command<R> + Send),
+    ) -> Result<(), CommandProcessorError> {
+        let name = command.name();
+        let help = help_string(command.help())?;
+        let borrowed = BorrowedCommand {
+            command: NonNull::from(command),
+            _borrow: PhantomData,
+        };
+
+        self.insert_command(
+            CommandName::Static(name),
+            Callback::Object(borrowed),
+            help,
+            self.duplicate_policy,
+            false,
+        )
+    }
+
+    /// Adds a command calling a closure
+    ///
+    /// The closure can capture state, such as peripheral handles, without
+    /// statics. It is stored by the application, and the processor borrows
+    /// it for its whole lifetime. Like the processor, it must be `Send`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    /// * `closure` - The closure to call when the command is processed
+    /// * `help` - The help string for the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added successfully
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already exists
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{Args, CommandProcessor, ReturnCode};
+    /// use core::fmt::Write;
+    ///
+    /// let mut duty = 0u8;
+    /// {
+    ///     let mut set_duty = |mut args: Args, _: Option<&mut (dyn Write + '_)>| {
+    ///         duty = args.next_parse()?;
+    ///         Ok(ReturnCode::Success)
+    ///     };
+    ///
+    ///     let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    ///     command_processor.add_command_fn("duty", &mut set_duty, None).unwrap();
+    ///     command_processor.process_command("duty 40", None).unwrap();
+    /// }
+    ///
+    /// assert_eq!(duty, 40);
+    /// ```
+    ///
+    pub fn add_command_fn(
+        &mut self,
+        command: impl Into<CommandName<NAME_SIZE>>,
+        closure: &'a mut CommandClosure<'a, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+    ) -> Result<(), CommandProcessorError> {
+        let borrowed = BorrowedClosure {
+            closure: NonNull::from(closure),
+            _borrow: PhantomData,
+        };
+
+        self.insert_command(
+            command.into(),
+            Callback::Closure(borrowed),
+            help,
+            self.duplicate_policy,
+            false,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    use core::fmt::Write;
+    use heapless::String;
+
+    struct Led {
+        on: bool,
+    }
+
+    fn toggle<'a>(
+        led: &mut Led,
+        _: Args,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> CommandCallbackReturn<'a> {
+        led.on = !led.on;
+        if let Some(writer) = writer {
+            let _ = writeln!(writer, "{}", led.on);
+        }
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_context_command() {
+        let mut led = Led { on: false };
+        let mut buffer: String<32> = String::new();
+
+        {
+            let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+            assert!(command_processor
+                .add_command_with_ctx("toggle", &mut led, toggle, None)
+                .is_ok());
+
+            for _ in 0..3 {
+                assert!(command_processor
+                    .process_command("toggle", Some(&mut buffer))
+                    .is_ok());
+            }
+        }
+
+        assert!(led.on);
+        assert_eq!(buffer, "true\nfalse\ntrue\n");
+    }
+
+    #[test]
+    fn test_closure_command() {
+        let mut led = Led { on: false };
+        let mut toggles = 0;
+
+        {
+            let mut toggle_closure = |args: Args, writer: Option<&mut (dyn Write + '_)>| {
+                toggles += 1;
+                toggle(&mut led, args, writer)
+            };
+
+            let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+            assert!(command_processor
+                .add_command_fn("toggle", &mut toggle_closure, None)
+                .is_ok());
+
+            for _ in 0..2 {
+                assert!(command_processor.process_command("toggle", None).is_ok());
+            }
+        }
+
+        assert!(!led.on);
+        assert_eq!(toggles, 2);
+    }
+
+    #[test]
+    fn test_shared_context() {
+        fn on<'a>(
+            led: &mut Led,
+            _: Args,
+            _: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            led.on = true;
+            Ok(ReturnCode::Success)
+        }
+
+        let mut led = Led { on: false };
+        let mut command_processor: CommandProcessor<8, 32, 32, 32, Led> = CommandProcessor::new();
+        assert!(command_processor.add_command_shared("on", on, None).is_ok());
+        assert!(command_processor
+            .add_command_shared("toggle", toggle, None)
+            .is_ok());
+
+        assert!(command_processor
+            .process_command_with(&mut led, "on", None)
+            .is_ok());
+        assert!(led.on);
+        assert!(command_processor
+            .process_command_with(&mut led, "toggle", None)
+            .is_ok());
+        assert!(!led.on);
+
+        assert!(matches!(
+            command_processor.process_command("on", None),
+            Err(crate::CommandProcessorError::NoContext)
+        ));
+    }
+
+    #[test]
+    fn test_command_object() {
+        struct Blink {
+            led: Led,
+            blinks: u32,
+        }
+
+        impl crate::Command for Blink {
+            fn name(&self) -> &'static str {
+                "blink"
+            }
+
+            fn help(&self) -> Option<&'static str> {
+                Some("Blinks the LED")
+            }
+
+            fn execute(
+                &mut self,
+                mut args: Args,
+                writer: Option<&mut dyn Write>,
+            ) -> Result<ReturnCode, crate::CommandProcessorError> {
+                for _ in 0..args.next_parse::<u32>()? {
+                    self.blinks += 1;
+                    toggle(&mut self.led, Args::new(&[]), None)?;
+                }
+                if let Some(writer) = writer {
+                    let _ = writeln!(writer, "{}", self.blinks);
+                }
+                Ok(ReturnCode::Success)
+            }
+        }
+
+        let mut blink = Blink {
+            led: Led { on: false },
+            blinks: 0,
+        };
+        let mut buffer: String<32> = String::new();
+
+        {
+            let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+            assert!(command_processor.add_command_object(&mut blink).is_ok());
+            assert!(command_processor
+                .process_command("blink 3", Some(&mut buffer))
+                .is_ok());
+            assert!(command_processor.process_command("blink x", None).is_err());
+        }
+
+        assert!(blink.led.on);
+        assert_eq!(blink.blinks, 3);
+        assert_eq!(buffer, "3\n");
+    }
+}
@@ -0,0 +1,221 @@
+// This module contains the diff output of commands polled repeatedly
+use crate::cache::command_key;
+use crate::{CommandProcessor, CommandProcessorError, Handler, SliceWriter, Status};
+
+use core::fmt::Write;
+use heapless::{String, Vec};
+
+/// The maximum size of the output compared in diff mode
+///
+/// Applies when no scratch buffer is set, otherwise the scratch buffer's size
+/// is the limit.
+pub const DIFF_BUFFER_SIZE: usize = 256;
+
+/// The number of output lines compared in diff mode, later lines are always written
+pub const DIFF_LINES: usize = 16;
+
+/// The lines written by the last command run in diff mode
+pub(crate) struct Diff<const LINE_SIZE: usize> {
+    /// The command name and its arguments, see `command_key`
+    key: String<LINE_SIZE>,
+    hashes: Vec<u32, DIFF_LINES>,
+}
+
+/// Hashes a line with FNV-1a
+fn hash(line: &str) -> u32 {
+    line.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets whether running a command again only writes the lines that changed
+    ///
+    /// Meant for status commands polled over slow links. The first run
+    /// writes the whole output, then each line is only written if it
+    /// differs from the line at the same position in the previous run's
+    /// output, lines being compared by hash. Running another command in
+    /// diff mode, or the same one with other arguments, starts over, and so
+    /// does a failed run.
+    ///
+    /// The output is captured in the scratch buffer if one is set, see
+    /// `set_scratch_buffer`, otherwise in a `DIFF_BUFFER_SIZE` buffer on the
+    /// stack. Output that doesn't fit is written as far as it was captured,
+    /// and fails with `CommandProcessorError::WriteError`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set diff mode for
+    /// * `diff` - Whether only changed lines are written
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If diff mode was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use core::sync::atomic::{AtomicU32, Ordering};
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// static UPTIME: AtomicU32 = AtomicU32::new(0);
+    ///
+    /// fn status<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     if let Some(writer) = writer {
+    ///         let _ = writeln!(writer, "fw: 1.2.0");
+    ///         let _ = writeln!(writer, "uptime: {}", UPTIME.fetch_add(1, Ordering::Relaxed));
+    ///     }
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("status", status, None).unwrap();
+    /// command_processor.set_diff_output("status", true).unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.process_command("status", Some(&mut writer)).unwrap();
+    /// command_processor.process_command("status", Some(&mut writer)).unwrap();
+    ///
+    /// assert_eq!(writer, "fw: 1.2.0\nuptime: 0\nuptime: 1\n");
+    /// ```
+    ///
+    pub fn set_diff_output(
+        &mut self,
+        command: &str,
+        diff: bool,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.diff_output = diff;
+        Ok(())
+    }
+
+    /// Forgets the previous output, so the next run in diff mode writes it whole
+    ///
+    /// To be called when a host reconnects for instance.
+    pub fn reset_diff_output(&mut self) {
+        self.diff = None;
+    }
+
+    /// Runs a command in diff mode, see `set_diff_output`
+    ///
+    /// Runs the command as usual if its arguments don't fit in a key.
+    pub(crate) fn run_diffed(
+        &mut self,
+        name: &str,
+        cache_key: Option<(String<LINE_SIZE>, u64)>,
+        handler: Handler<'a, C, R>,
+        args: &[&str],
+        context: Option<&mut C>,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<R, CommandProcessorError> {
+        let Some(key) = command_key(name, args) else {
+            return self.run_cached(cache_key, handler, args, context, Some(writer));
+        };
+
+        let (result, written) = match self.scratch.take() {
+            Some(scratch) => {
+                let mut capture = SliceWriter::new(scratch);
+                let result = self.run_cached(cache_key, handler, args, context, Some(&mut capture));
+                let written = self.write_diff(key, &result, &capture, writer);
+                self.scratch = Some(capture.into_inner());
+                (result, written)
+            }
+            None => {
+                let mut capture = SliceWriter::new([0u8; DIFF_BUFFER_SIZE]);
+                let result = self.run_cached(cache_key, handler, args, context, Some(&mut capture));
+                let written = self.write_diff(key, &result, &capture, writer);
+                (result, written)
+            }
+        };
+        written.and(result)
+    }
+
+    /// Writes the lines of the captured output that changed since the previous run
+    fn write_diff<B: AsRef<[u8]> + AsMut<[u8]>>(
+        &mut self,
+        key: String<LINE_SIZE>,
+        result: &Result<R, CommandProcessorError>,
+        capture: &SliceWriter<B>,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        let previous = self.diff.take().filter(|diff| diff.key == key);
+        let output = capture.as_str();
+
+        // Commands may ignore write errors, so check for overflows here
+        if result.is_err() || capture.overflowed() {
+            writer
+                .write_str(output)
+                .map_err(|_| CommandProcessorError::WriteError)?;
+            return match capture.overflowed() {
+                true => Err(CommandProcessorError::WriteError),
+                false => Ok(()),
+            };
+        }
+
+        let mut hashes = Vec::new();
+        for (index, line) in output.split_inclusive('\n').enumerate() {
+            let hash = hash(line);
+            let _ = hashes.push(hash);
+            let previous = previous.as_ref().and_then(|diff| diff.hashes.get(index));
+            if previous != Some(&hash) {
+                writer
+                    .write_str(line)
+                    .map_err(|_| CommandProcessorError::WriteError)?;
+            }
+        }
+
+        self.diff = Some(Diff { key, hashes });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, ReturnCode};
+
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    static COUNT: AtomicU32 = AtomicU32::new(0);
+
+    fn count<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        for arg in args.remaining() {
+            writeln!(writer, "{}", arg).map_err(|_| CommandProcessorError::WriteError)?;
+        }
+        writeln!(writer, "{}", count).map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_diff_output() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("count", count, None).is_ok());
+        assert!(command_processor.set_diff_output("count", true).is_ok());
+
+        let mut run = |line: &str| {
+            let mut writer = std::string::String::new();
+            assert!(command_processor
+                .process_command(line, Some(&mut writer))
+                .is_ok());
+            writer
+        };
+
+        assert_eq!(run("count a b"), "a\nb\n0\n");
+        assert_eq!(run("count a b"), "1\n");
+        // Other arguments start over
+        assert_eq!(run("count a"), "a\n2\n");
+        assert_eq!(run("count a"), "3\n");
+    }
+}
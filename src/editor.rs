@@ -0,0 +1,359 @@
+// This module contains the line editing of the byte-oriented input front-end
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The ESC character, starting the escape sequences of a VT100 terminal
+const ESC: u8 = 0x1b;
+
+/// Moves the cursor back over the erased character and blanks it
+const ERASE: &str = "\x08 \x08";
+
+/// Rings the terminal bell, when Tab has nothing to complete
+#[cfg(feature = "completion")]
+pub(crate) const BELL: &str = "\x07";
+
+/// An editing key of a VT100 terminal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+}
+
+/// Where `feed` is in an escape sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Escape {
+    /// Not in an escape sequence
+    Idle,
+    /// After `ESC`
+    Start,
+    /// After `ESC [`, with the numeric parameter received so far
+    Csi(u8),
+    /// After `ESC O`
+    Ss3,
+}
+
+impl Escape {
+    /// Feeds a byte to the escape sequence being received
+    ///
+    /// Returns `None` if the byte isn't part of a sequence, or the key the
+    /// sequence ended with, if any. Sequences of unknown keys are dropped,
+    /// and control characters abort them without being consumed, so a line
+    /// ending is never lost.
+    pub(crate) fn feed(&mut self, byte: u8) -> Option<Option<Key>> {
+        let (next, key) = match (*self, byte) {
+            (_, ESC) => (Escape::Start, None),
+            (Escape::Idle, _) => return None,
+            (_, 0x00..=0x1f) => {
+                *self = Escape::Idle;
+                return None;
+            }
+            (Escape::Start, b'[') => (Escape::Csi(0), None),
+            (Escape::Start, b'O') => (Escape::Ss3, None),
+            (Escape::Csi(param), b'0'..=b'9') => {
+                let digit = byte.saturating_sub(b'0');
+                let param = param.saturating_mul(10).saturating_add(digit);
+                (Escape::Csi(param), None)
+            }
+            // Modifiers such as `;5` for Ctrl are ignored
+            (Escape::Csi(param), 0x20..=0x3f) => (Escape::Csi(param), None),
+            (Escape::Csi(param), b'~') => (Escape::Idle, Self::tilde_key(param)),
+            (Escape::Csi(_) | Escape::Ss3, _) => (Escape::Idle, Self::letter_key(byte)),
+            (Escape::Start, _) => (Escape::Idle, None),
+        };
+
+        *self = next;
+        Some(key)
+    }
+
+    /// Returns the key of `ESC [ A` to `ESC [ H`, or of `ESC O A` to `ESC O H`
+    fn letter_key(byte: u8) -> Option<Key> {
+        match byte {
+            b'A' => Some(Key::Up),
+            b'B' => Some(Key::Down),
+            b'C' => Some(Key::Right),
+            b'D' => Some(Key::Left),
+            b'H' => Some(Key::Home),
+            b'F' => Some(Key::End),
+            _ => None,
+        }
+    }
+
+    /// Returns the key of `ESC [ <param> ~`
+    fn tilde_key(param: u8) -> Option<Key> {
+        match param {
+            1 | 7 => Some(Key::Home),
+            3 => Some(Key::Delete),
+            4 | 8 => Some(Key::End),
+            _ => None,
+        }
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Applies an editing key to the line being received
+    pub(crate) fn edit(
+        &mut self,
+        key: Key,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        let len = self.line.len();
+        let written = match key {
+            Key::Left if self.cursor > 0 => {
+                self.cursor = self.cursor.saturating_sub(1);
+                writer.write_str("\x08")
+            }
+            Key::Right if self.cursor < len => {
+                let moved = self.line.get(self.cursor..self.cursor.saturating_add(1));
+                self.cursor = self.cursor.saturating_add(1);
+                writer.write_str(moved.unwrap_or(""))
+            }
+            Key::Home if self.cursor > 0 => {
+                let moved = core::mem::take(&mut self.cursor);
+                write!(writer, "\x1b[{}D", moved)
+            }
+            Key::End if self.cursor < len => {
+                let moved = len.saturating_sub(self.cursor);
+                self.cursor = len;
+                write!(writer, "\x1b[{}C", moved)
+            }
+            Key::Delete if self.cursor < len => {
+                self.remove(self.cursor);
+                return self.redraw_tail(writer, 1);
+            }
+            #[cfg(feature = "history")]
+            Key::Up | Key::Down => return self.recall(key, writer),
+            _ => Ok(()),
+        };
+        written.map_err(|_| CommandProcessorError::WriteError)
+    }
+
+    /// Replaces the line with an older or newer one from the history
+    #[cfg(feature = "history")]
+    fn recall(&mut self, key: Key, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        let Some(history) = self.history.as_deref_mut() else {
+            return Ok(());
+        };
+        let recalled = match key {
+            Key::Up => history.older(),
+            _ => history.newer(),
+        };
+        let Some(recalled) = recalled else {
+            return Ok(());
+        };
+
+        // The history has the processor's line size, so this always fits
+        let mut line = String::new();
+        let _ = line.push_str(recalled);
+        let moved = core::mem::replace(&mut self.cursor, line.len());
+        self.line = line;
+
+        // Rewrites the line in place, then clears what is left of the old one
+        match moved {
+            0 => write!(writer, "{}\x1b[K", self.line),
+            _ => write!(writer, "\x1b[{}D{}\x1b[K", moved, self.line),
+        }
+        .map_err(|_| CommandProcessorError::WriteError)
+    }
+
+    /// Inserts text at the cursor and shows it, ringing the bell if it doesn't fit
+    #[cfg(feature = "completion")]
+    pub(crate) fn insert_str(
+        &mut self,
+        text: &str,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        let (head, tail) = self
+            .line
+            .split_at_checked(self.cursor)
+            .unwrap_or((&self.line, ""));
+        let mut line: String<LINE_SIZE> = String::new();
+        let fits = line
+            .push_str(head)
+            .and_then(|()| line.push_str(text))
+            .and_then(|()| line.push_str(tail))
+            .is_ok();
+        if !fits {
+            return writer
+                .write_str(BELL)
+                .map_err(|_| CommandProcessorError::WriteError);
+        }
+
+        self.line = line;
+        self.cursor = self.cursor.saturating_add(text.len());
+        writer
+            .write_str(text)
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        self.redraw_tail(writer, 0)
+    }
+
+    /// Inserts a character at the cursor, returning whether it fit
+    ///
+    /// The character is echoed if `set_echo` is on, otherwise the terminal
+    /// is expected to show it itself. The rest of the line is redrawn after
+    /// it.
+    pub(crate) fn insert(
+        &mut self,
+        c: char,
+        writer: &mut dyn Write,
+    ) -> Result<bool, CommandProcessorError> {
+        if self.cursor >= self.line.len() {
+            let pushed = self.line.push(c).is_ok();
+            self.cursor = self.line.len();
+            if pushed {
+                self.echo_char(c, writer)?;
+            }
+            return Ok(pushed);
+        }
+
+        let (head, tail) = self
+            .line
+            .split_at_checked(self.cursor)
+            .unwrap_or((&self.line, ""));
+        let mut line: String<LINE_SIZE> = String::new();
+        let fits = line
+            .push_str(head)
+            .and_then(|()| line.push(c))
+            .and_then(|()| line.push_str(tail))
+            .is_ok();
+        if !fits {
+            return Ok(false);
+        }
+
+        self.line = line;
+        self.cursor = self.cursor.saturating_add(1);
+        self.echo_char(c, writer)?;
+        self.redraw_tail(writer, 0).map(|()| true)
+    }
+
+    /// Writes a character received, if `set_echo` is on
+    fn echo_char(&self, c: char, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        match self.echo {
+            true => writer
+                .write_char(c)
+                .map_err(|_| CommandProcessorError::WriteError),
+            false => Ok(()),
+        }
+    }
+
+    /// Discards the line being received, blanking it on the terminal if `set_echo` is on
+    pub(crate) fn cancel(&mut self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        #[cfg(feature = "history")]
+        if let Some(history) = self.history.as_deref_mut() {
+            history.reset();
+        }
+        let shown = !self.line.is_empty() && self.echo;
+        let moved = core::mem::take(&mut self.cursor);
+        self.line.clear();
+        self.line_overflowed = false;
+
+        match (shown, moved) {
+            (false, _) => Ok(()),
+            (true, 0) => writer.write_str("\x1b[K"),
+            (true, _) => write!(writer, "\x1b[{}D\x1b[K", moved),
+        }
+        .map_err(|_| CommandProcessorError::WriteError)
+    }
+
+    /// Erases the character before the cursor, for the backspace key
+    pub(crate) fn erase(&mut self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        if self.cursor == 0 {
+            return Ok(());
+        }
+        self.cursor = self.cursor.saturating_sub(1);
+        self.remove(self.cursor);
+
+        if self.cursor == self.line.len() {
+            return writer
+                .write_str(ERASE)
+                .map_err(|_| CommandProcessorError::WriteError);
+        }
+        writer
+            .write_str("\x08")
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        self.redraw_tail(writer, 1)
+    }
+
+    /// Removes the character at an index of the line
+    fn remove(&mut self, index: usize) {
+        let (head, tail) = self
+            .line
+            .split_at_checked(index)
+            .unwrap_or((&self.line, ""));
+        let mut line: String<LINE_SIZE> = String::new();
+        // The line only gets shorter, so this always fits
+        let _ = line.push_str(head);
+        let _ = line.push_str(tail.get(1..).unwrap_or(""));
+        self.line = line;
+    }
+
+    /// Writes the prompt and the whole line on a fresh terminal line, the cursor back in place
+    pub(crate) fn redraw_line(&self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        let back = self.line.len().saturating_sub(self.cursor);
+        self.write_prompt(writer)?;
+        writer
+            .write_str(&self.line)
+            .and_then(|()| match back {
+                0 => Ok(()),
+                _ => write!(writer, "\x1b[{}D", back),
+            })
+            .map_err(|_| CommandProcessorError::WriteError)
+    }
+
+    /// Rewrites the line from the cursor on, blanking the characters removed
+    fn redraw_tail(
+        &self,
+        writer: &mut dyn Write,
+        removed: usize,
+    ) -> Result<(), CommandProcessorError> {
+        let tail = self.line.get(self.cursor..).unwrap_or("");
+        let back = tail.len().saturating_add(removed);
+        write!(writer, "{}{:removed$}", tail, "")
+            .and_then(|()| match back {
+                0 => Ok(()),
+                _ => write!(writer, "\x1b[{}D", back),
+            })
+            .map_err(|_| CommandProcessorError::WriteError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_sequences() {
+        let mut escape = Escape::Idle;
+        let mut keys = std::vec::Vec::new();
+        for &byte in b"\x1b[A\x1b[D\x1bOH\x1b[3~\x1b[1;5C\x1b[99~x" {
+            if let Some(Some(key)) = escape.feed(byte) {
+                keys.push(key);
+            }
+        }
+        assert_eq!(
+            keys,
+            [Key::Up, Key::Left, Key::Home, Key::Delete, Key::Right]
+        );
+
+        // A line ending aborts the sequence without being consumed
+        assert_eq!(escape.feed(b'x'), None);
+        assert_eq!(escape.feed(ESC), Some(None));
+        assert_eq!(escape.feed(b'\n'), None);
+        assert_eq!(escape, Escape::Idle);
+    }
+}
@@ -0,0 +1,150 @@
+// This module contains fixed-point decimal values for human input and output
+use crate::CommandProcessorError;
+
+use core::fmt;
+use core::str::FromStr;
+
+/// A decimal value with `DECIMALS` fractional digits, stored as a scaled integer
+///
+/// Lets calibration commands exchange fractional values with operators
+/// without floating point formatting. Parsing accepts both `.` and `,` as the
+/// decimal separator, formatting always uses `.`.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::Decimal;
+///
+/// let gain: Decimal<3> = "-1,25".parse().unwrap();
+///
+/// assert_eq!(gain.0, -1250);
+/// assert_eq!(format!("{}", gain), "-1.250");
+/// assert!("1.2345".parse::<Decimal<3>>().is_err());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Decimal<const DECIMALS: u32>(pub i64);
+
+impl<const DECIMALS: u32> Decimal<DECIMALS> {
+    /// The raw value of one, `10^DECIMALS`, or `None` if it doesn't fit
+    const SCALE: Option<i64> = 10i64.checked_pow(DECIMALS);
+}
+
+impl<const DECIMALS: u32> FromStr for Decimal<DECIMALS> {
+    type Err = CommandProcessorError;
+
+    /// Parses a decimal such as `12`, `-0.5` or `3,75`
+    ///
+    /// Fractional digits beyond `DECIMALS` are rejected unless they are zeros,
+    /// so precision is never lost silently.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scale = Self::SCALE.ok_or(CommandProcessorError::InvalidArguments)?;
+
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (integer, fraction) = match digits.split_once(['.', ',']) {
+            Some((integer, fraction)) => (integer, fraction),
+            None => (digits, ""),
+        };
+
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(CommandProcessorError::InvalidArguments);
+        }
+
+        let mut value: i64 = 0;
+        for c in integer.chars() {
+            let digit = c
+                .to_digit(10)
+                .ok_or(CommandProcessorError::InvalidArguments)?;
+            value = value
+                .checked_mul(10)
+                .and_then(|value| value.checked_add(i64::from(digit)))
+                .ok_or(CommandProcessorError::InvalidArguments)?;
+        }
+        value = value
+            .checked_mul(scale)
+            .ok_or(CommandProcessorError::InvalidArguments)?;
+
+        let mut unit = scale;
+        for c in fraction.chars() {
+            let digit = c
+                .to_digit(10)
+                .ok_or(CommandProcessorError::InvalidArguments)?;
+            unit = unit.checked_div(10).unwrap_or(0);
+            match unit {
+                0 if digit != 0 => return Err(CommandProcessorError::InvalidArguments),
+                _ => {
+                    value = i64::from(digit)
+                        .checked_mul(unit)
+                        .and_then(|digit| value.checked_add(digit))
+                        .ok_or(CommandProcessorError::InvalidArguments)?
+                }
+            }
+        }
+
+        match negative {
+            true => value
+                .checked_neg()
+                .map(Self)
+                .ok_or(CommandProcessorError::InvalidArguments),
+            false => Ok(Self(value)),
+        }
+    }
+}
+
+impl<const DECIMALS: u32> fmt::Display for Decimal<DECIMALS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scale = Self::SCALE.map_or(1, i64::unsigned_abs);
+        let magnitude = self.0.unsigned_abs();
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let integer = magnitude.checked_div(scale).unwrap_or(0);
+
+        match DECIMALS {
+            0 => write!(f, "{}{}", sign, integer),
+            _ => write!(
+                f,
+                "{}{}.{:0width$}",
+                sign,
+                integer,
+                magnitude.checked_rem(scale).unwrap_or(0),
+                width = DECIMALS as usize
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_parse() {
+        assert_eq!("12".parse::<Decimal<2>>().unwrap(), Decimal(1200));
+        assert_eq!("0.5".parse::<Decimal<2>>().unwrap(), Decimal(50));
+        assert_eq!("-,05".parse::<Decimal<2>>().unwrap(), Decimal(-5));
+        assert_eq!("+3,750".parse::<Decimal<2>>().unwrap(), Decimal(375));
+        assert_eq!("7".parse::<Decimal<0>>().unwrap(), Decimal(7));
+
+        for invalid in [
+            "",
+            "-",
+            ".",
+            "1.2.3",
+            "1e3",
+            "0.001",
+            "99999999999999999999",
+        ] {
+            assert!(invalid.parse::<Decimal<2>>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_decimal_display() {
+        assert_eq!(Decimal::<2>(1200).to_string(), "12.00");
+        assert_eq!(Decimal::<2>(-5).to_string(), "-0.05");
+        assert_eq!(Decimal::<0>(-7).to_string(), "-7");
+        assert_eq!(Decimal::<3>(i64::MIN).to_string(), "-9223372036854775.808");
+    }
+}
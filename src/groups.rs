@@ -0,0 +1,141 @@
+// This module contains the groups formed by commands with subcommands
+use crate::tokenizer::Tokens;
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// Returns the word following a group's path in a command name
+///
+/// With an empty path, the first word of the name. `None` if the command
+/// isn't in the group.
+pub(crate) fn next_word<'n>(command: &'n str, path: &str) -> Option<&'n str> {
+    let rest = match path.is_empty() {
+        true => command,
+        false => command.strip_prefix(path)?.strip_prefix(' ')?,
+    };
+    rest.split(' ').next()
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Returns whether commands were registered under a path, such as `net` for `net ping`
+    pub(crate) fn is_group(&self, path: &str) -> bool {
+        !path.is_empty()
+            && self
+                .commands
+                .iter()
+                .any(|cmd| next_word(&cmd.command, path).is_some())
+    }
+
+    /// Resolves the subcommand a line runs, when its name is a group
+    ///
+    /// Consumes the words of the subcommand, writing its whole name into
+    /// `path`. A group that is also a command runs with the following words
+    /// as arguments if they aren't subcommands. Otherwise the failing level
+    /// is written as `unknown subcommand: net bogus`, or `missing argument:
+    /// net` if the line stops at the group.
+    pub(crate) fn resolve_subcommand(
+        &self,
+        name: &str,
+        tokens: &mut Tokens,
+        path: &mut String<LINE_SIZE>,
+        writer: Option<&mut dyn Write>,
+    ) -> Result<(), CommandProcessorError> {
+        // The name is part of the line, so it fits
+        let _ = path.push_str(name);
+
+        while self.is_group(path) {
+            let command = self.commands.iter().any(|cmd| cmd.command == path.as_str());
+            let Some(word) = tokens.peek_raw() else {
+                if command {
+                    return Ok(());
+                }
+                if let Some(writer) = writer {
+                    let message = self.messages.missing_argument;
+                    self.output
+                        .write(writer, format_args!("{}: {}\n", message, path))?;
+                }
+                return Err(CommandProcessorError::MissingArgument);
+            };
+
+            let mut subcommand = path.clone();
+            let known = subcommand.push(' ').is_ok()
+                && subcommand.push_str(word).is_ok()
+                && self.commands.iter().any(|cmd| {
+                    cmd.command == subcommand.as_str()
+                        || next_word(&cmd.command, &subcommand).is_some()
+                });
+            match (known, command) {
+                (true, _) => {
+                    tokens.next();
+                    *path = subcommand;
+                }
+                (false, true) => return Ok(()),
+                (false, false) => {
+                    if let Some(writer) = writer {
+                        let message = self.messages.unknown_subcommand;
+                        self.output
+                            .write(writer, format_args!("{}: {} {}\n", message, path, word))?;
+                    }
+                    return Err(CommandProcessorError::UnknownSubcommand);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, ReturnCode};
+
+    fn echo<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "{:?}", args.remaining())
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for name in ["net ping", "net wifi scan", "led", "led blink"] {
+            assert!(command_processor.add_command(name, echo, None).is_ok());
+        }
+
+        let mut run = |line: &str| {
+            let mut writer = std::string::String::new();
+            let result = command_processor.process_command(line, Some(&mut writer));
+            (result, writer)
+        };
+
+        assert_eq!(run("net ping host").1, "[\"host\"]\n");
+        assert_eq!(run("net wifi scan").1, "[]\n");
+        // A group that is a command takes arguments
+        assert_eq!(run("led on").1, "[\"on\"]\n");
+        assert_eq!(run("led blink 3").1, "[\"3\"]\n");
+
+        let (result, output) = run("net wifi bogus");
+        assert!(matches!(
+            result,
+            Err(CommandProcessorError::UnknownSubcommand)
+        ));
+        assert_eq!(output, "unknown subcommand: net wifi bogus\n");
+        let (result, output) = run("net");
+        assert!(matches!(
+            result,
+            Err(CommandProcessorError::MissingArgument)
+        ));
+        assert_eq!(output, "missing argument: net\n");
+    }
+}
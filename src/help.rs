@@ -0,0 +1,734 @@
+// This module contains the help built-in
+use crate::groups::next_word;
+use crate::schema::Usage;
+use crate::{CommandItem, CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+#[cfg(feature = "input")]
+use core::fmt;
+use core::fmt::Write;
+use heapless::String;
+
+/// The page of help lines the input layer asks for, see `set_help_pager`
+#[cfg(feature = "input")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Page {
+    /// The number of lines before the page, then of the lines up to the next page
+    pub(crate) skip: usize,
+    /// Whether lines were left out after the page
+    pub(crate) more: bool,
+}
+
+/// A writer only passing the lines of a page through
+#[cfg(feature = "input")]
+struct PageWriter<'w> {
+    writer: &'w mut dyn Write,
+    skip: usize,
+    end: usize,
+    /// The number of complete lines written to it
+    seen: usize,
+    more: bool,
+}
+
+#[cfg(feature = "input")]
+impl Write for PageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for part in s.split_inclusive('\n') {
+            match self.seen {
+                seen if seen < self.skip => {}
+                seen if seen < self.end => self.writer.write_str(part)?,
+                _ => self.more = true,
+            }
+            if part.ends_with('\n') {
+                self.seen = self.seen.saturating_add(1);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Runs the help built-in
+    ///
+    /// * `help` - Prints the help string of every command, under category headers
+    ///   if commands have categories. Hidden commands are left out of every
+    ///   listing, see `set_hidden`.
+    /// * `help <command>` - Prints the usage, help string and long help of a single
+    ///   command, see `set_long_help`, or the help strings of the subcommands of
+    ///   a group such as `help net`
+    /// * `help <command> --examples` - Prints the example invocations of a command
+    /// * `help -s` - Prints only the command names, with their aliases, in columns
+    /// * `help -v` - Prints every command with its aliases, help string and metadata
+    /// * `help /pattern` - Prints the commands whose name or help contains the pattern
+    ///
+    /// When the input layer asks for a page, see `set_help_pager`, only the
+    /// lines of the page are written.
+    pub(crate) fn help<'l>(
+        &mut self,
+        args: impl Iterator<Item = &'l str>,
+        writer: &mut dyn Write,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        #[cfg(feature = "input")]
+        if let (Some(page), Some(lines)) = (self.page.take(), self.help_page_lines) {
+            let mut pager = PageWriter {
+                writer,
+                skip: page.skip,
+                end: page.skip.saturating_add(lines),
+                seen: 0,
+                more: false,
+            };
+            let result = self.run_help(args, &mut pager);
+            self.page = Some(Page {
+                skip: pager.end,
+                more: pager.more,
+            });
+            return result;
+        }
+
+        self.run_help(args, writer)
+    }
+
+    fn run_help<'l>(
+        &mut self,
+        args: impl Iterator<Item = &'l str>,
+        writer: &mut dyn Write,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let mut path: String<LINE_SIZE> = String::new();
+        let mut examples = false;
+        let mut short = false;
+        let mut verbose = false;
+
+        for arg in args {
+            match arg {
+                "--examples" => examples = true,
+                "-s" => short = true,
+                "-v" => verbose = true,
+                _ if arg.len() > 1 && arg.starts_with('/') && path.is_empty() => {
+                    return self.search_help_printer(arg.get(1..).unwrap_or(""), writer)
+                }
+                // Further words name a subcommand, as in `help net ping`
+                _ if path.is_empty() || self.is_group(&path) => {
+                    let pushed = match path.is_empty() {
+                        true => path.push_str(self.resolve_alias(arg)),
+                        false => path.push(' ').and_then(|()| path.push_str(arg)),
+                    };
+                    pushed.map_err(|_| CommandProcessorError::CommandNotFound)?;
+                }
+                _ => return Err(CommandProcessorError::UnexpectedArgument),
+            }
+        }
+        let command = (!path.is_empty()).then_some(path.as_str());
+
+        let command = match command {
+            Some(_) if short || verbose => return Err(CommandProcessorError::UnexpectedArgument),
+            Some(command) => command,
+            None if examples => return Err(CommandProcessorError::MissingArgument),
+            None if short && verbose => return Err(CommandProcessorError::UnexpectedArgument),
+            None if short => return self.short_help_printer(writer),
+            None if verbose => return self.verbose_help_printer(writer),
+            None => return self.help_printer(writer),
+        };
+
+        let Some(cmd) = self.commands.iter().find(|cmd| cmd.command == command) else {
+            if examples || !self.is_group(command) {
+                return Err(CommandProcessorError::CommandNotFound);
+            }
+            for cmd in self.commands.iter().filter(|cmd| !cmd.hidden) {
+                if next_word(&cmd.command, command).is_some() {
+                    self.write_help(cmd, "", writer)?;
+                }
+            }
+            return Ok(ReturnCode::Success);
+        };
+
+        if !examples {
+            self.write_usage(cmd, writer)?;
+            self.write_help(cmd, "", writer)?;
+            if let Some(long_help) = cmd.long_help {
+                self.output.write(writer, format_args!("{}\n", long_help))?;
+            }
+            return Ok(ReturnCode::Success);
+        }
+
+        if cmd.examples.is_empty() {
+            self.output
+                .write(writer, format_args!("{}\n", self.messages.no_examples))?;
+        }
+
+        for example in cmd.examples {
+            self.output.write(writer, format_args!("{}\n", example))?;
+        }
+
+        Ok(ReturnCode::Success)
+    }
+
+    fn help_printer(
+        &mut self,
+        writer: &mut dyn Write,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let visible = || self.commands.iter().filter(|cmd| !cmd.hidden);
+        for cmd in visible().filter(|cmd| cmd.category.is_none()) {
+            self.write_help(cmd, "", writer)?;
+        }
+
+        // Each category is written where it's first used
+        for (i, cmd) in visible().enumerate() {
+            let Some(category) = cmd.category else {
+                continue;
+            };
+            let mut earlier = visible().take(i);
+            if earlier.any(|cmd| cmd.category == Some(category)) {
+                continue;
+            }
+
+            self.output.write(writer, format_args!("{}:\n", category))?;
+            for cmd in visible().skip(i) {
+                if cmd.category == Some(category) {
+                    self.write_help(cmd, "  ", writer)?;
+                }
+            }
+        }
+
+        Ok(ReturnCode::Success)
+    }
+
+    /// Writes the usage line of a command, if it declared its arguments
+    fn write_usage(
+        &self,
+        cmd: &CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        if cmd.schema.is_empty() {
+            return Ok(());
+        }
+
+        let usage = Usage {
+            command: &cmd.command,
+            schema: cmd.schema,
+        };
+        self.output.write(writer, format_args!("{}\n", usage))
+    }
+
+    /// Writes the help of a command, from its help callback if it has one
+    fn write_help(
+        &self,
+        cmd: &CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>,
+        indent: &str,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        match (cmd.help_callback, &cmd.help) {
+            (Some(help_callback), _) => writer
+                .write_str(indent)
+                .and_then(|_| help_callback(writer))
+                .map_err(|_| CommandProcessorError::WriteError),
+            (None, Some(help)) if indent.is_empty() => {
+                self.output.write(writer, format_args!("{}\n", help))
+            }
+            (None, Some(help)) => self
+                .output
+                .write(writer, format_args!("{}{}\n", indent, help)),
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn search_help_printer(
+        &mut self,
+        pattern: &str,
+        writer: &mut dyn Write,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let mut found = false;
+
+        for cmd in self.commands.iter().filter(|cmd| !cmd.hidden) {
+            let help = cmd.help.as_deref();
+            if !contains_ignore_case(&cmd.command, pattern)
+                && !help.is_some_and(|help| contains_ignore_case(help, pattern))
+            {
+                continue;
+            }
+
+            found = true;
+            self.output
+                .write(writer, format_args!("{}\n", help.unwrap_or(&cmd.command)))?;
+        }
+
+        if !found {
+            self.output
+                .write(writer, format_args!("{}\n", self.messages.no_matches))?;
+        }
+
+        Ok(ReturnCode::Success)
+    }
+
+    fn short_help_printer(
+        &mut self,
+        writer: &mut dyn Write,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let visible = self.commands.iter().filter(|cmd| !cmd.hidden).count();
+        let width = self
+            .commands
+            .iter()
+            .filter(|cmd| !cmd.hidden)
+            .map(|cmd| self.aliased(&cmd.command).width())
+            .max()
+            .unwrap_or(0)
+            .saturating_add(2);
+        let columns = self.terminal_width.checked_div(width).unwrap_or(0).max(1);
+
+        for (i, cmd) in self.sorted_commands().filter(|cmd| !cmd.hidden).enumerate() {
+            let count = i.saturating_add(1);
+            let last = count.checked_rem(columns) == Some(0) || count == visible;
+            let aliased = self.aliased(&cmd.command);
+            match last {
+                true => self.output.write(writer, format_args!("{}\n", aliased))?,
+                false => {
+                    let padding = width.saturating_sub(aliased.width());
+                    self.output
+                        .write(writer, format_args!("{}{:padding$}", aliased, ""))?
+                }
+            }
+        }
+
+        Ok(ReturnCode::Success)
+    }
+
+    fn verbose_help_printer(
+        &mut self,
+        writer: &mut dyn Write,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        for cmd in self.sorted_commands().filter(|cmd| !cmd.hidden) {
+            self.output
+                .write(writer, format_args!("{}\n", self.aliased(&cmd.command)))?;
+
+            self.write_help(cmd, "    ", writer)?;
+
+            if let Some(category) = cmd.category {
+                self.output.write(
+                    writer,
+                    format_args!("    {}: {}\n", self.messages.category, category),
+                )?;
+            }
+
+            if let Some(max_args) = cmd.max_args {
+                self.output.write(
+                    writer,
+                    format_args!("    {}: {}\n", self.messages.max_arguments, max_args),
+                )?;
+            }
+
+            for example in cmd.examples {
+                self.output.write(
+                    writer,
+                    format_args!("    {}: {}\n", self.messages.example, example),
+                )?;
+            }
+        }
+
+        Ok(ReturnCode::Success)
+    }
+}
+
+/// Returns whether `text` contains `pattern`, ignoring ASCII case
+fn contains_ignore_case(text: &str, pattern: &str) -> bool {
+    let pattern = pattern.as_bytes();
+    text.as_bytes()
+        .windows(pattern.len().max(1))
+        .any(|window| window.eq_ignore_ascii_case(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::printer_demo;
+    use crate::{ArgKind, ArgSpec, CommandProcessor, CommandProcessorError};
+    use core::fmt::Write;
+    use heapless::String;
+
+    #[test]
+    fn test_help_examples() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("baud"),
+                printer_demo,
+                Some(String::from("baud <rate>"))
+            )
+            .is_ok());
+        assert!(command_processor
+            .add_command(String::from("reboot"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .set_examples("baud", &["baud 9600", "baud 115200"])
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help baud --examples", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "baud 9600\nbaud 115200\n");
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help baud", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "baud <rate>\n");
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help reboot --examples", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "no examples\n");
+
+        assert!(matches!(
+            command_processor.process_command("help nope", Some(&mut buffer)),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_help_usage() {
+        static SET_PWM: [ArgSpec; 3] = [
+            ArgSpec::required("channel", ArgKind::U8),
+            ArgSpec::required("duty", ArgKind::U8),
+            ArgSpec::flag("invert"),
+        ];
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                "set-pwm",
+                printer_demo,
+                Some(String::from("Sets a PWM duty"))
+            )
+            .is_ok());
+        assert!(command_processor.set_arguments("set-pwm", &SET_PWM).is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help set-pwm", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "set-pwm <channel:u8> <duty:u8> [--invert]\nSets a PWM duty\n"
+        );
+    }
+
+    #[test]
+    fn test_short_help() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_terminal_width(20);
+
+        for name in ["reboot", "baud", "gpio", "wifi", "status"] {
+            assert!(command_processor
+                .add_command(String::from(name), printer_demo, None)
+                .is_ok());
+        }
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help -s", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "baud    gpio\nreboot  status\nwifi\n");
+    }
+
+    #[test]
+    fn test_verbose_help() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("baud"),
+                printer_demo,
+                Some(String::from("baud <rate>"))
+            )
+            .is_ok());
+        assert!(command_processor.set_max_arguments("baud", Some(1)).is_ok());
+        assert!(command_processor
+            .set_examples("baud", &["baud 9600"])
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help -v", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "baud\n    baud <rate>\n    max arguments: 1\n    example: baud 9600\n"
+        );
+    }
+
+    #[test]
+    fn test_help_search() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("scan"),
+                printer_demo,
+                Some(String::from("scan: Scans for WiFi networks"))
+            )
+            .is_ok());
+        assert!(command_processor
+            .add_command(String::from("wifi-off"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command(
+                String::from("reboot"),
+                printer_demo,
+                Some(String::from("reboot: Reboots"))
+            )
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help /wifi", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "scan: Scans for WiFi networks\nwifi-off\n");
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help /gpio", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "no matching commands\n");
+    }
+
+    #[test]
+    fn test_help_callback() {
+        fn channels(writer: &mut dyn Write) -> core::fmt::Result {
+            writeln!(writer, "Reads a channel, 0 to {}", 3)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("adc", printer_demo, Some(String::from("Reads a channel")))
+            .is_ok());
+        assert!(command_processor.set_help_callback("adc", channels).is_ok());
+
+        let mut buffer: String<64> = String::new();
+        assert!(command_processor
+            .process_command("help adc", Some(&mut buffer))
+            .is_ok());
+        assert!(command_processor
+            .process_command("help -v", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "Reads a channel, 0 to 3\nadc\n    Reads a channel, 0 to 3\n"
+        );
+    }
+
+    #[test]
+    fn test_group_help() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for (name, help) in [
+            ("net ping", "Pings a host"),
+            ("net status", "Shows the link"),
+        ] {
+            assert!(command_processor
+                .add_command(name, printer_demo, Some(String::from(help)))
+                .is_ok());
+        }
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help net", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "Pings a host\nShows the link\n");
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help net ping", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "Pings a host\n");
+        assert!(matches!(
+            command_processor.process_command("help net bogus", Some(&mut buffer)),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_help_categories() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for (name, help) in [
+            ("gpio", "Reads a pin"),
+            ("reboot", "Reboots"),
+            ("ver", "Shows the version"),
+        ] {
+            assert!(command_processor
+                .add_command(name, printer_demo, Some(String::from(help)))
+                .is_ok());
+        }
+        assert!(command_processor.set_category("gpio", Some("GPIO")).is_ok());
+        assert!(command_processor
+            .set_category("reboot", Some("System"))
+            .is_ok());
+        assert!(matches!(
+            command_processor.set_category("nope", Some("System")),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "Shows the version\nGPIO:\n  Reads a pin\nSystem:\n  Reboots\n"
+        );
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help -v", Some(&mut buffer))
+            .is_ok());
+        assert!(buffer.contains("reboot\n    Reboots\n    category: System\n"));
+    }
+
+    #[test]
+    fn test_hidden_help() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for (name, help) in [("led", "Sets the LED"), ("burn-in", "Runs the burn-in")] {
+            assert!(command_processor
+                .add_command(name, printer_demo, Some(String::from(help)))
+                .is_ok());
+        }
+        assert!(command_processor.set_hidden("burn-in", true).is_ok());
+
+        let mut buffer = std::string::String::new();
+        for line in ["help", "help -s", "help -v", "help /burn"] {
+            assert!(command_processor
+                .process_command(line, Some(&mut buffer))
+                .is_ok());
+        }
+        assert_eq!(
+            buffer,
+            "Sets the LED\nled\nled\n    Sets the LED\nno matching commands\n"
+        );
+
+        // Its help is still there for those who know the name
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help burn-in", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "Runs the burn-in\n");
+    }
+
+    #[test]
+    fn test_help_aliases() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_terminal_width(40);
+        for (name, help) in [("reset", "Resets"), ("baud", "Sets the rate")] {
+            assert!(command_processor
+                .add_command(name, printer_demo, Some(String::from(help)))
+                .is_ok());
+        }
+        assert!(command_processor.add_alias("rst", "reset").is_ok());
+        assert!(command_processor.add_alias("r", "reset").is_ok());
+
+        let mut buffer = std::string::String::new();
+        for line in ["help -s", "help -v", "help rst"] {
+            assert!(command_processor
+                .process_command(line, Some(&mut buffer))
+                .is_ok());
+        }
+        assert_eq!(
+            buffer,
+            "baud            reset (rst, r)\n\
+             baud\n    Sets the rate\nreset (rst, r)\n    Resets\n\
+             Resets\n"
+        );
+    }
+
+    #[test]
+    fn test_long_help() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("reboot", printer_demo, Some(String::from("Reboots")))
+            .is_ok());
+        assert!(command_processor
+            .set_long_help("reboot", Some("Resets the core\nafter the flash writes"))
+            .is_ok());
+        assert!(matches!(
+            command_processor.set_long_help("reset", None),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help reboot", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "Reboots\nResets the core\nafter the flash writes\n");
+
+        // Listings only show the summary
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help -v", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "reboot\n    Reboots\n");
+        assert!(matches!(
+            command_processor.process_command("help reset", Some(&mut buffer)),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "input")]
+    fn test_help_pager() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for name in ["a", "b", "c", "d", "e"] {
+            assert!(command_processor
+                .add_command(name, printer_demo, Some(String::from(name)))
+                .is_ok());
+        }
+        command_processor.set_help_pager(Some(2));
+        command_processor.set_prompt(Some(crate::Prompt::Text("> ")));
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.feed(b"help\r\n", &mut buffer).is_ok());
+        assert_eq!(buffer, "a\nb\n--More--");
+
+        // The line feed after the carriage return isn't a key
+        buffer.clear();
+        assert!(command_processor.feed(b"\r\n", &mut buffer).is_ok());
+        assert_eq!(buffer, "\r\x1b[Kc\nd\n--More--");
+
+        // Arrow keys and other keys don't turn the page
+        buffer.clear();
+        assert!(command_processor.feed(b"\x1b[Bx", &mut buffer).is_ok());
+        assert_eq!(buffer, "");
+
+        buffer.clear();
+        assert!(command_processor.feed(b" ", &mut buffer).is_ok());
+        assert_eq!(buffer, "\r\x1b[Ke\nOK\n> ");
+
+        buffer.clear();
+        assert!(command_processor.feed(b"help\r\n", &mut buffer).is_ok());
+        assert!(command_processor.feed(b"q", &mut buffer).is_ok());
+        assert_eq!(buffer, "a\nb\n--More--\r\x1b[KOK\n> ");
+
+        // Other commands, and help without the input layer, aren't paged
+        #[cfg(feature = "builtins")]
+        {
+            buffer.clear();
+            assert!(command_processor
+                .feed(b"if a then help\r\n", &mut buffer)
+                .is_ok());
+            assert!(!buffer.contains("--More--"));
+            assert!(buffer.ends_with("a\nb\nc\nd\ne\nOK\n> "));
+        }
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("help", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "a\nb\nc\nd\ne\n");
+    }
+}
@@ -0,0 +1,182 @@
+// This module contains the history of the lines received through `feed`
+use crate::{CommandProcessor, Status};
+
+use heapless::{Deque, String};
+
+/// The last lines received through `feed`, recalled with the Up and Down keys
+///
+/// The application owns the history and lends it to the processor with
+/// `set_history`, or to a `Session` with `Session::set_history`, so only
+/// consoles used by people pay for it. Blank lines and repeats of the
+/// previous line aren't recorded, and the oldest line is forgotten when the
+/// history is full.
+///
+/// # Arguments
+///
+/// * `DEPTH` - The number of lines kept
+/// * `LINE_SIZE` - The maximum size of a line, as for the processor
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{CommandProcessor, History};
+///
+/// let mut history: History<4> = History::new();
+/// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+/// command_processor.set_history(&mut history);
+///
+/// let mut writer = String::new();
+/// command_processor.feed(b"nothing\n", &mut writer).unwrap();
+/// writer.clear();
+///
+/// // Up recalls the line, Enter runs it again
+/// command_processor.feed(b"\x1b[A", &mut writer).unwrap();
+/// assert_eq!(writer, "nothing\x1b[K");
+/// ```
+///
+pub struct History<const DEPTH: usize, const LINE_SIZE: usize = 32> {
+    lines: Deque<String<LINE_SIZE>, DEPTH>,
+    /// How far back the recalled line is, 0 for the newest
+    recalled: Option<usize>,
+}
+
+impl<const DEPTH: usize, const LINE_SIZE: usize> Default for History<DEPTH, LINE_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const DEPTH: usize, const LINE_SIZE: usize> History<DEPTH, LINE_SIZE> {
+    /// Creates an empty history
+    pub const fn new() -> Self {
+        Self {
+            lines: Deque::new(),
+            recalled: None,
+        }
+    }
+
+    /// Returns the recorded lines, the oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.lines.iter().map(|line| line.as_str())
+    }
+
+    /// Forgets every line
+    pub fn clear(&mut self) {
+        self.lines.clear();
+        self.recalled = None;
+    }
+}
+
+/// A history of any depth, as held by the processor
+pub(crate) trait Recall {
+    /// Records a line and stops browsing, returning whether it fit
+    fn record(&mut self, line: &str) -> bool;
+
+    /// Returns whether a line would fit, without recording it
+    #[cfg(feature = "builtins")]
+    fn fits(&self, line: &str) -> bool;
+
+    /// Returns the line before the one recalled
+    fn older(&mut self) -> Option<&str>;
+
+    /// Returns the line after the one recalled, empty past the newest
+    fn newer(&mut self) -> Option<&str>;
+
+    /// Stops browsing, so the next `older` returns the newest line
+    fn reset(&mut self);
+
+    /// Calls `visit` with every line, the oldest first
+    #[cfg(feature = "builtins")]
+    fn each(&self, visit: &mut dyn FnMut(&str));
+}
+
+impl<const DEPTH: usize, const LINE_SIZE: usize> Recall for History<DEPTH, LINE_SIZE> {
+    fn record(&mut self, line: &str) -> bool {
+        self.recalled = None;
+        if line.trim().is_empty() || self.lines.back().is_some_and(|last| last == line) {
+            return true;
+        }
+
+        let mut copy = String::new();
+        if copy.push_str(line).is_err() {
+            return false;
+        }
+        if self.lines.is_full() {
+            self.lines.pop_front();
+        }
+        let _ = self.lines.push_back(copy);
+        true
+    }
+
+    #[cfg(feature = "builtins")]
+    fn fits(&self, line: &str) -> bool {
+        line.len() <= LINE_SIZE
+    }
+
+    fn older(&mut self) -> Option<&str> {
+        let age = self.recalled.map_or(0, |age| age.saturating_add(1));
+        let line = self.lines.iter().rev().nth(age)?;
+        self.recalled = Some(age);
+        Some(line.as_str())
+    }
+
+    fn newer(&mut self) -> Option<&str> {
+        let age = self.recalled?.checked_sub(1);
+        self.recalled = age;
+        match age {
+            Some(age) => self.lines.iter().rev().nth(age).map(|line| line.as_str()),
+            None => Some(""),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.recalled = None;
+    }
+
+    #[cfg(feature = "builtins")]
+    fn each(&self, visit: &mut dyn FnMut(&str)) {
+        self.iter().for_each(visit);
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets the history the lines received through `feed` are recorded in
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The history, of any depth
+    ///
+    pub fn set_history<const DEPTH: usize>(&mut self, history: &'a mut History<DEPTH, LINE_SIZE>) {
+        self.history = Some(history);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut history: History<2> = History::new();
+        for line in ["first", "second", "second", " ", "third"] {
+            history.record(line);
+        }
+        assert!(history.iter().eq(["second", "third"]));
+
+        assert_eq!(history.older(), Some("third"));
+        assert_eq!(history.older(), Some("second"));
+        assert_eq!(history.older(), None);
+        assert_eq!(history.newer(), Some("third"));
+        assert_eq!(history.newer(), Some(""));
+        assert_eq!(history.newer(), None);
+    }
+}
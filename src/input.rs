@@ -0,0 +1,806 @@
+// This module contains the byte-oriented input front-end
+#[cfg(feature = "framing")]
+use crate::checksum::ChecksumWriter;
+use crate::editor::Escape;
+#[cfg(feature = "help")]
+use crate::help::Page;
+#[cfg(feature = "help")]
+use crate::tokenizer::Tokens;
+#[cfg(feature = "help")]
+use crate::ReturnCode;
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The CAN control character (Ctrl-X), discards the line received so far
+pub const CANCEL: u8 = 0x18;
+
+/// The BS control character (Ctrl-H), erases the last character received
+pub const BACKSPACE: u8 = 0x08;
+
+/// The DEL character, sent by most terminals for the backspace key
+pub const DELETE: u8 = 0x7f;
+
+/// The HT control character (Tab), completes the command name before the cursor
+pub const TAB: u8 = 0x09;
+
+/// The bytes ending a line received through `feed`, see `set_line_ending`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    /// `\r`, `\n` or `\r\n`, the `\n` of a `\r\n` pair being ignored
+    Any,
+    /// `\r` only, `\n` being ignored
+    Cr,
+    /// `\n` only, `\r` being ignored
+    Lf,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Feeds received bytes through the whole input path
+    ///
+    /// Bytes are accumulated into a line buffer until a line ending, see
+    /// `set_line_ending`, which is then tokenized and dispatched like `process_command`,
+    /// and its result reported to the writer with `report`. Empty lines are
+    /// ignored and so are bytes that aren't printable ASCII. A line longer
+    /// than the line buffer is discarded up to its end and reported as
+    /// `CommandProcessorError::LineTooLong`. A `CANCEL` byte silently
+    /// discards the line received so far, and a `BACKSPACE` or `DELETE`
+    /// byte erases the character before the cursor, writing `\x08 \x08` to
+    /// blank it on the terminal.
+    ///
+    /// The arrow keys, Home, End and Delete of a VT100 terminal move the
+    /// cursor and edit the line, the terminal being redrawn with escape
+    /// sequences. Characters typed in the middle of the line are inserted. Up and Down recall the lines of
+    /// the history, see `set_history`. Other escape sequences are dropped.
+    /// Typed characters are echoed back if `set_echo` is on.
+    /// A `TAB` byte completes the command name before the cursor, listing
+    /// the candidates when there are several. Each line is followed by the
+    /// prompt, see `set_prompt`.
+    ///
+    /// This is the single deterministic entry point for all input, which
+    /// makes it suitable for fuzzing: every byte is processed in bounded time
+    /// and any byte sequence is valid input.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The received bytes
+    /// * `writer` - The writer for command output and reports
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the bytes were processed
+    /// * `Err(CommandProcessorError::WriteError)` - If writing a report failed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::CommandProcessor;
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// let mut writer = String::new();
+    ///
+    /// command_processor.feed(b"unkn", &mut writer).unwrap();
+    /// command_processor.feed(b"own\r\n", &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "ERR: command not found\n");
+    /// ```
+    ///
+    pub fn feed(
+        &mut self,
+        bytes: &[u8],
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        self.feed_lines(bytes, writer, false).map(|_| ())
+    }
+
+    /// Feeds bytes up to the end of a line, which is handed over instead of run, see `feed`
+    ///
+    /// Lets the async console await the commands of an `AsyncCommandProcessor`.
+    /// The caller runs the line, then calls `end_taken_line`. Lines that can't
+    /// be handed over, paged help and lines with a response checksum, are run
+    /// here as with `feed`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok((usize, Option<String>))` - How many bytes were consumed, and the line if one ended
+    /// * `Err(CommandProcessorError::WriteError)` - If writing a report failed
+    ///
+    #[cfg(all(feature = "embassy", feature = "async"))]
+    pub(crate) fn feed_line(
+        &mut self,
+        bytes: &[u8],
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(usize, Option<String<LINE_SIZE>>), CommandProcessorError> {
+        self.feed_lines(bytes, writer, true)
+    }
+
+    /// Reports the result of a line handed over by `feed_line` and prompts for the next one
+    #[cfg(all(feature = "embassy", feature = "async"))]
+    pub(crate) fn end_taken_line(
+        &mut self,
+        result: &Result<R, CommandProcessorError>,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        self.report(result, writer)?;
+        self.write_prompt(writer)
+    }
+
+    /// Feeds bytes, stopping at the end of a line to hand it over if `take` is set
+    fn feed_lines(
+        &mut self,
+        bytes: &[u8],
+        writer: &mut (dyn Write + 'a),
+        take: bool,
+    ) -> Result<(usize, Option<String<LINE_SIZE>>), CommandProcessorError> {
+        if let (Some(clock), false) = (self.clock, bytes.is_empty()) {
+            self.last_activity_ms = Some(clock.now_ms());
+        }
+
+        if self.suspended {
+            return Ok((bytes.len(), None));
+        }
+
+        for (index, &byte) in bytes.iter().enumerate() {
+            #[cfg(feature = "binary")]
+            if self.feed_frame(byte)? {
+                continue;
+            }
+
+            let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+            #[cfg(feature = "help")]
+            if self.paged.is_some() {
+                self.page_key(byte, after_cr, writer)?;
+                continue;
+            }
+            if let Some(key) = self.escape.feed(byte) {
+                match key {
+                    Some(key) if !self.line_overflowed => self.edit(key, writer)?,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                b'\r' | b'\n' if self.ends_line(byte, after_cr) => {
+                    if let Some(line) = self.end_line(writer, take)? {
+                        return Ok((index.saturating_add(1), Some(line)));
+                    }
+                }
+                CANCEL => self.cancel(writer)?,
+                BACKSPACE | DELETE if !self.line_overflowed => self.erase(writer)?,
+                #[cfg(feature = "completion")]
+                TAB if !self.line_overflowed => self.complete(writer)?,
+                0x20..=0x7e if !self.line_overflowed => {
+                    self.line_overflowed = !self.insert(char::from(byte), writer)?
+                }
+                _ => {}
+            }
+        }
+
+        Ok((bytes.len(), None))
+    }
+
+    /// Feeds a single received byte, see `feed`
+    ///
+    /// Meant for receive interrupts and drivers handing over one byte at a
+    /// time. The line is dispatched once its `\r` or `\n` arrives.
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - The received byte
+    /// * `writer` - The writer to report results to
+    ///
+    /// # Returns
+    ///
+    /// The result of `feed`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::CommandProcessor;
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// let mut writer = String::new();
+    ///
+    /// for &byte in b"nothing\n" {
+    ///     command_processor.feed_byte(byte, &mut writer).unwrap();
+    /// }
+    ///
+    /// assert_eq!(writer, "ERR: command not found\n");
+    /// ```
+    ///
+    pub fn feed_byte(
+        &mut self,
+        byte: u8,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        self.feed(core::slice::from_ref(&byte), writer)
+    }
+
+    /// Returns when bytes were last fed, to detect dead links
+    ///
+    /// Pings and partial lines count as activity. Needs a clock, see
+    /// `set_clock`.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(u64)` - The time of the last `feed`, from the clock
+    /// * `None` - If nothing was fed since the clock was set
+    ///
+    pub fn last_activity_ms(&self) -> Option<u64> {
+        self.last_activity_ms
+    }
+
+    /// Sets whether the characters received are echoed back
+    ///
+    /// For transports whose terminal doesn't echo locally, such as a raw
+    /// serial link. Printable characters are written as they are received,
+    /// and line endings as `\n` before the line's output. A `CANCEL` byte
+    /// blanks the discarded line, and other control characters are never
+    /// echoed. Off by default, as most terminal emulators echo themselves.
+    ///
+    /// # Arguments
+    ///
+    /// * `echo` - Whether the characters received are echoed back
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::CommandProcessor;
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_echo(true);
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.feed(b"nothinx\x7fg\r", &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "nothinx\x08 \x08g\nERR: command not found\n");
+    /// ```
+    ///
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Sets the bytes ending a line received through `feed`
+    ///
+    /// Terminals send `\r`, `\n` or `\r\n` for Enter. By default any of them
+    /// ends the line, the `\n` following a `\r` being ignored so a `\r\n`
+    /// pair doesn't also end an empty line, which would write the prompt
+    /// twice. Transports known to send a single kind can ignore the other.
+    ///
+    /// # Arguments
+    ///
+    /// * `line_ending` - The bytes ending a line
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, LineEnding, Prompt};
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_prompt(Some(Prompt::Text("> ")));
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.feed(b"\r\n", &mut writer).unwrap();
+    /// assert_eq!(writer, "> ");
+    ///
+    /// // Lines end with `\r` only, the `\n` doesn't end the line
+    /// command_processor.set_line_ending(LineEnding::Cr);
+    /// writer.clear();
+    /// command_processor.feed(b"noth\ning\r", &mut writer).unwrap();
+    /// assert_eq!(writer, "ERR: command not found\n> ");
+    /// ```
+    ///
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// Parks the input before the device enters a low-power sleep
+    ///
+    /// Until `resume`, bytes fed are dropped, as a link waking the device
+    /// often delivers a garbled first byte. The line typed so far, the
+    /// cursor and the history are kept, so the processor, in RAM retained
+    /// during the sleep, picks up the session where it was left. An escape
+    /// sequence received in part is dropped, its end being lost.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, Prompt};
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_prompt(Some(Prompt::Text("> ")));
+    /// command_processor.set_echo(true);
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.feed(b"noth", &mut writer).unwrap();
+    /// command_processor.suspend();
+    /// // Deep sleep, woken by the link
+    /// command_processor.feed(b"\xff", &mut writer).unwrap();
+    /// command_processor.resume(&mut writer).unwrap();
+    /// command_processor.feed(b"ing\r", &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "noth\n> nothing\nERR: command not found\n> ");
+    /// ```
+    ///
+    pub fn suspend(&mut self) {
+        self.suspended = true;
+        self.escape = Escape::Idle;
+        self.after_cr = false;
+    }
+
+    /// Takes the input parked by `suspend` back, after waking up
+    ///
+    /// With echo on, see `set_echo`, the prompt and the line typed so far
+    /// are written again on a fresh line, so the terminal shows where the
+    /// session stands even if its display was disturbed during the sleep.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer of the terminal
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the input was resumed
+    /// * `Err(CommandProcessorError::WriteError)` - If redrawing the line failed
+    ///
+    pub fn resume(&mut self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        if !core::mem::take(&mut self.suspended) || !self.echo {
+            return Ok(());
+        }
+        writer
+            .write_str("\n")
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        self.redraw_line(writer)
+    }
+
+    /// Returns whether the input is parked, see `suspend`
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Returns whether a `\r` or `\n` byte ends the line, see `set_line_ending`
+    fn ends_line(&self, byte: u8, after_cr: bool) -> bool {
+        match (self.line_ending, byte) {
+            (LineEnding::Any, b'\n') => !after_cr,
+            (LineEnding::Any, _) | (LineEnding::Cr, b'\r') | (LineEnding::Lf, b'\n') => true,
+            _ => false,
+        }
+    }
+
+    /// Runs the line received so far and prompts for the next one, or hands it over if `take` is set
+    fn end_line(
+        &mut self,
+        writer: &mut (dyn Write + 'a),
+        take: bool,
+    ) -> Result<Option<String<LINE_SIZE>>, CommandProcessorError> {
+        if self.echo {
+            writer
+                .write_str("\n")
+                .map_err(|_| CommandProcessorError::WriteError)?;
+        }
+        if let Some(line) = self.run_line(writer, take)? {
+            return Ok(Some(line));
+        }
+        #[cfg(feature = "help")]
+        if self.paged.is_some() {
+            return Ok(None);
+        }
+        self.write_prompt(writer).map(|_| None)
+    }
+
+    fn run_line(
+        &mut self,
+        writer: &mut (dyn Write + 'a),
+        take: bool,
+    ) -> Result<Option<String<LINE_SIZE>>, CommandProcessorError> {
+        #[cfg_attr(not(feature = "framing"), allow(unused_mut))]
+        let mut line = core::mem::take(&mut self.line);
+        self.cursor = 0;
+        #[cfg(feature = "framing")]
+        let verified = match self.line_overflowed {
+            true => Ok(()),
+            false => self.verify_input(&mut line),
+        };
+        #[cfg(not(feature = "framing"))]
+        let verified: Result<(), CommandProcessorError> = Ok(());
+        #[cfg(feature = "history")]
+        if let Some(history) = self.history.as_deref_mut() {
+            match self.line_overflowed {
+                true => history.reset(),
+                // Lines are at most the history's line size
+                false => {
+                    let _ = history.record(&line);
+                }
+            }
+        }
+
+        let result = match (core::mem::take(&mut self.line_overflowed), verified) {
+            (true, _) => Err(CommandProcessorError::LineTooLong),
+            (false, Err(e)) => Err(e),
+            (false, Ok(())) if self.strip_comment(&line).trim().is_empty() => return Ok(None),
+            (false, Ok(())) => {
+                #[cfg(feature = "framing")]
+                if let Some(checksum) = self.response_checksum {
+                    return self
+                        .process_checksummed(&line, checksum, writer)
+                        .map(|_| None);
+                }
+                #[cfg(feature = "help")]
+                if self.help_page_lines.is_some_and(|lines| lines > 0) && self.runs_help(&line) {
+                    return self.run_paged(line, writer).map(|_| None);
+                }
+                if take {
+                    return Ok(Some(line));
+                }
+                self.process_command(&line, Some(&mut *writer))
+            }
+        };
+
+        #[cfg(feature = "framing")]
+        if let Some(checksum) = self.response_checksum {
+            return self
+                .report(&result, &mut ChecksumWriter::new(writer, checksum))
+                .map(|_| None);
+        }
+        self.report(&result, writer).map(|_| None)
+    }
+
+    /// Returns whether a line runs the `help` built-in, whose output is paged
+    #[cfg(feature = "help")]
+    fn runs_help(&self, line: &str) -> bool {
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(self.strip_comment(line), self.delimiters, &mut buffer);
+        let name = self.resolve_alias(tokens.next().unwrap_or(""));
+        name == "help" && !self.commands.iter().any(|cmd| cmd.command == name)
+    }
+
+    /// Runs a `help` line with its output paged, see `set_help_pager`
+    #[cfg(feature = "help")]
+    fn run_paged(
+        &mut self,
+        line: String<LINE_SIZE>,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        self.page = Some(Page {
+            skip: 0,
+            more: false,
+        });
+        let result = self.process_command(&line, Some(&mut *writer));
+        self.end_page(line, result, writer)
+    }
+
+    /// Writes the `--More--` prompt if the help has more pages, or reports its result
+    #[cfg(feature = "help")]
+    fn end_page(
+        &mut self,
+        line: String<LINE_SIZE>,
+        result: Result<R, CommandProcessorError>,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        match self.page.take() {
+            Some(page) if page.more && result.is_ok() => {
+                self.paged = Some((line, page.skip));
+                self.output
+                    .write(writer, format_args!("{}", self.messages.more))
+            }
+            _ => self.report(&result, writer),
+        }
+    }
+
+    /// Handles a key received at the `--More--` prompt
+    ///
+    /// Space and Enter write the next page, `q` and Ctrl-X end the help, and
+    /// other keys, including whole escape sequences such as arrow keys, are
+    /// ignored.
+    #[cfg(feature = "help")]
+    fn page_key(
+        &mut self,
+        byte: u8,
+        after_cr: bool,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        if self.escape.feed(byte).is_some() {
+            return Ok(());
+        }
+        match (byte, after_cr) {
+            // The end of the line that asked for help, or of the Enter key
+            (b'\n', true) => Ok(()),
+            (b' ' | b'\r' | b'\n' | b'q' | CANCEL, _) => match self.paged.take() {
+                Some((line, skip)) => self.next_page(line, skip, byte, writer),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the next page of the help, or ends it on `q` or Ctrl-X
+    ///
+    /// Only the help is written again, the line isn't run again.
+    #[cfg(feature = "help")]
+    fn next_page(
+        &mut self,
+        line: String<LINE_SIZE>,
+        skip: usize,
+        key: u8,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        writer
+            .write_str("\r\x1b[K")
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        match key {
+            b'q' | CANCEL => self.report(&Ok(R::from(ReturnCode::Success)), writer)?,
+            _ => {
+                self.page = Some(Page { skip, more: false });
+                let mut buffer = [0u8; LINE_SIZE];
+                let mut tokens =
+                    Tokens::new(self.strip_comment(&line), self.delimiters, &mut buffer);
+                tokens.next();
+                let result = self.help(tokens, writer).map(R::from);
+                self.end_page(line, result, writer)?;
+            }
+        }
+        match self.paged {
+            Some(_) => Ok(()),
+            None => self.write_prompt(writer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::hello;
+    use crate::{CommandProcessor, LineEnding};
+    use heapless::String;
+
+    #[test]
+    fn test_feed() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.feed(b"hel", &mut buffer).is_ok());
+        assert_eq!(buffer, "");
+        assert!(command_processor
+            .feed(b"lo\r\n\nnope\n", &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "hi\nOK\nERR: command not found\n");
+    }
+
+    #[test]
+    fn test_feed_line_too_long() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.feed(&[b'x'; 40], &mut buffer).is_ok());
+        assert!(command_processor.feed(b"\nhello\n", &mut buffer).is_ok());
+        assert_eq!(buffer, "ERR: line too long\nhi\nOK\n");
+    }
+
+    #[test]
+    fn test_feed_arbitrary_bytes() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        // A deterministic pseudo-random byte stream must never panic
+        let mut state: u32 = 0x1234_5678;
+        let mut bytes = [0u8; 4096];
+        for byte in bytes.iter_mut() {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            *byte = (state >> 16) as u8;
+        }
+
+        let mut buffer = std::string::String::new();
+        for chunk in bytes.chunks(7) {
+            let _ = command_processor.feed(chunk, &mut buffer);
+        }
+    }
+
+    #[test]
+    fn test_feed_comments() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .feed(b"# greet\nhello # twice\n", &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "hi\nOK\n");
+
+        command_processor.set_comment_char(Some(';'));
+        buffer.clear();
+        assert!(command_processor
+            .feed(b"hello; once\nhello # twice\n", &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "hi\nOK\nhi\nOK\n");
+    }
+
+    #[test]
+    fn test_feed_byte() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        for &byte in b"hel\x18hello\r\n" {
+            assert!(command_processor.feed_byte(byte, &mut buffer).is_ok());
+        }
+        assert_eq!(buffer, "hi\nOK\n");
+    }
+
+    #[test]
+    fn test_feed_backspace() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .feed(b"\x08helzz\x08\x7flo\n", &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "\x08 \x08\x08 \x08hi\nOK\n");
+    }
+
+    #[test]
+    fn test_feed_editing_keys() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        // Fixes `hxlo` into `hello` with the arrow keys, Delete and Home/End
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .feed(
+                b"hxlo\x1b[D\x1b[Dl\x1b[D\x1b[D\x1b[3~e\x1b[H\x1b[F\n",
+                &mut buffer
+            )
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "\x08\x08lo\x1b[2D\x08\x08llo \x1b[4Dllo\x1b[3D\x1b[2D\x1b[5Chi\nOK\n"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn test_feed_history() {
+        let mut history: crate::History<4> = crate::History::new();
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_history(&mut history);
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.feed(b"hello\n", &mut buffer).is_ok());
+        buffer.clear();
+
+        // A partial line is replaced by the recalled one, then cleared by Down
+        assert!(command_processor
+            .feed(b"he\x1b[D\x1b[A\n\x1b[A\x1b[B", &mut buffer)
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "\x08\x1b[1Dhello\x1b[Khi\nOK\nhello\x1b[K\x1b[5D\x1b[K"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "completion")]
+    fn test_feed_tab_completion() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command(String::from("heap"), hello, None)
+            .is_ok());
+
+        // Lists the candidates, then completes the unique one
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.feed(b"he\t", &mut buffer).is_ok());
+        assert_eq!(buffer, "\nhello  heap  \nhe");
+        buffer.clear();
+        assert!(command_processor.feed(b"l\t\n", &mut buffer).is_ok());
+        assert_eq!(buffer, "lo hi\nOK\n");
+
+        // Arguments and unknown names aren't completed
+        buffer.clear();
+        assert!(command_processor
+            .feed(b"x\thello h\t\x18", &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "\x07");
+    }
+
+    #[test]
+    fn test_feed_echo() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+        command_processor.set_echo(true);
+
+        // Control characters aren't echoed, and a cancelled line is blanked
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .feed(b"he\x01llo\x1b[Dx\x18hello\n", &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "hello\x08xo\x1b[1D\x1b[5D\x1b[Khello\nhi\nOK\n");
+    }
+
+    #[test]
+    fn test_feed_line_endings() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+        command_processor.set_echo(true);
+
+        // A CRLF pair ends a single line, but two LFs end an empty one too
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.feed(b"hello\r\n\n", &mut buffer).is_ok());
+        assert_eq!(buffer, "hello\nhi\nOK\n\n");
+
+        command_processor.set_line_ending(LineEnding::Lf);
+        buffer.clear();
+        assert!(command_processor.feed(b"hel\rlo\n", &mut buffer).is_ok());
+        assert_eq!(buffer, "hello\nhi\nOK\n");
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn test_suspend() {
+        let mut history: crate::History<4> = crate::History::new();
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_history(&mut history);
+        assert!(command_processor
+            .add_command(String::from("hello"), hello, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .feed(b"hello\nhel\x1b[", &mut buffer)
+            .is_ok());
+        command_processor.suspend();
+        assert!(command_processor.is_suspended());
+        assert!(command_processor.feed(b"\x00\n", &mut buffer).is_ok());
+        assert!(command_processor.resume(&mut buffer).is_ok());
+        assert!(!command_processor.is_suspended());
+
+        // The half escape sequence is gone, the line and history are kept
+        buffer.clear();
+        assert!(command_processor.feed(b"lo\n\x1b[A", &mut buffer).is_ok());
+        assert_eq!(buffer, "hi\nOK\nhello\x1b[K");
+    }
+}
@@ -1,32 +1,431 @@
 // This module contains a command processor
-#![cfg_attr(not(test), no_std)]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+// The core must never panic, safety-critical users rely on it
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::panic,
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::indexing_slicing,
+        clippy::string_slice,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented,
+        clippy::arithmetic_side_effects
+    )
+)]
+#[cfg(feature = "builtins")]
+use heapless::Deque;
 use heapless::{String, Vec};
 
 use core::fmt::Write;
 
+use context::Callback;
+use output::Output;
+#[cfg(feature = "builtins")]
+use schedule::Schedule;
+use schema::Mismatch;
+#[cfg(feature = "builtins")]
+use stored::Recording;
+use tokenizer::{tokenize, Tokens};
+#[cfg(feature = "builtins")]
+use variables::{Variables, EXPANSION_BUFFER_SIZE};
+
+#[cfg(feature = "builtins")]
+mod alarms;
+mod aliases;
+mod args;
+#[cfg(feature = "async")]
+mod async_processor;
+#[cfg(feature = "bench")]
+mod bench;
+#[cfg(feature = "binary")]
+mod binary;
+mod builder;
+mod cache;
+#[cfg(feature = "framing")]
+mod checksum;
+#[cfg(any(test, feature = "std"))]
+mod client;
+mod clock;
+#[cfg(feature = "completion")]
+mod completion;
+#[cfg(feature = "embassy")]
+mod console;
+mod context;
+mod diff;
+#[cfg(feature = "input")]
+mod editor;
+mod fixed;
+mod groups;
+#[cfg(feature = "help")]
+mod help;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "input")]
+mod input;
+#[cfg(feature = "builtins")]
+mod lock;
+mod messages;
+#[cfg(feature = "builtins")]
+mod metrics;
+mod mini;
+mod name;
+mod output;
+mod overrides;
+#[cfg(feature = "input")]
+mod prompt;
+#[cfg(feature = "input")]
+mod queue;
+mod register;
+#[cfg(feature = "builtins")]
+mod schedule;
+mod schema;
+mod scratch;
+#[cfg(feature = "builtins")]
+mod script;
+#[cfg(feature = "input")]
+mod session;
+mod session_info;
+#[cfg(any(test, feature = "std"))]
+mod shell_completion;
+mod shutdown;
+mod slice;
+#[cfg(feature = "builtins")]
+mod snapshot;
+#[cfg(feature = "builtins")]
+mod status_values;
+mod storage;
+#[cfg(feature = "builtins")]
+mod stored;
+mod table;
+#[cfg(test)]
+mod test_util;
+mod tokenizer;
+#[cfg(feature = "builtins")]
+mod trace;
+#[cfg(feature = "tutorial")]
+mod tutorial;
+#[cfg(feature = "builtins")]
+mod variables;
+
+#[cfg(feature = "builtins")]
+pub use alarms::{AlarmCallback, AlarmEvent, Threshold, MAX_ALARMS};
+pub use aliases::MAX_ALIASES;
+pub use args::{parse_duration, parse_int, ArgError, Args};
+#[cfg(feature = "async")]
+pub use async_processor::{AsyncCommandProcessor, AsyncCommands};
+#[cfg(feature = "bench")]
+pub use bench::{
+    benchmark_processor, run_benchmarks, Benchmark, DwtCycleCounter, BENCHMARKS, BENCH_ITERATIONS,
+};
+#[cfg(feature = "binary")]
+pub use binary::{command_id, BinaryArgs, BinaryCallback, BINARY_OUTPUT_SIZE, CAPABILITIES_ID};
+#[cfg(all(feature = "binary", feature = "input"))]
+pub use binary::{BinarySink, BINARY_FRAME_SIZE, BINARY_PING};
+pub use builder::CommandProcessorBuilder;
+#[cfg(feature = "framing")]
+pub use checksum::{Checksum, CHECKSUM_BUFFER_SIZE};
+#[cfg(any(test, feature = "std"))]
+pub use client::{ClientError, ClientResponse, Transport};
+pub use clock::Clock;
+#[cfg(feature = "completion")]
+pub use completion::{CompletionCallback, Completions};
+#[cfg(all(feature = "embassy", feature = "async"))]
+pub use console::run_async_console;
+#[cfg(feature = "embassy")]
+pub use console::{
+    run_console, ConsoleError, ConsoleEvents, ConsoleRead, ConsoleWrite, NoEvents,
+    CONSOLE_OUTPUT_SIZE, CONSOLE_READ_SIZE,
+};
+pub use context::{Command, CommandClosure, ContextCallback};
+pub use diff::{DIFF_BUFFER_SIZE, DIFF_LINES};
+pub use fixed::Decimal;
+#[cfg(feature = "history")]
+pub use history::History;
+#[cfg(feature = "input")]
+pub use input::{LineEnding, BACKSPACE, CANCEL, DELETE, TAB};
+pub use messages::{Messages, DEFAULT_MESSAGES};
+#[cfg(feature = "builtins")]
+pub use metrics::MAX_METRICS;
+pub use mini::{MiniCommand, MiniDispatcher};
+pub use name::CommandName;
+pub use output::{FlushCallback, WriteErrorPolicy, OUTPUT_LINE_SIZE};
+#[cfg(feature = "input")]
+pub use prompt::{Prompt, PromptCallback};
+#[cfg(feature = "input")]
+pub use queue::{CommandConsumer, LineConsumer, LineProducer, LineQueue};
+pub use register::{check_access, AddressRange, Endian, InMemory, Register, Width};
+#[cfg(feature = "builtins")]
+pub use schedule::MAX_SCHEDULES;
+pub use schema::{ArgKind, ArgSpec};
+pub use scratch::SliceWriter;
+#[cfg(feature = "builtins")]
+pub use script::{ScriptError, MAX_RETRIES};
+#[cfg(feature = "input")]
+pub use session::{Profile, Session};
+pub use session_info::{Link, SessionInfo};
+pub use shutdown::ShutdownCallback;
+pub use slice::{SliceCommand, SliceCommandProcessor};
+#[cfg(feature = "builtins")]
+pub use snapshot::SNAPSHOT_SIZE;
+#[cfg(feature = "builtins")]
+pub use status_values::{StatusValue, MAX_STATUS_VALUES};
+pub use storage::{Storage, StorageError};
+#[cfg(feature = "builtins")]
+pub use stored::{MAX_SCRIPT_SIZE, SCRIPT_NAME_SIZE};
+pub use table::CommandDef;
+#[cfg(feature = "builtins")]
+pub use trace::TRACE_DEPTH;
+#[cfg(feature = "tutorial")]
+pub use tutorial::TutorialStep;
+#[cfg(feature = "builtins")]
+pub use variables::{MAX_VARIABLES, VARIABLE_NAME_SIZE, VARIABLE_VALUE_SIZE};
+
 /// Return codes for commands
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ReturnCode {
     Success,
     Failure,
 }
 
+/// A status returned by commands
+///
+/// `ReturnCode` by default, an application can return its own status, to map
+/// to protocol responses for instance, by setting the processor's `R`. The
+/// built-ins return a `ReturnCode`, which converts into the status.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{ReturnCode, Status};
+///
+/// #[derive(Clone, Copy, Debug, PartialEq)]
+/// enum Response {
+///     Ack,
+///     Nack,
+///     Busy,
+/// }
+///
+/// impl From<ReturnCode> for Response {
+///     fn from(code: ReturnCode) -> Self {
+///         match code {
+///             ReturnCode::Success => Response::Ack,
+///             ReturnCode::Failure => Response::Nack,
+///         }
+///     }
+/// }
+///
+/// impl Status for Response {
+///     fn is_success(&self) -> bool {
+///         *self == Response::Ack
+///     }
+///
+///     fn code(&self) -> u32 {
+///         *self as u32
+///     }
+/// }
+/// ```
+///
+pub trait Status: Copy + From<ReturnCode> {
+    /// Returns whether the command succeeded
+    ///
+    /// Scripts, `if`, `retry` and tutorials only tell success from failure.
+    fn is_success(&self) -> bool;
+
+    /// Returns the code of the status, sent in binary responses
+    ///
+    /// `0` on success and `1` on failure by default, the codes of `ReturnCode`.
+    fn code(&self) -> u32 {
+        match self.is_success() {
+            true => 0,
+            false => 1,
+        }
+    }
+}
+
+impl Status for ReturnCode {
+    fn is_success(&self) -> bool {
+        *self == ReturnCode::Success
+    }
+}
+
 /// Return type for command callbacks
-pub type CommandCallbackReturn<'a> = Result<ReturnCode, CommandProcessorError>;
+pub type CommandCallbackReturn<'a, R = ReturnCode> = Result<R, CommandProcessorError>;
 
 /// Command callback type
-pub type CommandCallback<'a> = fn(Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a>;
+///
+/// Receives the arguments following the command name, as split by the
+/// tokenizer, and the writer.
+pub type CommandCallback<'a, R = ReturnCode> =
+    fn(Args, Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a, R>;
+
+/// Argument validation callback type
+///
+/// Receives the arguments of a command before it runs and returns a
+/// description of the problem if they are invalid.
+pub type ValidationCallback = fn(&[&str]) -> Result<(), &'static str>;
+
+/// The maximum number of arguments passed to a validation callback
+pub const MAX_ARGS: usize = 16;
+
+/// Help callback type
+///
+/// Writes a command's help, including the trailing newline, when it depends
+/// on runtime information such as the valid channel range.
+#[cfg(feature = "help")]
+pub type HelpCallback = fn(&mut dyn Write) -> core::fmt::Result;
+
+/// Output post-processing callback type
+#[cfg(feature = "hooks")]
+///
+/// Receives the result of a command and the output it wrote, and writes the
+/// transformed output to the writer before returning the (possibly mapped)
+/// result.
+pub type PostProcessCallback<'a, R = ReturnCode> = fn(
+    CommandCallbackReturn<'a, R>,
+    &str,
+    Option<&mut (dyn Write + 'a)>,
+) -> CommandCallbackReturn<'a, R>;
+
+/// The maximum size of the output captured for a post-processing callback
+///
+/// Applies when no scratch buffer is set, otherwise the scratch buffer's size
+/// is the limit.
+#[cfg(feature = "hooks")]
+pub const POST_PROCESS_BUFFER_SIZE: usize = 256;
+
+/// The maximum number of command overrides active at once
+pub const MAX_OVERRIDES: usize = 4;
+
+/// What adding a command whose name is already registered does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Fail with `CommandAlreadyExists`
+    Error,
+    /// Keep the registered command and report success
+    Ignore,
+    /// Replace the registered command, resetting its settings
+    Replace,
+}
+
+/// A change to the command table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableEvent<'e> {
+    /// A command was added
+    Added(&'e str),
+    /// A command was replaced by a new registration of the same name
+    Replaced(&'e str),
+    /// A command was removed
+    Removed(&'e str),
+    /// A disabled command was enabled again
+    Enabled(&'e str),
+    /// A command was disabled
+    Disabled(&'e str),
+    /// A hidden command was shown again
+    Shown(&'e str),
+    /// A command was hidden
+    Hidden(&'e str),
+}
+
+/// Table observer callback type
+///
+/// Called after every change to the command table, so menus, host caches or
+/// completion indices can stay in sync without polling.
+pub type TableObserver = fn(TableEvent);
+
+/// A translation from a deprecated command name to its replacement
+///
+/// The replacement can include arguments, any arguments given to the legacy
+/// command are appended after them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LegacyCommand {
+    pub old: &'static str,
+    pub new: &'static str,
+}
+
+/// A command armed with the `arm` built-in, see `set_requires_arming`
+struct Armed<const NAME_SIZE: usize> {
+    command: CommandName<NAME_SIZE>,
+    at_ms: u64,
+    /// The token the command must be run with
+    token: u16,
+    /// The id of the session that armed it, `None` without sessions
+    session: Option<u8>,
+}
+
+/// Returns the arming token presented as the last argument of a command, if any
+fn parse_arm_token(arg: &str) -> Option<u16> {
+    match arg.len() {
+        4 => u16::from_str_radix(arg, 16).ok(),
+        _ => None,
+    }
+}
 
 /// A command item
 ///
 /// # Arguments
 ///
 /// * `HELP_STR_SIZE` - The maximum size of the help string
+/// * `NAME_SIZE` - The maximum size of an owned command name
+/// * `C` - The type of the context shared by the commands
+/// * `R` - The status returned by the commands
 ///
-struct CommandItem<'a, const HELP_STR_SIZE: usize> {
-    command: String<32>,
-    callback: CommandCallback<'a>,
+struct CommandItem<'a, const HELP_STR_SIZE: usize, const NAME_SIZE: usize, C, R> {
+    command: CommandName<NAME_SIZE>,
+    callback: Callback<'a, C, R>,
     help: Option<String<HELP_STR_SIZE>>,
+    #[cfg(feature = "help")]
+    help_callback: Option<HelpCallback>,
+    #[cfg(feature = "help")]
+    category: Option<&'static str>,
+    #[cfg(feature = "help")]
+    long_help: Option<&'static str>,
+    max_args: Option<usize>,
+    dry_run_callback: Option<CommandCallback<'a, R>>,
+    requires_arming: bool,
+    exclusive: bool,
+    hidden: bool,
+    disabled: bool,
+    #[cfg(feature = "binary")]
+    id: u16,
+    #[cfg(feature = "binary")]
+    binary_callback: Option<BinaryCallback<'a, R>>,
+    #[cfg(feature = "builtins")]
+    safe: bool,
+    cooldown_ms: u64,
+    cache_ttl_ms: u64,
+    diff_output: bool,
+    last_run_ms: Option<u64>,
+    validator: Option<ValidationCallback>,
+    #[cfg(feature = "completion")]
+    completion: Option<CompletionCallback>,
+    #[cfg(feature = "hooks")]
+    post_processor: Option<PostProcessCallback<'a, R>>,
+    version: u16,
+    examples: &'static [&'static str],
+    default: bool,
+    raw_tail: Option<usize>,
+    schema: &'static [ArgSpec],
+}
+
+/// The arguments a command runs with
+#[derive(Clone, Copy)]
+pub(crate) enum Input<'r> {
+    /// Text arguments, from a line or a binary request
+    Text(&'r [&'r str]),
+    /// Postcard encoded arguments, for the command's binary handler
+    #[cfg(feature = "binary")]
+    Typed(&'r [u8]),
+}
+
+/// What runs a command, copied out of its table entry
+pub(crate) struct Handler<'a, C, R> {
+    callback: Callback<'a, C, R>,
+    #[cfg(feature = "hooks")]
+    post_processor: Option<PostProcessCallback<'a, R>>,
+    dry_run: bool,
 }
 
 /// A command processor
@@ -35,17 +434,29 @@ struct CommandItem<'a, const HELP_STR_SIZE: usize> {
 ///
 /// * `NUM_COMMANDS` - The maximum number of commands the processor can hold
 /// * `HELP_STR_SIZE` - The maximum size of the help string
+/// * `NAME_SIZE` - The maximum size of a command name built at runtime, 32 by
+///   default. Processors only registering `&'static str` names can set it to 0
+///   to avoid reserving RAM for names.
+/// * `LINE_SIZE` - The maximum size of a command line, 32 by default. Longer
+///   lines are rejected with `CommandProcessorError::LineTooLong`.
+/// * `C` - The type of the context shared by the commands added with
+///   `add_command_shared`, `()` by default
+/// * `R` - The status returned by the commands, see `Status`, `ReturnCode`
+///   by default
 ///
 /// # Example
 ///
 /// ```
-/// use command_processor::{CommandProcessor, CommandProcessorError, ReturnCode, CommandCallbackReturn};
+/// use command_processor::{Args, CommandProcessor, CommandProcessorError, ReturnCode, CommandCallbackReturn};
 /// use heapless::String;
 /// use core::fmt::Write;
 ///
 /// fn printer_demo<'a>(
-///    _: Option<&mut (dyn Write + 'a)>,
+///    _: Args,
+///    writer: Option<&mut (dyn Write + 'a)>,
 /// ) -> CommandCallbackReturn<'a> {
+///    let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+///    writeln!(writer, "hello").map_err(|_| CommandProcessorError::WriteError)?;
 ///    Ok(ReturnCode::Success)
 /// }
 ///
@@ -56,21 +467,137 @@ struct CommandItem<'a, const HELP_STR_SIZE: usize> {
 ///     printer_demo,
 ///     Some(String::<32>::from("Prints a message")),
 /// ).unwrap();
-///     
-/// let mut writer: String<32> = String::new();
 ///
-/// command_processor.process_command(&String::from("help"), Some(&mut writer)).unwrap();
+/// let mut writer: String<32> = String::new();
 ///
-/// assert_eq!(writer, "Prints a message\n");
+/// command_processor.process_command("printer", Some(&mut writer)).unwrap();
 ///
-/// writer.clear();
+/// assert_eq!(writer, "hello\n");
 ///
+/// // With the `help` feature, `help` lists the help of the commands
+/// #[cfg(feature = "help")]
+/// {
+///     writer.clear();
+///     command_processor.process_command("help", Some(&mut writer)).unwrap();
+///     assert_eq!(writer, "Prints a message\n");
+/// }
 /// ```
 ///
-pub struct CommandProcessor<'a, const NUM_COMMANDS: usize, const HELP_STR_SIZE: usize> {
-    commands: Vec<CommandItem<'a, HELP_STR_SIZE>, NUM_COMMANDS>,
+pub struct CommandProcessor<
+    'a,
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize = 32,
+    const LINE_SIZE: usize = 32,
+    C = (),
+    R = ReturnCode,
+> {
+    commands: Vec<CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>, NUM_COMMANDS>,
+    messages: &'a Messages,
+    strict: bool,
+    #[cfg(feature = "builtins")]
+    abort_on_error: bool,
+    dry_run: bool,
+    clock: Option<&'a (dyn Clock + Sync)>,
+    armed: Option<Armed<NAME_SIZE>>,
+    /// The state the arming tokens are drawn from
+    #[cfg(feature = "builtins")]
+    arm_seed: u64,
+    arm_window_ms: u64,
+    /// The session running an exclusive operation, `Some(None)` without sessions
+    busy: Option<Option<u8>>,
+    shutdown: shutdown::ShutdownState,
+    shutdown_timeout_ms: Option<u64>,
+    shutdown_callback: Option<ShutdownCallback>,
+    #[cfg(feature = "builtins")]
+    lock: Option<lock::Lock>,
+    #[cfg(feature = "builtins")]
+    lock_timeout_ms: u64,
+    legacy_commands: &'a [LegacyCommand],
+    aliases: Vec<(&'a str, &'a str), MAX_ALIASES>,
+    duplicate_policy: DuplicatePolicy,
+    observer: Option<TableObserver>,
+    comment: Option<char>,
+    delimiters: Option<&'a [char]>,
+    #[cfg(feature = "tutorial")]
+    tutorial: Option<(&'a [TutorialStep], usize)>,
+    #[cfg(feature = "help")]
+    terminal_width: usize,
+    output: Output,
+    #[cfg(feature = "input")]
+    line: String<LINE_SIZE>,
+    #[cfg(feature = "input")]
+    line_overflowed: bool,
+    #[cfg(feature = "input")]
+    cursor: usize,
+    #[cfg(feature = "input")]
+    escape: editor::Escape,
+    #[cfg(feature = "history")]
+    history: Option<&'a mut (dyn history::Recall + Send)>,
+    #[cfg(feature = "framing")]
+    response_checksum: Option<Checksum>,
+    #[cfg(feature = "framing")]
+    input_checksum: Option<Checksum>,
+    #[cfg(feature = "input")]
+    last_activity_ms: Option<u64>,
+    #[cfg(feature = "input")]
+    prompt: Option<Prompt<'a>>,
+    #[cfg(feature = "input")]
+    echo: bool,
+    #[cfg(feature = "input")]
+    line_ending: LineEnding,
+    #[cfg(feature = "input")]
+    after_cr: bool,
+    #[cfg(feature = "input")]
+    suspended: bool,
+    #[cfg(all(feature = "help", feature = "input"))]
+    help_page_lines: Option<usize>,
+    #[cfg(all(feature = "help", feature = "input"))]
+    page: Option<help::Page>,
+    /// The help line whose output continues on the next key, and the lines written
+    #[cfg(all(feature = "help", feature = "input"))]
+    paged: Option<(String<LINE_SIZE>, usize)>,
+    #[cfg(all(feature = "binary", feature = "input"))]
+    frame: Vec<u8, BINARY_FRAME_SIZE>,
+    #[cfg(all(feature = "binary", feature = "input"))]
+    frame_state: binary::FrameState,
+    #[cfg(all(feature = "binary", feature = "input"))]
+    binary_sink: Option<BinarySink>,
+    scratch: Option<&'a mut [u8]>,
+    cache: Option<cache::Cache<'a, LINE_SIZE, R>>,
+    diff: Option<diff::Diff<LINE_SIZE>>,
+    #[cfg(feature = "builtins")]
+    variables: Variables,
+    #[cfg(feature = "builtins")]
+    storage: Option<&'a mut (dyn Storage + Send)>,
+    #[cfg(feature = "builtins")]
+    recording: Option<Recording>,
+    #[cfg(feature = "builtins")]
+    running_script: bool,
+    #[cfg(feature = "builtins")]
+    schedules: Vec<Schedule, MAX_SCHEDULES>,
+    #[cfg(feature = "builtins")]
+    status_values: Vec<(&'a str, &'a (dyn StatusValue + Sync)), MAX_STATUS_VALUES>,
+    #[cfg(feature = "builtins")]
+    metrics: Vec<(&'a str, metrics::Metric), MAX_METRICS>,
+    #[cfg(feature = "builtins")]
+    alarms: Vec<alarms::Alarm, MAX_ALARMS>,
+    #[cfg(feature = "builtins")]
+    alarm_callback: Option<AlarmCallback>,
+    #[cfg(feature = "builtins")]
+    trace: Deque<trace::TraceRecord, TRACE_DEPTH>,
+    overrides: Vec<(CommandName<NAME_SIZE>, Callback<'a, C, R>), MAX_OVERRIDES>,
+    context: Option<context::LentContext<C>>,
+    session: Option<SessionInfo>,
 }
 
+// Processors are moved into the tasks running them, such as RTIC's local resources
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<CommandProcessor<'static, 1, 1>>();
+    assert_send::<CommandProcessor<'static, 1, 1, 32, 32, u32>>();
+};
+
 /// Errors that can occur when using the command processor
 #[derive(Debug)]
 pub enum CommandProcessorError {
@@ -79,271 +606,3313 @@ pub enum CommandProcessorError {
     CommandListFull,
     WriteError,
     NoWriter,
+    UnexpectedArgument,
+    DryRunUnsupported,
+    ClockUnavailable,
+    MissingArgument,
+    NotArmed,
+    CooldownActive,
+    InvalidArguments,
+    LineTooLong,
+    InvalidConfiguration,
+    HelpTooLong,
+    OverrideStackFull,
+    NotOverridden,
+    UnterminatedQuote,
+    MalformedRequest,
+    WrongArgumentCount,
+    TooManyVariables,
+    UnknownVariable,
+    NoContext,
+    StorageUnavailable,
+    StorageError,
+    ScriptNotFound,
+    ScriptTooLong,
+    TooManySchedules,
+    Busy,
+    Locked,
+    TooManyStatusValues,
+    UnknownStatus,
+    TooManyMetrics,
+    TooManyAlarms,
+    UnknownSubcommand,
+    ShuttingDown,
+    TooManyAliases,
+    PermissionDenied,
+    ChecksumMismatch,
+    CommandDisabled,
 }
 
-impl<'a, const NUM_COMMANDS: usize, const HELP_STR_SIZE: usize> Default
-    for CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE>
+/// Argument that runs a single command in dry-run mode
+const DRY_RUN_FLAG: &str = "--dry-run";
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > Default for CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
 {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<'a, const NUM_COMMANDS: usize, const HELP_STR_SIZE: usize>
-    CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE>
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
 {
+    /// The size of the processor itself, in bytes
+    ///
+    /// Excludes what it borrows, such as a `History`, storage, a
+    /// scratch buffer or the output writer, and the stack used while a line
+    /// is processed. Add their sizes, and `POST_PROCESS_STACK_FOOTPRINT`
+    /// with the `hooks` feature, for the RAM a configuration needs.
+    ///
+    /// Can be checked at build time to enforce a memory budget:
+    ///
+    /// ```
+    /// use command_processor::CommandProcessor;
+    ///
+    /// const _: () = assert!(CommandProcessor::<8, 32>::MEMORY_FOOTPRINT < 16 * 1024);
+    /// ```
+    ///
+    pub const MEMORY_FOOTPRINT: usize = core::mem::size_of::<Self>();
+
+    /// The RAM used by a single command table entry, in bytes
+    pub const COMMAND_FOOTPRINT: usize =
+        core::mem::size_of::<CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>>();
+
+    /// The RAM used by the command table, in bytes, included in `MEMORY_FOOTPRINT`
+    pub const TABLE_FOOTPRINT: usize =
+        core::mem::size_of::<Vec<CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>, NUM_COMMANDS>>();
+
+    /// The RAM used by the input line buffer, in bytes, included in `MEMORY_FOOTPRINT`
+    #[cfg(feature = "input")]
+    pub const LINE_BUFFER_FOOTPRINT: usize = core::mem::size_of::<String<LINE_SIZE>>();
+
+    /// The stack used while a command with a post-processor runs, in bytes
+    ///
+    /// This is not included in `MEMORY_FOOTPRINT` as it is only used during
+    /// the call. It drops to the size of a `SliceWriter` when a scratch
+    /// buffer is set.
+    #[cfg(feature = "hooks")]
+    pub const POST_PROCESS_STACK_FOOTPRINT: usize =
+        core::mem::size_of::<SliceWriter<[u8; POST_PROCESS_BUFFER_SIZE]>>();
+
     pub fn new() -> Self {
         Self {
             commands: Vec::new(),
+            messages: &DEFAULT_MESSAGES,
+            strict: false,
+            #[cfg(feature = "builtins")]
+            abort_on_error: false,
+            dry_run: false,
+            clock: None,
+            armed: None,
+            #[cfg(feature = "builtins")]
+            arm_seed: 0,
+            busy: None,
+            shutdown: shutdown::ShutdownState::Running,
+            shutdown_timeout_ms: None,
+            shutdown_callback: None,
+            #[cfg(feature = "builtins")]
+            lock: None,
+            #[cfg(feature = "builtins")]
+            lock_timeout_ms: 300_000,
+            arm_window_ms: 10_000,
+            legacy_commands: &[],
+            aliases: Vec::new(),
+            duplicate_policy: DuplicatePolicy::Error,
+            observer: None,
+            comment: Some('#'),
+            delimiters: None,
+            #[cfg(feature = "tutorial")]
+            tutorial: None,
+            #[cfg(feature = "help")]
+            terminal_width: 80,
+            output: Output::new(),
+            #[cfg(feature = "input")]
+            line: String::new(),
+            #[cfg(feature = "input")]
+            line_overflowed: false,
+            #[cfg(feature = "input")]
+            cursor: 0,
+            #[cfg(feature = "input")]
+            escape: editor::Escape::Idle,
+            #[cfg(feature = "history")]
+            history: None,
+            #[cfg(feature = "framing")]
+            response_checksum: None,
+            #[cfg(feature = "framing")]
+            input_checksum: None,
+            #[cfg(feature = "input")]
+            last_activity_ms: None,
+            #[cfg(feature = "input")]
+            prompt: None,
+            #[cfg(feature = "input")]
+            echo: false,
+            #[cfg(feature = "input")]
+            line_ending: LineEnding::Any,
+            #[cfg(feature = "input")]
+            after_cr: false,
+            #[cfg(feature = "input")]
+            suspended: false,
+            #[cfg(all(feature = "help", feature = "input"))]
+            help_page_lines: None,
+            #[cfg(all(feature = "help", feature = "input"))]
+            page: None,
+            #[cfg(all(feature = "help", feature = "input"))]
+            paged: None,
+            #[cfg(all(feature = "binary", feature = "input"))]
+            frame: Vec::new(),
+            #[cfg(all(feature = "binary", feature = "input"))]
+            frame_state: binary::FrameState::Text,
+            #[cfg(all(feature = "binary", feature = "input"))]
+            binary_sink: None,
+            scratch: None,
+            cache: None,
+            diff: None,
+            #[cfg(feature = "builtins")]
+            variables: Variables::default(),
+            #[cfg(feature = "builtins")]
+            storage: None,
+            #[cfg(feature = "builtins")]
+            recording: None,
+            #[cfg(feature = "builtins")]
+            running_script: false,
+            #[cfg(feature = "builtins")]
+            schedules: Vec::new(),
+            #[cfg(feature = "builtins")]
+            status_values: Vec::new(),
+            #[cfg(feature = "builtins")]
+            metrics: Vec::new(),
+            #[cfg(feature = "builtins")]
+            alarms: Vec::new(),
+            #[cfg(feature = "builtins")]
+            alarm_callback: None,
+            #[cfg(feature = "builtins")]
+            trace: Deque::new(),
+            overrides: Vec::new(),
+            context: None,
+            session: None,
         }
     }
 
-    /// Adds a command to the command processor
+    /// Sets the table translating deprecated command names to their replacements
+    ///
+    /// When a command isn't found, the table is searched for it. If an entry
+    /// matches, a deprecation warning is written and the replacement is
+    /// processed instead, with the original arguments appended.
     ///
     /// # Arguments
     ///
-    /// * `command` - The command to add
-    /// * `callback` - The callback to call when the command is processed
-    /// * `help` - The help string for the command
+    /// * `legacy_commands` - The translation table
     ///
-    /// # Returns
+    pub fn set_legacy_commands(&mut self, legacy_commands: &'a [LegacyCommand]) {
+        self.legacy_commands = legacy_commands;
+    }
+
+    /// Sets the terminal width used to lay out `help -s`, 80 columns by default
     ///
-    /// * `Ok(())` - If the command was added successfully
-    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already exists
-    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    /// # Arguments
     ///
-    pub fn add_command(
-        &mut self,
-        command: String<32>,
-        callback: CommandCallback<'a>,
-        help: Option<String<HELP_STR_SIZE>>,
-    ) -> Result<(), CommandProcessorError> {
-        // Check if command already exists
-        for cmd in self.commands.iter() {
-            if cmd.command == command {
-                return Err(CommandProcessorError::CommandAlreadyExists);
-            }
-        }
-
-        self.commands
-            .push(CommandItem {
-                command,
-                callback,
-                help,
-            })
-            .map_err(|_| CommandProcessorError::CommandListFull)
+    /// * `terminal_width` - The width of the terminal in characters
+    ///
+    #[cfg(feature = "help")]
+    pub fn set_terminal_width(&mut self, terminal_width: usize) {
+        self.terminal_width = terminal_width;
     }
 
-    /// Removes a command from the command processor
+    /// Pages the output of `help` for terminals shorter than the help, off by default
+    ///
+    /// With a page size, `help` lines received through `feed` write that many
+    /// lines followed by a `--More--` prompt. Space or Enter writes the next
+    /// page, `q` ends the help, and other keys are ignored. Only the help is
+    /// paged, the output of other lines is written whole. Commands run
+    /// without `feed`, such as from scripts or `process_command`, are never
+    /// paged.
     ///
     /// # Arguments
     ///
-    /// * `command` - The command to remove
+    /// * `lines` - The number of lines per page, a terminal's height minus one, or `None` to not page
     ///
-    /// # Returns
+    /// # Example
     ///
-    /// * `Ok(())` - If the command was removed successfully
-    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    /// use heapless::String;
     ///
-    pub fn remove_command(&mut self, command: String<32>) -> Result<(), CommandProcessorError> {
-        for (i, cmd) in self.commands.iter().enumerate() {
-            if cmd.command == command {
-                self.commands.swap_remove(i);
-                return Ok(());
-            }
-        }
-
-        Err(CommandProcessorError::CommandNotFound)
+    /// fn demo<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// for (name, help) in [("led", "Sets the LED"), ("reboot", "Reboots"), ("uptime", "Shows the uptime")] {
+    ///     command_processor.add_command(name, demo, Some(String::from(help))).unwrap();
+    /// }
+    /// command_processor.set_help_pager(Some(2));
+    ///
+    /// let mut writer = std::string::String::new();
+    /// command_processor.feed(b"help\n", &mut writer).unwrap();
+    /// assert_eq!(writer, "Sets the LED\nReboots\n--More--");
+    ///
+    /// writer.clear();
+    /// command_processor.feed(b" ", &mut writer).unwrap();
+    /// assert_eq!(writer, "\r\x1b[KShows the uptime\nOK\n");
+    /// ```
+    ///
+    #[cfg(all(feature = "help", feature = "input"))]
+    pub fn set_help_pager(&mut self, lines: Option<usize>) {
+        self.help_page_lines = lines.filter(|&lines| lines > 0);
     }
 
-    /// Processes a command and calls the callback
+    /// Sets the session the following commands run in
     ///
-    /// # Arguments
+    /// Callbacks read it with `Args::session`. A `Session` sets its profile's
+    /// session while it feeds the processor.
     ///
-    /// * `command` - The command to process
-    /// * `writer` - The writer the command can write with.
+    /// # Arguments
     ///
-    /// # Returns
+    /// * `session` - The session, or `None` if there isn't any
     ///
-    /// * `Ok(ReturnCode)` - If the command was processed successfully
-    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
-    /// * `Err(CommandProcessorError::NoWriter)` - If the command requires a writer but none was provided
-    /// * `Err(CommandProcessorError::WriteError)` - If the command failed to write
-    pub fn process_command(
-        &mut self,
-        command: &String<32>,
-        writer: Option<&mut (dyn Write + 'a)>,
-    ) -> Result<ReturnCode, CommandProcessorError> {
-        if command == "help" {
-            match writer {
-                Some(writer) => return self.help_printer(writer),
-                None => return Err(CommandProcessorError::NoWriter),
-            }
-        }
-
-        match self.commands.iter().find(|cmd| cmd.command == *command) {
-            Some(cmd) => (cmd.callback)(writer),
-            None => Err(CommandProcessorError::CommandNotFound),
-        }
+    pub fn set_session_info(&mut self, session: Option<SessionInfo>) {
+        self.session = session;
     }
 
-    fn help_printer(
-        &mut self,
-        writer: &mut (dyn Write + 'a),
-    ) -> Result<ReturnCode, CommandProcessorError> {
-        for cmd in self.commands.iter() {
-            if let Some(help) = &cmd.help {
-                writeln!(writer, "{}", help).map_err(|_| CommandProcessorError::WriteError)?;
-            }
-        }
-
-        Ok(ReturnCode::Success)
+    /// Returns the session the commands run in, see `set_session_info`
+    pub fn session_info(&self) -> Option<&SessionInfo> {
+        self.session.as_ref()
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    fn printer_demo<'a>(_: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
-        Ok(ReturnCode::Success)
+    /// Sets what happens when writing the processor's own output fails
+    ///
+    /// Applies to the output of built-in commands and to processor messages
+    /// such as usage errors. The default policy is
+    /// `WriteErrorPolicy::Abort`.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The write error policy
+    ///
+    pub fn set_write_error_policy(&mut self, policy: WriteErrorPolicy) {
+        self.output.policy = policy;
     }
 
-    #[test]
-    fn test_command_processor() {
-        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
-
-        assert!(command_processor
-            .add_command(
-                String::from("test"),
-                printer_demo,
-                Some(String::from("Test command"))
-            )
-            .is_ok());
-
-        let result = command_processor.process_command(&String::from("test"), None);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), ReturnCode::Success);
+    /// Sets the callback used to drain the transport before a failed write is retried
+    ///
+    /// # Arguments
+    ///
+    /// * `flush` - The flush callback
+    ///
+    pub fn set_flush_callback(&mut self, flush: FlushCallback) {
+        self.output.flush = Some(flush);
     }
 
-    #[test]
-    fn test_no_commands() {
-        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
-
-        let result = command_processor.process_command(&String::from("test"), None);
-        assert!(result.is_err());
+    /// Sets a scratch buffer shared by the features needing temporary memory
+    ///
+    /// Without a scratch buffer, features such as output post-processing use
+    /// dedicated buffers on the stack. With one, they all share it instead,
+    /// so its size also bounds what they can hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `scratch` - The scratch buffer
+    ///
+    pub fn set_scratch_buffer(&mut self, scratch: &'a mut [u8]) {
+        self.scratch = Some(scratch);
     }
 
-    #[test]
-    fn test_too_many_commands() {
-        let mut command_processor: CommandProcessor<1, 32> = CommandProcessor::new();
-
-        assert!(command_processor
-            .add_command(
-                String::from("test"),
-                printer_demo,
-                Some(String::from("Test command"))
-            )
-            .is_ok());
-
-        assert!(command_processor
-            .add_command(
-                String::from("test2"),
-                printer_demo,
-                Some(String::from("Test command 2"))
-            )
-            .is_err());
+    /// Borrows the scratch buffer, if one was set
+    ///
+    /// Commands and front-ends can use it between calls to the processor, its
+    /// contents are not preserved across them.
+    pub fn scratch_buffer(&mut self) -> Option<&mut [u8]> {
+        self.scratch.as_deref_mut()
     }
 
-    #[test]
-    fn test_remove_command() {
-        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
-
-        assert!(command_processor
-            .add_command(
-                String::from("test"),
-                printer_demo,
-                Some(String::from("Test command"))
-            )
-            .is_ok());
+    /// Sets the clock used by time-based features such as arming
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The clock to read the current time from
+    ///
+    pub fn set_clock(&mut self, clock: &'a (dyn Clock + Sync)) {
+        self.clock = Some(clock);
+    }
 
-        assert!(command_processor
-            .remove_command(String::from("test"))
-            .is_ok());
+    /// Sets how long an `arm <command>` stays valid, 10 seconds by default
+    ///
+    /// # Arguments
+    ///
+    /// * `window_ms` - The time in milliseconds the armed command can be run in
+    ///
+    pub fn set_arm_window(&mut self, window_ms: u64) {
+        self.arm_window_ms = window_ms;
+    }
 
-        let result = command_processor.process_command(&String::from("test"), None);
-        assert!(result.is_err());
+    /// Enables or disables dry-run (simulate) mode for the session
+    ///
+    /// In dry-run mode commands call their dry-run callback instead of their
+    /// callback, and commands without one fail with
+    /// `CommandProcessorError::DryRunUnsupported`. A single command can also
+    /// be run in dry-run mode by passing `--dry-run` as one of its arguments,
+    /// if it has a dry-run callback. Commands without one receive `--dry-run`
+    /// as any other argument. Callbacks can tell with `Args::dry_run`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - Whether dry-run mode is enabled
+    ///
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
     }
 
-    #[test]
-    fn test_remove_command_not_found() {
-        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// Returns whether dry-run (simulate) mode is enabled for the session
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
 
-        assert!(command_processor
-            .add_command(
-                String::from("test"),
-                printer_demo,
-                Some(String::from("Test command"))
-            )
-            .is_ok());
+    /// Enables or disables strict argument checking
+    ///
+    /// In strict mode, commands that declare a maximum number of arguments
+    /// reject any extra arguments with a usage error. In the default
+    /// permissive mode extra arguments are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether strict argument checking is enabled
+    ///
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
 
-        assert!(command_processor
-            .remove_command(String::from("test2"))
-            .is_err());
+    /// Replaces the message catalog used for processor-generated text
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The message catalog to use
+    ///
+    pub fn set_messages(&mut self, messages: &'a Messages) {
+        self.messages = messages;
     }
 
-    #[test]
-    fn test_writable_command() {
-        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// Returns the message catalog used for processor-generated text
+    pub fn messages(&self) -> &'a Messages {
+        self.messages
+    }
 
-        assert!(command_processor
-            .add_command(
-                String::from("test"),
-                printer_demo,
+    /// Writes a one line status report for the result of a command
+    ///
+    /// Successful commands report the catalog's `ok` string, failed commands
+    /// report its `error` string, followed by the error description if the
+    /// processor itself failed.
+    ///
+    /// # Arguments
+    ///
+    /// * `result` - The result returned by `process_command`
+    /// * `writer` - The writer to write the report to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the report was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the report failed to write
+    ///
+    pub fn report(
+        &self,
+        result: &Result<R, CommandProcessorError>,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        match result {
+            Ok(code) if code.is_success() => writeln!(writer, "{}", self.messages.ok),
+            Ok(_) => writeln!(writer, "{}", self.messages.error),
+            Err(e) => writeln!(
+                writer,
+                "{}: {}",
+                self.messages.error,
+                e.message(self.messages)
+            ),
+        }
+        .map_err(|_| CommandProcessorError::WriteError)
+    }
+
+    /// Adds a command to the command processor
+    ///
+    /// Names made of several words, such as `net ping` and `net status`,
+    /// register subcommands, `net` becoming a group dispatching to them.
+    /// Groups can be nested, and a group can be a command of its own, taking
+    /// the words that aren't subcommands as arguments. `help` and Tab
+    /// completion follow the groups.
+    ///
+    /// A command named like a built-in, such as `help`, `arm` or `status`,
+    /// takes precedence over it, as every built-in yields to registered
+    /// commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add, either a `&'static str` which is never
+    ///   copied, or a `String<NAME_SIZE>` for names built at runtime
+    /// * `callback` - The callback to call when the command is processed
+    /// * `help` - The help string for the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added successfully
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already
+    ///   exists, or with the `binary` feature, if another command has its ID
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    ///
+    pub fn add_command(
+        &mut self,
+        command: impl Into<CommandName<NAME_SIZE>>,
+        callback: CommandCallback<'a, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+    ) -> Result<(), CommandProcessorError> {
+        self.insert_command(
+            command.into(),
+            Callback::Plain(callback),
+            help,
+            self.duplicate_policy,
+            false,
+        )
+    }
+
+    /// Adds a default command, which other registrations of the same name override
+    ///
+    /// Lets a shared module provide a basic implementation, such as a
+    /// `version` command, that products can replace by registering their own,
+    /// before or after the default, without a duplicate error.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    /// * `callback` - The callback to call when the command is processed
+    /// * `help` - The help string for the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added, or a non-default command of
+    ///   the same name already exists
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If another default
+    ///   command of the same name exists, under the duplicate policy
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    ///
+    pub fn add_default_command(
+        &mut self,
+        command: impl Into<CommandName<NAME_SIZE>>,
+        callback: CommandCallback<'a, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+    ) -> Result<(), CommandProcessorError> {
+        self.insert_command(
+            command.into(),
+            Callback::Plain(callback),
+            help,
+            self.duplicate_policy,
+            true,
+        )
+    }
+
+    /// Adds a command, handling an existing command of the same name as given
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    /// * `callback` - The callback to call when the command is processed
+    /// * `help` - The help string for the command
+    /// * `policy` - What to do if the command already exists, overriding the
+    ///   processor's policy for this call
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added, ignored or replaced
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already
+    ///   exists and the policy is `DuplicatePolicy::Error`
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command list is full
+    ///
+    pub fn add_command_with_policy(
+        &mut self,
+        command: impl Into<CommandName<NAME_SIZE>>,
+        callback: CommandCallback<'a, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+        policy: DuplicatePolicy,
+    ) -> Result<(), CommandProcessorError> {
+        self.insert_command(
+            command.into(),
+            Callback::Plain(callback),
+            help,
+            policy,
+            false,
+        )
+    }
+
+    /// Sets what adding an already registered command does, an error by default
+    ///
+    /// Module mounting patterns may register the same command several times
+    /// and want that to be idempotent.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The duplicate policy
+    ///
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Sets the character starting a comment, `#` by default
+    ///
+    /// Comments run to the end of the line, so annotated command sequences
+    /// can be pasted verbatim. Lines holding only a comment are ignored by
+    /// `feed`.
+    ///
+    /// # Arguments
+    ///
+    /// * `comment` - The comment character, or `None` to disable comments
+    ///
+    pub fn set_comment_char(&mut self, comment: Option<char>) {
+        self.comment = comment;
+    }
+
+    /// Sets the characters separating tokens, whitespace by default
+    ///
+    /// Machine protocols may separate arguments with other characters, such
+    /// as `,` for AT-style hosts. Commands get the same arguments whatever the
+    /// dialect.
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiters` - The delimiter set, or `None` for whitespace
+    ///
+    pub fn set_delimiters(&mut self, delimiters: Option<&'a [char]>) {
+        self.delimiters = delimiters;
+    }
+
+    /// Sets the callback notified of changes to the command table
+    ///
+    /// # Arguments
+    ///
+    /// * `observer` - The table observer
+    ///
+    pub fn set_table_observer(&mut self, observer: TableObserver) {
+        self.observer = Some(observer);
+    }
+
+    fn notify(observer: Option<TableObserver>, event: TableEvent) {
+        if let Some(observer) = observer {
+            observer(event);
+        }
+    }
+
+    fn insert_command(
+        &mut self,
+        command: CommandName<NAME_SIZE>,
+        callback: Callback<'a, C, R>,
+        help: Option<String<HELP_STR_SIZE>>,
+        policy: DuplicatePolicy,
+        default: bool,
+    ) -> Result<(), CommandProcessorError> {
+        #[cfg(feature = "binary")]
+        let id = binary::command_id(command.as_str());
+        let item = CommandItem {
+            command,
+            callback,
+            help,
+            #[cfg(feature = "help")]
+            help_callback: None,
+            #[cfg(feature = "help")]
+            category: None,
+            #[cfg(feature = "help")]
+            long_help: None,
+            max_args: None,
+            dry_run_callback: None,
+            requires_arming: false,
+            exclusive: false,
+            hidden: false,
+            disabled: false,
+            #[cfg(feature = "binary")]
+            id,
+            #[cfg(feature = "binary")]
+            binary_callback: None,
+            #[cfg(feature = "builtins")]
+            safe: false,
+            cooldown_ms: 0,
+            cache_ttl_ms: 0,
+            diff_output: false,
+            last_run_ms: None,
+            validator: None,
+            #[cfg(feature = "completion")]
+            completion: None,
+            #[cfg(feature = "hooks")]
+            post_processor: None,
+            version: 1,
+            examples: &[],
+            default,
+            raw_tail: None,
+            schema: &[],
+        };
+
+        // Check if command already exists
+        if let Some(existing) = self
+            .commands
+            .iter_mut()
+            .find(|cmd| cmd.command == *item.command)
+        {
+            // Defaults give way to other registrations, whatever the order
+            let policy = match (existing.default, default) {
+                (true, false) => DuplicatePolicy::Replace,
+                (false, true) => DuplicatePolicy::Ignore,
+                _ => policy,
+            };
+
+            return match policy {
+                DuplicatePolicy::Error => Err(CommandProcessorError::CommandAlreadyExists),
+                DuplicatePolicy::Ignore => Ok(()),
+                DuplicatePolicy::Replace => {
+                    *existing = item;
+                    Self::notify(self.observer, TableEvent::Replaced(&existing.command));
+                    Ok(())
+                }
+            };
+        }
+
+        // Binary requests couldn't tell the commands apart
+        #[cfg(feature = "binary")]
+        if self.commands.iter().any(|cmd| cmd.id == id) {
+            return Err(CommandProcessorError::CommandAlreadyExists);
+        }
+
+        self.commands
+            .push(item)
+            .map_err(|_| CommandProcessorError::CommandListFull)?;
+
+        if let Some(cmd) = self.commands.last() {
+            Self::notify(self.observer, TableEvent::Added(&cmd.command));
+        }
+
+        Ok(())
+    }
+
+    /// Removes a command from the command processor
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was removed successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn remove_command(&mut self, command: &str) -> Result<(), CommandProcessorError> {
+        for (i, cmd) in self.commands.iter().enumerate() {
+            if cmd.command == command {
+                self.commands.swap_remove(i);
+                Self::notify(self.observer, TableEvent::Removed(command));
+                return Ok(());
+            }
+        }
+
+        Err(CommandProcessorError::CommandNotFound)
+    }
+
+    /// Declares the maximum number of arguments a command accepts
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to declare the maximum for
+    /// * `max_args` - The maximum number of arguments, or `None` for no limit
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the maximum was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_max_arguments(
+        &mut self,
+        command: &str,
+        max_args: Option<usize>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.max_args = max_args;
+        Ok(())
+    }
+
+    /// Declares the arguments a command accepts
+    ///
+    /// The arguments are checked against the schema before the command runs.
+    /// If too few or too many are given, the command fails with
+    /// `CommandProcessorError::WrongArgumentCount`, and if one doesn't have
+    /// its declared type, with `CommandProcessorError::InvalidArguments`. The
+    /// usage is written along with the command's help in both cases.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to declare the arguments of
+    /// * `schema` - The arguments, required ones first, or `&[]` for no checks
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the schema was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_arguments(
+        &mut self,
+        command: &str,
+        schema: &'static [ArgSpec],
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.schema = schema;
+        Ok(())
+    }
+
+    /// Sets the callback to call instead of the command's callback in dry-run mode
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the dry-run callback for
+    /// * `callback` - The callback that rehearses the command without side effects
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the dry-run callback was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_dry_run_callback(
+        &mut self,
+        command: &str,
+        callback: CommandCallback<'a, R>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.dry_run_callback = Some(callback);
+        Ok(())
+    }
+
+    /// Marks a command as requiring a previous `arm <command>` before it runs
+    ///
+    /// Critical commands marked this way only run if `arm <command>` was
+    /// processed within the arm window by the same session, so they need two
+    /// deliberate steps. `arm` writes a token of four hexadecimal digits,
+    /// which the command must be given as its last argument, so a stale or
+    /// another session's arming can't run it. The token isn't a secret, it
+    /// only ties the run to the arming it confirms. Arming requires a clock
+    /// to be set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::sync::atomic::{AtomicU64, Ordering};
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, Clock, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// struct Ticks(AtomicU64);
+    ///
+    /// impl Clock for Ticks {
+    ///     fn now_ms(&self) -> u64 {
+    ///         self.0.load(Ordering::Relaxed)
+    ///     }
+    /// }
+    ///
+    /// fn wipe<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     if let Some(writer) = writer {
+    ///         let _ = writeln!(writer, "wiping {}", args.remaining().join(" "));
+    ///     }
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let ticks = Ticks(AtomicU64::new(1_000));
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_clock(&ticks);
+    /// command_processor.add_command("wipe", wipe, None).unwrap();
+    /// command_processor.set_requires_arming("wipe", true).unwrap();
+    ///
+    /// // The `arm` built-in comes with the `builtins` feature
+    /// #[cfg(feature = "builtins")]
+    /// {
+    ///     let mut writer = String::new();
+    ///     command_processor.process_command("arm wipe", Some(&mut writer)).unwrap();
+    ///     let token = writer.trim_end().rsplit(' ').next().unwrap().to_string();
+    ///
+    ///     writer.clear();
+    ///     let line = format!("wipe logs {}", token);
+    ///     command_processor.process_command(&line, Some(&mut writer)).unwrap();
+    ///     assert_eq!(writer, "wiping logs\n");
+    /// }
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to mark
+    /// * `requires_arming` - Whether the command requires arming
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was marked successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_requires_arming(
+        &mut self,
+        command: &str,
+        requires_arming: bool,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.requires_arming = requires_arming;
+        Ok(())
+    }
+
+    /// Sets whether a command starts an operation other sessions must not interleave with
+    ///
+    /// Once an exclusive command succeeded, such as one starting a flash
+    /// erase, the processor is busy until `release_exclusive` is called,
+    /// usually when the operation completes. Meanwhile, exclusive commands
+    /// from other sessions fail with `CommandProcessorError::Busy`, while
+    /// the session that started the operation can still run them. Sessions
+    /// are told apart by the id of their `SessionInfo`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set as exclusive or not
+    /// * `exclusive` - Whether the command is exclusive
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was updated successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, Link, ReturnCode, SessionInfo};
+    ///
+    /// fn erase<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     // Start erasing the flash
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("erase", erase, None).unwrap();
+    /// command_processor.set_exclusive("erase", true).unwrap();
+    ///
+    /// command_processor.set_session_info(Some(SessionInfo::new(0, Link::Serial)));
+    /// command_processor.process_command("erase", None).unwrap();
+    ///
+    /// command_processor.set_session_info(Some(SessionInfo::new(1, Link::Ble)));
+    /// assert!(matches!(
+    ///     command_processor.process_command("erase", None),
+    ///     Err(CommandProcessorError::Busy)
+    /// ));
+    ///
+    /// // Once the flash is erased
+    /// command_processor.release_exclusive();
+    /// assert!(command_processor.process_command("erase", None).is_ok());
+    /// ```
+    ///
+    pub fn set_exclusive(
+        &mut self,
+        command: &str,
+        exclusive: bool,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.exclusive = exclusive;
+        Ok(())
+    }
+
+    /// Ends the operation started by an exclusive command, see `set_exclusive`
+    pub fn release_exclusive(&mut self) {
+        self.busy = None;
+    }
+
+    /// Returns whether an exclusive command's operation is running, see `set_exclusive`
+    pub fn is_busy(&self) -> bool {
+        self.busy.is_some()
+    }
+
+    /// Hides a command from `help` and tab completion, for factory or diagnostic commands
+    ///
+    /// Hidden commands still run when typed, and `help <command>` still
+    /// writes their help for those who know the name.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to hide or show
+    /// * `hidden` - Whether the command is hidden
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was updated successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    /// use heapless::String;
+    ///
+    /// fn demo<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("led", demo, Some(String::from("Sets the LED"))).unwrap();
+    /// command_processor.add_command("calibrate", demo, Some(String::from("Factory only"))).unwrap();
+    /// command_processor.set_hidden("calibrate", true).unwrap();
+    ///
+    /// assert!(command_processor.process_command("calibrate", None).is_ok());
+    ///
+    /// #[cfg(feature = "help")]
+    /// {
+    ///     let mut writer = String::<64>::new();
+    ///     command_processor.process_command("help", Some(&mut writer)).unwrap();
+    ///     assert_eq!(writer, "Sets the LED\n");
+    /// }
+    /// ```
+    ///
+    pub fn set_hidden(&mut self, command: &str, hidden: bool) -> Result<(), CommandProcessorError> {
+        let cmd = self.find_command_mut(command)?;
+        if cmd.hidden != hidden {
+            cmd.hidden = hidden;
+            let event = match hidden {
+                true => TableEvent::Hidden(command),
+                false => TableEvent::Shown(command),
+            };
+            Self::notify(self.observer, event);
+        }
+        Ok(())
+    }
+
+    /// Enables or disables a command, while the peripheral it drives is off for instance
+    ///
+    /// Disabled commands stay registered and listed, but fail with
+    /// `CommandProcessorError::CommandDisabled` until they're enabled again.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to enable or disable
+    /// * `enabled` - Whether the command is enabled
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was updated successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_enabled(
+        &mut self,
+        command: &str,
+        enabled: bool,
+    ) -> Result<(), CommandProcessorError> {
+        let cmd = self.find_command_mut(command)?;
+        if cmd.disabled == enabled {
+            cmd.disabled = !enabled;
+            let event = match enabled {
+                true => TableEvent::Enabled(command),
+                false => TableEvent::Disabled(command),
+            };
+            Self::notify(self.observer, event);
+        }
+        Ok(())
+    }
+
+    /// Sets the minimum interval between two runs of a command
+    ///
+    /// Running the command again before the interval elapsed fails with
+    /// `CommandProcessorError::CooldownActive`. Only runs whose callback
+    /// returned a success status start the interval, so failed attempts can
+    /// be retried. Cooldowns require a clock to be set.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the cooldown for
+    /// * `cooldown_ms` - The minimum interval in milliseconds, 0 to disable
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the cooldown was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_cooldown(
+        &mut self,
+        command: &str,
+        cooldown_ms: u64,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.cooldown_ms = cooldown_ms;
+        Ok(())
+    }
+
+    /// Sets a callback that validates a command's arguments before it runs
+    ///
+    /// If the validation fails, the description returned by the validator is
+    /// written along with the command's help as usage, and the command fails
+    /// with `CommandProcessorError::InvalidArguments` without being run.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to validate the arguments of
+    /// * `validator` - The validation callback
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the validator was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_validator(
+        &mut self,
+        command: &str,
+        validator: ValidationCallback,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.validator = Some(validator);
+        Ok(())
+    }
+
+    /// Sets a callback that post-processes a command's output and result
+    ///
+    /// The command's output is captured, up to `POST_PROCESS_BUFFER_SIZE`
+    /// bytes, and handed to the post-processor along with the result and the
+    /// writer. This allows legacy commands to be adapted, for example to a
+    /// machine readable format, without changing their callback.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to post-process
+    /// * `post_processor` - The post-processing callback
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the post-processor was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    #[cfg(feature = "hooks")]
+    pub fn set_post_processor(
+        &mut self,
+        command: &str,
+        post_processor: PostProcessCallback<'a, R>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.post_processor = Some(post_processor);
+        Ok(())
+    }
+
+    /// Sets the interface version of a command, 1 by default
+    ///
+    /// The version is included in the command table export, so changes to a
+    /// command's arguments or output can be flagged by host tooling.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the version of
+    /// * `version` - The interface version
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the version was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_version(
+        &mut self,
+        command: &str,
+        version: u16,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.version = version;
+        Ok(())
+    }
+
+    /// Generates the help of a command with a callback instead of its help string
+    ///
+    /// The callback is used wherever `help` prints the help string. Searches
+    /// with `help /pattern` still only look at the help string.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the help callback of
+    /// * `help_callback` - The callback writing the help
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the help callback was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    #[cfg(feature = "help")]
+    pub fn set_help_callback(
+        &mut self,
+        command: &str,
+        help_callback: HelpCallback,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.help_callback = Some(help_callback);
+        Ok(())
+    }
+
+    /// Sets the detailed help of a command, written by `help <command>` only
+    ///
+    /// The help string stays a one-line summary for the listings, and the
+    /// long help, which can span several lines, follows the command's usage
+    /// and summary when the command is asked about by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the long help of
+    /// * `long_help` - The long help, or `None` to remove it
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the long help was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    /// use heapless::String;
+    ///
+    /// fn demo<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("reboot", demo, Some(String::from("Reboots"))).unwrap();
+    /// command_processor
+    ///     .set_long_help("reboot", Some("Waits for the flash writes\nthen resets the core"))
+    ///     .unwrap();
+    ///
+    /// let mut writer = String::<128>::new();
+    /// command_processor.process_command("help", Some(&mut writer)).unwrap();
+    /// command_processor.process_command("help reboot", Some(&mut writer)).unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer,
+    ///     "Reboots\nReboots\nWaits for the flash writes\nthen resets the core\n"
+    /// );
+    /// ```
+    ///
+    #[cfg(feature = "help")]
+    pub fn set_long_help(
+        &mut self,
+        command: &str,
+        long_help: Option<&'static str>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.long_help = long_help;
+        Ok(())
+    }
+
+    /// Files a command under a category, such as `System` or `GPIO`
+    ///
+    /// Once a command has a category, `help` writes each category as a
+    /// `GPIO:` header followed by the help of its commands, indented, in the
+    /// order the categories were first used. Commands without a category are
+    /// listed first, without a header. `help -v` writes the category of each
+    /// command.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the category of
+    /// * `category` - The category, or `None` to remove it
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the category was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    /// use heapless::String;
+    ///
+    /// fn demo<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// for (name, help, category) in [
+    ///     ("reboot", "Reboots", "System"),
+    ///     ("led", "Sets the LED", "GPIO"),
+    ///     ("uptime", "Shows the uptime", "System"),
+    /// ] {
+    ///     command_processor.add_command(name, demo, Some(String::from(help))).unwrap();
+    ///     command_processor.set_category(name, Some(category)).unwrap();
+    /// }
+    ///
+    /// let mut writer = String::<128>::new();
+    /// command_processor.process_command("help", Some(&mut writer)).unwrap();
+    /// assert_eq!(
+    ///     writer,
+    ///     "System:\n  Reboots\n  Shows the uptime\nGPIO:\n  Sets the LED\n"
+    /// );
+    /// ```
+    ///
+    #[cfg(feature = "help")]
+    pub fn set_category(
+        &mut self,
+        command: &str,
+        category: Option<&'static str>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.category = category;
+        Ok(())
+    }
+
+    /// Passes the rest of the line, unmodified, as a command's last argument
+    ///
+    /// The argument at `index` starts at its first character and runs to the
+    /// end of the line, keeping its spaces, quotes and comment characters, so
+    /// commands such as `publish <topic> <payload>` get the payload as typed.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the raw argument of
+    /// * `index` - The index of the raw argument, or `None` to split every argument
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the raw argument was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_raw_tail(
+        &mut self,
+        command: &str,
+        index: Option<usize>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.raw_tail = index;
+        Ok(())
+    }
+
+    /// Replaces the help string of a command
+    ///
+    /// Lets applications adjust or translate help strings after registration,
+    /// for example when the language setting changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the help string of
+    /// * `help` - The new help string, or `None` to remove it
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the help string was set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_help(
+        &mut self,
+        command: &str,
+        help: Option<String<HELP_STR_SIZE>>,
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.help = help;
+        Ok(())
+    }
+
+    /// Sets example invocations of a command, printed by `help <command> --examples`
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set the examples of
+    /// * `examples` - The example invocations
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the examples were set successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn set_examples(
+        &mut self,
+        command: &str,
+        examples: &'static [&'static str],
+    ) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.examples = examples;
+        Ok(())
+    }
+
+    /// Writes a canonical export of the registered commands
+    ///
+    /// The export starts with a `command-table 1` header line followed by one
+    /// line per command, sorted by name, in the form
+    /// `name|version|max_args|flags|help`. `max_args` is `-` if unlimited and
+    /// `flags` contains `a` if the command requires arming, `d` if it
+    /// supports dry-run and `h` if it's hidden. The output only depends on the registered commands,
+    /// so it can be diffed between firmware releases.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to write the export to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the export was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the export failed to write
+    ///
+    pub fn export(&self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        writeln!(writer, "command-table 1").map_err(|_| CommandProcessorError::WriteError)?;
+
+        for cmd in self.sorted_commands() {
+            write!(writer, "{}|{}|", cmd.command, cmd.version)
+                .and_then(|_| match cmd.max_args {
+                    Some(max_args) => write!(writer, "{}|", max_args),
+                    None => write!(writer, "-|"),
+                })
+                .and_then(|_| match cmd.requires_arming {
+                    true => write!(writer, "a"),
+                    false => Ok(()),
+                })
+                .and_then(|_| match cmd.dry_run_callback {
+                    Some(_) => write!(writer, "d"),
+                    None => Ok(()),
+                })
+                .and_then(|_| match cmd.hidden {
+                    true => write!(writer, "h"),
+                    false => Ok(()),
+                })
+                .and_then(|_| writeln!(writer, "|{}", cmd.help.as_deref().unwrap_or("")))
+                .map_err(|_| CommandProcessorError::WriteError)?;
+        }
+
+        Ok(())
+    }
+
+    /// Iterates over the commands in name order, without allocating
+    fn sorted_commands(
+        &self,
+    ) -> impl Iterator<Item = &CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>> {
+        let mut previous: Option<&str> = None;
+        core::iter::from_fn(move || {
+            let next = self
+                .commands
+                .iter()
+                .filter(|cmd| previous.is_none_or(|previous| cmd.command.as_str() > previous))
+                .min_by(|a, b| a.command.as_str().cmp(b.command.as_str()))?;
+            previous = Some(next.command.as_str());
+            Some(next)
+        })
+    }
+
+    fn find_command_mut(
+        &mut self,
+        command: &str,
+    ) -> Result<&mut CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>, CommandProcessorError> {
+        self.commands
+            .iter_mut()
+            .find(|cmd| cmd.command == command)
+            .ok_or(CommandProcessorError::CommandNotFound)
+    }
+
+    /// Processes a command and calls the callback
+    ///
+    /// Anything from the comment character on is ignored, see
+    /// `set_comment_char`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command line to process, the command name followed by its arguments
+    /// * `writer` - The writer the command can write with.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode)` - If the command was processed successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    /// * `Err(CommandProcessorError::NoWriter)` - If the command requires a writer but none was provided
+    /// * `Err(CommandProcessorError::WriteError)` - If the command failed to write
+    /// * `Err(CommandProcessorError::UnexpectedArgument)` - If strict mode rejected an extra argument
+    /// * `Err(CommandProcessorError::DryRunUnsupported)` - If the command can't run in dry-run mode
+    /// * `Err(CommandProcessorError::NotArmed)` - If the command requires arming but wasn't armed
+    /// * `Err(CommandProcessorError::ClockUnavailable)` - If the command needs a clock but none was set
+    /// * `Err(CommandProcessorError::CooldownActive)` - If the command ran too recently
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the command's validator rejected the arguments
+    /// * `Err(CommandProcessorError::LineTooLong)` - If the line is longer than `LINE_SIZE`, or a legacy command's translation doesn't fit
+    /// * `Err(CommandProcessorError::NoContext)` - If the command takes the shared context, see `process_command_with`
+    /// * `Err(CommandProcessorError::Busy)` - If another session's exclusive operation is running, see `set_exclusive`
+    /// * `Err(CommandProcessorError::Locked)` - If another session locked the console and the command isn't safe, see `set_safe`
+    /// * `Err(CommandProcessorError::ShuttingDown)` - If `shutdown` was called
+    pub fn process_command(
+        &mut self,
+        command: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.context = None;
+        self.process_line(command, writer)
+    }
+
+    // The writer is only reborrowed to advance tutorials
+    #[cfg_attr(
+        not(feature = "tutorial"),
+        allow(unused_mut, clippy::needless_option_as_deref)
+    )]
+    pub(crate) fn process_line(
+        &mut self,
+        command: &str,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.check_accepting(command)?;
+
+        #[cfg(feature = "builtins")]
+        if self.recording.is_some() {
+            return self.record(command).map(R::from);
+        }
+
+        #[cfg(feature = "builtins")]
+        let started_ms = self.clock.map(|clock| clock.now_ms());
+        let mut result = self.dispatch(command, writer.as_deref_mut());
+        #[cfg(feature = "builtins")]
+        self.record_trace(command, started_ms, &result);
+
+        #[cfg(feature = "tutorial")]
+        if self.tutorial.is_some() {
+            let mut buffer = [0u8; LINE_SIZE];
+            let name = Tokens::new(self.strip_comment(command), self.delimiters, &mut buffer)
+                .next()
+                .unwrap_or("");
+            self.advance_tutorial(name, &result, writer)?;
+        }
+
+        // Lines skipped by the write error policy make the output incomplete
+        if self.output.take_failed() && result.is_ok() {
+            result = Err(CommandProcessorError::WriteError);
+        }
+
+        result
+    }
+
+    /// Checks that the processor accepts a line at all
+    pub(crate) fn check_accepting(&self, command: &str) -> Result<(), CommandProcessorError> {
+        if self.is_shutting_down() {
+            return Err(CommandProcessorError::ShuttingDown);
+        }
+        if command.len() > LINE_SIZE {
+            return Err(CommandProcessorError::LineTooLong);
+        }
+        Ok(())
+    }
+
+    /// Returns whether another session's exclusive operation is running, see `set_exclusive`
+    pub(crate) fn is_busy_for(&self, session: Option<u8>) -> bool {
+        self.busy.is_some_and(|owner| owner != session)
+    }
+
+    /// Returns the line without its trailing comment
+    pub(crate) fn strip_comment<'l>(&self, line: &'l str) -> &'l str {
+        match self.comment {
+            Some(comment) => tokenizer::strip_comment(line, comment),
+            None => line,
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        command: &str,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let line = self.strip_comment(command);
+
+        #[cfg(feature = "builtins")]
+        if let Some((name, command)) = variables::parse_capture(line) {
+            return self.capture(name, command);
+        }
+
+        // Lines are at most LINE_SIZE bytes, and unescaping never lengthens them
+        let mut path: String<LINE_SIZE> = String::new();
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(line, self.delimiters, &mut buffer);
+        let name = self.resolve_alias(tokens.next().unwrap_or(""));
+
+        #[cfg(feature = "builtins")]
+        self.check_lock(name, tokens.peek_raw())?;
+
+        // Registered commands take precedence over built-ins of the same name
+        let registered = self.commands.iter().any(|cmd| cmd.command == name);
+        match name {
+            _ if registered => {}
+            #[cfg(feature = "help")]
+            "help" => {
+                return match writer {
+                    Some(writer) => self.help(tokens, writer).map(R::from),
+                    None => Err(CommandProcessorError::NoWriter),
+                }
+            }
+            #[cfg(feature = "builtins")]
+            "simulate" => return self.simulate(tokens.next(), writer).map(R::from),
+            #[cfg(feature = "builtins")]
+            "arm" => return self.arm(tokens.next(), writer).map(R::from),
+            #[cfg(feature = "builtins")]
+            "let" => return self.let_variable(tokens, writer).map(R::from),
+            #[cfg(feature = "builtins")]
+            "if" => return self.if_else(line, writer),
+            #[cfg(feature = "builtins")]
+            "retry" => return self.retry(line, writer),
+            #[cfg(feature = "builtins")]
+            "script" => return self.script(tokens, writer),
+            #[cfg(feature = "builtins")]
+            "lock" => return self.lock().map(R::from),
+            #[cfg(feature = "builtins")]
+            "unlock" => return self.unlock().map(R::from),
+            #[cfg(feature = "builtins")]
+            "status" => return self.status(tokens.next(), writer).map(R::from),
+            #[cfg(feature = "builtins")]
+            "metrics" => return self.metrics(tokens, writer).map(R::from),
+            #[cfg(feature = "builtins")]
+            "trace" => return self.trace(tokens, writer).map(R::from),
+            #[cfg(all(feature = "binary", feature = "builtins"))]
+            "capabilities" => return self.capabilities(tokens, writer).map(R::from),
+            #[cfg(feature = "tutorial")]
+            "tutorial" => return self.tutorial(tokens.next(), writer).map(R::from),
+            _ => {}
+        }
+
+        let name = match self.is_group(name) {
+            true => {
+                let writer = writer.as_deref_mut().map(|writer| writer as &mut dyn Write);
+                self.resolve_subcommand(name, &mut tokens, &mut path, writer)?;
+                #[cfg(feature = "builtins")]
+                self.check_lock(&path, None)?;
+                path.as_str()
+            }
+            false => name,
+        };
+
+        let entry = self.commands.iter().find(|cmd| cmd.command == name);
+        let raw_tail = entry.and_then(|cmd| cmd.raw_tail);
+        // Only commands that can be rehearsed take the flag
+        let takes_dry_run = entry.is_some_and(|cmd| cmd.dry_run_callback.is_some());
+
+        #[cfg(feature = "builtins")]
+        let mut expansions = [0u8; EXPANSION_BUFFER_SIZE];
+        #[cfg(feature = "builtins")]
+        let mut expansions = expansions.as_mut_slice();
+
+        let mut dry_run = self.dry_run;
+        let mut args: Vec<&str, MAX_ARGS> = Vec::new();
+        loop {
+            // The raw tail is the rest of the line, comments and quotes included
+            if raw_tail == Some(args.len()) {
+                let tail = command.get(tokens.offset()..).unwrap_or("");
+                if !tail.is_empty() {
+                    args.push(tail)
+                        .map_err(|_| CommandProcessorError::InvalidArguments)?;
+                }
+                break;
+            }
+
+            match tokens.next() {
+                Some(DRY_RUN_FLAG) if takes_dry_run => dry_run = true,
+                Some(arg) => {
+                    #[cfg(feature = "builtins")]
+                    let arg = self.variables.expand(arg, &mut expansions)?;
+                    args.push(arg)
+                        .map_err(|_| CommandProcessorError::InvalidArguments)?
+                }
+                None => break,
+            }
+        }
+        tokens.check()?;
+
+        if !self.commands.iter().any(|cmd| cmd.command == name) {
+            return self.process_legacy(command, name, writer);
+        }
+
+        self.execute(name, &args, dry_run, writer)
+    }
+
+    /// Runs a registered command with its arguments
+    pub(crate) fn execute(
+        &mut self,
+        name: &str,
+        args: &[&str],
+        dry_run: bool,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.execute_input(name, Input::Text(args), dry_run, writer)
+    }
+
+    /// Runs a registered command with text arguments, or its binary handler
+    pub(crate) fn execute_input(
+        &mut self,
+        name: &str,
+        input: Input<'_>,
+        dry_run: bool,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        // Binary requests reach commands without going through `process_line`
+        if self.is_shutting_down() {
+            return Err(CommandProcessorError::ShuttingDown);
+        }
+
+        let cmd = self
+            .commands
+            .iter()
+            .find(|cmd| cmd.command == name)
+            .ok_or(CommandProcessorError::CommandNotFound)?;
+        if cmd.disabled {
+            return Err(CommandProcessorError::CommandDisabled);
+        }
+
+        let text = match input {
+            Input::Text(args) => Some(args),
+            #[cfg(feature = "binary")]
+            Input::Typed(_) => None,
+        };
+        let args = text.unwrap_or(&[]);
+
+        // The arming token isn't an argument of the command
+        let (args, token) = match args.split_last() {
+            Some((last, rest)) if cmd.requires_arming && !dry_run => match parse_arm_token(last) {
+                Some(token) => (rest, Some(token)),
+                None => (args, None),
+            },
+            _ => (args, None),
+        };
+
+        // Typed arguments aren't text, their handler decodes and checks them
+        if text.is_some() {
+            self.check_args(cmd, args, writer.as_deref_mut())?;
+        }
+
+        let session = self.session.map(|session| session.id);
+        let exclusive = cmd.exclusive && !dry_run;
+        if exclusive && self.is_busy_for(session) {
+            return Err(CommandProcessorError::Busy);
+        }
+
+        let mut cooldown_from = None;
+        if (cmd.requires_arming || cmd.cooldown_ms > 0) && !dry_run {
+            let now = self
+                .clock
+                .ok_or(CommandProcessorError::ClockUnavailable)?
+                .now_ms();
+
+            if let Some(last_run) = cmd.last_run_ms {
+                if now.saturating_sub(last_run) < cmd.cooldown_ms {
+                    return Err(CommandProcessorError::CooldownActive);
+                }
+            }
+
+            if cmd.requires_arming {
+                match &self.armed {
+                    Some(armed)
+                        if armed.command == name
+                            && armed.session == session
+                            && Some(armed.token) == token
+                            && now.saturating_sub(armed.at_ms) <= self.arm_window_ms =>
+                    {
+                        self.armed = None
+                    }
+                    _ => return Err(CommandProcessorError::NotArmed),
+                }
+            }
+
+            if cmd.cooldown_ms > 0 {
+                cooldown_from = Some(now);
+            }
+        }
+
+        let handler = Handler {
+            callback: match (dry_run, input) {
+                (true, Input::Text(_)) => cmd
+                    .dry_run_callback
+                    .map(Callback::Plain)
+                    .ok_or(CommandProcessorError::DryRunUnsupported)?,
+                (false, Input::Text(_)) => cmd.callback,
+                #[cfg(feature = "binary")]
+                (true, Input::Typed(_)) => return Err(CommandProcessorError::DryRunUnsupported),
+                #[cfg(feature = "binary")]
+                (false, Input::Typed(_)) => cmd
+                    .binary_callback
+                    .map(Callback::Binary)
+                    .ok_or(CommandProcessorError::MalformedRequest)?,
+            },
+            #[cfg(feature = "hooks")]
+            post_processor: cmd.post_processor,
+            dry_run,
+        };
+
+        // SAFETY: the context is only set during `process_command_with`, which
+        // borrows it mutably for the whole call, and callbacks can't reenter
+        // the processor. Other entry points clear it first.
+        let context = self
+            .context
+            .map(|context| unsafe { &mut *context.as_ptr() });
+
+        let (cache_ttl_ms, diff_output) = match dry_run {
+            true => (0, false),
+            false => (cmd.cache_ttl_ms, cmd.diff_output),
+        };
+        let cache_key = self.cache_key(name, args, cache_ttl_ms);
+        let result = match (input, diff_output, writer) {
+            #[cfg(feature = "binary")]
+            (Input::Typed(payload), _, writer) => self.run_typed(handler, payload, context, writer),
+            (_, true, Some(writer)) => {
+                self.run_diffed(name, cache_key, handler, args, context, writer)
+            }
+            (_, _, writer) => self.run_cached(cache_key, handler, args, context, writer),
+        };
+
+        // Failed runs don't start the cooldown, so they can be retried
+        if let (Some(now), true) = (cooldown_from, result.as_ref().is_ok_and(Status::is_success)) {
+            self.find_command_mut(name)?.last_run_ms = Some(now);
+        }
+        self.claim_exclusive(exclusive, session, result)
+    }
+
+    /// Runs a command's callback, through its post-processor if any
+    pub(crate) fn run(
+        &mut self,
+        handler: Handler<'a, C, R>,
+        args: &[&str],
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let session = self.session;
+        let args = Args::new(args)
+            .with_session(session.as_ref())
+            .with_dry_run(handler.dry_run);
+        self.run_args(handler, args, context, writer)
+    }
+
+    /// Runs a command's callback with its arguments, see `run`
+    pub(crate) fn run_args(
+        &mut self,
+        handler: Handler<'a, C, R>,
+        args: Args<'_>,
+        context: Option<&mut C>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let callback = handler.callback;
+        #[cfg(feature = "hooks")]
+        if let Some(post_processor) = handler.post_processor {
+            return match self.scratch.take() {
+                Some(scratch) => {
+                    let mut capture = SliceWriter::new(scratch);
+                    let result = Self::post_process(
+                        callback,
+                        args,
+                        context,
+                        post_processor,
+                        &mut capture,
+                        writer,
+                    );
+                    self.scratch = Some(capture.into_inner());
+                    result
+                }
+                None => {
+                    let mut capture = SliceWriter::new([0u8; POST_PROCESS_BUFFER_SIZE]);
+                    Self::post_process(
+                        callback,
+                        args,
+                        context,
+                        post_processor,
+                        &mut capture,
+                        writer,
+                    )
+                }
+            };
+        }
+
+        callback.call(args, context, writer)
+    }
+
+    /// Checks the text arguments of a command against its limit, schema and validator
+    ///
+    /// The usage is written along with the problem, if there is a writer.
+    fn check_args(
+        &self,
+        cmd: &CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>,
+        args: &[&str],
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<(), CommandProcessorError> {
+        if self.strict {
+            if let Some((max, extra)) = cmd.max_args.and_then(|max| Some((max, args.get(max)?))) {
+                if let Some(writer) = writer {
+                    Self::usage_error(
+                        &self.output,
+                        writer,
+                        cmd,
+                        self.messages.unexpected_argument,
+                        extra,
+                        Some((args, max)),
+                    )?;
+                }
+                return Err(CommandProcessorError::UnexpectedArgument);
+            }
+        }
+
+        if let Err(mismatch) = schema::check(cmd.schema, args) {
+            let error = mismatch.error();
+            let (detail, marked) = match mismatch {
+                Mismatch::Missing(name) => (name, None),
+                Mismatch::Extra(index) => (
+                    args.get(index).copied().unwrap_or_default(),
+                    Some((args, index)),
+                ),
+                Mismatch::Invalid(index, name) => (name, Some((args, index))),
+            };
+
+            if let Some(writer) = writer {
+                let message = error.message(self.messages);
+                Self::usage_error(&self.output, writer, cmd, message, detail, marked)?;
+            }
+            return Err(error);
+        }
+
+        if let Some(validator) = cmd.validator {
+            if let Err(description) = validator(args) {
+                if let Some(writer) = writer {
+                    Self::usage_error(
+                        &self.output,
+                        writer,
+                        cmd,
+                        self.messages.invalid_arguments,
+                        description,
+                        None,
+                    )?;
+                }
+                return Err(CommandProcessorError::InvalidArguments);
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the processor busy once an exclusive command succeeded
+    fn claim_exclusive(
+        &mut self,
+        exclusive: bool,
+        session: Option<u8>,
+        result: Result<R, CommandProcessorError>,
+    ) -> Result<R, CommandProcessorError> {
+        if exclusive && result.as_ref().is_ok_and(Status::is_success) {
+            self.busy = Some(session);
+        }
+        result
+    }
+
+    #[cfg(feature = "hooks")]
+    fn post_process<B: AsRef<[u8]> + AsMut<[u8]> + 'a>(
+        callback: Callback<'a, C, R>,
+        args: Args<'_>,
+        context: Option<&mut C>,
+        post_processor: PostProcessCallback<'a, R>,
+        capture: &mut SliceWriter<B>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        // Commands may ignore write errors, so check for overflows here to
+        // make sure the post-processor sees an accurate status
+        let result = match callback.call(args, context, Some(capture)) {
+            Ok(_) if capture.overflowed() => Err(CommandProcessorError::WriteError),
+            result => result,
+        };
+        post_processor(result, capture.as_str(), writer)
+    }
+
+    fn process_legacy(
+        &mut self,
+        command: &str,
+        name: &str,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let legacy = self
+            .legacy_commands
+            .iter()
+            .find(|legacy| legacy.old == name)
+            .ok_or(CommandProcessorError::CommandNotFound)?;
+
+        // Only translate to registered commands, so translations can't loop
+        let new_name = tokenize(legacy.new, self.delimiters).next().unwrap_or("");
+        if !self.commands.iter().any(|cmd| cmd.command == new_name) {
+            return Err(CommandProcessorError::CommandNotFound);
+        }
+
+        // Delimiters may precede the name, but no other token
+        let rest = command
+            .find(name)
+            .and_then(|start| command.get(start.saturating_add(name.len())..))
+            .unwrap_or("");
+        let mut line: String<LINE_SIZE> = String::new();
+        line.push_str(legacy.new)
+            .and_then(|_| line.push_str(rest))
+            .map_err(|_| CommandProcessorError::LineTooLong)?;
+
+        if let Some(writer) = writer.as_mut() {
+            self.output.write(
+                writer,
+                format_args!(
+                    "{}: {} -> {}\n",
+                    self.messages.deprecated, legacy.old, legacy.new
+                ),
+            )?;
+        }
+
+        self.dispatch(&line, writer)
+    }
+
+    #[cfg(feature = "builtins")]
+    fn arm(
+        &mut self,
+        argument: Option<&str>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let name = argument.ok_or(CommandProcessorError::MissingArgument)?;
+        let clock = self.clock.ok_or(CommandProcessorError::ClockUnavailable)?;
+
+        let cmd = self
+            .commands
+            .iter()
+            .find(|cmd| cmd.command == name)
+            .ok_or(CommandProcessorError::CommandNotFound)?;
+
+        let now = clock.now_ms();
+        let command = cmd.command.clone();
+        let token = self.next_arm_token(now);
+        self.armed = Some(Armed {
+            command,
+            at_ms: now,
+            token,
+            session: self.session.map(|session| session.id),
+        });
+
+        if let Some(writer) = writer {
+            self.output.write(
+                writer,
+                format_args!("{}: {} {:04x}\n", self.messages.armed, name, token),
+            )?;
+        }
+
+        Ok(ReturnCode::Success)
+    }
+
+    /// Returns a new arming token, mixed from the time and the previous tokens
+    #[cfg(feature = "builtins")]
+    fn next_arm_token(&mut self, now_ms: u64) -> u16 {
+        // SplitMix64, so armings within the same millisecond get other tokens
+        self.arm_seed = self
+            .arm_seed
+            .wrapping_add(now_ms)
+            .wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut mixed = self.arm_seed;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        (mixed ^ (mixed >> 31)) as u16
+    }
+
+    #[cfg(feature = "builtins")]
+    fn simulate(
+        &mut self,
+        argument: Option<&str>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        match argument {
+            Some("on") => self.dry_run = true,
+            Some("off") => self.dry_run = false,
+            Some(_) => return Err(CommandProcessorError::UnexpectedArgument),
+            None => {
+                let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+                let state = match self.dry_run {
+                    true => self.messages.simulate_on,
+                    false => self.messages.simulate_off,
+                };
+                self.output.write(writer, format_args!("{}\n", state))?;
+            }
+        }
+
+        Ok(ReturnCode::Success)
+    }
+
+    /// Writes a usage error, with the offending argument marked if known
+    ///
+    /// The marked argument is underlined with carets in a copy of the line:
+    ///
+    /// ```text
+    /// unexpected argument: now
+    /// reboot now
+    ///        ^^^
+    /// ```
+    fn usage_error(
+        output: &Output,
+        writer: &mut (dyn Write + 'a),
+        cmd: &CommandItem<'a, HELP_STR_SIZE, NAME_SIZE, C, R>,
+        message: &str,
+        detail: &str,
+        marked: Option<(&[&str], usize)>,
+    ) -> Result<(), CommandProcessorError> {
+        output.write(writer, format_args!("{}: {}\n", message, detail))?;
+
+        if let Some((args, index)) = marked {
+            let mut offset = cmd.command.len();
+            output.write(writer, format_args!("{}", cmd.command))?;
+
+            for (i, arg) in args.iter().enumerate() {
+                if i < index {
+                    offset = offset.saturating_add(arg.len()).saturating_add(1);
+                }
+                output.write(writer, format_args!(" {}", arg))?;
+            }
+
+            let width = args.get(index).map_or(0, |arg| arg.len());
+            output.write(
+                writer,
+                format_args!(
+                    "\n{:offset$} {:^<width$}\n",
+                    "",
+                    "",
+                    offset = offset,
+                    width = width
+                ),
+            )?;
+        }
+
+        if let Some(help) = &cmd.help {
+            output.write(writer, format_args!("{}\n", help))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{printer_demo, TestClock};
+
+    #[test]
+    fn test_command_processor() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
+                Some(String::from("Test command"))
+            )
+            .is_ok());
+
+        let result = command_processor.process_command("test", None);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), ReturnCode::Success);
+    }
+
+    #[test]
+    fn test_no_commands() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        let result = command_processor.process_command("test", None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_too_many_commands() {
+        let mut command_processor: CommandProcessor<1, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
+                Some(String::from("Test command"))
+            )
+            .is_ok());
+
+        assert!(command_processor
+            .add_command(
+                String::from("test2"),
+                printer_demo,
+                Some(String::from("Test command 2"))
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_remove_command() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
+                Some(String::from("Test command"))
+            )
+            .is_ok());
+
+        assert!(command_processor.remove_command("test").is_ok());
+
+        let result = command_processor.process_command("test", None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_remove_command_not_found() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
+                Some(String::from("Test command"))
+            )
+            .is_ok());
+
+        assert!(command_processor.remove_command("test2").is_err());
+    }
+
+    #[test]
+    fn test_writable_command() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
+                Some(String::from("Test command"))
+            )
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        let result = command_processor.process_command("test", Some(&mut buffer));
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), ReturnCode::Success);
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
                 Some(String::from("Test command"))
             )
             .is_ok());
 
+        let result = command_processor.process_command("unknown", None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_builtins_yield() {
+        fn mine<'a>(
+            args: Args,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writeln!(writer, "mine {}", args.remaining().join(" "))
+                .map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+
+        // Every built-in yields to a registered command of the same name
+        let builtins = [
+            "help",
+            "simulate",
+            "arm",
+            "let",
+            "if",
+            "retry",
+            "script",
+            "lock",
+            "unlock",
+            "status",
+            "metrics",
+            "trace",
+            "capabilities",
+            "tutorial",
+        ];
+        let mut command_processor: CommandProcessor<16, 32> = CommandProcessor::new();
+        for name in builtins {
+            assert!(command_processor.add_command(name, mine, None).is_ok());
+        }
+        for name in builtins {
+            let mut buffer = std::string::String::new();
+            let line = std::format!("{} x", name);
+            assert!(command_processor
+                .process_command(&line, Some(&mut buffer))
+                .is_ok());
+            assert_eq!(buffer, "mine x\n", "{}", name);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "help")]
+    fn test_help_command() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("test"),
+                printer_demo,
+                Some(String::from("test: Test command"))
+            )
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+
+        assert!(command_processor
+            .process_command("help", Some(&mut buffer))
+            .is_ok());
+
+        assert_eq!(buffer, std::string::String::from("test: Test command\n"));
+    }
+
+    #[test]
+    fn test_report() {
+        static CUSTOM: Messages = Messages {
+            ok: "DONE",
+            error: "FAIL",
+            ..DEFAULT_MESSAGES
+        };
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        let mut buffer = std::string::String::new();
+
+        let result = command_processor.process_command("unknown", None);
+        assert!(command_processor.report(&result, &mut buffer).is_ok());
+        assert_eq!(buffer, "ERR: command not found\n");
+
+        command_processor.set_messages(&CUSTOM);
+        buffer.clear();
+
+        assert!(command_processor
+            .report(&Ok(ReturnCode::Success), &mut buffer)
+            .is_ok());
+        assert!(command_processor
+            .report(&Err(CommandProcessorError::CommandNotFound), &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "DONE\nFAIL: command not found\n");
+    }
+
+    #[test]
+    fn test_strict_arguments() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("reboot"),
+                printer_demo,
+                Some(String::from("reboot: Reboots"))
+            )
+            .is_ok());
+        assert!(command_processor
+            .set_max_arguments("reboot", Some(0))
+            .is_ok());
+
+        // Extra arguments are ignored in the default permissive mode
+        let result = command_processor.process_command("reboot nwo", None);
+        assert_eq!(result.unwrap(), ReturnCode::Success);
+
+        command_processor.set_strict(true);
+
+        let mut buffer = std::string::String::new();
+        let result = command_processor.process_command("reboot nwo", Some(&mut buffer));
+        assert!(matches!(
+            result,
+            Err(CommandProcessorError::UnexpectedArgument)
+        ));
+        assert_eq!(
+            buffer,
+            "unexpected argument: nwo\nreboot nwo\n       ^^^\nreboot: Reboots\n"
+        );
+
+        let result = command_processor.process_command("reboot", None);
+        assert_eq!(result.unwrap(), ReturnCode::Success);
+
+        assert!(command_processor
+            .set_max_arguments("unknown", None)
+            .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "builtins")]
+    fn test_dry_run() {
+        fn rehearse<'a>(
+            args: Args,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            assert!(args.dry_run());
+            if let Some(writer) = writer {
+                writeln!(writer, "would erase").map_err(|_| CommandProcessorError::WriteError)?;
+            }
+            Ok(ReturnCode::Success)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("erase"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command(String::from("reboot"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .set_dry_run_callback("erase", rehearse)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("erase --dry-run", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "would erase\n");
+        assert!(!command_processor.is_dry_run());
+
+        // Commands that can't be rehearsed take the flag as an argument
+        fn echo<'a>(
+            args: Args,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            assert!(!args.dry_run());
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writeln!(writer, "{:?}", args.remaining())
+                .map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+        buffer.clear();
+        assert!(command_processor
+            .process_command("echo --dry-run", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "[\"--dry-run\"]\n");
+
+        assert!(command_processor
+            .process_command("simulate on", None)
+            .is_ok());
+        assert!(command_processor.is_dry_run());
+
+        assert!(matches!(
+            command_processor.process_command("reboot", None),
+            Err(CommandProcessorError::DryRunUnsupported)
+        ));
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("simulate", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "simulation on\n");
+
+        assert!(command_processor
+            .process_command("simulate off", None)
+            .is_ok());
+        assert_eq!(
+            command_processor.process_command("reboot", None).unwrap(),
+            ReturnCode::Success
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "builtins")]
+    fn test_arming() {
+        let clock = TestClock::new(1_000);
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("wipe"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor.set_requires_arming("wipe", true).is_ok());
+
+        assert!(matches!(
+            command_processor.process_command("arm wipe", None),
+            Err(CommandProcessorError::ClockUnavailable)
+        ));
+
+        command_processor.set_clock(&clock);
+        command_processor.set_arm_window(5_000);
+
+        assert!(matches!(
+            command_processor.process_command("wipe", None),
+            Err(CommandProcessorError::NotArmed)
+        ));
+
+        let mut buffer = std::string::String::new();
+        let mut arm = |command_processor: &mut CommandProcessor<8, 32>| {
+            buffer.clear();
+            assert!(command_processor
+                .process_command("arm wipe", Some(&mut buffer))
+                .is_ok());
+            assert!(buffer.starts_with("armed: wipe "));
+            buffer.trim_end().rsplit(' ').next().unwrap().to_string()
+        };
+
+        // The command must present the token arm wrote
+        let token = arm(&mut command_processor);
+        assert!(matches!(
+            command_processor.process_command("wipe", None),
+            Err(CommandProcessorError::NotArmed)
+        ));
+        let wrong = format!("{:04x}", u16::from_str_radix(&token, 16).unwrap() ^ 1);
+        assert!(matches!(
+            command_processor.process_command(&format!("wipe {}", wrong), None),
+            Err(CommandProcessorError::NotArmed)
+        ));
+        clock.set(3_000);
+        assert_eq!(
+            command_processor
+                .process_command(&format!("wipe {}", token), None)
+                .unwrap(),
+            ReturnCode::Success
+        );
+
+        // Arming is consumed by the run
+        assert!(matches!(
+            command_processor.process_command(&format!("wipe {}", token), None),
+            Err(CommandProcessorError::NotArmed)
+        ));
+
+        // Arming expires after the window
+        let token = arm(&mut command_processor);
+        clock.set(9_000);
+        assert!(matches!(
+            command_processor.process_command(&format!("wipe {}", token), None),
+            Err(CommandProcessorError::NotArmed)
+        ));
+
+        // Arming only holds for the session that armed
+        command_processor.set_session_info(Some(SessionInfo::new(1, Link::Serial)));
+        let token = arm(&mut command_processor);
+        assert_ne!(arm(&mut command_processor), token);
+        let token = arm(&mut command_processor);
+        command_processor.set_session_info(Some(SessionInfo::new(2, Link::Serial)));
+        assert!(matches!(
+            command_processor.process_command(&format!("wipe {}", token), None),
+            Err(CommandProcessorError::NotArmed)
+        ));
+        command_processor.set_session_info(Some(SessionInfo::new(1, Link::Serial)));
+        assert!(command_processor
+            .process_command(&format!("wipe {}", token), None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let clock = TestClock::new(0);
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("factory_reset"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .set_cooldown("factory_reset", 60_000)
+            .is_ok());
+
+        assert!(matches!(
+            command_processor.process_command("factory_reset", None),
+            Err(CommandProcessorError::ClockUnavailable)
+        ));
+
+        command_processor.set_clock(&clock);
+
+        assert!(command_processor
+            .process_command("factory_reset", None)
+            .is_ok());
+
+        clock.set(59_999);
+        assert!(matches!(
+            command_processor.process_command("factory_reset", None),
+            Err(CommandProcessorError::CooldownActive)
+        ));
+
+        clock.set(60_000);
+        assert!(command_processor
+            .process_command("factory_reset", None)
+            .is_ok());
+
+        // A failed run doesn't start the cooldown
+        fn failing<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            Err(CommandProcessorError::WriteError)
+        }
+        assert!(command_processor
+            .add_command("flash", failing, None)
+            .is_ok());
+        assert!(command_processor.set_cooldown("flash", 60_000).is_ok());
+        assert!(matches!(
+            command_processor.process_command("flash", None),
+            Err(CommandProcessorError::WriteError)
+        ));
+        assert!(matches!(
+            command_processor.process_command("flash", None),
+            Err(CommandProcessorError::WriteError)
+        ));
+
+        // Nor does a run reporting a failure
+        fn refusing<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            Ok(ReturnCode::Failure)
+        }
+        assert!(command_processor
+            .add_command("erase", refusing, None)
+            .is_ok());
+        assert!(command_processor.set_cooldown("erase", 60_000).is_ok());
+        for _ in 0..2 {
+            assert!(matches!(
+                command_processor.process_command("erase", None),
+                Ok(ReturnCode::Failure)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validator() {
+        fn one_number(args: &[&str]) -> Result<(), &'static str> {
+            match args {
+                [value] if value.parse::<u32>().is_ok() => Ok(()),
+                _ => Err("expected a single number"),
+            }
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("baud"),
+                printer_demo,
+                Some(String::from("baud <rate>"))
+            )
+            .is_ok());
+        assert!(command_processor.set_validator("baud", one_number).is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(matches!(
+            command_processor.process_command("baud fast", Some(&mut buffer)),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+        assert_eq!(
+            buffer,
+            "invalid arguments: expected a single number\nbaud <rate>\n"
+        );
+
+        assert!(command_processor
+            .process_command("baud 115200", None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_argument_schema() {
+        static SET_PWM: [ArgSpec; 3] = [
+            ArgSpec::required("channel", ArgKind::U8),
+            ArgSpec::required("duty", ArgKind::U8),
+            ArgSpec::flag("invert"),
+        ];
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                "set-pwm",
+                printer_demo,
+                Some(String::from("Sets a PWM duty"))
+            )
+            .is_ok());
+        assert!(command_processor.set_arguments("set-pwm", &SET_PWM).is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(matches!(
+            command_processor.process_command("set-pwm 1", Some(&mut buffer)),
+            Err(CommandProcessorError::WrongArgumentCount)
+        ));
+        assert_eq!(buffer, "wrong argument count: duty\nSets a PWM duty\n");
+
+        buffer.clear();
+        assert!(matches!(
+            command_processor.process_command("set-pwm 1 300", Some(&mut buffer)),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+        assert_eq!(
+            buffer,
+            "invalid arguments: duty\nset-pwm 1 300\n          ^^^\nSets a PWM duty\n"
+        );
+
+        assert!(matches!(
+            command_processor.process_command("set-pwm 1 50 2", None),
+            Err(CommandProcessorError::WrongArgumentCount)
+        ));
+        assert!(command_processor
+            .process_command("set-pwm --invert 1 50", None)
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "hooks")]
+    fn test_post_processor() {
+        fn legacy<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writeln!(writer, "temp 21").map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Failure)
+        }
+
+        fn machine<'a>(
+            result: CommandCallbackReturn<'a>,
+            output: &str,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            for line in output.lines() {
+                writeln!(writer, "{}", line.replace(' ', "="))
+                    .map_err(|_| CommandProcessorError::WriteError)?;
+            }
+            result.map(|_| ReturnCode::Success)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("temp"), legacy, None)
+            .is_ok());
+        assert!(command_processor
+            .set_post_processor("temp", machine)
+            .is_ok());
+
         let mut buffer = std::string::String::new();
-        let result = command_processor.process_command(&String::from("test"), Some(&mut buffer));
-        assert!(result.is_ok());
+        let result = command_processor.process_command("temp", Some(&mut buffer));
         assert_eq!(result.unwrap(), ReturnCode::Success);
+        assert_eq!(buffer, "temp=21\n");
     }
 
     #[test]
-    fn test_unknown_command() {
+    fn test_legacy_commands() {
+        static LEGACY: [LegacyCommand; 2] = [
+            LegacyCommand {
+                old: "rst",
+                new: "reset --dry-run",
+            },
+            LegacyCommand {
+                old: "loop",
+                new: "loop",
+            },
+        ];
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("reset"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .set_dry_run_callback("reset", printer_demo)
+            .is_ok());
+        command_processor.set_legacy_commands(&LEGACY);
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("rst now", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "deprecated: rst -> reset --dry-run\n");
+
+        assert!(matches!(
+            command_processor.process_command("loop", None),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_export() {
         let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
 
         assert!(command_processor
             .add_command(
-                String::from("test"),
+                String::from("wipe"),
                 printer_demo,
-                Some(String::from("Test command"))
+                Some(String::from("wipe: Erases"))
             )
             .is_ok());
+        assert!(command_processor
+            .add_command(String::from("baud"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor.set_requires_arming("wipe", true).is_ok());
+        assert!(command_processor
+            .set_dry_run_callback("wipe", printer_demo)
+            .is_ok());
+        assert!(command_processor.set_max_arguments("wipe", Some(0)).is_ok());
+        assert!(command_processor.set_version("baud", 2).is_ok());
 
-        let result = command_processor.process_command(&String::from("unknown"), None);
-        assert!(result.is_err());
+        let mut buffer = std::string::String::new();
+        assert!(command_processor.export(&mut buffer).is_ok());
+        assert_eq!(
+            buffer,
+            "command-table 1\nbaud|2|-||\nwipe|1|0|ad|wipe: Erases\n"
+        );
     }
 
     #[test]
-    fn test_help_command() {
+    #[cfg(feature = "help")]
+    fn test_write_error_policy() {
+        /// A writer accepting a limited number of writes
+        struct Limited(usize, std::string::String);
+
+        impl Write for Limited {
+            fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                if self.0 == 0 {
+                    return Err(core::fmt::Error);
+                }
+                self.0 -= 1;
+                self.1.write_str(s)
+            }
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        for (name, help) in [("a", "a: A"), ("b", "b: B"), ("c", "c: C")] {
+            assert!(command_processor
+                .add_command(String::from(name), printer_demo, Some(String::from(help)))
+                .is_ok());
+        }
+
+        // Each line takes a single write
+        let mut writer = Limited(1, std::string::String::new());
+        assert!(matches!(
+            command_processor.process_command("help", Some(&mut writer)),
+            Err(CommandProcessorError::WriteError)
+        ));
+        assert_eq!(writer.1, "a: A\n");
+
+        command_processor.set_write_error_policy(WriteErrorPolicy::Retry);
+        command_processor.set_flush_callback(|| true);
+        let mut writer = Limited(1, std::string::String::new());
+        assert!(matches!(
+            command_processor.process_command("help", Some(&mut writer)),
+            Err(CommandProcessorError::WriteError)
+        ));
+
+        // Skipped lines still complete the command but report the error
+        command_processor.set_write_error_policy(WriteErrorPolicy::SkipLine);
+        let mut writer = Limited(1, std::string::String::new());
+        assert!(matches!(
+            command_processor.process_command("help", Some(&mut writer)),
+            Err(CommandProcessorError::WriteError)
+        ));
+        assert!(command_processor.process_command("a", None).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "hooks")]
+    fn test_post_processor_sees_overflow() {
+        fn chatty<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            if let Some(writer) = writer {
+                for _ in 0..POST_PROCESS_BUFFER_SIZE {
+                    let _ = write!(writer, "xx");
+                }
+            }
+            Ok(ReturnCode::Success)
+        }
+
+        fn status<'a>(
+            result: CommandCallbackReturn<'a>,
+            _: &str,
+            _: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            result
+        }
+
         let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
 
         assert!(command_processor
-            .add_command(
-                String::from("test"),
+            .add_command(String::from("dump"), chatty, None)
+            .is_ok());
+        assert!(command_processor.set_post_processor("dump", status).is_ok());
+
+        assert!(matches!(
+            command_processor.process_command("dump", None),
+            Err(CommandProcessorError::WriteError)
+        ));
+    }
+
+    #[cfg(not(feature = "strict-capacity"))]
+    #[test]
+    #[cfg(feature = "hooks")]
+    fn test_post_processor_truncates() {
+        fn chatty<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            for _ in 0..POST_PROCESS_BUFFER_SIZE {
+                write!(writer, "xx").map_err(|_| CommandProcessorError::WriteError)?;
+            }
+            Ok(ReturnCode::Success)
+        }
+
+        fn length<'a>(
+            _: CommandCallbackReturn<'a>,
+            output: &str,
+            _: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            assert_eq!(output.len(), POST_PROCESS_BUFFER_SIZE);
+            Ok(ReturnCode::Success)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("dump"), chatty, None)
+            .is_ok());
+        assert!(command_processor.set_post_processor("dump", length).is_ok());
+        assert!(command_processor.process_command("dump", None).is_ok());
+    }
+
+    #[test]
+    #[cfg(all(feature = "hooks", feature = "input"))]
+    fn test_memory_footprint() {
+        type Small<'a> = CommandProcessor<'a, 2, 16>;
+        type Large<'a> = CommandProcessor<'a, 16, 16>;
+
+        const {
+            assert!(
+                Small::MEMORY_FOOTPRINT >= Small::TABLE_FOOTPRINT + Small::LINE_BUFFER_FOOTPRINT
+            )
+        };
+        const { assert!(Small::TABLE_FOOTPRINT >= 2 * Small::COMMAND_FOOTPRINT) };
+        assert_eq!(
+            Large::MEMORY_FOOTPRINT - Small::MEMORY_FOOTPRINT,
+            14 * Small::COMMAND_FOOTPRINT
+        );
+        const { assert!(Small::POST_PROCESS_STACK_FOOTPRINT > POST_PROCESS_BUFFER_SIZE) };
+
+        // Borrowed buffers aren't part of the instance
+        let mut scratch = [0u8; 512];
+        let mut command_processor = Small::new();
+        command_processor.set_scratch_buffer(&mut scratch);
+        assert_eq!(
+            core::mem::size_of_val(&command_processor),
+            Small::MEMORY_FOOTPRINT
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "hooks")]
+    fn test_scratch_buffer() {
+        fn long<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            write!(writer, "0123456789").map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+
+        fn echo<'a>(
+            result: CommandCallbackReturn<'a>,
+            output: &str,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            write!(writer, "{}", output).map_err(|_| CommandProcessorError::WriteError)?;
+            result
+        }
+
+        let mut scratch = [0u8; 4];
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.scratch_buffer().is_none());
+
+        command_processor.set_scratch_buffer(&mut scratch);
+        assert_eq!(command_processor.scratch_buffer().map(|s| s.len()), Some(4));
+
+        assert!(command_processor
+            .add_command(String::from("long"), long, None)
+            .is_ok());
+        assert!(command_processor.set_post_processor("long", echo).is_ok());
+
+        // The capture is bounded by the scratch buffer
+        let mut buffer = std::string::String::new();
+        assert!(matches!(
+            command_processor.process_command("long", Some(&mut buffer)),
+            Err(CommandProcessorError::WriteError)
+        ));
+        if !cfg!(feature = "strict-capacity") {
+            assert_eq!(buffer, "0123");
+        }
+    }
+
+    #[test]
+    fn test_static_names() {
+        let mut command_processor: CommandProcessor<8, 32, 0> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("reboot", printer_demo, None)
+            .is_ok());
+        assert!(matches!(
+            command_processor.add_command("reboot", printer_demo, None),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+        assert!(command_processor.process_command("reboot", None).is_ok());
+
+        // Static-only processors don't reserve RAM for names
+        const {
+            assert!(
+                CommandProcessor::<8, 32, 0>::COMMAND_FOOTPRINT
+                    < CommandProcessor::<8, 32>::COMMAND_FOOTPRINT
+            )
+        };
+
+        // Dynamic names still work alongside static ones
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        let mut name: String<32> = String::new();
+        assert!(write!(name, "gpio{}", 3).is_ok());
+
+        assert!(command_processor
+            .add_command("reboot", printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command(name, printer_demo, None)
+            .is_ok());
+        assert!(command_processor.process_command("gpio3", None).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_policy() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("a", printer_demo, Some(String::from("first")))
+            .is_ok());
+
+        command_processor.set_duplicate_policy(DuplicatePolicy::Ignore);
+        assert!(command_processor
+            .add_command("a", printer_demo, Some(String::from("second")))
+            .is_ok());
+        assert_eq!(command_processor.commands.len(), 1);
+        assert_eq!(
+            command_processor.commands[0].help,
+            Some(String::from("first"))
+        );
+
+        assert!(command_processor
+            .add_command_with_policy(
+                "a",
                 printer_demo,
-                Some(String::from("test: Test command"))
+                Some(String::from("third")),
+                DuplicatePolicy::Replace
+            )
+            .is_ok());
+        assert_eq!(command_processor.commands.len(), 1);
+        assert_eq!(
+            command_processor.commands[0].help,
+            Some(String::from("third"))
+        );
+
+        assert!(matches!(
+            command_processor.add_command_with_policy(
+                "a",
+                printer_demo,
+                None,
+                DuplicatePolicy::Error
+            ),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+    }
+
+    #[test]
+    fn test_default_commands() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        // A product command registered after the default replaces it
+        assert!(command_processor
+            .add_default_command("version", printer_demo, Some(String::from("default")))
+            .is_ok());
+        assert!(command_processor
+            .add_command("version", printer_demo, Some(String::from("product")))
+            .is_ok());
+
+        // A default registered after the product command is ignored
+        assert!(command_processor
+            .add_default_command("version", printer_demo, Some(String::from("default")))
+            .is_ok());
+
+        assert_eq!(command_processor.commands.len(), 1);
+        assert_eq!(
+            command_processor.commands[0].help,
+            Some(String::from("product"))
+        );
+
+        // Product commands still conflict with each other
+        assert!(matches!(
+            command_processor.add_command("version", printer_demo, None),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+    }
+
+    #[test]
+    fn test_table_observer() {
+        use std::cell::RefCell;
+
+        thread_local! {
+            static EVENTS: RefCell<std::string::String> = const { RefCell::new(std::string::String::new()) };
+        }
+
+        fn observer(event: TableEvent) {
+            EVENTS.with(|events| events.borrow_mut().push_str(&format!("{:?} ", event)));
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_table_observer(observer);
+
+        assert!(command_processor
+            .add_command("a", printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command_with_policy("a", printer_demo, None, DuplicatePolicy::Replace)
+            .is_ok());
+        assert!(command_processor.remove_command("a").is_ok());
+
+        // Settings only notify when they change
+        assert!(command_processor
+            .add_command("b", printer_demo, None)
+            .is_ok());
+        for enabled in [false, false, true] {
+            assert!(command_processor.set_enabled("b", enabled).is_ok());
+        }
+        for hidden in [true, false, false] {
+            assert!(command_processor.set_hidden("b", hidden).is_ok());
+        }
+
+        EVENTS.with(|events| {
+            assert_eq!(
+                *events.borrow(),
+                "Added(\"a\") Replaced(\"a\") Removed(\"a\") Added(\"b\") \
+                 Disabled(\"b\") Enabled(\"b\") Hidden(\"b\") Shown(\"b\") "
             )
+        });
+    }
+
+    #[test]
+    fn test_set_enabled() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("pump", printer_demo, None)
+            .is_ok());
+        assert!(command_processor.set_enabled("pump", false).is_ok());
+        assert!(matches!(
+            command_processor.process_command("pump", None),
+            Err(CommandProcessorError::CommandDisabled)
+        ));
+        assert!(matches!(
+            command_processor.set_enabled("nope", false),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        assert!(command_processor.set_enabled("pump", true).is_ok());
+        let mut buffer = String::<64>::new();
+        assert!(command_processor
+            .process_command("pump", Some(&mut buffer))
+            .is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "help")]
+    fn test_set_help() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("test", printer_demo, Some(String::from("Test command")))
             .is_ok());
+        assert!(command_processor
+            .set_help("test", Some(String::from("Testbefehl")))
+            .is_ok());
+        assert!(matches!(
+            command_processor.set_help("nope", None),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
 
         let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("help", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "Testbefehl\n");
+    }
+
+    #[test]
+    fn test_delimiters() {
+        fn pair(args: &[&str]) -> Result<(), &'static str> {
+            match args {
+                ["key", "value"] => Ok(()),
+                _ => Err("expected key and value"),
+            }
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
 
         assert!(command_processor
-            .process_command(&String::from("help"), Some(&mut buffer))
+            .add_command("set", printer_demo, None)
             .is_ok());
+        assert!(command_processor.set_validator("set", pair).is_ok());
 
-        assert_eq!(buffer, std::string::String::from("test: Test command\n"));
+        command_processor.set_delimiters(Some(&[',']));
+        assert!(command_processor
+            .process_command("set,key,value", None)
+            .is_ok());
+        assert!(command_processor
+            .process_command("set key value", None)
+            .is_err());
+
+        command_processor.set_delimiters(None);
+        assert!(command_processor
+            .process_command("set key value", None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_raw_tail() {
+        fn payload(args: &[&str]) -> Result<(), &'static str> {
+            match args {
+                ["topic", "hi,  \"you\" # 1"] => Ok(()),
+                _ => Err("payload mangled"),
+            }
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("publish", printer_demo, None)
+            .is_ok());
+        assert!(command_processor.set_validator("publish", payload).is_ok());
+
+        let line = "publish topic hi,  \"you\" # 1";
+        assert!(command_processor.process_command(line, None).is_err());
+
+        assert!(command_processor.set_raw_tail("publish", Some(1)).is_ok());
+        assert!(command_processor.process_command(line, None).is_ok());
+    }
+
+    #[test]
+    fn test_callback_arguments() {
+        fn set_baud<'a>(
+            mut args: Args,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            let baud: u32 = args.next_parse()?;
+            args.finish()?;
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            write!(writer, "{}", baud).map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("set-baud", set_baud, None)
+            .is_ok());
+
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .process_command("set-baud  115200 ", Some(&mut writer))
+            .is_ok());
+        assert_eq!(writer, "115200");
+
+        assert!(matches!(
+            command_processor.process_command("set-baud", None),
+            Err(CommandProcessorError::MissingArgument)
+        ));
+    }
+
+    #[test]
+    fn test_quoted_arguments() {
+        fn echo<'a>(
+            args: Args,
+            writer: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            write!(writer, "{:?}", args.remaining())
+                .map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+
+        let mut writer = std::string::String::new();
+        let line = r#"echo "a # b" \"c\" # d"#;
+        assert!(command_processor
+            .process_command(line, Some(&mut writer))
+            .is_ok());
+        assert_eq!(writer, r#"["a # b", "\"c\""]"#);
+
+        assert!(matches!(
+            command_processor.process_command("echo \"a b", None),
+            Err(CommandProcessorError::UnterminatedQuote)
+        ));
+    }
+
+    #[test]
+    fn test_custom_status() {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        enum Response {
+            Ack,
+            Nack,
+            Busy,
+        }
+
+        impl From<ReturnCode> for Response {
+            fn from(code: ReturnCode) -> Self {
+                match code {
+                    ReturnCode::Success => Response::Ack,
+                    ReturnCode::Failure => Response::Nack,
+                }
+            }
+        }
+
+        impl Status for Response {
+            fn is_success(&self) -> bool {
+                *self == Response::Ack
+            }
+        }
+
+        fn flash<'a>(
+            _: Args,
+            _: Option<&mut (dyn Write + 'a)>,
+        ) -> CommandCallbackReturn<'a, Response> {
+            Ok(Response::Busy)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32, 32, 32, (), Response> =
+            CommandProcessor::new();
+        assert!(command_processor.add_command("flash", flash, None).is_ok());
+
+        let mut buffer = std::string::String::new();
+        let result = command_processor.process_command("flash", None);
+        assert!(matches!(result, Ok(Response::Busy)));
+        assert!(command_processor.report(&result, &mut buffer).is_ok());
+
+        // Built-ins return their code converted to the status
+        #[cfg(feature = "builtins")]
+        {
+            let mut run = |line: &str| command_processor.process_command(line, None);
+            assert!(matches!(run("let a 1"), Ok(Response::Ack)));
+            assert!(matches!(run("retry 2 flash"), Ok(Response::Busy)));
+        }
+
+        assert_eq!(buffer, "ERR\n");
+    }
+
+    #[test]
+    fn test_line_size() {
+        let line = "printer with a line over 32 bytes long";
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("printer", printer_demo, None)
+            .is_ok());
+        assert!(matches!(
+            command_processor.process_command(line, None),
+            Err(CommandProcessorError::LineTooLong)
+        ));
+
+        let mut command_processor: CommandProcessor<8, 32, 32, 64> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("printer", printer_demo, None)
+            .is_ok());
+        assert!(command_processor.process_command(line, None).is_ok());
+    }
+
+    #[test]
+    fn test_exclusive_commands() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("erase", printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command("status", printer_demo, None)
+            .is_ok());
+        assert!(command_processor.set_exclusive("erase", true).is_ok());
+
+        let uart = Some(SessionInfo::new(0, Link::Serial));
+        let ble = Some(SessionInfo::new(1, Link::Ble));
+        command_processor.set_session_info(uart);
+        assert!(command_processor.process_command("erase", None).is_ok());
+        assert!(command_processor.is_busy());
+
+        // Only the other session's exclusive commands are refused
+        command_processor.set_session_info(ble);
+        assert!(matches!(
+            command_processor.process_command("erase", None),
+            Err(CommandProcessorError::Busy)
+        ));
+        assert!(command_processor.process_command("status", None).is_ok());
+        command_processor.set_session_info(uart);
+        assert!(command_processor.process_command("erase", None).is_ok());
+
+        command_processor.release_exclusive();
+        command_processor.set_session_info(ble);
+        assert!(command_processor.process_command("erase", None).is_ok());
     }
 }
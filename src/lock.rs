@@ -0,0 +1,275 @@
+// This module contains the `lock` and `unlock` built-ins
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+/// The built-ins other sessions can't run while the console is locked
+///
+/// `if` and `retry` aren't, as they only run other lines, which are checked
+/// themselves.
+const UNSAFE_BUILTINS: [&str; 7] = [
+    "simulate", "arm", "let", "script", "lock", "unlock", "tutorial",
+];
+
+/// The built-ins other sessions can only read with while the console is locked
+///
+/// They run without arguments, or with one of these first arguments. Others,
+/// such as `metrics reset` or `trace clear`, change their state.
+const READING_BUILTINS: [(&str, &[&str]); 2] = [("metrics", &["csv"]), ("trace", &[])];
+
+/// The session holding the console with `lock`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Lock {
+    /// The id of the session, `None` without sessions
+    owner: Option<u8>,
+    /// When the session last ran a command, if a clock is set
+    used_ms: Option<u64>,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets whether a command only reads, so it runs while another session holds the lock
+    ///
+    /// A session taking the console with the `lock` built-in, to update the
+    /// firmware from a service tool for instance, keeps it until it runs
+    /// `unlock`, its session is closed with `close_session`, or it stays
+    /// idle for the lock timeout. Meanwhile, other sessions can only run
+    /// safe commands, `help` and built-ins reading state such as `metrics`,
+    /// anything else fails with `CommandProcessorError::Locked`. Sessions are told apart by the id
+    /// of their `SessionInfo`.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to set as safe or not
+    /// * `safe` - Whether the command is safe
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was updated successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, Link, ReturnCode, SessionInfo};
+    ///
+    /// fn nothing<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("status", nothing, None).unwrap();
+    /// command_processor.add_command("flash", nothing, None).unwrap();
+    /// command_processor.set_safe("status", true).unwrap();
+    ///
+    /// command_processor.set_session_info(Some(SessionInfo::new(0, Link::Serial)));
+    /// command_processor.process_command("lock", None).unwrap();
+    ///
+    /// command_processor.set_session_info(Some(SessionInfo::new(1, Link::Ble)));
+    /// assert!(command_processor.process_command("status", None).is_ok());
+    /// assert!(matches!(
+    ///     command_processor.process_command("flash", None),
+    ///     Err(CommandProcessorError::Locked)
+    /// ));
+    ///
+    /// // The service tool disconnected
+    /// command_processor.close_session(0);
+    /// assert!(command_processor.process_command("flash", None).is_ok());
+    /// ```
+    ///
+    pub fn set_safe(&mut self, command: &str, safe: bool) -> Result<(), CommandProcessorError> {
+        self.find_command_mut(command)?.safe = safe;
+        Ok(())
+    }
+
+    /// Sets how long the session holding the lock can stay idle, 5 minutes by default
+    ///
+    /// The timeout only applies when a clock is set, see `set_clock`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout_ms` - The idle time after which the lock is released, in milliseconds
+    ///
+    pub fn set_lock_timeout(&mut self, timeout_ms: u64) {
+        self.lock_timeout_ms = timeout_ms;
+    }
+
+    /// Releases the lock if the session holds it, see `set_safe`
+    ///
+    /// To be called when the transport of the session disconnects.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the closed session
+    ///
+    pub fn close_session(&mut self, id: u8) {
+        if self.lock.is_some_and(|lock| lock.owner == Some(id)) {
+            self.lock = None;
+        }
+    }
+
+    /// Returns whether a session holds the lock, see `set_safe`
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+
+    /// Checks that the current session may run a command while the console is locked
+    ///
+    /// Commands of the session holding the lock keep it from timing out.
+    /// Built-ins are checked with their first argument, if any.
+    pub(crate) fn check_lock(
+        &mut self,
+        name: &str,
+        argument: Option<&str>,
+    ) -> Result<(), CommandProcessorError> {
+        if self.may_run_unsafe() {
+            return Ok(());
+        }
+
+        let safe = match self.commands.iter().find(|cmd| cmd.command == name) {
+            Some(cmd) => cmd.safe,
+            None => match READING_BUILTINS
+                .iter()
+                .find(|(builtin, _)| *builtin == name)
+            {
+                Some((_, reads)) => argument.is_none_or(|argument| reads.contains(&argument)),
+                // Unknown commands fail on their own, or run as their replacement
+                None => !UNSAFE_BUILTINS.contains(&name),
+            },
+        };
+        match safe {
+            true => Ok(()),
+            false => Err(CommandProcessorError::Locked),
+        }
+    }
+
+    /// Returns whether the current session may run commands that aren't safe
+    ///
+    /// That is if the console isn't locked, the session holds the lock,
+    /// which is then kept from timing out, or the lock timed out.
+    pub(crate) fn may_run_unsafe(&mut self) -> bool {
+        let session = self.session.map(|session| session.id);
+        let now = self.clock.map(|clock| clock.now_ms());
+        let Some(lock) = self.lock.as_mut() else {
+            return true;
+        };
+
+        if lock.owner == session {
+            lock.used_ms = now;
+            return true;
+        }
+        if let (Some(now), Some(used)) = (now, lock.used_ms) {
+            if now.saturating_sub(used) >= self.lock_timeout_ms {
+                self.lock = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Takes the console for the current session
+    pub(crate) fn lock(&mut self) -> Result<ReturnCode, CommandProcessorError> {
+        self.lock = Some(Lock {
+            owner: self.session.map(|session| session.id),
+            used_ms: self.clock.map(|clock| clock.now_ms()),
+        });
+        Ok(ReturnCode::Success)
+    }
+
+    /// Gives the console back to every session
+    pub(crate) fn unlock(&mut self) -> Result<ReturnCode, CommandProcessorError> {
+        self.lock = None;
+        Ok(ReturnCode::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestClock;
+    use crate::{
+        Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, Link, ReturnCode,
+        SessionInfo,
+    };
+
+    use core::fmt::Write;
+
+    fn nothing<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_lock() {
+        let clock = TestClock::new(0);
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_clock(&clock);
+        command_processor.set_lock_timeout(1_000);
+        assert!(command_processor
+            .add_command("flash", nothing, None)
+            .is_ok());
+
+        let tool = Some(SessionInfo::new(0, Link::Serial));
+        let app = Some(SessionInfo::new(1, Link::Ble));
+        command_processor.set_session_info(tool);
+        assert!(command_processor.process_command("lock", None).is_ok());
+
+        // Other sessions can't run unsafe commands, nor unlock
+        command_processor.set_session_info(app);
+        for line in ["flash", "unlock", "lock", "let x 1", "retry 2 flash"] {
+            assert!(matches!(
+                command_processor.process_command(line, None),
+                Err(CommandProcessorError::Locked)
+            ));
+        }
+        assert!(matches!(
+            command_processor.process_command("unknown", None),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+        #[cfg(feature = "binary")]
+        {
+            let mut response = [0u8; 64];
+            let len = command_processor
+                .process_binary(b"\x05flash\x00", &mut response)
+                .unwrap();
+            assert_eq!(&response[..len], b"\x01\x19locked by another session\x00");
+        }
+
+        // Nor change the state of built-ins, only read it
+        for line in [
+            "metrics reset",
+            "metrics alarm temp above 80",
+            "trace clear",
+            "tutorial",
+        ] {
+            assert!(matches!(
+                command_processor.process_command(line, None),
+                Err(CommandProcessorError::Locked)
+            ));
+        }
+        let mut buffer = std::string::String::new();
+        for line in ["metrics", "metrics csv", "trace"] {
+            assert!(command_processor
+                .process_command(line, Some(&mut buffer))
+                .is_ok());
+        }
+
+        // The owner keeps the lock alive
+        command_processor.set_session_info(tool);
+        clock.set(900);
+        assert!(command_processor.process_command("flash", None).is_ok());
+        command_processor.set_session_info(app);
+        clock.set(1_800);
+        assert!(command_processor.process_command("flash", None).is_err());
+
+        // Until it stays idle for too long
+        clock.set(1_900);
+        assert!(command_processor.process_command("flash", None).is_ok());
+        assert!(!command_processor.is_locked());
+    }
+}
@@ -0,0 +1,230 @@
+// This module contains the message catalog used for processor-generated text
+use crate::CommandProcessorError;
+
+/// A table of every string the command processor writes on its own behalf
+///
+/// Products can provide their own table to rebrand or translate console text
+/// without forking the crate. Help strings and command output are not routed
+/// through the catalog, as those are owned by the application.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{CommandProcessor, Messages, DEFAULT_MESSAGES};
+///
+/// static GERMAN: Messages = Messages {
+///     ok: "OK",
+///     error: "FEHLER",
+///     command_not_found: "Befehl nicht gefunden",
+///     ..DEFAULT_MESSAGES
+/// };
+///
+/// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+/// command_processor.set_messages(&GERMAN);
+///
+/// assert_eq!(command_processor.messages().error, "FEHLER");
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Messages {
+    pub ok: &'static str,
+    pub error: &'static str,
+    pub command_already_exists: &'static str,
+    pub command_not_found: &'static str,
+    pub command_list_full: &'static str,
+    pub write_error: &'static str,
+    pub no_writer: &'static str,
+    pub unexpected_argument: &'static str,
+    pub dry_run_unsupported: &'static str,
+    pub simulate_on: &'static str,
+    pub simulate_off: &'static str,
+    pub clock_unavailable: &'static str,
+    pub missing_argument: &'static str,
+    pub not_armed: &'static str,
+    pub armed: &'static str,
+    pub cooldown_active: &'static str,
+    pub invalid_arguments: &'static str,
+    pub line_too_long: &'static str,
+    pub invalid_configuration: &'static str,
+    pub help_too_long: &'static str,
+    pub override_stack_full: &'static str,
+    pub not_overridden: &'static str,
+    pub unterminated_quote: &'static str,
+    pub malformed_request: &'static str,
+    pub wrong_argument_count: &'static str,
+    pub too_many_variables: &'static str,
+    pub unknown_variable: &'static str,
+    pub no_context: &'static str,
+    pub storage_unavailable: &'static str,
+    pub storage_error: &'static str,
+    pub script_not_found: &'static str,
+    pub script_too_long: &'static str,
+    pub too_many_schedules: &'static str,
+    pub busy: &'static str,
+    pub locked: &'static str,
+    pub too_many_status_values: &'static str,
+    pub unknown_status: &'static str,
+    pub too_many_metrics: &'static str,
+    pub too_many_alarms: &'static str,
+    pub unknown_subcommand: &'static str,
+    pub shutting_down: &'static str,
+    pub too_many_aliases: &'static str,
+    pub permission_denied: &'static str,
+    pub checksum_mismatch: &'static str,
+    pub command_disabled: &'static str,
+    pub deprecated: &'static str,
+    pub tutorial_step: &'static str,
+    pub tutorial_retry: &'static str,
+    pub tutorial_complete: &'static str,
+    pub script_aborted: &'static str,
+    pub tutorial_inactive: &'static str,
+    pub no_examples: &'static str,
+    pub max_arguments: &'static str,
+    pub example: &'static str,
+    pub category: &'static str,
+    pub more: &'static str,
+    pub no_matches: &'static str,
+}
+
+/// The default, English message catalog
+pub const DEFAULT_MESSAGES: Messages = Messages {
+    ok: "OK",
+    error: "ERR",
+    command_already_exists: "command already exists",
+    command_not_found: "command not found",
+    command_list_full: "command list full",
+    write_error: "write error",
+    no_writer: "no writer",
+    unexpected_argument: "unexpected argument",
+    dry_run_unsupported: "command does not support dry run",
+    simulate_on: "simulation on",
+    simulate_off: "simulation off",
+    clock_unavailable: "no clock available",
+    missing_argument: "missing argument",
+    not_armed: "command must be armed first",
+    armed: "armed",
+    cooldown_active: "command is cooling down",
+    invalid_arguments: "invalid arguments",
+    line_too_long: "line too long",
+    invalid_configuration: "invalid configuration",
+    help_too_long: "help string too long",
+    override_stack_full: "too many overrides",
+    not_overridden: "command not overridden",
+    unterminated_quote: "unterminated quote",
+    malformed_request: "malformed request",
+    wrong_argument_count: "wrong argument count",
+    too_many_variables: "too many variables",
+    unknown_variable: "unknown variable",
+    no_context: "no context",
+    storage_unavailable: "no storage",
+    storage_error: "storage error",
+    script_not_found: "script not found",
+    script_too_long: "script too long",
+    too_many_schedules: "too many schedules",
+    busy: "busy",
+    locked: "locked by another session",
+    too_many_status_values: "too many status values",
+    unknown_status: "unknown status",
+    too_many_metrics: "too many metrics",
+    too_many_alarms: "too many alarms",
+    unknown_subcommand: "unknown subcommand",
+    shutting_down: "shutting down",
+    too_many_aliases: "too many aliases",
+    permission_denied: "permission denied",
+    checksum_mismatch: "checksum mismatch",
+    command_disabled: "command disabled",
+    deprecated: "deprecated",
+    tutorial_step: "step",
+    tutorial_retry: "step failed, try again",
+    tutorial_complete: "tutorial complete",
+    script_aborted: "script aborted at line",
+    tutorial_inactive: "no tutorial running",
+    no_examples: "no examples",
+    max_arguments: "max arguments",
+    example: "example",
+    category: "category",
+    more: "--More--",
+    no_matches: "no matching commands",
+};
+
+impl Default for Messages {
+    fn default() -> Self {
+        DEFAULT_MESSAGES
+    }
+}
+
+impl CommandProcessorError {
+    /// Returns the description of the error from the given message catalog
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The message catalog to look the description up in
+    ///
+    pub fn message(&self, messages: &Messages) -> &'static str {
+        match self {
+            CommandProcessorError::CommandAlreadyExists => messages.command_already_exists,
+            CommandProcessorError::CommandNotFound => messages.command_not_found,
+            CommandProcessorError::CommandListFull => messages.command_list_full,
+            CommandProcessorError::WriteError => messages.write_error,
+            CommandProcessorError::NoWriter => messages.no_writer,
+            CommandProcessorError::UnexpectedArgument => messages.unexpected_argument,
+            CommandProcessorError::DryRunUnsupported => messages.dry_run_unsupported,
+            CommandProcessorError::ClockUnavailable => messages.clock_unavailable,
+            CommandProcessorError::MissingArgument => messages.missing_argument,
+            CommandProcessorError::NotArmed => messages.not_armed,
+            CommandProcessorError::CooldownActive => messages.cooldown_active,
+            CommandProcessorError::InvalidArguments => messages.invalid_arguments,
+            CommandProcessorError::LineTooLong => messages.line_too_long,
+            CommandProcessorError::InvalidConfiguration => messages.invalid_configuration,
+            CommandProcessorError::HelpTooLong => messages.help_too_long,
+            CommandProcessorError::OverrideStackFull => messages.override_stack_full,
+            CommandProcessorError::NotOverridden => messages.not_overridden,
+            CommandProcessorError::UnterminatedQuote => messages.unterminated_quote,
+            CommandProcessorError::MalformedRequest => messages.malformed_request,
+            CommandProcessorError::WrongArgumentCount => messages.wrong_argument_count,
+            CommandProcessorError::TooManyVariables => messages.too_many_variables,
+            CommandProcessorError::UnknownVariable => messages.unknown_variable,
+            CommandProcessorError::NoContext => messages.no_context,
+            CommandProcessorError::StorageUnavailable => messages.storage_unavailable,
+            CommandProcessorError::StorageError => messages.storage_error,
+            CommandProcessorError::ScriptNotFound => messages.script_not_found,
+            CommandProcessorError::ScriptTooLong => messages.script_too_long,
+            CommandProcessorError::TooManySchedules => messages.too_many_schedules,
+            CommandProcessorError::Busy => messages.busy,
+            CommandProcessorError::Locked => messages.locked,
+            CommandProcessorError::TooManyStatusValues => messages.too_many_status_values,
+            CommandProcessorError::UnknownStatus => messages.unknown_status,
+            CommandProcessorError::TooManyMetrics => messages.too_many_metrics,
+            CommandProcessorError::TooManyAlarms => messages.too_many_alarms,
+            CommandProcessorError::UnknownSubcommand => messages.unknown_subcommand,
+            CommandProcessorError::ShuttingDown => messages.shutting_down,
+            CommandProcessorError::TooManyAliases => messages.too_many_aliases,
+            CommandProcessorError::PermissionDenied => messages.permission_denied,
+            CommandProcessorError::ChecksumMismatch => messages.checksum_mismatch,
+            CommandProcessorError::CommandDisabled => messages.command_disabled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            CommandProcessorError::CommandNotFound.message(&DEFAULT_MESSAGES),
+            "command not found"
+        );
+
+        let custom = Messages {
+            command_not_found: "unknown",
+            ..DEFAULT_MESSAGES
+        };
+
+        assert_eq!(
+            CommandProcessorError::CommandNotFound.message(&custom),
+            "unknown"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// This module contains the registry of counters and gauges and the `metrics` built-in
+use crate::tokenizer::Tokens;
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::Write;
+
+/// The maximum number of metrics
+pub const MAX_METRICS: usize = 16;
+
+/// The value of a metric
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Metric {
+    /// A count of events, only going up until reset
+    Counter(u64),
+    /// A reading, such as a queue depth, set as it changes
+    Gauge(i64),
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Adds to a counter, reported by the `metrics` built-in
+    ///
+    /// The counter is created at 0 the first time it's incremented, and
+    /// saturates instead of wrapping. `metrics` writes every metric as
+    /// `name: value` lines, in the order they were created, `metrics csv`
+    /// writes them as `kind,name,value` lines for scripts, and `metrics
+    /// reset` sets the counters back to 0. Thresholds can be set on them,
+    /// see `set_alarm_callback`. A command registered as
+    /// `metrics` takes precedence over the built-in.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the counter
+    /// * `by` - The amount to add
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the counter was incremented successfully
+    /// * `Err(CommandProcessorError::TooManyMetrics)` - If `MAX_METRICS` metrics already exist
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the name is a gauge
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::CommandProcessor;
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.increment_counter("rx_frames", 3).unwrap();
+    /// command_processor.set_gauge("queue_depth", -1).unwrap();
+    /// command_processor.increment_counter("rx_frames", 1).unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.process_command("metrics", Some(&mut writer)).unwrap();
+    /// command_processor.process_command("metrics reset", None).unwrap();
+    /// command_processor.process_command("metrics csv", Some(&mut writer)).unwrap();
+    ///
+    /// assert_eq!(
+    ///     writer,
+    ///     "rx_frames: 4\nqueue_depth: -1\ncounter,rx_frames,0\ngauge,queue_depth,-1\n"
+    /// );
+    /// ```
+    ///
+    pub fn increment_counter(
+        &mut self,
+        name: &'a str,
+        by: u64,
+    ) -> Result<(), CommandProcessorError> {
+        let count = match self.metric_mut(name, Metric::Counter(0))? {
+            Metric::Counter(count) => {
+                *count = count.saturating_add(by);
+                *count
+            }
+            Metric::Gauge(_) => return Err(CommandProcessorError::InvalidArguments),
+        };
+        self.check_alarms(name, i64::try_from(count).unwrap_or(i64::MAX));
+        Ok(())
+    }
+
+    /// Sets a gauge, reported by the `metrics` built-in, see `increment_counter`
+    ///
+    /// The gauge is created the first time it's set. Resetting the metrics
+    /// leaves gauges as they are, as they report a current state.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the gauge
+    /// * `value` - The current value
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the gauge was set successfully
+    /// * `Err(CommandProcessorError::TooManyMetrics)` - If `MAX_METRICS` metrics already exist
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the name is a counter
+    ///
+    pub fn set_gauge(&mut self, name: &'a str, value: i64) -> Result<(), CommandProcessorError> {
+        match self.metric_mut(name, Metric::Gauge(value))? {
+            Metric::Gauge(gauge) => *gauge = value,
+            Metric::Counter(_) => return Err(CommandProcessorError::InvalidArguments),
+        }
+        self.check_alarms(name, value);
+        Ok(())
+    }
+
+    /// Sets every counter back to 0, like `metrics reset`
+    ///
+    /// The alarms of the counters are cleared, see `set_alarm_callback`.
+    pub fn reset_metrics(&mut self) {
+        for index in 0..self.metrics.len() {
+            let Some((name, Metric::Counter(count))) = self.metrics.get_mut(index) else {
+                continue;
+            };
+            *count = 0;
+            let name = *name;
+            self.check_alarms(name, 0);
+        }
+    }
+
+    /// Returns a metric, created with an initial value if it doesn't exist
+    fn metric_mut(
+        &mut self,
+        name: &'a str,
+        initial: Metric,
+    ) -> Result<&mut Metric, CommandProcessorError> {
+        let index = match self.metrics.iter().position(|(key, _)| *key == name) {
+            Some(index) => index,
+            None => {
+                self.metrics
+                    .push((name, initial))
+                    .map_err(|_| CommandProcessorError::TooManyMetrics)?;
+                self.metrics.len().saturating_sub(1)
+            }
+        };
+        self.metrics
+            .get_mut(index)
+            .map(|(_, metric)| metric)
+            .ok_or(CommandProcessorError::TooManyMetrics)
+    }
+
+    /// Runs the metrics built-in, see `increment_counter`
+    pub(crate) fn metrics(
+        &mut self,
+        mut tokens: Tokens,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let subcommand = tokens.next();
+        if subcommand == Some("alarm") {
+            return self.alarm(tokens, writer);
+        }
+        if tokens.next().is_some() {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        tokens.check()?;
+
+        let csv = match subcommand {
+            None => false,
+            Some("csv") => true,
+            Some("reset") => {
+                self.reset_metrics();
+                return Ok(ReturnCode::Success);
+            }
+            Some(_) => return Err(CommandProcessorError::InvalidArguments),
+        };
+
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        for (name, metric) in &self.metrics {
+            match (csv, metric) {
+                (false, Metric::Counter(count)) => self
+                    .output
+                    .write(writer, format_args!("{}: {}\n", name, count)),
+                (false, Metric::Gauge(value)) => self
+                    .output
+                    .write(writer, format_args!("{}: {}\n", name, value)),
+                (true, Metric::Counter(count)) => self
+                    .output
+                    .write(writer, format_args!("counter,{},{}\n", name, count)),
+                (true, Metric::Gauge(value)) => self
+                    .output
+                    .write(writer, format_args!("gauge,{},{}\n", name, value)),
+            }?;
+        }
+        Ok(ReturnCode::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .increment_counter("errors", u64::MAX)
+            .is_ok());
+        assert!(command_processor.increment_counter("errors", 1).is_ok());
+        assert!(command_processor.set_gauge("temp", 21).is_ok());
+        assert!(matches!(
+            command_processor.set_gauge("errors", 0),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .process_command("metrics", Some(&mut writer))
+            .is_ok());
+        assert_eq!(writer, "errors: 18446744073709551615\ntemp: 21\n");
+
+        assert!(matches!(
+            command_processor.process_command("metrics nope", None),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+        let names = [
+            "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n",
+        ];
+        for name in names.iter().take(MAX_METRICS - 2) {
+            assert!(command_processor.increment_counter(name, 1).is_ok());
+        }
+        assert!(matches!(
+            command_processor.increment_counter("extra", 1),
+            Err(CommandProcessorError::TooManyMetrics)
+        ));
+    }
+}
@@ -0,0 +1,124 @@
+// This module contains a minimal dispatcher for bootloaders
+use crate::{Args, CommandCallback, CommandCallbackReturn, CommandProcessorError};
+
+use core::fmt::Write;
+
+/// A command in a `MiniDispatcher` table
+pub struct MiniCommand<'a> {
+    pub name: &'static str,
+    pub callback: CommandCallback<'a>,
+}
+
+/// A stripped down dispatcher for bootloaders
+///
+/// The command table is a static slice and the writer is fixed at creation,
+/// so the dispatcher is only two fat references in RAM, four words: 16 bytes
+/// on 32-bit targets. Lines are matched exactly
+/// against the command names, so callbacks always receive no arguments, and
+/// there is no help and no built-ins. Callbacks have the same type as the full `CommandProcessor`'s,
+/// so commands can be shared between the bootloader and the application.
+///
+/// # Example
+///
+/// ```
+/// use core::fmt::Write;
+/// use command_processor::{Args, CommandCallbackReturn, MiniCommand, MiniDispatcher, ReturnCode};
+///
+/// fn boot<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// static COMMANDS: [MiniCommand; 1] = [MiniCommand { name: "boot", callback: boot }];
+///
+/// let mut writer = String::new();
+/// let mut dispatcher = MiniDispatcher::new(&COMMANDS, &mut writer);
+///
+/// assert_eq!(dispatcher.dispatch("boot").unwrap(), ReturnCode::Success);
+/// assert!(dispatcher.dispatch("boot now").is_err());
+/// ```
+///
+pub struct MiniDispatcher<'a, 'w> {
+    commands: &'a [MiniCommand<'a>],
+    writer: &'w mut (dyn Write + 'a),
+}
+
+// The table and the writer, a slice and a trait object, are two words each
+const _: () = assert!(core::mem::size_of::<MiniDispatcher>() == 4 * core::mem::size_of::<usize>());
+
+impl<'a, 'w> MiniDispatcher<'a, 'w> {
+    /// Creates a dispatcher over a command table
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The command table
+    /// * `writer` - The writer passed to every command
+    ///
+    pub fn new(commands: &'a [MiniCommand<'a>], writer: &'w mut (dyn Write + 'a)) -> Self {
+        Self { commands, writer }
+    }
+
+    /// Runs the command whose name is exactly the given line
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The received line, without its line ending
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode)` - The return code of the command
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If no command has this name
+    ///
+    pub fn dispatch(&mut self, line: &str) -> CommandCallbackReturn<'a> {
+        let commands = self.commands.iter().map(|cmd| (cmd.name, cmd.callback));
+        run(commands, line, &[], Some(&mut *self.writer))
+    }
+}
+
+/// Runs the command of a table with the given name
+///
+/// The dispatch shared by `MiniDispatcher` and `SliceCommandProcessor`, whose
+/// tables only differ in what they hold besides names and callbacks.
+pub(crate) fn run<'a>(
+    mut commands: impl Iterator<Item = (&'static str, CommandCallback<'a>)>,
+    name: &str,
+    args: &[&str],
+    writer: Option<&mut (dyn Write + 'a)>,
+) -> CommandCallbackReturn<'a> {
+    let (_, callback) = commands
+        .find(|(command, _)| *command == name)
+        .ok_or(CommandProcessorError::CommandNotFound)?;
+
+    callback(Args::new(args), writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hello;
+    use crate::ReturnCode;
+
+    #[test]
+    fn test_mini_dispatcher() {
+        let commands = [MiniCommand {
+            name: "hello",
+            callback: hello,
+        }];
+        let mut writer = std::string::String::new();
+        let mut dispatcher = MiniDispatcher::new(&commands, &mut writer);
+
+        assert!(matches!(
+            dispatcher.dispatch("hello"),
+            Ok(ReturnCode::Success)
+        ));
+        assert!(matches!(
+            dispatcher.dispatch("hell"),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+        assert!(matches!(
+            dispatcher.dispatch("hello "),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        assert_eq!(writer, "hi\n");
+    }
+}
@@ -0,0 +1,77 @@
+// This module contains the storage of command names
+use heapless::String;
+
+use core::fmt;
+use core::ops::Deref;
+
+/// The name of a registered command
+///
+/// Names known at compile time are stored as `&'static str` and never copied
+/// into RAM, identical string literals are shared by the linker. Only names
+/// built at runtime are copied into owned storage of `NAME_SIZE` bytes.
+///
+/// # Arguments
+///
+/// * `NAME_SIZE` - The maximum size of an owned name
+///
+#[derive(Debug, Clone)]
+pub enum CommandName<const NAME_SIZE: usize> {
+    Static(&'static str),
+    Owned(String<NAME_SIZE>),
+}
+
+impl<const NAME_SIZE: usize> CommandName<NAME_SIZE> {
+    /// Returns the name as a string slice
+    pub fn as_str(&self) -> &str {
+        match self {
+            CommandName::Static(name) => name,
+            CommandName::Owned(name) => name.as_str(),
+        }
+    }
+}
+
+impl<const NAME_SIZE: usize> Deref for CommandName<NAME_SIZE> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const NAME_SIZE: usize> fmt::Display for CommandName<NAME_SIZE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl<const NAME_SIZE: usize> PartialEq<str> for CommandName<NAME_SIZE> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const NAME_SIZE: usize> PartialEq<&str> for CommandName<NAME_SIZE> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const NAME_SIZE: usize, const OTHER_SIZE: usize> PartialEq<String<OTHER_SIZE>>
+    for CommandName<NAME_SIZE>
+{
+    fn eq(&self, other: &String<OTHER_SIZE>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const NAME_SIZE: usize> From<&'static str> for CommandName<NAME_SIZE> {
+    fn from(name: &'static str) -> Self {
+        CommandName::Static(name)
+    }
+}
+
+impl<const NAME_SIZE: usize> From<String<NAME_SIZE>> for CommandName<NAME_SIZE> {
+    fn from(name: String<NAME_SIZE>) -> Self {
+        CommandName::Owned(name)
+    }
+}
@@ -0,0 +1,263 @@
+// This module contains the handling of write errors on processor output
+use crate::CommandProcessorError;
+
+use core::cell::Cell;
+use core::fmt::{self, Arguments, Write};
+use heapless::String;
+
+/// The size of the buffer each line is formatted into before it's written
+///
+/// A line is written with a single write, so a failed write is retried or
+/// skipped whole. Longer lines are written in chunks of this size, and only
+/// the failed chunk is retried.
+pub const OUTPUT_LINE_SIZE: usize = 128;
+
+/// What the processor does when writing its own output fails mid-command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteErrorPolicy {
+    /// Stop the command at the first failed write and return `WriteError`
+    Abort,
+    /// Skip the failed line and carry on, returning `WriteError` once the
+    /// command completed so the caller knows the output is incomplete
+    SkipLine,
+    /// Call the flush callback and write the failed line again, aborting if
+    /// the flush callback fails or the retry fails too
+    Retry,
+}
+
+/// Flush callback type
+///
+/// Called before a failed write is retried, to drain the transport. Returns
+/// `true` if the write is worth retrying.
+pub type FlushCallback = fn() -> bool;
+
+/// Writes processor output according to the write error policy
+pub(crate) struct Output {
+    pub(crate) policy: WriteErrorPolicy,
+    pub(crate) flush: Option<FlushCallback>,
+    failed: Cell<bool>,
+}
+
+/// Formats a line into chunks of `OUTPUT_LINE_SIZE` bytes, each written whole
+struct LineWriter<'o, 'w> {
+    output: &'o Output,
+    writer: &'w mut dyn Write,
+    buffer: String<OUTPUT_LINE_SIZE>,
+    /// Set once a chunk was skipped, dropping the rest of the line
+    skipped: bool,
+    result: Result<(), CommandProcessorError>,
+}
+
+impl LineWriter<'_, '_> {
+    /// Writes the buffered chunk, applying the write error policy on failure
+    fn send(&mut self) -> fmt::Result {
+        let written = match self.skipped {
+            true => Ok(false),
+            false => self.output.send(self.writer, &self.buffer),
+        };
+        self.buffer.clear();
+        match written {
+            Ok(sent) => {
+                self.skipped = !sent;
+                Ok(())
+            }
+            Err(e) => {
+                self.result = Err(e);
+                Err(fmt::Error)
+            }
+        }
+    }
+}
+
+impl Write for LineWriter<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+        while !rest.is_empty() {
+            let room = self.buffer.capacity().saturating_sub(self.buffer.len());
+            let mut split = rest.len().min(room);
+            while !rest.is_char_boundary(split) {
+                split = split.saturating_sub(1);
+            }
+            match rest.split_at_checked(split) {
+                Some((head, tail)) if !head.is_empty() => {
+                    let _ = self.buffer.push_str(head);
+                    rest = tail;
+                }
+                _ => self.send()?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Output {
+    pub(crate) const fn new() -> Self {
+        Self {
+            policy: WriteErrorPolicy::Abort,
+            flush: None,
+            failed: Cell::new(false),
+        }
+    }
+
+    /// Writes formatted output, applying the write error policy on failure
+    ///
+    /// The output is formatted into a line buffer first, so a failed write
+    /// never leaves part of a line on the writer, nor repeats it on retry.
+    pub(crate) fn write(
+        &self,
+        writer: &mut dyn Write,
+        args: Arguments,
+    ) -> Result<(), CommandProcessorError> {
+        let mut line = LineWriter {
+            output: self,
+            writer,
+            buffer: String::new(),
+            skipped: false,
+            result: Ok(()),
+        };
+        if line.write_fmt(args).is_ok() && !line.buffer.is_empty() {
+            let _ = line.send();
+        }
+        line.result
+    }
+
+    /// Writes a chunk, returning whether it was written or skipped
+    fn send(&self, writer: &mut dyn Write, chunk: &str) -> Result<bool, CommandProcessorError> {
+        if writer.write_str(chunk).is_ok() {
+            return Ok(true);
+        }
+
+        match self.policy {
+            WriteErrorPolicy::Abort => Err(CommandProcessorError::WriteError),
+            WriteErrorPolicy::SkipLine => {
+                self.failed.set(true);
+                Ok(false)
+            }
+            WriteErrorPolicy::Retry => match self.flush {
+                Some(flush) if flush() => writer
+                    .write_str(chunk)
+                    .map(|_| true)
+                    .map_err(|_| CommandProcessorError::WriteError),
+                _ => Err(CommandProcessorError::WriteError),
+            },
+        }
+    }
+
+    /// Returns whether a line was skipped since the last call, clearing the flag
+    pub(crate) fn take_failed(&self) -> bool {
+        self.failed.replace(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer failing every other write
+    struct Flaky {
+        output: std::string::String,
+        fail: bool,
+    }
+
+    impl Write for Flaky {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.fail = !self.fail;
+            match self.fail {
+                true => Err(fmt::Error),
+                false => self.output.write_str(s),
+            }
+        }
+    }
+
+    #[test]
+    fn test_policies() {
+        let mut flaky = Flaky {
+            output: std::string::String::new(),
+            fail: false,
+        };
+
+        let mut output = Output::new();
+        assert!(output.write(&mut flaky, format_args!("a\n")).is_err());
+
+        output.policy = WriteErrorPolicy::SkipLine;
+        assert!(output.write(&mut flaky, format_args!("b\n")).is_ok());
+        assert!(output.write(&mut flaky, format_args!("c\n")).is_ok());
+        assert!(output.take_failed());
+        assert!(!output.take_failed());
+
+        output.policy = WriteErrorPolicy::Retry;
+        output.flush = Some(|| true);
+        assert!(output.write(&mut flaky, format_args!("d\n")).is_ok());
+        assert!(output.write(&mut flaky, format_args!("e\n")).is_ok());
+        output.flush = None;
+        assert!(output.write(&mut flaky, format_args!("f\n")).is_err());
+
+        assert_eq!(flaky.output, "b\nd\ne\n");
+    }
+
+    /// A writer failing the given write, as a full transmit FIFO would
+    struct FailsAt {
+        output: std::string::String,
+        writes: usize,
+        fail_at: usize,
+    }
+
+    impl Write for FailsAt {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.writes += 1;
+            match self.writes == self.fail_at {
+                true => Err(fmt::Error),
+                false => self.output.write_str(s),
+            }
+        }
+    }
+
+    #[test]
+    fn test_partial_writes() {
+        let line = "x".repeat(OUTPUT_LINE_SIZE + 2);
+
+        // Without line buffering, the retry would repeat the name
+        let mut output = Output::new();
+        output.policy = WriteErrorPolicy::Retry;
+        output.flush = Some(|| true);
+        let mut writer = FailsAt {
+            output: std::string::String::new(),
+            writes: 0,
+            fail_at: 2,
+        };
+        assert!(output.write(&mut writer, format_args!("a\n")).is_ok());
+        assert!(output
+            .write(&mut writer, format_args!("{}: {}\n", "led", "Sets the LED"))
+            .is_ok());
+        assert_eq!(writer.output, "a\nled: Sets the LED\n");
+
+        // Lines longer than the buffer only retry the failed chunk
+        writer.output.clear();
+        writer.writes = 0;
+        assert!(output
+            .write(&mut writer, format_args!("{}\n", line))
+            .is_ok());
+        assert_eq!(writer.output, format!("{}\n", line));
+
+        // A skipped line leaves nothing behind
+        output.policy = WriteErrorPolicy::SkipLine;
+        writer.output.clear();
+        writer.writes = 0;
+        writer.fail_at = 1;
+        assert!(output
+            .write(&mut writer, format_args!("{}: {}\n", "led", "Sets the LED"))
+            .is_ok());
+        assert!(output.write(&mut writer, format_args!("b\n")).is_ok());
+        assert!(output.take_failed());
+        assert_eq!(writer.output, "b\n");
+
+        // As does the rest of a long line once a chunk was skipped
+        writer.output.clear();
+        writer.writes = 0;
+        assert!(output
+            .write(&mut writer, format_args!("{}\n", line))
+            .is_ok());
+        assert!(output.take_failed());
+        assert_eq!(writer.output, "");
+    }
+}
@@ -0,0 +1,127 @@
+// This module contains temporary command overrides
+use crate::context::Callback;
+use crate::{CommandCallback, CommandProcessor, CommandProcessorError, Status};
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Temporarily replaces the callback of a command
+    ///
+    /// The replaced callback is saved and restored by `pop_override`, so a
+    /// test mode can, for example, turn `reboot` into a no-op and put it back
+    /// afterwards. Overrides of the same command stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to override
+    /// * `callback` - The callback to use until the override is popped
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was overridden
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    /// * `Err(CommandProcessorError::OverrideStackFull)` - If `MAX_OVERRIDES`
+    ///   overrides are already active
+    ///
+    pub fn push_override(
+        &mut self,
+        command: &str,
+        callback: CommandCallback<'a, R>,
+    ) -> Result<(), CommandProcessorError> {
+        let cmd = self
+            .commands
+            .iter_mut()
+            .find(|cmd| cmd.command == command)
+            .ok_or(CommandProcessorError::CommandNotFound)?;
+
+        self.overrides
+            .push((cmd.command.clone(), cmd.callback))
+            .map_err(|_| CommandProcessorError::OverrideStackFull)?;
+        cmd.callback = Callback::Plain(callback);
+
+        Ok(())
+    }
+
+    /// Restores the callback replaced by the latest override of a command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The overridden command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the previous callback was restored
+    /// * `Err(CommandProcessorError::NotOverridden)` - If the command has no active override
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was removed
+    ///   meanwhile, the override is discarded
+    ///
+    pub fn pop_override(&mut self, command: &str) -> Result<(), CommandProcessorError> {
+        let index = self
+            .overrides
+            .iter()
+            .rposition(|(name, _)| *name == command)
+            .ok_or(CommandProcessorError::NotOverridden)?;
+        let (_, callback) = self.overrides.remove(index);
+
+        self.find_command_mut(command)?.callback = callback;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, ReturnCode,
+        MAX_OVERRIDES,
+    };
+    use core::fmt::Write;
+
+    fn reboot<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Failure)
+    }
+
+    fn noop<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command("reboot", reboot, None)
+            .is_ok());
+        assert!(matches!(
+            command_processor.pop_override("reboot"),
+            Err(CommandProcessorError::NotOverridden)
+        ));
+
+        for _ in 0..MAX_OVERRIDES {
+            assert!(command_processor.push_override("reboot", noop).is_ok());
+        }
+        assert!(matches!(
+            command_processor.push_override("reboot", noop),
+            Err(CommandProcessorError::OverrideStackFull)
+        ));
+        assert!(matches!(
+            command_processor.process_command("reboot", None),
+            Ok(ReturnCode::Success)
+        ));
+
+        for _ in 0..MAX_OVERRIDES {
+            assert!(command_processor.pop_override("reboot").is_ok());
+        }
+        assert!(matches!(
+            command_processor.process_command("reboot", None),
+            Ok(ReturnCode::Failure)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+// This module contains the prompt of the interactive front-end
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+use core::fmt::Write;
+
+/// Prompt rendering callback type
+///
+/// Writes a prompt that depends on runtime information, such as the current
+/// mode or an error count.
+pub type PromptCallback = fn(&mut dyn Write) -> core::fmt::Result;
+
+/// The prompt written before each line typed on a terminal
+#[derive(Clone, Copy)]
+pub enum Prompt<'a> {
+    /// A fixed prompt, such as `"> "`
+    Text(&'a str),
+    /// A prompt rendered by a callback
+    Callback(PromptCallback),
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets the prompt written after each line received through `feed`
+    ///
+    /// The prompt follows the report of the line, and is written again when
+    /// the line is redrawn, such as after listing completions. Write it once
+    /// with `write_prompt` when the terminal connects, so the first line gets
+    /// one too. There is no prompt by default, as machines don't want one.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The prompt, or `None` to remove it
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, Prompt};
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_prompt(Some(Prompt::Text("> ")));
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.write_prompt(&mut writer).unwrap();
+    /// command_processor.feed(b"nothing\n", &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "> ERR: command not found\n> ");
+    /// ```
+    ///
+    pub fn set_prompt(&mut self, prompt: Option<Prompt<'a>>) {
+        self.prompt = prompt;
+    }
+
+    /// Writes the prompt, if any, see `set_prompt`
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer of the terminal
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the prompt was written successfully, or there is none
+    /// * `Err(CommandProcessorError::WriteError)` - If the prompt failed to write
+    ///
+    pub fn write_prompt(&self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        match self.prompt {
+            Some(Prompt::Text(text)) => writer.write_str(text),
+            Some(Prompt::Callback(callback)) => callback(writer),
+            None => Ok(()),
+        }
+        .map_err(|_| CommandProcessorError::WriteError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hello;
+
+    fn mode(writer: &mut dyn Write) -> core::fmt::Result {
+        write!(writer, "[idle]$ ")
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("hello", hello, None).is_ok());
+        assert!(command_processor.add_command("help2", hello, None).is_ok());
+        command_processor.set_prompt(Some(Prompt::Callback(mode)));
+
+        // Empty lines get a new prompt
+        let mut writer = std::string::String::new();
+        assert!(command_processor.feed(b"hello\n\n", &mut writer).is_ok());
+        assert_eq!(writer, "hi\nOK\n[idle]$ [idle]$ ");
+
+        // Listed completions get a redrawn one
+        #[cfg(feature = "completion")]
+        {
+            writer.clear();
+            assert!(command_processor.feed(b"hel\t\x18", &mut writer).is_ok());
+            assert_eq!(writer, "\nhello  help2  \n[idle]$ hel");
+        }
+    }
+}
@@ -0,0 +1,381 @@
+// This module contains the queue connecting a receive interrupt to the processor
+use crate::input::CANCEL;
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::Write;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use heapless::spsc::{Consumer, Producer, Queue};
+
+/// A lock-free queue carrying received bytes from an interrupt to the processor
+///
+/// The queue is split into a `LineProducer`, fed byte by byte from the
+/// receive interrupt, and a `LineConsumer`, drained into the processor from
+/// thread mode. Neither half needs a critical section.
+///
+/// When the queue is full, the rest of the line being received is dropped and
+/// counted, and a `CANCEL` byte takes the place of its line ending, so the
+/// processor discards the partial line instead of running a truncated command.
+///
+/// The queue holds `N - 1` bytes.
+///
+/// # Example
+///
+/// ```
+/// use core::fmt::Write;
+/// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, LineQueue, ReturnCode};
+///
+/// fn reset<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// let mut queue: LineQueue<64> = LineQueue::new();
+/// let (mut producer, mut consumer) = queue.split();
+///
+/// // In the receive interrupt
+/// for &byte in b"reset\n" {
+///     producer.push(byte);
+/// }
+///
+/// // In thread mode
+/// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+/// command_processor.add_command("reset", reset, None).unwrap();
+/// let mut writer = String::new();
+/// consumer.pump(&mut command_processor, &mut writer).unwrap();
+///
+/// assert_eq!(writer, "OK\n");
+/// ```
+///
+pub struct LineQueue<const N: usize> {
+    queue: Queue<u8, N>,
+    dropped: AtomicUsize,
+}
+
+/// The interrupt half of a `LineQueue`
+pub struct LineProducer<'q, const N: usize> {
+    producer: Producer<'q, u8, N>,
+    dropped: &'q AtomicUsize,
+    lost: bool,
+    cancel_pending: bool,
+}
+
+// The halves are moved into the tasks running them, see `CommandProcessor::split`
+const _: fn() = || {
+    fn assert_send<T: Send>() {}
+    assert_send::<LineProducer<'static, 1>>();
+    assert_send::<CommandConsumer<'static, 'static, 1, 1, 1, 32, 32>>();
+};
+
+/// The thread mode half of a `LineQueue`
+pub struct LineConsumer<'q, const N: usize> {
+    consumer: Consumer<'q, u8, N>,
+    dropped: &'q AtomicUsize,
+}
+
+impl<const N: usize> Default for LineQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LineQueue<N> {
+    pub const fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Splits the queue into its producer and consumer halves
+    pub fn split(&mut self) -> (LineProducer<'_, N>, LineConsumer<'_, N>) {
+        let (producer, consumer) = self.queue.split();
+
+        (
+            LineProducer {
+                producer,
+                dropped: &self.dropped,
+                lost: false,
+                cancel_pending: false,
+            },
+            LineConsumer {
+                consumer,
+                dropped: &self.dropped,
+            },
+        )
+    }
+}
+
+impl<const N: usize> LineProducer<'_, N> {
+    /// Queues a received byte, never blocks
+    ///
+    /// # Arguments
+    ///
+    /// * `byte` - The received byte
+    ///
+    pub fn push(&mut self, byte: u8) {
+        let end_of_line = matches!(byte, b'\r' | b'\n');
+
+        // A lost line must be cancelled before anything else is queued
+        if self.cancel_pending {
+            if self.producer.enqueue(CANCEL).is_err() {
+                self.lost |= !end_of_line;
+                self.count_dropped();
+                return;
+            }
+            self.cancel_pending = false;
+        }
+
+        if self.lost {
+            // Drop the rest of the line, its line ending becomes a cancel
+            match end_of_line {
+                true => {
+                    self.lost = false;
+                    self.cancel_pending = self.producer.enqueue(CANCEL).is_err();
+                }
+                false => self.count_dropped(),
+            }
+        } else if self.producer.enqueue(byte).is_err() {
+            self.lost = !end_of_line;
+            self.cancel_pending = end_of_line;
+            self.count_dropped();
+        }
+    }
+
+    fn count_dropped(&self) {
+        // The producer is the only writer, so this doesn't need a
+        // read-modify-write atomic, which some cores lack
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        self.dropped
+            .store(dropped.wrapping_add(1), Ordering::Relaxed);
+    }
+}
+
+impl<'q, const N: usize> LineConsumer<'q, N> {
+    /// Returns the number of bytes dropped because the queue was full
+    ///
+    /// The count wraps around on overflow.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Removes the oldest byte from the queue
+    pub fn dequeue(&mut self) -> Option<u8> {
+        self.consumer.dequeue()
+    }
+
+    /// Feeds every queued byte to a command processor
+    ///
+    /// # Arguments
+    ///
+    /// * `command_processor` - The command processor to feed
+    /// * `writer` - The writer for command output and reports
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the queue was drained
+    /// * `Err(CommandProcessorError::WriteError)` - If writing a report failed
+    ///
+    pub fn pump<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    >(
+        &mut self,
+        command_processor: &mut CommandProcessor<
+            'a,
+            NUM_COMMANDS,
+            HELP_STR_SIZE,
+            NAME_SIZE,
+            LINE_SIZE,
+            C,
+            R,
+        >,
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        while let Some(byte) = self.consumer.dequeue() {
+            command_processor.feed(&[byte], writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The thread mode half of a command processor split with a `LineQueue`
+///
+/// Owns the processor, so the only state shared with the receive interrupt is
+/// the lock-free queue. See `CommandProcessor::split`.
+pub struct CommandConsumer<
+    'q,
+    'a,
+    const N: usize,
+    const NUM_COMMANDS: usize,
+    const HELP_STR_SIZE: usize,
+    const NAME_SIZE: usize,
+    const LINE_SIZE: usize,
+    C = (),
+    R = ReturnCode,
+> {
+    consumer: LineConsumer<'q, N>,
+    command_processor:
+        CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Splits the processor into an interrupt half and a thread mode half
+    ///
+    /// The `LineProducer` is fed from the receive interrupt, at any priority,
+    /// and the `CommandConsumer` runs the commands from a lower priority task.
+    /// The halves share nothing but the lock-free queue, so with RTIC each
+    /// half can be a task local resource.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` - The queue connecting the halves
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, LineQueue, ReturnCode};
+    ///
+    /// fn reset<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut queue: LineQueue<64> = LineQueue::new();
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("reset", reset, None).unwrap();
+    /// let (mut producer, mut consumer) = command_processor.split(&mut queue);
+    ///
+    /// // In the receive interrupt
+    /// for &byte in b"reset\n" {
+    ///     producer.push(byte);
+    /// }
+    ///
+    /// // In the idle task
+    /// let mut writer = String::new();
+    /// consumer.poll(&mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "OK\n");
+    /// ```
+    ///
+    pub fn split<'q, const N: usize>(
+        self,
+        queue: &'q mut LineQueue<N>,
+    ) -> (
+        LineProducer<'q, N>,
+        CommandConsumer<'q, 'a, N, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>,
+    ) {
+        let (producer, consumer) = queue.split();
+
+        (
+            producer,
+            CommandConsumer {
+                consumer,
+                command_processor: self,
+            },
+        )
+    }
+}
+
+impl<
+        'a,
+        const N: usize,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandConsumer<'_, 'a, N, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Runs the commands received so far
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer for command output and reports
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the queue was drained
+    /// * `Err(CommandProcessorError::WriteError)` - If writing a report failed
+    ///
+    pub fn poll(&mut self, writer: &mut (dyn Write + 'a)) -> Result<(), CommandProcessorError> {
+        self.consumer.pump(&mut self.command_processor, writer)
+    }
+
+    /// Returns the number of bytes dropped because the queue was full
+    pub fn dropped(&self) -> usize {
+        self.consumer.dropped()
+    }
+
+    /// Borrows the processor, to configure it after splitting
+    pub fn command_processor(
+        &mut self,
+    ) -> &mut CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R> {
+        &mut self.command_processor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hello;
+
+    #[test]
+    fn test_line_queue_overflow() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("hello", hello, None).is_ok());
+
+        let mut queue: LineQueue<8> = LineQueue::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut writer = std::string::String::new();
+
+        // "hellohello\n" doesn't fit, the line is cancelled
+        for &byte in b"hellohello\n" {
+            producer.push(byte);
+        }
+        assert_eq!(consumer.dropped(), 3);
+        assert!(consumer.pump(&mut command_processor, &mut writer).is_ok());
+        assert_eq!(writer, "");
+
+        for &byte in b"hello\n" {
+            producer.push(byte);
+        }
+        assert!(consumer.pump(&mut command_processor, &mut writer).is_ok());
+        assert_eq!(writer, "hi\nOK\n");
+    }
+
+    #[test]
+    fn test_split() {
+        let mut queue: LineQueue<16> = LineQueue::new();
+        let command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        let (mut producer, mut consumer) = command_processor.split(&mut queue);
+
+        assert!(consumer
+            .command_processor()
+            .add_command("hello", hello, None)
+            .is_ok());
+
+        for &byte in b"hello\nnope\n" {
+            producer.push(byte);
+        }
+
+        let mut writer = std::string::String::new();
+        assert!(consumer.poll(&mut writer).is_ok());
+        assert_eq!(writer, "hi\nOK\nERR: command not found\n");
+        assert_eq!(consumer.dropped(), 0);
+    }
+}
@@ -0,0 +1,517 @@
+// This module contains the register access helpers for memory and driver commands
+use crate::args::parse_int;
+use crate::CommandProcessorError;
+
+use core::fmt;
+use core::str::FromStr;
+
+/// The width of a register access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width {
+    U8,
+    U16,
+    U32,
+}
+
+impl Width {
+    /// Returns the number of bytes of an access
+    pub fn bytes(self) -> usize {
+        match self {
+            Width::U8 => 1,
+            Width::U16 => 2,
+            Width::U32 => 4,
+        }
+    }
+
+    /// Returns the largest value of the width
+    fn max(self) -> u32 {
+        match self {
+            Width::U8 => u32::from(u8::MAX),
+            Width::U16 => u32::from(u16::MAX),
+            Width::U32 => u32::MAX,
+        }
+    }
+}
+
+impl FromStr for Width {
+    type Err = CommandProcessorError;
+
+    /// Parses a width in bits, `8`, `16` or `32`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Width::U8),
+            "16" => Ok(Width::U16),
+            "32" => Ok(Width::U32),
+            _ => Err(CommandProcessorError::InvalidArguments),
+        }
+    }
+}
+
+/// The byte order of a register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl FromStr for Endian {
+    type Err = CommandProcessorError;
+
+    /// Parses a byte order, `le` or `be`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "le" => Ok(Endian::Little),
+            "be" => Ok(Endian::Big),
+            _ => Err(CommandProcessorError::InvalidArguments),
+        }
+    }
+}
+
+/// A range of addresses memory commands may access, see `check_access`
+///
+/// Debug command packs can be shipped in production builds, behind an
+/// access level, if their `mem` and `flash` commands only reach the
+/// peripherals and memory listed by the application, such as a sensor's
+/// registers and a log region, and not the key storage or the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressRange {
+    start: usize,
+    end: usize,
+    writable: bool,
+}
+
+impl AddressRange {
+    /// Allows reading the addresses from `start` to `end`, excluded
+    ///
+    /// # Safety
+    ///
+    /// Every aligned 8, 16 and 32-bit read in the range must be valid, and
+    /// free of side effects that would break the code owning the range.
+    pub const unsafe fn read_only(start: usize, end: usize) -> Self {
+        AddressRange {
+            start,
+            end,
+            writable: false,
+        }
+    }
+
+    /// Allows reading and writing the addresses from `start` to `end`, excluded
+    ///
+    /// # Safety
+    ///
+    /// Every aligned 8, 16 and 32-bit read and write in the range must be
+    /// valid, and free of side effects that would break the code owning the
+    /// range.
+    pub const unsafe fn read_write(start: usize, end: usize) -> Self {
+        AddressRange {
+            start,
+            end,
+            writable: true,
+        }
+    }
+
+    /// Returns whether the `len` bytes from `address` are in the range
+    fn contains(&self, address: usize, len: usize) -> bool {
+        address >= self.start && address.checked_add(len).is_some_and(|end| end <= self.end)
+    }
+}
+
+/// Checks that an access of `len` bytes from `address` is allowed
+///
+/// The access must fit in a single range, and the range must be writable
+/// for writes.
+///
+/// # Arguments
+///
+/// * `ranges` - The allowed ranges
+/// * `address` - The first address accessed
+/// * `len` - The number of bytes accessed
+/// * `write` - Whether the access is a write
+///
+/// # Returns
+///
+/// * `Ok(())` - If the access is allowed
+/// * `Err(CommandProcessorError::PermissionDenied)` - If the access is outside the ranges
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{check_access, AddressRange};
+///
+/// // The flash log region, which the debug pack's `flash` command may erase
+/// static FLASH_RANGES: [AddressRange; 1] =
+///     [unsafe { AddressRange::read_write(0x0807_0000, 0x0808_0000) }];
+///
+/// assert!(check_access(&FLASH_RANGES, 0x0807_f000, 0x1000, true).is_ok());
+/// assert!(check_access(&FLASH_RANGES, 0x0807_f000, 0x2000, true).is_err());
+/// assert!(check_access(&FLASH_RANGES, 0x0800_0000, 4, false).is_err());
+/// ```
+///
+pub fn check_access(
+    ranges: &[AddressRange],
+    address: usize,
+    len: usize,
+    write: bool,
+) -> Result<(), CommandProcessorError> {
+    match ranges
+        .iter()
+        .any(|range| range.contains(address, len) && (range.writable || !write))
+    {
+        true => Ok(()),
+        false => Err(CommandProcessorError::PermissionDenied),
+    }
+}
+
+/// A register value with the width of its access
+///
+/// Memory commands written by hand tend to read 32 bits where 8 were asked
+/// for, to write values that don't fit the register, to fault on unaligned
+/// addresses, or to show the bytes of a big-endian peripheral backwards.
+/// `Register` does the access with the given width and byte order only,
+/// checks the value and the alignment first, and formats the value with as
+/// many digits as the register has, such as `0x00ff`.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{Endian, Register, Width};
+///
+/// // A big-endian register of a network peripheral
+/// let mut peripheral = [0u8; 4];
+/// let address = peripheral.as_mut_ptr() as usize;
+///
+/// let value = Register::parse(Width::U16, "0x1234").unwrap();
+/// unsafe { value.write(address, Endian::Big) }.unwrap();
+/// assert_eq!(peripheral[..2], [0x12, 0x34]);
+///
+/// let read = unsafe { Register::read(address, Width::U16, Endian::Big) }.unwrap();
+/// assert_eq!(format!("{}", read), "0x1234");
+/// assert_eq!(format!("{}", read.in_memory(Endian::Little)), "34 12");
+/// assert!(Register::parse(Width::U8, "0x100").is_err());
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    width: Width,
+    value: u32,
+}
+
+impl Register {
+    /// Creates a register value
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the register
+    /// * `value` - The value
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Register)` - The register value
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the value doesn't fit the width
+    ///
+    pub fn new(width: Width, value: u32) -> Result<Self, CommandProcessorError> {
+        match value <= width.max() {
+            true => Ok(Register { width, value }),
+            false => Err(CommandProcessorError::InvalidArguments),
+        }
+    }
+
+    /// Parses a register value from an integer literal, see `parse_int`
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the register
+    /// * `literal` - The value, such as `0x1f` or `255`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Register)` - The register value
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the literal is invalid or doesn't fit the width
+    ///
+    pub fn parse(width: Width, literal: &str) -> Result<Self, CommandProcessorError> {
+        let value = parse_int(literal).ok_or(CommandProcessorError::InvalidArguments)?;
+        Self::new(width, value)
+    }
+
+    /// Returns the width of the register
+    pub fn width(self) -> Width {
+        self.width
+    }
+
+    /// Returns the value of the register
+    pub fn value(self) -> u32 {
+        self.value
+    }
+
+    /// Reads a register with a single volatile access of its width
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the register
+    /// * `width` - The width of the register
+    /// * `endian` - The byte order of the register
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Register)` - The value read
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the address isn't aligned to the width
+    ///
+    /// # Safety
+    ///
+    /// The address must be readable with an access of the width, and
+    /// reading it must not break the invariants of the code owning it.
+    pub unsafe fn read(
+        address: usize,
+        width: Width,
+        endian: Endian,
+    ) -> Result<Self, CommandProcessorError> {
+        // SAFETY: the pointers are aligned, and the caller guarantees the
+        // access is valid.
+        let value = match width {
+            Width::U8 => u32::from(core::ptr::read_volatile(Self::pointer::<u8>(address)?)),
+            Width::U16 => {
+                let raw = core::ptr::read_volatile(Self::pointer::<u16>(address)?);
+                u32::from(match endian {
+                    Endian::Little => u16::from_le(raw),
+                    Endian::Big => u16::from_be(raw),
+                })
+            }
+            Width::U32 => {
+                let raw = core::ptr::read_volatile(Self::pointer::<u32>(address)?);
+                match endian {
+                    Endian::Little => u32::from_le(raw),
+                    Endian::Big => u32::from_be(raw),
+                }
+            }
+        };
+        Ok(Register { width, value })
+    }
+
+    /// Writes the value to a register with a single volatile access of its width
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the register
+    /// * `endian` - The byte order of the register
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the value was written
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the address isn't aligned to the width
+    ///
+    /// # Safety
+    ///
+    /// The address must be writable with an access of the width, and
+    /// writing it must not break the invariants of the code owning it.
+    pub unsafe fn write(self, address: usize, endian: Endian) -> Result<(), CommandProcessorError> {
+        // SAFETY: the pointers are aligned, the values fit their width, and
+        // the caller guarantees the access is valid.
+        match self.width {
+            Width::U8 => core::ptr::write_volatile(
+                Self::pointer::<u8>(address)?.cast_mut(),
+                self.value as u8,
+            ),
+            Width::U16 => {
+                let value = self.value as u16;
+                let raw = match endian {
+                    Endian::Little => value.to_le(),
+                    Endian::Big => value.to_be(),
+                };
+                core::ptr::write_volatile(Self::pointer::<u16>(address)?.cast_mut(), raw)
+            }
+            Width::U32 => {
+                let raw = match endian {
+                    Endian::Little => self.value.to_le(),
+                    Endian::Big => self.value.to_be(),
+                };
+                core::ptr::write_volatile(Self::pointer::<u32>(address)?.cast_mut(), raw)
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a register, if the allowed ranges contain it, see `check_access`
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The allowed ranges
+    /// * `address` - The address of the register
+    /// * `width` - The width of the register
+    /// * `endian` - The byte order of the register
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Register)` - The value read
+    /// * `Err(CommandProcessorError::PermissionDenied)` - If the register is outside the ranges
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the address isn't aligned to the width
+    ///
+    pub fn read_allowed(
+        ranges: &[AddressRange],
+        address: usize,
+        width: Width,
+        endian: Endian,
+    ) -> Result<Self, CommandProcessorError> {
+        check_access(ranges, address, width.bytes(), false)?;
+        // SAFETY: creating the range guaranteed reads in it are valid
+        unsafe { Self::read(address, width, endian) }
+    }
+
+    /// Writes the value to a register, if the allowed ranges contain it, see `check_access`
+    ///
+    /// # Arguments
+    ///
+    /// * `ranges` - The allowed ranges
+    /// * `address` - The address of the register
+    /// * `endian` - The byte order of the register
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the value was written
+    /// * `Err(CommandProcessorError::PermissionDenied)` - If the register is outside the writable ranges
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the address isn't aligned to the width
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{AddressRange, Endian, Register, Width};
+    ///
+    /// let mut registers = [0u32; 2];
+    /// let start = registers.as_mut_ptr() as usize;
+    /// let ranges = [
+    ///     unsafe { AddressRange::read_write(start, start + 4) },
+    ///     unsafe { AddressRange::read_only(start + 4, start + 8) },
+    /// ];
+    ///
+    /// let value = Register::parse(Width::U32, "0xcafe").unwrap();
+    /// assert!(value.write_allowed(&ranges, start, Endian::Little).is_ok());
+    /// assert!(value.write_allowed(&ranges, start + 4, Endian::Little).is_err());
+    /// assert!(Register::read_allowed(&ranges, start + 4, Width::U32, Endian::Little).is_ok());
+    /// assert!(Register::read_allowed(&ranges, start + 8, Width::U32, Endian::Little).is_err());
+    /// assert_eq!(registers[0], 0xcafe_u32.to_le());
+    /// ```
+    ///
+    pub fn write_allowed(
+        self,
+        ranges: &[AddressRange],
+        address: usize,
+        endian: Endian,
+    ) -> Result<(), CommandProcessorError> {
+        check_access(ranges, address, self.width.bytes(), true)?;
+        // SAFETY: creating the range guaranteed writes in it are valid
+        unsafe { self.write(address, endian) }
+    }
+
+    /// Returns the bytes of the value in the order they are in memory, such as `78 56 34 12`
+    ///
+    /// # Arguments
+    ///
+    /// * `endian` - The byte order of the register
+    ///
+    pub fn in_memory(self, endian: Endian) -> InMemory {
+        InMemory {
+            register: self,
+            endian,
+        }
+    }
+
+    /// Returns the address as a pointer, if it's aligned for `T`
+    fn pointer<T>(address: usize) -> Result<*const T, CommandProcessorError> {
+        let pointer = address as *const T;
+        match pointer.is_aligned() && !pointer.is_null() {
+            true => Ok(pointer),
+            false => Err(CommandProcessorError::InvalidArguments),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    /// Formats the value in hexadecimal, with two digits per byte of the register
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.width.bytes().saturating_mul(2);
+        write!(f, "0x{:0digits$x}", self.value, digits = digits)
+    }
+}
+
+/// The bytes of a register value in memory order, see `Register::in_memory`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InMemory {
+    register: Register,
+    endian: Endian,
+}
+
+impl fmt::Display for InMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.register.value.to_le_bytes();
+        let bytes = bytes.get(..self.register.width.bytes()).unwrap_or(&[]);
+        let mut separator = "";
+        let mut write = |byte: &u8| {
+            let result = write!(f, "{}{:02x}", separator, byte);
+            separator = " ";
+            result
+        };
+        match self.endian {
+            Endian::Little => bytes.iter().try_for_each(&mut write),
+            Endian::Big => bytes.iter().rev().try_for_each(&mut write),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_access() {
+        let mut memory = [0u32; 2];
+        let address = memory.as_mut_ptr() as usize;
+
+        let value = Register::parse(Width::U32, "0x12345678").unwrap();
+        assert!(unsafe { value.write(address, Endian::Little) }.is_ok());
+        assert_eq!(memory[0], u32::from_le(0x12345678));
+        assert_eq!(
+            format!("{}", value.in_memory(Endian::Little)),
+            "78 56 34 12"
+        );
+        assert_eq!(format!("{}", value.in_memory(Endian::Big)), "12 34 56 78");
+
+        // Narrow reads only touch their bytes, whatever the byte order
+        let byte = unsafe { Register::read(address, Width::U8, Endian::Big) }.unwrap();
+        assert_eq!(format!("{}", byte), "0x78");
+        let half = unsafe { Register::read(address + 2, Width::U16, Endian::Big) }.unwrap();
+        assert_eq!(half.value(), 0x3412);
+
+        assert!(matches!(
+            unsafe { Register::read(address + 1, Width::U16, Endian::Little) },
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+        assert!(Register::parse(Width::U16, "0x10000").is_err());
+        assert!(matches!("16".parse(), Ok(Width::U16)));
+        assert!(matches!("be".parse(), Ok(Endian::Big)));
+    }
+
+    #[test]
+    fn test_address_ranges() {
+        let mut memory = [0u16; 4];
+        let start = memory.as_mut_ptr() as usize;
+        let ranges = [
+            unsafe { AddressRange::read_only(start, start + 4) },
+            unsafe { AddressRange::read_write(start + 4, start + 8) },
+        ];
+
+        let value = Register::parse(Width::U16, "0x1234").unwrap();
+        assert!(value.write_allowed(&ranges, start + 4, Endian::Big).is_ok());
+        assert!(matches!(
+            value.write_allowed(&ranges, start, Endian::Big),
+            Err(CommandProcessorError::PermissionDenied)
+        ));
+        assert_eq!(memory, [0, 0, 0x1234_u16.to_be(), 0]);
+
+        // Accesses can't straddle two ranges, nor wrap around
+        assert!(Register::read_allowed(&ranges, start + 2, Width::U16, Endian::Big).is_ok());
+        assert!(matches!(
+            Register::read_allowed(&ranges, start + 2, Width::U32, Endian::Big),
+            Err(CommandProcessorError::PermissionDenied)
+        ));
+        assert!(check_access(&ranges, usize::MAX, 2, false).is_err());
+    }
+}
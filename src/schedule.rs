@@ -0,0 +1,284 @@
+// This module contains the periodic runs of stored scripts
+use crate::stored::{MAX_SCRIPT_SIZE, SCRIPT_NAME_SIZE};
+use crate::{parse_duration, CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The maximum number of scheduled scripts
+pub const MAX_SCHEDULES: usize = 4;
+
+/// The storage key of the schedules, which script names can't start with
+pub(crate) const SCHEDULE_KEY: &str = ".schedule";
+
+/// The size of the schedules once saved, a name and an interval per line
+const SAVED_SIZE: usize = MAX_SCHEDULES * (SCRIPT_NAME_SIZE + 22);
+
+/// A stored script run periodically
+pub(crate) struct Schedule {
+    name: String<SCRIPT_NAME_SIZE>,
+    interval_ms: u64,
+    /// When the script runs next, set at the first `run_schedules`
+    next_ms: Option<u64>,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Loads the schedules saved by `script every`, usually at startup
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the schedules were loaded, or none were saved
+    /// * `Err(CommandProcessorError::StorageUnavailable)` - If no storage was set
+    /// * `Err(CommandProcessorError::StorageError)` - If the storage failed or holds invalid schedules
+    ///
+    pub fn load_schedules(&mut self) -> Result<(), CommandProcessorError> {
+        let mut buffer = [0u8; SAVED_SIZE];
+        let loaded = match self.storage.as_deref() {
+            Some(storage) => storage.load(SCHEDULE_KEY, &mut buffer),
+            None => return Err(CommandProcessorError::StorageUnavailable),
+        };
+        let len = loaded.map_err(|_| CommandProcessorError::StorageError)?;
+        let saved = buffer
+            .get(..len.unwrap_or(0))
+            .and_then(|saved| core::str::from_utf8(saved).ok())
+            .ok_or(CommandProcessorError::StorageError)?;
+
+        self.schedules.clear();
+        for line in saved.lines() {
+            let (name, interval) = line
+                .split_once(' ')
+                .ok_or(CommandProcessorError::StorageError)?;
+            let mut copy = String::new();
+            copy.push_str(name)
+                .map_err(|_| CommandProcessorError::StorageError)?;
+            let schedule = Schedule {
+                name: copy,
+                interval_ms: interval
+                    .parse()
+                    .map_err(|_| CommandProcessorError::StorageError)?,
+                next_ms: None,
+            };
+            self.schedules
+                .push(schedule)
+                .map_err(|_| CommandProcessorError::StorageError)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the scheduled scripts that are due
+    ///
+    /// Called periodically, from the main loop for instance. Each script
+    /// first runs one interval after the first call, then every interval
+    /// after its last run, and writes to the writer like with `script run`.
+    /// Every due script runs even if an earlier one fails. Nothing runs
+    /// while `script save` is recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer the scripts write with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode::Success)` - If every script that ran succeeded
+    /// * `Ok(ReturnCode::Failure)` or `Err` - The result of the first failing script otherwise
+    /// * `Err(CommandProcessorError::ClockUnavailable)` - If no clock was set
+    ///
+    pub fn run_schedules(
+        &mut self,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        if self.schedules.is_empty() || self.recording.is_some() {
+            return Ok(ReturnCode::Success.into());
+        }
+        let now = self
+            .clock
+            .ok_or(CommandProcessorError::ClockUnavailable)?
+            .now_ms();
+
+        self.context = None;
+        let mut first_failure = None;
+        for index in 0..self.schedules.len() {
+            let Some(schedule) = self.schedules.get_mut(index) else {
+                break;
+            };
+            let next_ms = *schedule
+                .next_ms
+                .get_or_insert(now.saturating_add(schedule.interval_ms));
+            if now < next_ms {
+                continue;
+            }
+            schedule.next_ms = Some(now.saturating_add(schedule.interval_ms));
+
+            let name = schedule.name.clone();
+            let result = self.run_stored(&name, writer.as_deref_mut());
+            if !result.as_ref().is_ok_and(Status::is_success) {
+                first_failure = first_failure.or(Some(result));
+            }
+        }
+
+        first_failure.unwrap_or(Ok(ReturnCode::Success.into()))
+    }
+
+    /// Schedules a stored script to run every interval, such as `30s` or `5min`
+    pub(crate) fn schedule(
+        &mut self,
+        name: &str,
+        interval: &str,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let interval_ms = parse_duration(interval)
+            .and_then(|interval| u64::try_from(interval.as_millis()).ok())
+            .filter(|interval_ms| *interval_ms > 0)
+            .ok_or(CommandProcessorError::InvalidArguments)?;
+
+        let mut buffer = [0u8; MAX_SCRIPT_SIZE];
+        let loaded = match self.storage.as_deref() {
+            Some(storage) => storage.load(name, &mut buffer),
+            None => return Err(CommandProcessorError::StorageUnavailable),
+        };
+        loaded
+            .map_err(|_| CommandProcessorError::StorageError)?
+            .ok_or(CommandProcessorError::ScriptNotFound)?;
+
+        // Saved schedules separate the name from the interval with a space
+        let mut copy = String::new();
+        copy.push_str(name)
+            .ok()
+            .filter(|()| !name.contains(' '))
+            .ok_or(CommandProcessorError::InvalidArguments)?;
+        let schedule = Schedule {
+            name: copy,
+            interval_ms,
+            next_ms: None,
+        };
+
+        match self
+            .schedules
+            .iter_mut()
+            .find(|schedule| schedule.name == name)
+        {
+            Some(existing) => *existing = schedule,
+            None => self
+                .schedules
+                .push(schedule)
+                .map_err(|_| CommandProcessorError::TooManySchedules)?,
+        }
+        self.save_schedules()?;
+        Ok(ReturnCode::Success)
+    }
+
+    /// Stops running a stored script periodically, returning whether it was
+    pub(crate) fn cancel_schedule(&mut self, name: &str) -> Result<bool, CommandProcessorError> {
+        let Some(position) = self
+            .schedules
+            .iter()
+            .position(|schedule| schedule.name == name)
+        else {
+            return Ok(false);
+        };
+
+        self.schedules.swap_remove(position);
+        self.save_schedules()?;
+        Ok(true)
+    }
+
+    /// Writes the schedules, after the stored scripts of `script list`
+    pub(crate) fn list_schedules(
+        &self,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        for schedule in &self.schedules {
+            self.output.write(
+                writer,
+                format_args!("{} every {}ms\n", schedule.name, schedule.interval_ms),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn save_schedules(&mut self) -> Result<(), CommandProcessorError> {
+        let mut saved: String<SAVED_SIZE> = String::new();
+        for schedule in &self.schedules {
+            writeln!(saved, "{} {}", schedule.name, schedule.interval_ms)
+                .map_err(|_| CommandProcessorError::StorageError)?;
+        }
+
+        let storage = self
+            .storage
+            .as_deref_mut()
+            .ok_or(CommandProcessorError::StorageUnavailable)?;
+        let result = match saved.is_empty() {
+            true => storage.remove(SCHEDULE_KEY).map(|_| ()),
+            false => storage.store(SCHEDULE_KEY, saved.as_bytes()),
+        };
+        result.map_err(|_| CommandProcessorError::StorageError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stored::tests::Memory;
+    use crate::test_util::TestClock;
+    use crate::{Args, CommandCallbackReturn};
+
+    fn count<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        write!(writer, ".").map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_schedules() {
+        let clock = TestClock::new(1_000);
+        let mut storage = Memory::default();
+        let mut buffer: String<64> = String::new();
+
+        {
+            let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+            assert!(command_processor.add_command("count", count, None).is_ok());
+            command_processor.set_clock(&clock);
+            command_processor.set_storage(&mut storage);
+
+            let mut run = |line: &str| command_processor.process_command(line, None);
+            assert!(run("script save health").is_ok());
+            assert!(run("count").is_ok());
+            assert!(run("end").is_ok());
+            assert!(matches!(
+                run("script every missing 1s"),
+                Err(CommandProcessorError::ScriptNotFound)
+            ));
+            assert!(matches!(
+                run("script every health soon"),
+                Err(CommandProcessorError::InvalidArguments)
+            ));
+            assert!(run("script every health 2s").is_ok());
+
+            // The first run is an interval after the first call
+            for now in [1_000, 2_999, 3_000, 4_000, 5_000] {
+                clock.set(now);
+                assert!(command_processor.run_schedules(Some(&mut buffer)).is_ok());
+            }
+            assert_eq!(buffer, "..");
+
+            // A restart loads the schedule back
+            command_processor.schedules.clear();
+            assert!(command_processor.load_schedules().is_ok());
+            assert!(command_processor.list_schedules(&mut buffer).is_ok());
+            assert!(command_processor
+                .process_command("script cancel health", None)
+                .is_ok());
+        }
+
+        assert_eq!(buffer, "..health every 2000ms\n");
+        assert_eq!(storage.0.len(), 1);
+    }
+}
@@ -0,0 +1,241 @@
+// This module contains the argument schemas declared for commands
+use crate::CommandProcessorError;
+#[cfg(feature = "help")]
+use core::fmt;
+
+use core::str::FromStr;
+
+/// The type of a declared argument
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    /// Any text
+    Str,
+    U8,
+    U16,
+    U32,
+    I32,
+    F32,
+    /// A `--name` flag, which can appear anywhere and isn't counted
+    Flag,
+}
+
+impl ArgKind {
+    /// Returns the name of the type shown in usage lines
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            ArgKind::Str | ArgKind::Flag => None,
+            ArgKind::U8 => Some("u8"),
+            ArgKind::U16 => Some("u16"),
+            ArgKind::U32 => Some("u32"),
+            ArgKind::I32 => Some("i32"),
+            ArgKind::F32 => Some("f32"),
+        }
+    }
+
+    /// Checks that an argument can be parsed as this type
+    fn accepts(self, arg: &str) -> bool {
+        fn parses<T: FromStr>(arg: &str) -> bool {
+            arg.parse::<T>().is_ok()
+        }
+
+        match self {
+            ArgKind::Str | ArgKind::Flag => true,
+            ArgKind::U8 => parses::<u8>(arg),
+            ArgKind::U16 => parses::<u16>(arg),
+            ArgKind::U32 => parses::<u32>(arg),
+            ArgKind::I32 => parses::<i32>(arg),
+            ArgKind::F32 => parses::<f32>(arg),
+        }
+    }
+}
+
+/// A declared argument of a command
+///
+/// A command's schema lists its arguments in order, required ones first.
+/// The processor checks the arguments against it before calling the
+/// command, so callbacks don't have to check the count or the types again.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{ArgKind, ArgSpec};
+///
+/// static SET_PWM: [ArgSpec; 3] = [
+///     ArgSpec::required("channel", ArgKind::U8),
+///     ArgSpec::optional("duty", ArgKind::U8),
+///     ArgSpec::flag("invert"),
+/// ];
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+impl ArgSpec {
+    /// Declares an argument that must be given
+    pub const fn required(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: false,
+        }
+    }
+
+    /// Declares an argument that can be left out
+    pub const fn optional(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            optional: true,
+        }
+    }
+
+    /// Declares a `--name` flag
+    pub const fn flag(name: &'static str) -> Self {
+        Self {
+            name,
+            kind: ArgKind::Flag,
+            optional: true,
+        }
+    }
+}
+
+/// The usage line of a command, synthesized from its schema
+///
+/// Written as `set-pwm <channel:u8> [duty:u8] [--invert]`, with required
+/// arguments in angle brackets, optional ones and flags in square brackets.
+#[cfg(feature = "help")]
+pub(crate) struct Usage<'u> {
+    pub(crate) command: &'u str,
+    pub(crate) schema: &'u [ArgSpec],
+}
+
+#[cfg(feature = "help")]
+impl fmt::Display for Usage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.command)?;
+
+        for spec in self.schema {
+            let (open, close) = match spec.optional {
+                true => ('[', ']'),
+                false => ('<', '>'),
+            };
+            match (spec.kind, spec.kind.name()) {
+                (ArgKind::Flag, _) => write!(f, " [--{}]", spec.name)?,
+                (_, Some(kind)) => write!(f, " {}{}:{}{}", open, spec.name, kind, close)?,
+                (_, None) => write!(f, " {}{}{}", open, spec.name, close)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why arguments don't match a schema
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Mismatch {
+    /// A required argument is missing
+    Missing(&'static str),
+    /// The argument at this position is one too many
+    Extra(usize),
+    /// The argument at this position doesn't have the declared type
+    Invalid(usize, &'static str),
+}
+
+impl Mismatch {
+    /// Returns the error a command fails with
+    pub(crate) fn error(self) -> CommandProcessorError {
+        match self {
+            Mismatch::Missing(_) | Mismatch::Extra(_) => CommandProcessorError::WrongArgumentCount,
+            Mismatch::Invalid(_, _) => CommandProcessorError::InvalidArguments,
+        }
+    }
+}
+
+/// Checks arguments against a schema, an empty schema accepts any arguments
+///
+/// Arguments still written as `$NAME` are only counted, as their values are
+/// only known once the line runs, see `check_script`.
+pub(crate) fn check(schema: &[ArgSpec], args: &[&str]) -> Result<(), Mismatch> {
+    if schema.is_empty() {
+        return Ok(());
+    }
+
+    let is_flag = |arg: &str| {
+        arg.strip_prefix("--").is_some_and(|name| {
+            schema
+                .iter()
+                .any(|spec| spec.kind == ArgKind::Flag && spec.name == name)
+        })
+    };
+    let mut positional = schema.iter().filter(|spec| spec.kind != ArgKind::Flag);
+
+    for (position, arg) in args.iter().enumerate() {
+        if is_flag(arg) {
+            continue;
+        }
+
+        let spec = positional.next().ok_or(Mismatch::Extra(position))?;
+        if !spec.kind.accepts(arg) && !arg.starts_with('$') {
+            return Err(Mismatch::Invalid(position, spec.name));
+        }
+    }
+
+    match positional.find(|spec| !spec.optional) {
+        Some(spec) => Err(Mismatch::Missing(spec.name)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SET_PWM: [ArgSpec; 3] = [
+        ArgSpec::required("channel", ArgKind::U8),
+        ArgSpec::optional("duty", ArgKind::U8),
+        ArgSpec::flag("invert"),
+    ];
+
+    #[test]
+    fn test_check() {
+        assert_eq!(check(&[], &["any", "thing"]), Ok(()));
+        assert_eq!(check(&SET_PWM, &["1"]), Ok(()));
+        assert_eq!(check(&SET_PWM, &["--invert", "1", "50"]), Ok(()));
+        assert_eq!(check(&SET_PWM, &[]), Err(Mismatch::Missing("channel")));
+        assert_eq!(
+            check(&SET_PWM, &["--invert"]),
+            Err(Mismatch::Missing("channel"))
+        );
+        assert_eq!(check(&SET_PWM, &["1", "2", "3"]), Err(Mismatch::Extra(2)));
+        assert_eq!(
+            check(&SET_PWM, &["1", "300"]),
+            Err(Mismatch::Invalid(1, "duty"))
+        );
+        assert_eq!(
+            check(&SET_PWM, &["--fast", "1"]),
+            Err(Mismatch::Invalid(0, "channel"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "help")]
+    fn test_usage() {
+        let usage = Usage {
+            command: "set-pwm",
+            schema: &SET_PWM,
+        };
+        assert_eq!(
+            std::format!("{}", usage),
+            "set-pwm <channel:u8> [duty:u8] [--invert]"
+        );
+
+        let usage = Usage {
+            command: "echo",
+            schema: &[ArgSpec::required("text", ArgKind::Str)],
+        };
+        assert_eq!(std::format!("{}", usage), "echo <text>");
+    }
+}
@@ -0,0 +1,150 @@
+// This module contains the writer used to build text in scratch memory
+use crate::CommandProcessorError;
+
+use core::fmt::{self, Write};
+
+/// Checks that text fit in its buffer, the contract of the `strict-capacity` feature
+///
+/// Returns `error` if the text didn't fit and the feature is enabled, or
+/// `Ok` to carry on with the text truncated.
+pub(crate) fn check_capacity(
+    fits: bool,
+    error: CommandProcessorError,
+) -> Result<(), CommandProcessorError> {
+    match fits || !cfg!(feature = "strict-capacity") {
+        true => Ok(()),
+        false => Err(error),
+    }
+}
+
+/// A writer building text in a byte buffer
+///
+/// The buffer is either borrowed, such as a slice of the scratch buffer, or
+/// owned, such as an array. Used to build responses and capture output in
+/// scratch memory. Text that
+/// doesn't fit is truncated on a character boundary, or fails to write with
+/// the `strict-capacity` feature. Either way the overflow is remembered.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::SliceWriter;
+/// use core::fmt::Write;
+///
+/// let mut buffer = [0u8; 8];
+/// let mut writer = SliceWriter::new(&mut buffer[..]);
+///
+/// write!(writer, "id={}", 42).unwrap();
+///
+/// assert_eq!(writer.as_str(), "id=42");
+/// assert!(!writer.overflowed());
+/// ```
+///
+pub struct SliceWriter<B> {
+    buffer: B,
+    len: usize,
+    overflowed: bool,
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> SliceWriter<B> {
+    /// Creates a writer writing into the start of a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to write into
+    ///
+    pub fn new(buffer: B) -> Self {
+        Self {
+            buffer,
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Returns the buffer, giving it back to its owner
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+
+    /// Returns the text written so far
+    pub fn as_str(&self) -> &str {
+        self.buffer
+            .as_ref()
+            .get(..self.len)
+            .and_then(|written| core::str::from_utf8(written).ok())
+            .unwrap_or("")
+    }
+
+    /// Returns whether any text didn't fit in the buffer
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// Forgets the text written so far
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+
+    fn push(&mut self, s: &str) -> bool {
+        let end = match self.len.checked_add(s.len()) {
+            Some(end) => end,
+            None => return false,
+        };
+
+        match self.buffer.as_mut().get_mut(self.len..end) {
+            Some(space) => {
+                space.copy_from_slice(s.as_bytes());
+                self.len = end;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Write for SliceWriter<B> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.push(s) {
+            return Ok(());
+        }
+
+        self.overflowed = true;
+        check_capacity(false, CommandProcessorError::WriteError).map_err(|_| fmt::Error)?;
+
+        // Keep what fits, on a character boundary
+        let mut encoded = [0u8; 4];
+        for c in s.chars() {
+            if !self.push(c.encode_utf8(&mut encoded)) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_writer_overflow() {
+        let mut buffer = [0u8; 4];
+        let mut writer = SliceWriter::new(&mut buffer[..]);
+
+        assert!(write!(writer, "ab").is_ok());
+        let result = write!(writer, "cé");
+
+        assert!(writer.overflowed());
+        if cfg!(feature = "strict-capacity") {
+            assert!(result.is_err());
+            assert_eq!(writer.as_str(), "ab");
+        } else {
+            assert!(result.is_ok());
+            assert_eq!(writer.as_str(), "abc");
+        }
+
+        writer.clear();
+        assert_eq!(writer.as_str(), "");
+    }
+}
@@ -0,0 +1,540 @@
+// This module contains the control flow built-ins for scripts
+use crate::schema::{self, Mismatch};
+use crate::variables::parse_capture;
+use crate::{
+    parse_int, CommandProcessor, CommandProcessorError, ReturnCode, Status, Tokens, DRY_RUN_FLAG,
+    MAX_ARGS,
+};
+
+use core::fmt::Write;
+use heapless::{String, Vec};
+
+/// The maximum number of attempts of a `retry`
+pub const MAX_RETRIES: u32 = 1000;
+
+/// An error found in a script, with the number of its line, from 1
+#[derive(Debug)]
+pub struct ScriptError {
+    pub line: usize,
+    pub error: CommandProcessorError,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Enables or disables stopping scripts at their first failing line
+    ///
+    /// When enabled, `run_script` stops at the first line that fails or
+    /// returns `ReturnCode::Failure`, and writes its number and the line
+    /// followed by the error report. Otherwise the following lines still run.
+    ///
+    /// # Arguments
+    ///
+    /// * `abort_on_error` - Whether scripts stop at their first failing line
+    ///
+    pub fn set_abort_on_error(&mut self, abort_on_error: bool) {
+        self.abort_on_error = abort_on_error;
+    }
+
+    /// Runs a script, one command per line
+    ///
+    /// Blank lines and comment lines are skipped. Lines have the same 32 byte
+    /// limit as those received through `feed`.
+    ///
+    /// # Arguments
+    ///
+    /// * `script` - The script to run
+    /// * `writer` - The writer the commands write with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode::Success)` - If every line succeeded
+    /// * `Ok(ReturnCode::Failure)` or `Err` - The result of the first failing line otherwise
+    /// * `Err(CommandProcessorError::LineTooLong)` - If a line doesn't fit the line buffer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, CommandProcessorError};
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_abort_on_error(true);
+    ///
+    /// let mut output = heapless::String::<128>::new();
+    /// let result = command_processor.run_script("let a 1\n# setup\nfrobnicate\nlet b 2\n", Some(&mut output));
+    ///
+    /// assert!(matches!(result, Err(CommandProcessorError::CommandNotFound)));
+    /// assert_eq!(output, "script aborted at line 3: frobnicate\nERR: command not found\n");
+    /// assert_eq!(command_processor.variable("b"), None);
+    /// ```
+    ///
+    pub fn run_script(
+        &mut self,
+        script: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        self.context = None;
+        self.run_lines(script, writer)
+    }
+
+    /// Runs the lines of a script, see `run_script`
+    pub(crate) fn run_lines(
+        &mut self,
+        script: &str,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let mut first_failure = None;
+
+        for (index, line) in script.lines().enumerate() {
+            if self.strip_comment(line).trim().is_empty() {
+                continue;
+            }
+
+            let mut buffer: String<32> = String::new();
+            let result = match buffer.push_str(line) {
+                Ok(()) => self.process_line(&buffer, writer.as_deref_mut()),
+                Err(()) => Err(CommandProcessorError::LineTooLong),
+            };
+            if result.as_ref().is_ok_and(Status::is_success) {
+                continue;
+            }
+
+            if !self.abort_on_error {
+                first_failure = first_failure.or(Some(result));
+                continue;
+            }
+
+            if let Some(writer) = writer {
+                self.output.write(
+                    writer,
+                    format_args!(
+                        "{} {}: {}\n",
+                        self.messages.script_aborted,
+                        index.saturating_add(1),
+                        line
+                    ),
+                )?;
+                self.report(&result, writer)?;
+            }
+            return result;
+        }
+
+        first_failure.unwrap_or(Ok(ReturnCode::Success.into()))
+    }
+
+    /// Checks a script without running it
+    ///
+    /// Every line is tokenized and its command looked up, along with the
+    /// commands of `if`, `retry` and `NAME=$(command)` lines. The arguments
+    /// are checked against the command's schema and, in strict mode, its
+    /// maximum number of arguments. `$NAME` arguments are only counted, as
+    /// their values are only known when the script runs. Validators aren't
+    /// called.
+    ///
+    /// # Arguments
+    ///
+    /// * `script` - The script to check
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every line passed the checks
+    /// * `Err(ScriptError)` - The first error, with its line
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, CommandProcessorError};
+    ///
+    /// let command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    ///
+    /// let error = command_processor.check_script("let a 1\nif frobnicate then let b 2\n").unwrap_err();
+    /// assert_eq!(error.line, 2);
+    /// assert!(matches!(error.error, CommandProcessorError::CommandNotFound));
+    /// ```
+    ///
+    pub fn check_script(&self, script: &str) -> Result<(), ScriptError> {
+        for (index, line) in script.lines().enumerate() {
+            let result = match line.len() > 32 {
+                true => Err(CommandProcessorError::LineTooLong),
+                false => self.check_line(line),
+            };
+
+            result.map_err(|error| ScriptError {
+                line: index.saturating_add(1),
+                error,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Checks a line of a script, see `check_script`
+    fn check_line(&self, line: &str) -> Result<(), CommandProcessorError> {
+        let line = self.strip_comment(line);
+        if let Some((_, command)) = parse_capture(line) {
+            return self.check_line(command);
+        }
+
+        let mut path: String<LINE_SIZE> = String::new();
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(line, self.delimiters, &mut buffer);
+        let name = match tokens.next() {
+            Some(name) => self.resolve_alias(name),
+            None => return Ok(()),
+        };
+
+        let registered = self.commands.iter().any(|cmd| cmd.command == name);
+        match name {
+            _ if registered => {}
+            #[cfg(feature = "help")]
+            "help" => return tokens.check(),
+            #[cfg(feature = "tutorial")]
+            "tutorial" => return tokens.check(),
+            "simulate" | "arm" | "let" | "script" | "lock" | "unlock" => return tokens.check(),
+            "status" | "metrics" | "trace" => return tokens.check(),
+            #[cfg(feature = "binary")]
+            "capabilities" => return tokens.check(),
+            "if" => {
+                let (condition, consequence, alternative) = self.parse_if(line)?;
+                self.check_line(condition)?;
+                self.check_line(consequence)?;
+                return alternative.map_or(Ok(()), |alternative| self.check_line(alternative));
+            }
+            "retry" => return self.check_line(self.parse_retry(line)?.1),
+            _ => {}
+        }
+
+        let name = match self.is_group(name) {
+            true => {
+                self.resolve_subcommand(name, &mut tokens, &mut path, None)?;
+                path.as_str()
+            }
+            false => name,
+        };
+
+        let Some(cmd) = self.commands.iter().find(|cmd| cmd.command == name) else {
+            tokens.check()?;
+            return match self.legacy_commands.iter().any(|legacy| legacy.old == name) {
+                true => Ok(()),
+                false => Err(CommandProcessorError::CommandNotFound),
+            };
+        };
+
+        let mut args: Vec<&str, MAX_ARGS> = Vec::new();
+        loop {
+            // The raw tail is a single argument, whatever it holds
+            if cmd.raw_tail == Some(args.len()) {
+                if tokens.next().is_some() {
+                    args.push("")
+                        .map_err(|_| CommandProcessorError::InvalidArguments)?;
+                }
+                break;
+            }
+
+            match tokens.next() {
+                Some(DRY_RUN_FLAG) if cmd.dry_run_callback.is_some() => {}
+                Some(arg) => args
+                    .push(arg)
+                    .map_err(|_| CommandProcessorError::InvalidArguments)?,
+                None => break,
+            }
+        }
+        tokens.check()?;
+
+        if self.strict && cmd.max_args.is_some_and(|max| args.len() > max) {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        schema::check(cmd.schema, &args).map_err(Mismatch::error)
+    }
+
+    /// Runs the if built-in
+    ///
+    /// `if <command> then <command> [else <command>]` runs the first command,
+    /// then the second if it returned `ReturnCode::Success`, or the third
+    /// otherwise. Like in a shell, a condition failing with an error, a
+    /// missing command for instance, counts as not successful. Ifs can't be
+    /// nested.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line, starting with `if`
+    /// * `writer` - The writer the commands write with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode)` - The result of the command run after the condition, success if none
+    /// * `Err(CommandProcessorError::MissingArgument)` - If the condition or a command is missing
+    ///
+    pub(crate) fn if_else(
+        &mut self,
+        line: &str,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let (condition, consequence, alternative) = self.parse_if(line)?;
+
+        let succeeded = self
+            .dispatch(condition, writer.as_deref_mut())
+            .is_ok_and(|code| code.is_success());
+
+        match (succeeded, alternative) {
+            (true, _) => self.dispatch(consequence, writer),
+            (false, Some(alternative)) => self.dispatch(alternative, writer),
+            (false, None) => Ok(ReturnCode::Success.into()),
+        }
+    }
+
+    /// Splits an `if` line into its condition, consequence and alternative
+    fn parse_if<'l>(
+        &self,
+        line: &'l str,
+    ) -> Result<(&'l str, &'l str, Option<&'l str>), CommandProcessorError> {
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(line, self.delimiters, &mut buffer);
+        tokens.next();
+        let start = tokens.offset();
+
+        let mut then = None;
+        let mut otherwise = None;
+        loop {
+            let offset = tokens.offset();
+            // Quoted keywords are arguments
+            let keyword = |keyword: &str| {
+                line.get(offset..)
+                    .is_some_and(|rest| rest.starts_with(keyword))
+            };
+
+            match tokens.next() {
+                Some("then") if then.is_none() && keyword("then") => then = Some(offset),
+                Some("else") if then.is_some() && otherwise.is_none() && keyword("else") => {
+                    otherwise = Some(offset)
+                }
+                Some(_) => {}
+                None => break,
+            }
+        }
+        tokens.check()?;
+
+        let then = then.ok_or(CommandProcessorError::MissingArgument)?;
+        let part = |start: usize, end: Option<usize>| {
+            let part = match end {
+                Some(end) => line.get(start..end),
+                None => line.get(start..),
+            };
+            part.map(str::trim)
+                .filter(|part| !part.is_empty())
+                .ok_or(CommandProcessorError::MissingArgument)
+        };
+
+        let condition = part(start, Some(then))?;
+        let consequence = part(then.saturating_add("then".len()), otherwise)?;
+        let alternative = match otherwise {
+            Some(otherwise) => Some(part(otherwise.saturating_add("else".len()), None)?),
+            None => None,
+        };
+        Ok((condition, consequence, alternative))
+    }
+
+    /// Runs the retry built-in
+    ///
+    /// `retry <n> <command>` runs the command until it returns
+    /// `ReturnCode::Success`, at most `n` times, to poll a status until it
+    /// is ready. `n` can't exceed `MAX_RETRIES`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The line, starting with `retry`
+    /// * `writer` - The writer the command writes with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode::Success)` - If an attempt succeeded
+    /// * `Ok(ReturnCode::Failure)` or `Err` - The result of the last attempt otherwise
+    /// * `Err(CommandProcessorError::MissingArgument)` - If the count or the command is missing
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the count isn't between 1 and `MAX_RETRIES`
+    ///
+    pub(crate) fn retry(
+        &mut self,
+        line: &str,
+        mut writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let (count, command) = self.parse_retry(line)?;
+
+        let mut result = Ok(ReturnCode::Failure.into());
+        for _ in 0..count {
+            result = self.dispatch(command, writer.as_deref_mut());
+            if result.as_ref().is_ok_and(Status::is_success) {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Splits a `retry` line into its count and command
+    fn parse_retry<'l>(&self, line: &'l str) -> Result<(u32, &'l str), CommandProcessorError> {
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(line, self.delimiters, &mut buffer);
+        tokens.next();
+
+        let count = tokens
+            .next()
+            .ok_or(CommandProcessorError::MissingArgument)?;
+        let count = parse_int::<u32>(count)
+            .filter(|count| (1..=MAX_RETRIES).contains(count))
+            .ok_or(CommandProcessorError::InvalidArguments)?;
+        let command = line
+            .get(tokens.offset()..)
+            .map(str::trim)
+            .filter(|command| !command.is_empty())
+            .ok_or(CommandProcessorError::MissingArgument)?;
+        Ok((count, command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ArgKind, ArgSpec, Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError,
+        ReturnCode,
+    };
+    use core::fmt::Write;
+
+    fn done<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Failure)
+    }
+
+    fn echo<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "{}", args.remaining().join(" "))
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_run_script() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("done", done, None).is_ok());
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+
+        let script = "echo a\n\ndone\nmissing\necho b";
+        let mut buffer = std::string::String::new();
+        assert!(matches!(
+            command_processor.run_script(script, Some(&mut buffer)),
+            Ok(ReturnCode::Failure)
+        ));
+        assert_eq!(buffer, "a\nb\n");
+
+        command_processor.set_abort_on_error(true);
+        buffer.clear();
+        assert!(matches!(
+            command_processor.run_script(script, Some(&mut buffer)),
+            Ok(ReturnCode::Failure)
+        ));
+        assert_eq!(buffer, "a\nscript aborted at line 3: done\nERR\n");
+    }
+
+    #[test]
+    fn test_check_script() {
+        static ECHO: [ArgSpec; 1] = [ArgSpec::required("value", ArgKind::U8)];
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("done", done, None).is_ok());
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+        assert!(command_processor.set_arguments("echo", &ECHO).is_ok());
+
+        let check = |script: &str| {
+            command_processor
+                .check_script(script)
+                .map_err(|error| (error.line, error.error))
+        };
+
+        assert!(check("# setup\nlet a 1\nX=$(echo 1)\nretry 3 done\n\necho $a").is_ok());
+        assert!(matches!(
+            check("done\nif done then echo 300"),
+            Err((2, CommandProcessorError::InvalidArguments))
+        ));
+        assert!(matches!(
+            check("done\ndone\necho 1 2"),
+            Err((3, CommandProcessorError::WrongArgumentCount))
+        ));
+        assert!(matches!(
+            check("retry 3 missing"),
+            Err((1, CommandProcessorError::CommandNotFound))
+        ));
+        assert!(matches!(
+            check("echo \"1"),
+            Err((1, CommandProcessorError::UnterminatedQuote))
+        ));
+    }
+
+    #[test]
+    fn test_retry() {
+        fn ready<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writer
+                .write_str(".")
+                .map_err(|_| CommandProcessorError::WriteError)?;
+            READY_POLLS.with(|polls| {
+                polls.set(polls.get() + 1);
+                match polls.get() {
+                    3 => Ok(ReturnCode::Success),
+                    _ => Ok(ReturnCode::Failure),
+                }
+            })
+        }
+
+        std::thread_local! {
+            static READY_POLLS: core::cell::Cell<u32> = const { core::cell::Cell::new(0) };
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("ready", ready, None).is_ok());
+
+        let mut buffer = std::string::String::new();
+        let mut run = |line: &str| command_processor.process_command(line, Some(&mut buffer));
+
+        assert!(matches!(run("retry 2 ready"), Ok(ReturnCode::Failure)));
+        assert!(matches!(run("retry 5 ready"), Ok(ReturnCode::Success)));
+        assert!(matches!(
+            run("retry 0 ready"),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+        assert!(matches!(
+            run("retry 3"),
+            Err(CommandProcessorError::MissingArgument)
+        ));
+        assert!(matches!(
+            run("retry 3 missing"),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+        assert_eq!(buffer, "...");
+    }
+
+    #[test]
+    fn test_if_else() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("done", done, None).is_ok());
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+
+        let mut buffer = std::string::String::new();
+        let mut run = |line: &str| command_processor.process_command(line, Some(&mut buffer));
+
+        assert!(run("if done then echo a else echo b").is_ok());
+        assert!(run("if echo c then echo \"else\" d").is_ok());
+        assert!(run("if missing then echo e").is_ok());
+        assert!(matches!(
+            run("if done echo f"),
+            Err(CommandProcessorError::MissingArgument)
+        ));
+        assert!(matches!(
+            run("if done then echo g else"),
+            Err(CommandProcessorError::MissingArgument)
+        ));
+        assert_eq!(buffer, "b\nc\nelse d\n");
+    }
+}
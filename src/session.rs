@@ -0,0 +1,494 @@
+// This module contains the sessions of transports sharing a processor
+#[cfg(feature = "binary")]
+use crate::binary::{self, BinarySink, BINARY_FRAME_SIZE};
+use crate::editor::Escape;
+#[cfg(feature = "history")]
+use crate::history::Recall;
+#[cfg(feature = "framing")]
+use crate::Checksum;
+#[cfg(feature = "history")]
+use crate::History;
+use crate::{
+    CommandProcessor, CommandProcessorError, LineEnding, Messages, Prompt, SessionInfo, Status,
+    DEFAULT_MESSAGES,
+};
+
+use core::fmt::Write;
+use core::mem::swap;
+use heapless::String;
+#[cfg(feature = "binary")]
+use heapless::Vec;
+
+/// The front-end configuration of a transport
+///
+/// A console on a UART, a BLE link for an app and a CAN bus each want their
+/// own reports, strictness and framing, while running the same commands.
+/// Each transport gets a `Session` with its profile, and they all feed one
+/// processor.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{LineEnding, Profile, DEFAULT_MESSAGES, Messages};
+///
+/// static TERSE: Messages = Messages {
+///     ok: "0",
+///     error: "1",
+///     ..DEFAULT_MESSAGES
+/// };
+///
+/// let ble = Profile {
+///     messages: &TERSE,
+///     strict: true,
+///     line_ending: LineEnding::Lf,
+///     ..Profile::DEFAULT
+/// };
+/// ```
+///
+#[derive(Clone, Copy)]
+pub struct Profile<'a> {
+    /// The message catalog of the reports, see `set_messages`
+    pub messages: &'a Messages,
+    /// Whether extra arguments are rejected, see `set_strict`
+    pub strict: bool,
+    /// The character starting a comment, see `set_comment_char`
+    pub comment: Option<char>,
+    /// The characters separating tokens, see `set_delimiters`
+    pub delimiters: Option<&'a [char]>,
+    /// The checksum of the responses, see `set_response_checksum`
+    #[cfg(feature = "framing")]
+    pub response_checksum: Option<Checksum>,
+    /// The checksum of the lines received, see `set_input_checksum`
+    #[cfg(feature = "framing")]
+    pub input_checksum: Option<Checksum>,
+    /// The session the commands run in, see `set_session_info`
+    pub session: Option<SessionInfo>,
+    /// The prompt of the terminal, see `set_prompt`
+    pub prompt: Option<Prompt<'a>>,
+    /// Whether the characters received are echoed back, see `set_echo`
+    pub echo: bool,
+    /// The bytes ending a line, see `set_line_ending`
+    pub line_ending: LineEnding,
+    /// The number of help lines per page, see `set_help_pager`
+    #[cfg(feature = "help")]
+    pub help_page_lines: Option<usize>,
+    /// The sink of binary responses, see `set_binary_sink`
+    #[cfg(feature = "binary")]
+    pub binary_sink: Option<BinarySink>,
+}
+
+impl Profile<'static> {
+    /// The configuration of a new processor
+    pub const DEFAULT: Self = Self {
+        messages: &DEFAULT_MESSAGES,
+        strict: false,
+        comment: Some('#'),
+        delimiters: None,
+        #[cfg(feature = "framing")]
+        response_checksum: None,
+        #[cfg(feature = "framing")]
+        input_checksum: None,
+        session: None,
+        prompt: None,
+        echo: false,
+        line_ending: LineEnding::Any,
+        #[cfg(feature = "help")]
+        help_page_lines: None,
+        #[cfg(feature = "binary")]
+        binary_sink: None,
+    };
+}
+
+impl Default for Profile<'static> {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A transport feeding a processor shared with other transports
+///
+/// The session holds the transport's profile and its own line buffer, so
+/// lines arriving interleaved on several transports don't mix.
+///
+/// # Arguments
+///
+/// * `LINE_SIZE` - The maximum size of a command line, as for the processor
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{CommandProcessor, Profile, Session};
+///
+/// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+/// let mut uart = Session::new(Profile::DEFAULT);
+/// let mut ble = Session::new(Profile {
+///     comment: None,
+///     ..Profile::DEFAULT
+/// });
+///
+/// let mut console = String::new();
+/// let mut app = String::new();
+/// uart.feed(&mut command_processor, b"unkn", &mut console).unwrap();
+/// ble.feed(&mut command_processor, b"nothing\n", &mut app).unwrap();
+/// uart.feed(&mut command_processor, b"own\n", &mut console).unwrap();
+///
+/// assert_eq!(console, "ERR: command not found\n");
+/// assert_eq!(app, "ERR: command not found\n");
+/// ```
+///
+pub struct Session<'a, const LINE_SIZE: usize = 32> {
+    profile: Profile<'a>,
+    line: String<LINE_SIZE>,
+    line_overflowed: bool,
+    cursor: usize,
+    after_cr: bool,
+    escape: Escape,
+    #[cfg(feature = "history")]
+    history: Option<&'a mut (dyn Recall + Send)>,
+    last_activity_ms: Option<u64>,
+    #[cfg(feature = "help")]
+    paged: Option<(String<LINE_SIZE>, usize)>,
+    #[cfg(feature = "binary")]
+    frame: Vec<u8, BINARY_FRAME_SIZE>,
+    #[cfg(feature = "binary")]
+    frame_state: binary::FrameState,
+}
+
+impl<'a, const LINE_SIZE: usize> Session<'a, LINE_SIZE> {
+    /// Creates a session with an empty line buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - The configuration of the transport
+    ///
+    pub fn new(profile: Profile<'a>) -> Self {
+        Self {
+            profile,
+            line: String::new(),
+            line_overflowed: false,
+            cursor: 0,
+            after_cr: false,
+            escape: Escape::Idle,
+            #[cfg(feature = "history")]
+            history: None,
+            last_activity_ms: None,
+            #[cfg(feature = "help")]
+            paged: None,
+            #[cfg(feature = "binary")]
+            frame: Vec::new(),
+            #[cfg(feature = "binary")]
+            frame_state: binary::FrameState::Text,
+        }
+    }
+
+    /// Returns the configuration of the transport
+    pub fn profile(&self) -> &Profile<'a> {
+        &self.profile
+    }
+
+    /// Sets the history of the transport, see `CommandProcessor::set_history`
+    #[cfg(feature = "history")]
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The history, of any depth
+    ///
+    pub fn set_history<const DEPTH: usize>(&mut self, history: &'a mut History<DEPTH, LINE_SIZE>) {
+        self.history = Some(history);
+    }
+
+    /// Returns when bytes were last fed to the session, see `last_activity_ms`
+    ///
+    /// Sessions whose link went quiet for too long can then be dropped.
+    pub fn last_activity_ms(&self) -> Option<u64> {
+        self.last_activity_ms
+    }
+
+    /// Feeds bytes received on the transport, see `CommandProcessor::feed`
+    ///
+    /// The processor runs with the session's profile and line buffer, and
+    /// gets its own back afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `command_processor` - The processor shared by the transports
+    /// * `bytes` - The received bytes
+    /// * `writer` - The writer of the transport
+    ///
+    /// # Returns
+    ///
+    /// The result of `CommandProcessor::feed`
+    ///
+    pub fn feed<
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        C,
+        R: Status,
+    >(
+        &mut self,
+        command_processor: &mut CommandProcessor<
+            'a,
+            NUM_COMMANDS,
+            HELP_STR_SIZE,
+            NAME_SIZE,
+            LINE_SIZE,
+            C,
+            R,
+        >,
+        bytes: &[u8],
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        self.swap(command_processor);
+        let result = command_processor.feed(bytes, writer);
+        self.swap(command_processor);
+        result
+    }
+
+    /// Exchanges the session's state with the processor's
+    fn swap<
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        C,
+        R: Status,
+    >(
+        &mut self,
+        command_processor: &mut CommandProcessor<
+            'a,
+            NUM_COMMANDS,
+            HELP_STR_SIZE,
+            NAME_SIZE,
+            LINE_SIZE,
+            C,
+            R,
+        >,
+    ) {
+        swap(&mut self.profile.messages, &mut command_processor.messages);
+        swap(&mut self.profile.strict, &mut command_processor.strict);
+        swap(&mut self.profile.comment, &mut command_processor.comment);
+        swap(
+            &mut self.profile.delimiters,
+            &mut command_processor.delimiters,
+        );
+        #[cfg(feature = "framing")]
+        {
+            swap(
+                &mut self.profile.response_checksum,
+                &mut command_processor.response_checksum,
+            );
+            swap(
+                &mut self.profile.input_checksum,
+                &mut command_processor.input_checksum,
+            );
+        }
+        swap(&mut self.profile.session, &mut command_processor.session);
+        swap(&mut self.profile.prompt, &mut command_processor.prompt);
+        swap(&mut self.profile.echo, &mut command_processor.echo);
+        swap(
+            &mut self.profile.line_ending,
+            &mut command_processor.line_ending,
+        );
+        swap(&mut self.line, &mut command_processor.line);
+        swap(&mut self.cursor, &mut command_processor.cursor);
+        swap(&mut self.after_cr, &mut command_processor.after_cr);
+        swap(&mut self.escape, &mut command_processor.escape);
+        #[cfg(feature = "history")]
+        swap(&mut self.history, &mut command_processor.history);
+        swap(
+            &mut self.last_activity_ms,
+            &mut command_processor.last_activity_ms,
+        );
+        swap(
+            &mut self.line_overflowed,
+            &mut command_processor.line_overflowed,
+        );
+
+        // A session at its `--More--` prompt doesn't take the others' keys
+        #[cfg(feature = "help")]
+        {
+            swap(
+                &mut self.profile.help_page_lines,
+                &mut command_processor.help_page_lines,
+            );
+            swap(&mut self.paged, &mut command_processor.paged);
+        }
+
+        #[cfg(feature = "binary")]
+        {
+            swap(
+                &mut self.profile.binary_sink,
+                &mut command_processor.binary_sink,
+            );
+            swap(&mut self.frame, &mut command_processor.frame);
+            swap(&mut self.frame_state, &mut command_processor.frame_state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, Link, ReturnCode};
+
+    static TERSE: Messages = Messages {
+        ok: "0",
+        error: "1",
+        ..DEFAULT_MESSAGES
+    };
+
+    fn ping<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "pong").map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("ping", ping, None).is_ok());
+
+        let mut uart = Session::new(Profile::DEFAULT);
+        let mut ble = Session::new(Profile {
+            messages: &TERSE,
+            strict: true,
+            ..Profile::DEFAULT
+        });
+
+        // Partial lines on one transport don't mix with the other's
+        let mut console = std::string::String::new();
+        let mut app = std::string::String::new();
+        assert!(uart
+            .feed(&mut command_processor, b"pi", &mut console)
+            .is_ok());
+        assert!(ble
+            .feed(&mut command_processor, b"ping\nnope\n", &mut app)
+            .is_ok());
+        assert!(uart
+            .feed(&mut command_processor, b"ng x\n", &mut console)
+            .is_ok());
+
+        assert_eq!(console, "pong\nOK\n");
+        assert_eq!(app, "pong\n0\n1: command not found\n");
+        assert!(ble.profile().strict);
+        assert!(!command_processor.strict);
+    }
+
+    fn count<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "{}", args.remaining().len())
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_session_parsing() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("count", count, None).is_ok());
+
+        let mut uart = Session::new(Profile::DEFAULT);
+        let mut host = Session::new(Profile {
+            delimiters: Some(&[',']),
+            #[cfg(feature = "framing")]
+            input_checksum: Some(crate::Checksum::Xor),
+            ..Profile::DEFAULT
+        });
+
+        // Each session splits and verifies lines its own way
+        let line = "count,a b,c";
+        #[cfg(feature = "framing")]
+        let line = format!(
+            "{}*{:02X}",
+            line,
+            crate::Checksum::Xor.compute(line.as_bytes())
+        );
+        let mut console = std::string::String::new();
+        let mut bus = std::string::String::new();
+        assert!(host
+            .feed(
+                &mut command_processor,
+                format!("{}\n", line).as_bytes(),
+                &mut bus
+            )
+            .is_ok());
+        assert!(uart
+            .feed(&mut command_processor, b"count a,b c\n", &mut console)
+            .is_ok());
+
+        assert_eq!(bus, "2\nOK\n");
+        assert_eq!(console, "2\nOK\n");
+        assert!(command_processor.delimiters.is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "help")]
+    fn test_session_pager() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        for name in ["led", "ping", "reboot"] {
+            assert!(command_processor
+                .add_command(name, ping, Some(String::from(name)))
+                .is_ok());
+        }
+
+        let mut uart = Session::new(Profile {
+            help_page_lines: Some(2),
+            ..Profile::DEFAULT
+        });
+        let mut ble = Session::new(Profile::DEFAULT);
+
+        let mut console = std::string::String::new();
+        let mut app = std::string::String::new();
+        assert!(uart
+            .feed(&mut command_processor, b"help\n", &mut console)
+            .is_ok());
+        assert_eq!(console, "led\nping\n--More--");
+
+        // The other session's lines aren't keys of the pager
+        assert!(ble.feed(&mut command_processor, b"led\n", &mut app).is_ok());
+        assert_eq!(app, "pong\nOK\n");
+
+        console.clear();
+        assert!(uart
+            .feed(&mut command_processor, b" ", &mut console)
+            .is_ok());
+        assert_eq!(console, "\r\x1b[Kreboot\nOK\n");
+    }
+
+    fn whoami<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        let session = args.session().ok_or(CommandProcessorError::NoContext)?;
+        writeln!(writer, "{} {:?}", session.id, session.link)
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_session_info() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("whoami", whoami, None)
+            .is_ok());
+
+        let mut uart = Session::new(Profile {
+            session: Some(SessionInfo::new(0, Link::Serial)),
+            ..Profile::DEFAULT
+        });
+        let mut can = Session::new(Profile {
+            session: Some(SessionInfo::new(1, Link::Can)),
+            ..Profile::DEFAULT
+        });
+
+        let mut console = std::string::String::new();
+        let mut bus = std::string::String::new();
+        assert!(uart
+            .feed(&mut command_processor, b"whoami\n", &mut console)
+            .is_ok());
+        assert!(can
+            .feed(&mut command_processor, b"whoami\n", &mut bus)
+            .is_ok());
+
+        assert_eq!(console, "0 Serial\nOK\n");
+        assert_eq!(bus, "1 Can\nOK\n");
+        assert!(command_processor.session_info().is_none());
+    }
+}
@@ -0,0 +1,87 @@
+// This module contains the description of the session a command runs in
+
+/// The kind of link a session runs over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// A UART, or a USB CDC port
+    Serial,
+    /// A Bluetooth Low Energy link
+    Ble,
+    /// A CAN bus
+    Can,
+    /// A TCP or UDP connection
+    Network,
+    /// Any other link, numbered by the application
+    Other(u8),
+}
+
+/// The session a command runs in, as seen by its callback
+///
+/// Set with `set_session_info`, or through the profile of a `Session`, and
+/// read by callbacks with `Args::session`, so commands can tailor their
+/// output to the link and record who ran them.
+///
+/// # Example
+///
+/// ```
+/// use core::fmt::Write;
+/// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, Link, ReturnCode, SessionInfo};
+///
+/// fn status<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     let Some(writer) = writer else {
+///         return Ok(ReturnCode::Success);
+///     };
+///     // No colors for machines
+///     let _ = match args.session().is_some_and(SessionInfo::is_terminal) {
+///         true => writeln!(writer, "\x1b[32mready\x1b[0m"),
+///         false => writeln!(writer, "ready"),
+///     };
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+/// command_processor.add_command("status", status, None).unwrap();
+/// command_processor.set_session_info(Some(SessionInfo {
+///     terminal_size: None,
+///     ..SessionInfo::new(1, Link::Ble)
+/// }));
+///
+/// let mut writer = String::new();
+/// command_processor.process_command("status", Some(&mut writer)).unwrap();
+/// assert_eq!(writer, "ready\n");
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// The identifier of the session, chosen by the application
+    pub id: u8,
+    /// The link the session runs over
+    pub link: Link,
+    /// The access level of the session, higher levels being more privileged
+    pub access_level: u8,
+    /// The columns and rows of the terminal, `None` for machine sessions
+    pub terminal_size: Option<(u16, u16)>,
+}
+
+impl SessionInfo {
+    /// Describes an unprivileged session on an 80x24 terminal
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier of the session
+    /// * `link` - The link the session runs over
+    ///
+    pub const fn new(id: u8, link: Link) -> Self {
+        Self {
+            id,
+            link,
+            access_level: 0,
+            terminal_size: Some((80, 24)),
+        }
+    }
+
+    /// Returns whether a terminal is attached, meaning a person reads the output
+    pub fn is_terminal(&self) -> bool {
+        self.terminal_size.is_some()
+    }
+}
@@ -0,0 +1,184 @@
+// This module contains shell completion script generation for host tools
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+use core::fmt::Write;
+
+/// Commands implemented by the processor itself
+const BUILTIN_COMMANDS: [&str; 4] = ["arm", "help", "simulate", "tutorial"];
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Writes a bash completion script completing the registered command names
+    ///
+    /// Intended to be run on the host with the same command table that is
+    /// compiled for the device, so a host wrapper tool gets completion that
+    /// matches the firmware.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The name of the host tool to complete for
+    /// * `writer` - The writer to write the script to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the script was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the script failed to write
+    ///
+    pub fn write_bash_completion(
+        &self,
+        program: &str,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        let function = function_name(program);
+
+        writeln!(writer, "{}() {{", function)
+            .and_then(|_| writeln!(writer, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\""))
+            .and_then(|_| writeln!(writer, "    if [ \"$COMP_CWORD\" -eq 1 ]; then"))
+            .and_then(|_| write!(writer, "        COMPREPLY=($(compgen -W \""))
+            .map_err(|_| CommandProcessorError::WriteError)?;
+
+        let mut separator = "";
+        let names = BUILTIN_COMMANDS.into_iter().chain(
+            self.sorted_commands()
+                .filter(|cmd| !cmd.hidden)
+                .map(|cmd| cmd.command.as_str()),
+        );
+        for name in names {
+            write!(writer, "{}{}", separator, name)
+                .map_err(|_| CommandProcessorError::WriteError)?;
+            separator = " ";
+        }
+
+        writeln!(writer, "\" -- \"$cur\"))")
+            .and_then(|_| writeln!(writer, "    fi"))
+            .and_then(|_| writeln!(writer, "}}"))
+            .and_then(|_| writeln!(writer, "complete -F {} {}", function, program))
+            .map_err(|_| CommandProcessorError::WriteError)
+    }
+
+    /// Writes a zsh completion script completing the registered command names
+    ///
+    /// Commands are described by their help string where one is registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The name of the host tool to complete for
+    /// * `writer` - The writer to write the script to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the script was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the script failed to write
+    ///
+    pub fn write_zsh_completion(
+        &self,
+        program: &str,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        let function = function_name(program);
+
+        writeln!(writer, "#compdef {}", program)
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| writeln!(writer, "{}() {{", function))
+            .and_then(|_| writeln!(writer, "    local -a commands"))
+            .and_then(|_| writeln!(writer, "    commands=("))
+            .map_err(|_| CommandProcessorError::WriteError)?;
+
+        for name in BUILTIN_COMMANDS {
+            writeln!(writer, "        '{}'", name)
+                .map_err(|_| CommandProcessorError::WriteError)?;
+        }
+
+        for cmd in self.sorted_commands().filter(|cmd| !cmd.hidden) {
+            write!(
+                writer,
+                "        '{}",
+                escape(&cmd.command.replace(':', "\\:"))
+            )
+            .and_then(|_| match &cmd.help {
+                Some(help) => write!(writer, ":{}", escape(help)),
+                None => Ok(()),
+            })
+            .and_then(|_| writeln!(writer, "'"))
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        }
+
+        writeln!(writer, "    )")
+            .and_then(|_| writeln!(writer, "    if (( CURRENT == 2 )); then"))
+            .and_then(|_| writeln!(writer, "        _describe 'command' commands"))
+            .and_then(|_| writeln!(writer, "    fi"))
+            .and_then(|_| writeln!(writer, "}}"))
+            .and_then(|_| writeln!(writer))
+            .and_then(|_| writeln!(writer, "{} \"$@\"", function))
+            .map_err(|_| CommandProcessorError::WriteError)
+    }
+}
+
+/// Returns the shell function name used for a program's completion
+fn function_name(program: &str) -> std::string::String {
+    let mut function = std::string::String::from("_");
+    function.extend(program.chars().map(|c| match c.is_ascii_alphanumeric() {
+        true => c,
+        false => '_',
+    }));
+    function
+}
+
+/// Escapes a string for use inside single quotes
+fn escape(text: &str) -> std::string::String {
+    text.replace('\'', "'\\''")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::printer_demo;
+    use crate::CommandProcessor;
+    use heapless::String;
+
+    #[test]
+    fn test_bash_completion() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("wipe"), printer_demo, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command(String::from("baud"), printer_demo, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .write_bash_completion("dev-console", &mut buffer)
+            .is_ok());
+        assert!(buffer.contains("compgen -W \"arm help simulate tutorial baud wipe\""));
+        assert!(buffer.ends_with("complete -F _dev_console dev-console\n"));
+    }
+
+    #[test]
+    fn test_zsh_completion() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(
+                String::from("baud"),
+                printer_demo,
+                Some(String::from("Sets the 'baud' rate"))
+            )
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .write_zsh_completion("console", &mut buffer)
+            .is_ok());
+        assert!(buffer.starts_with("#compdef console\n"));
+        assert!(buffer.contains("        'baud:Sets the '\\''baud'\\'' rate'\n"));
+    }
+}
@@ -0,0 +1,207 @@
+// This module contains the graceful shutdown of the processor
+use crate::{CommandProcessor, CommandProcessorError, Status};
+
+/// Shutdown callback type
+///
+/// Called once the processor is quiesced, with `true` if the exclusive
+/// operation still running was abandoned after the shutdown timeout, so the
+/// application can abort it, close its sessions and flush its logs before
+/// rebooting.
+pub type ShutdownCallback = fn(aborted: bool);
+
+/// Where the processor is in its shutdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ShutdownState {
+    Running,
+    /// Waiting for the exclusive operation, since the time of the first call if a clock is set
+    Draining(Option<u64>),
+    Done,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Stops accepting commands, before a firmware update reboot for instance
+    ///
+    /// From the first call, every line, binary request and async command
+    /// fails with `CommandProcessorError::ShuttingDown`. While an exclusive
+    /// command's operation is running, see `set_exclusive`, the call fails
+    /// with `CommandProcessorError::Busy` and is to be repeated until the
+    /// operation is released with `release_exclusive`, or the shutdown
+    /// timeout elapsed, see `set_shutdown_timeout`. The processor is then
+    /// quiesced: the console lock is released as if every session closed,
+    /// the flush callback drains the transport, see `set_flush_callback`,
+    /// and the shutdown callback is called once.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the processor is quiesced
+    /// * `Err(CommandProcessorError::Busy)` - If the exclusive operation is still running
+    /// * `Err(CommandProcessorError::ClockUnavailable)` - If a shutdown timeout is set but no clock
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, ReturnCode};
+    ///
+    /// fn erase<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// fn closed(aborted: bool) {
+    ///     assert!(!aborted);
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("erase", erase, None).unwrap();
+    /// command_processor.set_exclusive("erase", true).unwrap();
+    /// command_processor.set_shutdown_callback(closed);
+    /// command_processor.process_command("erase", None).unwrap();
+    ///
+    /// assert!(matches!(command_processor.shutdown(), Err(CommandProcessorError::Busy)));
+    /// assert!(matches!(
+    ///     command_processor.process_command("erase", None),
+    ///     Err(CommandProcessorError::ShuttingDown)
+    /// ));
+    ///
+    /// // Once the flash is erased
+    /// command_processor.release_exclusive();
+    /// assert!(command_processor.shutdown().is_ok());
+    /// ```
+    ///
+    pub fn shutdown(&mut self) -> Result<(), CommandProcessorError> {
+        let now = self.clock.map(|clock| clock.now_ms());
+        let since = match self.shutdown {
+            ShutdownState::Running => now,
+            ShutdownState::Draining(since) => since.or(now),
+            ShutdownState::Done => return Ok(()),
+        };
+        self.shutdown = ShutdownState::Draining(since);
+
+        let aborted = match (self.busy, self.shutdown_timeout_ms) {
+            (None, _) => false,
+            (Some(_), None) => return Err(CommandProcessorError::Busy),
+            (Some(_), Some(timeout_ms)) => {
+                let elapsed = match (now, since) {
+                    (Some(now), Some(since)) => now.saturating_sub(since),
+                    _ => return Err(CommandProcessorError::ClockUnavailable),
+                };
+                if elapsed < timeout_ms {
+                    return Err(CommandProcessorError::Busy);
+                }
+                true
+            }
+        };
+
+        self.busy = None;
+        #[cfg(feature = "builtins")]
+        {
+            self.lock = None;
+        }
+        self.shutdown = ShutdownState::Done;
+        if let Some(flush) = self.output.flush {
+            flush();
+        }
+        if let Some(callback) = self.shutdown_callback {
+            callback(aborted);
+        }
+        Ok(())
+    }
+
+    /// Sets how long `shutdown` waits for an exclusive operation before abandoning it
+    ///
+    /// The timeout requires a clock, see `set_clock`. Without a timeout,
+    /// `shutdown` waits for `release_exclusive`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout_ms` - The time to wait in milliseconds, or `None` to wait indefinitely
+    ///
+    pub fn set_shutdown_timeout(&mut self, timeout_ms: Option<u64>) {
+        self.shutdown_timeout_ms = timeout_ms;
+    }
+
+    /// Sets the callback called once `shutdown` quiesced the processor
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The shutdown callback
+    ///
+    pub fn set_shutdown_callback(&mut self, callback: ShutdownCallback) {
+        self.shutdown_callback = Some(callback);
+    }
+
+    /// Returns whether `shutdown` was called
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown != ShutdownState::Running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestClock;
+    use crate::{Args, CommandCallbackReturn, ReturnCode};
+
+    use core::fmt::Write;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    static ABORTED: AtomicU8 = AtomicU8::new(0);
+
+    fn erase<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Success)
+    }
+
+    fn closed(aborted: bool) {
+        ABORTED.fetch_add(u8::from(aborted), Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_shutdown_timeout() {
+        let clock = TestClock::new(1_000);
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("erase", erase, None).is_ok());
+        assert!(command_processor.set_exclusive("erase", true).is_ok());
+        command_processor.set_shutdown_timeout(Some(500));
+        command_processor.set_shutdown_callback(closed);
+        assert!(command_processor.process_command("erase", None).is_ok());
+
+        assert!(matches!(
+            command_processor.shutdown(),
+            Err(CommandProcessorError::ClockUnavailable)
+        ));
+        assert!(command_processor.is_shutting_down());
+
+        // The timeout runs from the first call with a clock
+        command_processor.set_clock(&clock);
+        assert!(matches!(
+            command_processor.shutdown(),
+            Err(CommandProcessorError::Busy)
+        ));
+        clock.set(1_499);
+        assert!(matches!(
+            command_processor.shutdown(),
+            Err(CommandProcessorError::Busy)
+        ));
+        clock.set(1_500);
+        assert!(command_processor.shutdown().is_ok());
+        assert!(!command_processor.is_busy());
+        assert_eq!(ABORTED.load(Ordering::Relaxed), 1);
+
+        // Later calls and lines don't run anything
+        assert!(command_processor.shutdown().is_ok());
+        assert_eq!(ABORTED.load(Ordering::Relaxed), 1);
+        assert!(matches!(
+            command_processor.process_command("help", None),
+            Err(CommandProcessorError::ShuttingDown)
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+// This module contains a command processor backed by caller-provided memory
+use crate::mini;
+use crate::{
+    CommandCallback, CommandCallbackReturn, CommandProcessorError, Messages, ReturnCode,
+    DEFAULT_MESSAGES, MAX_ARGS,
+};
+
+use core::fmt::Write;
+use core::mem::MaybeUninit;
+use heapless::Vec;
+
+/// A command registered with a `SliceCommandProcessor`
+#[derive(Clone, Copy)]
+pub struct SliceCommand<'a> {
+    command: &'static str,
+    callback: CommandCallback<'a>,
+    help: Option<&'static str>,
+}
+
+/// A command processor whose capacity is decided at runtime
+///
+/// Unlike `CommandProcessor`, this variant has no const generics: the command
+/// table and the line buffer are slices provided by the caller. Every
+/// instance shares a single copy of the code, which keeps binary size down in
+/// firmware that needs processors of many different sizes. In exchange, names
+/// and help strings must be `&'static str`, and only the basic features are
+/// supported: commands, the `help` built-in and byte-oriented input.
+///
+/// # Example
+///
+/// ```
+/// use core::fmt::Write;
+/// use core::mem::MaybeUninit;
+/// use command_processor::{Args, CommandCallbackReturn, ReturnCode, SliceCommand, SliceCommandProcessor};
+///
+/// fn reboot<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// let mut commands = [MaybeUninit::<SliceCommand>::uninit(); 4];
+/// let mut line = [0u8; 32];
+/// let mut command_processor = SliceCommandProcessor::new(&mut commands, &mut line);
+///
+/// command_processor.add_command("reboot", reboot, Some("Reboots the device")).unwrap();
+///
+/// let mut writer = String::new();
+/// command_processor.feed(b"reboot\n", &mut writer).unwrap();
+///
+/// assert_eq!(writer, "OK\n");
+/// ```
+///
+pub struct SliceCommandProcessor<'a> {
+    commands: &'a mut [MaybeUninit<SliceCommand<'a>>],
+    len: usize,
+    line: &'a mut [u8],
+    line_len: usize,
+    line_overflowed: bool,
+    messages: &'a Messages,
+}
+
+impl<'a> SliceCommandProcessor<'a> {
+    /// Creates a command processor using the given memory
+    ///
+    /// # Arguments
+    ///
+    /// * `commands` - The storage for the command table, its length is the
+    ///   maximum number of commands
+    /// * `line` - The storage for the input line buffer, its length is the
+    ///   maximum line length
+    ///
+    pub fn new(commands: &'a mut [MaybeUninit<SliceCommand<'a>>], line: &'a mut [u8]) -> Self {
+        Self {
+            commands,
+            len: 0,
+            line,
+            line_len: 0,
+            line_overflowed: false,
+            messages: &DEFAULT_MESSAGES,
+        }
+    }
+
+    /// Sets the message catalog used for processor-generated text
+    ///
+    /// # Arguments
+    ///
+    /// * `messages` - The message catalog to use
+    ///
+    pub fn set_messages(&mut self, messages: &'a Messages) {
+        self.messages = messages;
+    }
+
+    /// Returns the registered commands
+    fn commands(&self) -> &[SliceCommand<'a>] {
+        let initialized = self.commands.get(..self.len).unwrap_or(&[]);
+
+        // SAFETY: the first `len` entries are always initialized by
+        // `add_command`, and `MaybeUninit<T>` has the same layout as `T`.
+        unsafe {
+            core::slice::from_raw_parts(
+                initialized.as_ptr().cast::<SliceCommand<'a>>(),
+                initialized.len(),
+            )
+        }
+    }
+
+    /// Adds a command to the command processor
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to add
+    /// * `callback` - The callback to call when the command is processed
+    /// * `help` - The help string for the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was added successfully
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If the command already exists
+    /// * `Err(CommandProcessorError::CommandListFull)` - If the command table is full
+    ///
+    pub fn add_command(
+        &mut self,
+        command: &'static str,
+        callback: CommandCallback<'a>,
+        help: Option<&'static str>,
+    ) -> Result<(), CommandProcessorError> {
+        if self.commands().iter().any(|cmd| cmd.command == command) {
+            return Err(CommandProcessorError::CommandAlreadyExists);
+        }
+
+        let slot = self
+            .commands
+            .get_mut(self.len)
+            .ok_or(CommandProcessorError::CommandListFull)?;
+
+        slot.write(SliceCommand {
+            command,
+            callback,
+            help,
+        });
+        self.len = self.len.saturating_add(1);
+
+        Ok(())
+    }
+
+    /// Removes a command from the command processor
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command to remove
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the command was removed successfully
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    ///
+    pub fn remove_command(&mut self, command: &str) -> Result<(), CommandProcessorError> {
+        let index = self
+            .commands()
+            .iter()
+            .position(|cmd| cmd.command == command)
+            .ok_or(CommandProcessorError::CommandNotFound)?;
+
+        // Entries are `Copy`, so the last one can be moved over the removed one
+        let last = self.len.saturating_sub(1);
+        self.commands.swap(index, last);
+        self.len = last;
+
+        Ok(())
+    }
+
+    /// Processes a command
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command line to process
+    /// * `writer` - The writer to pass to the command
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode)` - The return code of the command
+    /// * `Err(CommandProcessorError::CommandNotFound)` - If the command was not found
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If there are more than `MAX_ARGS` arguments
+    /// * `Err(CommandProcessorError::NoWriter)` - If `help` was called without a writer
+    /// * `Err(CommandProcessorError::WriteError)` - If `help` failed to write
+    ///
+    pub fn process_command(
+        &self,
+        command: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> CommandCallbackReturn<'a> {
+        let mut tokens = command.split_whitespace();
+        let name = tokens.next().unwrap_or_default();
+
+        if name == "help" {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+
+            // As in `CommandProcessor`, commands without help aren't listed
+            for help in self.commands().iter().filter_map(|cmd| cmd.help) {
+                writeln!(writer, "{}", help).map_err(|_| CommandProcessorError::WriteError)?;
+            }
+
+            return Ok(ReturnCode::Success);
+        }
+
+        let mut args: Vec<&str, MAX_ARGS> = Vec::new();
+        for arg in tokens {
+            args.push(arg)
+                .map_err(|_| CommandProcessorError::InvalidArguments)?;
+        }
+
+        let commands = self
+            .commands()
+            .iter()
+            .map(|cmd| (cmd.command, cmd.callback));
+        mini::run(commands, name, &args, writer)
+    }
+
+    /// Feeds received bytes through the whole input path
+    ///
+    /// Behaves like `CommandProcessor::feed`, using the caller-provided line
+    /// buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The received bytes
+    /// * `writer` - The writer for command output and reports
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the bytes were processed
+    /// * `Err(CommandProcessorError::WriteError)` - If writing a report failed
+    ///
+    pub fn feed(
+        &mut self,
+        bytes: &[u8],
+        writer: &mut (dyn Write + 'a),
+    ) -> Result<(), CommandProcessorError> {
+        for &byte in bytes {
+            match byte {
+                b'\r' | b'\n' => self.end_line(writer)?,
+                0x20..=0x7e if !self.line_overflowed => match self.line.get_mut(self.line_len) {
+                    Some(slot) => {
+                        *slot = byte;
+                        self.line_len = self.line_len.saturating_add(1);
+                    }
+                    None => self.line_overflowed = true,
+                },
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end_line(&mut self, writer: &mut (dyn Write + 'a)) -> Result<(), CommandProcessorError> {
+        let len = core::mem::take(&mut self.line_len);
+        let line = self.line.get(..len).unwrap_or(&[]);
+        // Only printable ASCII is ever stored, so the line is valid UTF-8
+        let line = core::str::from_utf8(line).unwrap_or_default();
+
+        let result = match core::mem::take(&mut self.line_overflowed) {
+            true => Err(CommandProcessorError::LineTooLong),
+            false if line.trim().is_empty() => return Ok(()),
+            false => self.process_command(line, Some(&mut *writer)),
+        };
+
+        match result {
+            Ok(ReturnCode::Success) => writeln!(writer, "{}", self.messages.ok),
+            Ok(ReturnCode::Failure) => writeln!(writer, "{}", self.messages.error),
+            Err(e) => writeln!(
+                writer,
+                "{}: {}",
+                self.messages.error,
+                e.message(self.messages)
+            ),
+        }
+        .map_err(|_| CommandProcessorError::WriteError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::hello;
+
+    #[test]
+    fn test_slice_processor() {
+        let mut commands = [MaybeUninit::<SliceCommand>::uninit(); 2];
+        let mut line = [0u8; 8];
+        let mut command_processor = SliceCommandProcessor::new(&mut commands, &mut line);
+
+        assert!(command_processor
+            .add_command("a", hello, Some("first"))
+            .is_ok());
+        assert!(matches!(
+            command_processor.add_command("a", hello, None),
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+        assert!(command_processor.add_command("b", hello, None).is_ok());
+        assert!(matches!(
+            command_processor.add_command("c", hello, None),
+            Err(CommandProcessorError::CommandListFull)
+        ));
+
+        assert!(command_processor.remove_command("a").is_ok());
+        assert!(command_processor
+            .add_command("c", hello, Some("third"))
+            .is_ok());
+
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .feed(b"b\nhelp\na\nlong line\n", &mut writer)
+            .is_ok());
+
+        assert_eq!(
+            writer,
+            "hi\nOK\nthird\nOK\nERR: command not found\nERR: line too long\n"
+        );
+    }
+}
@@ -0,0 +1,366 @@
+// This module contains the snapshots of the console state kept across soft reboots
+#[cfg(feature = "history")]
+use crate::scratch::check_capacity;
+use crate::variables::Variables;
+use crate::{CommandProcessor, CommandProcessorError, SliceWriter, Status};
+
+use core::fmt::Write;
+
+/// The size of the snapshot saved to the storage, see `store_snapshot`
+pub const SNAPSHOT_SIZE: usize = 512;
+
+/// The storage key of the snapshot, which script names can't start with
+const SNAPSHOT_KEY: &str = ".snapshot";
+
+/// The first line of a snapshot, with the version of its format
+const HEADER: &str = "snapshot 1\n";
+
+/// The prefix of the last line of a snapshot, followed by the hash of the lines before it
+const END: &str = "end ";
+
+/// Hashes the text of a snapshot, to tell it from whatever a RAM region holds after power-up
+fn fnv1a(text: &str) -> u32 {
+    text.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Saves the console state to a buffer, such as a RAM region kept across soft resets
+    ///
+    /// The snapshot holds the access level of the session, see
+    /// `set_session_info`, the session variables and the history lines, so
+    /// a watchdog reboot in the middle of a service session can be followed
+    /// by `restore_snapshot`. Variables whose value spans several lines
+    /// aren't saved. The snapshot is text ending with a hash, so a buffer
+    /// holding anything else is rejected when restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - The buffer to save the snapshot to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(usize)` - The length of the snapshot
+    /// * `Err(CommandProcessorError::WriteError)` - If the snapshot doesn't fit in the buffer
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, Link, SessionInfo};
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_session_info(Some(SessionInfo {
+    ///     access_level: 2,
+    ///     ..SessionInfo::new(0, Link::Serial)
+    /// }));
+    /// command_processor.process_command("let unit 7", None).unwrap();
+    ///
+    /// // Kept in a RAM region the startup code doesn't clear
+    /// let mut retained = [0u8; 128];
+    /// let len = command_processor.save_snapshot(&mut retained).unwrap();
+    ///
+    /// // After the watchdog reset
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.set_session_info(Some(SessionInfo::new(0, Link::Serial)));
+    /// command_processor.restore_snapshot(&retained[..len]).unwrap();
+    ///
+    /// assert_eq!(command_processor.variable("unit"), Some("7"));
+    /// assert!(command_processor.restore_snapshot(&[0u8; 16]).is_err());
+    /// ```
+    ///
+    pub fn save_snapshot(&self, buffer: &mut [u8]) -> Result<usize, CommandProcessorError> {
+        let mut writer = SliceWriter::new(buffer);
+        let _ = writer.write_str(HEADER);
+        if let Some(session) = self.session {
+            let _ = writeln!(writer, "access {} {}", session.id, session.access_level);
+        }
+        for (name, value) in self.variables.iter() {
+            if !value.contains(['\r', '\n']) {
+                let _ = writeln!(writer, "let {} {}", name, value);
+            }
+        }
+        #[cfg(feature = "history")]
+        if let Some(history) = self.history.as_deref() {
+            history.each(&mut |line| {
+                let _ = writeln!(writer, "history {}", line);
+            });
+        }
+
+        let hash = fnv1a(writer.as_str());
+        let _ = writeln!(writer, "{}{:08x}", END, hash);
+        match writer.overflowed() {
+            true => Err(CommandProcessorError::WriteError),
+            false => Ok(writer.as_str().len()),
+        }
+    }
+
+    /// Restores the console state saved by `save_snapshot`
+    ///
+    /// The session variables are replaced, and the history lines recorded
+    /// in the history, if one is set. The access level is only restored if
+    /// the current session has the id of the saved one, so the snapshot is
+    /// to be restored once the session is set again. Snapshots grant the
+    /// access level they hold, so they must come from memory only the
+    /// firmware writes, and be cleared once restored. The whole snapshot is
+    /// checked before anything is restored, so a rejected snapshot leaves
+    /// the state as it was.
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - The snapshot
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the state was restored
+    /// * `Err(CommandProcessorError::StorageError)` - If the snapshot is invalid
+    ///
+    pub fn restore_snapshot(&mut self, snapshot: &[u8]) -> Result<(), CommandProcessorError> {
+        let text =
+            core::str::from_utf8(snapshot).map_err(|_| CommandProcessorError::StorageError)?;
+        let (body, end) = text
+            .rfind(END)
+            .and_then(|start| text.split_at_checked(start))
+            .ok_or(CommandProcessorError::StorageError)?;
+        let hash = end
+            .strip_prefix(END)
+            .and_then(|hash| hash.strip_suffix('\n'))
+            .and_then(|hash| u32::from_str_radix(hash, 16).ok());
+        if !body.starts_with(HEADER) || !body.ends_with('\n') || hash != Some(fnv1a(body)) {
+            return Err(CommandProcessorError::StorageError);
+        }
+
+        let mut variables = Variables::default();
+        let mut access = None;
+        for line in body.lines().skip(1) {
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match kind {
+                "access" => {
+                    let mut fields = rest.split(' ').map(str::parse::<u8>);
+                    let (Some(Ok(id)), Some(Ok(level))) = (fields.next(), fields.next()) else {
+                        return Err(CommandProcessorError::StorageError);
+                    };
+                    access = Some((id, level));
+                }
+                "let" => {
+                    let (name, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                    variables
+                        .set(name, Some(value))
+                        .map_err(|_| CommandProcessorError::StorageError)?;
+                }
+                #[cfg(feature = "history")]
+                "history" => {
+                    if let Some(history) = self.history.as_deref() {
+                        check_capacity(history.fits(rest), CommandProcessorError::StorageError)?;
+                    }
+                }
+                // Builds without an input front-end have no history
+                #[cfg(not(feature = "history"))]
+                "history" => {}
+                _ => return Err(CommandProcessorError::StorageError),
+            }
+        }
+
+        // The snapshot is accepted, restore it
+        self.variables = variables;
+        if let Some((id, level)) = access {
+            if let Some(session) = self.session.as_mut().filter(|session| session.id == id) {
+                session.access_level = level;
+            }
+        }
+        #[cfg(feature = "history")]
+        if let Some(history) = self.history.as_deref_mut() {
+            for line in body
+                .lines()
+                .filter_map(|line| line.strip_prefix("history "))
+            {
+                history.record(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the console state to the storage, see `save_snapshot`
+    ///
+    /// For devices without a RAM region kept across resets. The snapshot
+    /// has to fit in `SNAPSHOT_SIZE` bytes.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the snapshot was saved
+    /// * `Err(CommandProcessorError::StorageUnavailable)` - If no storage was set
+    /// * `Err(CommandProcessorError::StorageError)` - If the storage failed
+    /// * `Err(CommandProcessorError::WriteError)` - If the snapshot doesn't fit in `SNAPSHOT_SIZE` bytes
+    ///
+    pub fn store_snapshot(&mut self) -> Result<(), CommandProcessorError> {
+        let mut buffer = [0u8; SNAPSHOT_SIZE];
+        let len = self.save_snapshot(&mut buffer)?;
+        let snapshot = buffer.get(..len).unwrap_or(&[]);
+        self.storage
+            .as_deref_mut()
+            .ok_or(CommandProcessorError::StorageUnavailable)?
+            .store(SNAPSHOT_KEY, snapshot)
+            .map_err(|_| CommandProcessorError::StorageError)
+    }
+
+    /// Restores and removes the snapshot saved by `store_snapshot`, usually at startup
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the state was restored
+    /// * `Ok(false)` - If no snapshot was saved
+    /// * `Err(CommandProcessorError::StorageUnavailable)` - If no storage was set
+    /// * `Err(CommandProcessorError::StorageError)` - If the storage failed or holds an invalid snapshot
+    ///
+    pub fn load_snapshot(&mut self) -> Result<bool, CommandProcessorError> {
+        let mut buffer = [0u8; SNAPSHOT_SIZE];
+        let storage = self
+            .storage
+            .as_deref_mut()
+            .ok_or(CommandProcessorError::StorageUnavailable)?;
+        let loaded = storage.load(SNAPSHOT_KEY, &mut buffer);
+        let Some(len) = loaded.map_err(|_| CommandProcessorError::StorageError)? else {
+            return Ok(false);
+        };
+        // A snapshot is only good for the next startup
+        storage
+            .remove(SNAPSHOT_KEY)
+            .map_err(|_| CommandProcessorError::StorageError)?;
+
+        self.restore_snapshot(buffer.get(..len).unwrap_or(&[]))?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // The history is part of the input front-end
+    #[test]
+    #[cfg(feature = "history")]
+    fn test_snapshot() {
+        use super::*;
+        use crate::stored::tests::Memory;
+        use crate::{History, Link, SessionInfo};
+
+        let session = SessionInfo::new(1, Link::Serial);
+        let mut storage = Memory::default();
+        let mut history: History<4> = History::new();
+        let mut restored_history: History<4> = History::new();
+        let mut buffer = [0u8; 128];
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_history(&mut history);
+        command_processor.set_session_info(Some(SessionInfo {
+            access_level: 3,
+            ..session
+        }));
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .feed(b"let mode \"service run\"\nlet x\n", &mut writer)
+            .is_ok());
+
+        assert!(command_processor.save_snapshot(&mut [0u8; 16]).is_err());
+        let len = command_processor.save_snapshot(&mut buffer).unwrap_or(0);
+        let snapshot = core::str::from_utf8(buffer.get(..len).unwrap_or(&[])).unwrap_or("");
+        assert!(snapshot.starts_with(
+            "snapshot 1\naccess 1 3\nlet mode service run\nhistory let mode \"service run\"\nhistory let x\nend "
+        ));
+        command_processor.set_storage(&mut storage);
+        assert!(command_processor.store_snapshot().is_ok());
+        drop(command_processor);
+
+        // A corrupted snapshot restores nothing
+        let mut restored: CommandProcessor<8, 32> = CommandProcessor::new();
+        if let Some(byte) = buffer.get_mut(12) {
+            *byte ^= 1;
+        }
+        assert!(matches!(
+            restored.restore_snapshot(buffer.get(..len).unwrap_or(&[])),
+            Err(CommandProcessorError::StorageError)
+        ));
+
+        // Through the storage, once
+        restored.set_history(&mut restored_history);
+        restored.set_storage(&mut storage);
+        restored.set_session_info(Some(session));
+        assert!(matches!(restored.load_snapshot(), Ok(true)));
+        assert!(matches!(restored.load_snapshot(), Ok(false)));
+        assert_eq!(restored.variable("mode"), Some("service run"));
+        assert_eq!(
+            restored.session.map(|session| session.access_level),
+            Some(3)
+        );
+        writer.clear();
+        assert!(restored.feed(b"\x1b[A", &mut writer).is_ok());
+        assert_eq!(writer, "let x\x1b[K");
+    }
+
+    #[test]
+    fn test_snapshot_rejected_whole() {
+        use super::*;
+        use crate::{Link, SessionInfo};
+
+        let session = SessionInfo::new(1, Link::Serial);
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_session_info(Some(session));
+        assert!(command_processor
+            .process_command("let keep 1", None)
+            .is_ok());
+
+        // The invalid variable comes after lines that would have been restored
+        let body = "snapshot 1\naccess 1 3\nlet unit 7\nlet 9lives 9\n";
+        let snapshot = format!("{}{}{:08x}\n", body, END, fnv1a(body));
+        assert!(matches!(
+            command_processor.restore_snapshot(snapshot.as_bytes()),
+            Err(CommandProcessorError::StorageError)
+        ));
+        assert_eq!(command_processor.variable("keep"), Some("1"));
+        assert_eq!(command_processor.variable("unit"), None);
+        assert_eq!(
+            command_processor
+                .session
+                .map(|session| session.access_level),
+            Some(0)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn test_snapshot_history_overflow() {
+        use super::*;
+        use crate::History;
+
+        let mut history: History<4, 64> = History::new();
+        let mut buffer = [0u8; 128];
+        let mut command_processor: CommandProcessor<8, 32, 32, 64> = CommandProcessor::new();
+        command_processor.set_history(&mut history);
+        let mut writer = std::string::String::new();
+        let line = "x".repeat(40);
+        assert!(command_processor
+            .feed(format!("{}\n", line).as_bytes(), &mut writer)
+            .is_ok());
+        let len = command_processor.save_snapshot(&mut buffer).unwrap_or(0);
+        drop(command_processor);
+
+        // Lines longer than the restored history's are dropped, or fail the restore
+        let mut restored_history: History<4> = History::new();
+        let mut restored: CommandProcessor<8, 32> = CommandProcessor::new();
+        restored.set_history(&mut restored_history);
+        let result = restored.restore_snapshot(buffer.get(..len).unwrap_or(&[]));
+        match cfg!(feature = "strict-capacity") {
+            true => assert!(matches!(result, Err(CommandProcessorError::StorageError))),
+            false => assert!(result.is_ok()),
+        }
+        drop(restored);
+        assert_eq!(restored_history.iter().count(), 0);
+    }
+}
@@ -0,0 +1,186 @@
+// This module contains the registry of status values and the `status` built-in
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::{self, Write};
+
+/// The maximum number of published status values
+pub const MAX_STATUS_VALUES: usize = 8;
+
+/// A status value published by a module, see `publish_status`
+///
+/// Implemented for closures writing the value, so a module can publish a
+/// reading without defining a type.
+pub trait StatusValue {
+    /// Writes the current value, without a trailing newline
+    fn write_value(&self, writer: &mut dyn Write) -> fmt::Result;
+}
+
+impl<F: Fn(&mut dyn Write) -> fmt::Result> StatusValue for F {
+    fn write_value(&self, writer: &mut dyn Write) -> fmt::Result {
+        self(writer)
+    }
+}
+
+/// Formats a status value
+struct Rendered<'v>(&'v dyn StatusValue);
+
+impl fmt::Display for Rendered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write_value(f)
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Publishes a named status value, rendered by the `status` built-in
+    ///
+    /// `status` writes every value as `name: value` lines, in the order they
+    /// were published, and `status <name>` writes a single one. Publishing
+    /// a name again replaces its value. A command registered as `status`
+    /// takes precedence over the built-in.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the value
+    /// * `value` - The value, usually a closure reading it
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the value was published successfully
+    /// * `Err(CommandProcessorError::TooManyStatusValues)` - If `MAX_STATUS_VALUES` values are already published
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use core::sync::atomic::{AtomicU32, Ordering};
+    /// use command_processor::CommandProcessor;
+    ///
+    /// static BATTERY_MV: AtomicU32 = AtomicU32::new(3700);
+    ///
+    /// let battery = |writer: &mut dyn Write| write!(writer, "{} mV", BATTERY_MV.load(Ordering::Relaxed));
+    /// let link = |writer: &mut dyn Write| writer.write_str("up");
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.publish_status("battery", &battery).unwrap();
+    /// command_processor.publish_status("link", &link).unwrap();
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.process_command("status", Some(&mut writer)).unwrap();
+    /// assert_eq!(writer, "battery: 3700 mV\nlink: up\n");
+    /// ```
+    ///
+    pub fn publish_status(
+        &mut self,
+        name: &'a str,
+        value: &'a (dyn StatusValue + Sync),
+    ) -> Result<(), CommandProcessorError> {
+        match self.status_values.iter_mut().find(|(key, _)| *key == name) {
+            Some(entry) => entry.1 = value,
+            None => self
+                .status_values
+                .push((name, value))
+                .map_err(|_| CommandProcessorError::TooManyStatusValues)?,
+        }
+        Ok(())
+    }
+
+    /// Withdraws a status value, returning whether it was published
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the value
+    ///
+    pub fn withdraw_status(&mut self, name: &str) -> bool {
+        let Some(index) = self.status_values.iter().position(|(key, _)| *key == name) else {
+            return false;
+        };
+        self.status_values.remove(index);
+        true
+    }
+
+    /// Writes the published status values, or a single one
+    pub(crate) fn status(
+        &mut self,
+        name: Option<&str>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+
+        if let Some(name) = name {
+            let (_, value) = self
+                .status_values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .ok_or(CommandProcessorError::UnknownStatus)?;
+            self.output
+                .write(writer, format_args!("{}: {}\n", name, Rendered(*value)))?;
+            return Ok(ReturnCode::Success);
+        }
+
+        for (name, value) in &self.status_values {
+            self.output
+                .write(writer, format_args!("{}: {}\n", name, Rendered(*value)))?;
+        }
+        Ok(ReturnCode::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn};
+
+    fn status<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "custom").map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_status_values() {
+        let uptime = |writer: &mut dyn Write| write!(writer, "{} s", 42);
+        let mode = |writer: &mut dyn Write| writer.write_str("idle");
+        let armed = |writer: &mut dyn Write| writer.write_str("armed");
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.publish_status("uptime", &uptime).is_ok());
+        assert!(command_processor.publish_status("mode", &mode).is_ok());
+        assert!(command_processor.publish_status("mode", &armed).is_ok());
+
+        let mut buffer = std::string::String::new();
+        let mut run = |command_processor: &mut CommandProcessor<8, 32>, line: &str| {
+            buffer.clear();
+            let result = command_processor.process_command(line, Some(&mut buffer));
+            (result, buffer.clone())
+        };
+
+        assert_eq!(
+            run(&mut command_processor, "status").1,
+            "uptime: 42 s\nmode: armed\n"
+        );
+        assert_eq!(
+            run(&mut command_processor, "status mode").1,
+            "mode: armed\n"
+        );
+        assert!(matches!(
+            run(&mut command_processor, "status nope").0,
+            Err(CommandProcessorError::UnknownStatus)
+        ));
+
+        // An application's own status command wins
+        assert!(command_processor.withdraw_status("uptime"));
+        assert!(command_processor
+            .add_command("status", status, None)
+            .is_ok());
+        assert_eq!(run(&mut command_processor, "status").1, "custom\n");
+    }
+}
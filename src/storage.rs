@@ -0,0 +1,55 @@
+// This module contains the storage abstraction used to persist scripts
+
+/// A failure of the storage, such as a full or worn out flash sector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StorageError;
+
+/// A persistent key-value store
+///
+/// Stored scripts survive resets by being saved to a storage the application
+/// provides, usually on top of a flash sector or an EEPROM. Keys are the
+/// script names and values their text.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{Storage, StorageError};
+///
+/// struct Flash;
+///
+/// impl Storage for Flash {
+///     fn load(&self, key: &str, buffer: &mut [u8]) -> Result<Option<usize>, StorageError> {
+///         // Read the entry from flash here
+///         Ok(None)
+///     }
+///
+///     fn store(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+///         Err(StorageError)
+///     }
+///
+///     fn remove(&mut self, key: &str) -> Result<bool, StorageError> {
+///         Ok(false)
+///     }
+///
+///     fn keys(&self, visit: &mut dyn FnMut(&str)) -> Result<(), StorageError> {
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+pub trait Storage {
+    /// Reads an entry into the buffer
+    ///
+    /// Returns the length of the entry, or `None` if there is none. Entries
+    /// longer than the buffer are a `StorageError`.
+    fn load(&self, key: &str, buffer: &mut [u8]) -> Result<Option<usize>, StorageError>;
+
+    /// Writes an entry, replacing any previous one with the same key
+    fn store(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError>;
+
+    /// Removes an entry, returning whether there was one
+    fn remove(&mut self, key: &str) -> Result<bool, StorageError>;
+
+    /// Calls `visit` with the key of every entry
+    fn keys(&self, visit: &mut dyn FnMut(&str)) -> Result<(), StorageError>;
+}
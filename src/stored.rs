@@ -0,0 +1,300 @@
+// This module contains the scripts stored by name with the script built-in
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status, Storage, Tokens};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The maximum size of a stored script
+pub const MAX_SCRIPT_SIZE: usize = 256;
+
+/// The maximum size of a stored script's name
+pub const SCRIPT_NAME_SIZE: usize = 16;
+
+/// The line ending the recording of a script
+const END: &str = "end";
+
+/// A script being recorded by `script save`
+pub(crate) struct Recording {
+    name: String<SCRIPT_NAME_SIZE>,
+    script: String<MAX_SCRIPT_SIZE>,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Sets the storage the script built-in saves scripts to
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - The storage holding the scripts, by name
+    ///
+    pub fn set_storage(&mut self, storage: &'a mut (dyn Storage + Send)) {
+        self.storage = Some(storage);
+    }
+
+    /// Returns whether `script save` is recording the lines processed
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Runs the script built-in
+    ///
+    /// `script save <name>` records the following lines, without running
+    /// them, until a line holding only `end`, then stores them. `script run
+    /// <name>` runs a stored script like `run_script`, `script list` lists
+    /// the stored scripts and `script delete <name>` removes one. Stored
+    /// scripts can't run other stored scripts. `script every <name>
+    /// <interval>` runs a script periodically through `run_schedules`, and
+    /// `script cancel <name>` stops it.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - The tokens following `script`
+    /// * `writer` - The writer the commands write with
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ReturnCode)` - The result of the subcommand
+    /// * `Err(CommandProcessorError::StorageUnavailable)` - If no storage was set
+    /// * `Err(CommandProcessorError::ScriptNotFound)` - If there is no script with the name
+    /// * `Err(CommandProcessorError::StorageError)` - If the storage failed
+    /// * `Err(CommandProcessorError::InvalidArguments)` - If the name is too long or invalid, or a stored script runs another
+    /// * `Err(CommandProcessorError::TooManySchedules)` - If `MAX_SCHEDULES` scripts are already scheduled
+    ///
+    pub(crate) fn script(
+        &mut self,
+        mut tokens: Tokens,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        let subcommand = tokens
+            .next()
+            .ok_or(CommandProcessorError::MissingArgument)?;
+        let name = match subcommand {
+            "list" => None,
+            _ => Some(
+                tokens
+                    .next()
+                    .ok_or(CommandProcessorError::MissingArgument)?,
+            ),
+        };
+        let interval = match subcommand {
+            "every" => Some(
+                tokens
+                    .next()
+                    .ok_or(CommandProcessorError::MissingArgument)?,
+            ),
+            _ => None,
+        };
+        if tokens.next().is_some() {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        tokens.check()?;
+
+        if self.storage.is_none() {
+            return Err(CommandProcessorError::StorageUnavailable);
+        }
+
+        match (subcommand, name) {
+            ("save", Some(name)) => {
+                if name.starts_with('.') {
+                    return Err(CommandProcessorError::InvalidArguments);
+                }
+                let mut copy = String::new();
+                copy.push_str(name)
+                    .map_err(|_| CommandProcessorError::InvalidArguments)?;
+                self.recording = Some(Recording {
+                    name: copy,
+                    script: String::new(),
+                });
+                Ok(ReturnCode::Success.into())
+            }
+            ("run", Some(name)) => self.run_stored(name, writer),
+            ("delete", Some(name)) => match self.storage.as_deref_mut().map(|s| s.remove(name)) {
+                Some(Ok(true)) => {
+                    self.cancel_schedule(name)?;
+                    Ok(ReturnCode::Success.into())
+                }
+                Some(Ok(false)) => Err(CommandProcessorError::ScriptNotFound),
+                _ => Err(CommandProcessorError::StorageError),
+            },
+            ("every", Some(name)) => self.schedule(name, interval.unwrap_or("")).map(R::from),
+            ("cancel", Some(name)) => match self.cancel_schedule(name)? {
+                true => Ok(ReturnCode::Success.into()),
+                false => Err(CommandProcessorError::ScriptNotFound),
+            },
+            ("list", None) => {
+                let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+                let mut written = Ok(());
+                let listed = self.storage.as_deref().map(|storage| {
+                    storage.keys(&mut |name| {
+                        // Other keys start with a dot, such as the schedules'
+                        if written.is_ok() && !name.starts_with('.') {
+                            written = self.output.write(writer, format_args!("{}\n", name));
+                        }
+                    })
+                });
+                written?;
+                if !matches!(listed, Some(Ok(()))) {
+                    return Err(CommandProcessorError::StorageError);
+                }
+                self.list_schedules(writer)?;
+                Ok(ReturnCode::Success.into())
+            }
+            _ => Err(CommandProcessorError::InvalidArguments),
+        }
+    }
+
+    /// Records a line of the script being saved, storing it at `end`
+    ///
+    /// A script that doesn't fit `MAX_SCRIPT_SIZE` is dropped.
+    pub(crate) fn record(&mut self, line: &str) -> Result<ReturnCode, CommandProcessorError> {
+        let Some(recording) = self.recording.as_mut() else {
+            return Ok(ReturnCode::Success);
+        };
+
+        if line.trim() != END {
+            let recorded = recording
+                .script
+                .push_str(line)
+                .and_then(|()| recording.script.push('\n'));
+            if recorded.is_err() {
+                self.recording = None;
+                return Err(CommandProcessorError::ScriptTooLong);
+            }
+            return Ok(ReturnCode::Success);
+        }
+
+        let recording = self.recording.take();
+        let stored = match (self.storage.as_deref_mut(), recording) {
+            (Some(storage), Some(recording)) => {
+                storage.store(&recording.name, recording.script.as_bytes())
+            }
+            _ => return Err(CommandProcessorError::StorageUnavailable),
+        };
+        stored.map_err(|_| CommandProcessorError::StorageError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    /// Loads a stored script and runs it
+    pub(crate) fn run_stored(
+        &mut self,
+        name: &str,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<R, CommandProcessorError> {
+        if self.running_script {
+            return Err(CommandProcessorError::InvalidArguments);
+        }
+
+        let mut buffer = [0u8; MAX_SCRIPT_SIZE];
+        let loaded = match self.storage.as_deref() {
+            Some(storage) => storage.load(name, &mut buffer),
+            None => return Err(CommandProcessorError::StorageUnavailable),
+        };
+        let len = loaded
+            .map_err(|_| CommandProcessorError::StorageError)?
+            .ok_or(CommandProcessorError::ScriptNotFound)?;
+        let script = buffer
+            .get(..len)
+            .and_then(|script| core::str::from_utf8(script).ok())
+            .ok_or(CommandProcessorError::StorageError)?;
+
+        self.running_script = true;
+        let result = self.run_lines(script, writer);
+        self.running_script = false;
+        result
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn, StorageError};
+    use std::{string::ToString, vec::Vec};
+
+    /// A storage in RAM
+    #[derive(Default)]
+    pub(crate) struct Memory(pub(crate) Vec<(std::string::String, Vec<u8>)>);
+
+    impl Storage for Memory {
+        fn load(&self, key: &str, buffer: &mut [u8]) -> Result<Option<usize>, StorageError> {
+            let Some((_, value)) = self.0.iter().find(|(name, _)| name == key) else {
+                return Ok(None);
+            };
+            let target = buffer.get_mut(..value.len()).ok_or(StorageError)?;
+            target.copy_from_slice(value);
+            Ok(Some(value.len()))
+        }
+
+        fn store(&mut self, key: &str, value: &[u8]) -> Result<(), StorageError> {
+            self.0.retain(|(name, _)| name != key);
+            self.0.push((key.to_string(), value.to_vec()));
+            Ok(())
+        }
+
+        fn remove(&mut self, key: &str) -> Result<bool, StorageError> {
+            let len = self.0.len();
+            self.0.retain(|(name, _)| name != key);
+            Ok(self.0.len() != len)
+        }
+
+        fn keys(&self, visit: &mut dyn FnMut(&str)) -> Result<(), StorageError> {
+            self.0.iter().for_each(|(name, _)| visit(name));
+            Ok(())
+        }
+    }
+
+    fn echo<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "{:?}", args.remaining())
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_stored_scripts() {
+        let mut storage = Memory::default();
+        let mut buffer = std::string::String::new();
+
+        {
+            let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+            assert!(command_processor.add_command("echo", echo, None).is_ok());
+            assert!(matches!(
+                command_processor.process_command("script list", None),
+                Err(CommandProcessorError::StorageUnavailable)
+            ));
+            command_processor.set_storage(&mut storage);
+
+            let mut run = |line: &str| command_processor.process_command(line, Some(&mut buffer));
+            assert!(run("script save boot").is_ok());
+            assert!(run("echo booting").is_ok());
+            assert!(run("script run boot").is_ok());
+            assert!(run("end").is_ok());
+
+            // The script runs, up to its line running itself
+            assert!(matches!(
+                run("script run boot"),
+                Err(CommandProcessorError::InvalidArguments)
+            ));
+            assert!(run("script list").is_ok());
+            assert!(matches!(
+                run("script run missing"),
+                Err(CommandProcessorError::ScriptNotFound)
+            ));
+            assert!(run("script delete boot").is_ok());
+            assert!(matches!(
+                run("script delete boot"),
+                Err(CommandProcessorError::ScriptNotFound)
+            ));
+        }
+
+        assert_eq!(buffer, "[\"booting\"]\nboot\n");
+        assert!(storage.0.is_empty());
+    }
+}
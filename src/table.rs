@@ -0,0 +1,227 @@
+// This module contains command tables registered in one go
+use crate::{
+    CommandCallback, CommandProcessor, CommandProcessorError, DuplicatePolicy, ReturnCode, Status,
+};
+use heapless::String;
+
+/// A command definition, as listed in a command table
+#[derive(Clone, Copy)]
+pub struct CommandDef<'a, R = ReturnCode> {
+    pub name: &'static str,
+    pub callback: CommandCallback<'a, R>,
+    pub help: Option<&'static str>,
+}
+
+/// Builds an array of `CommandDef` from a list of commands
+///
+/// Each entry is `name => callback`, optionally followed by `: help`. As the
+/// array's length is the number of entries, `CommandProcessor::from_table`
+/// infers `NUM_COMMANDS` from it, so the capacity can't get out of sync with
+/// the registrations.
+///
+/// # Example
+///
+/// ```
+/// use command_processor::{command_table, Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+/// use core::fmt::Write;
+///
+/// fn led<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// fn reboot<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+///     Ok(ReturnCode::Success)
+/// }
+///
+/// let command_processor: CommandProcessor<_, 32> = CommandProcessor::from_table(command_table![
+///     "led" => led: "Toggles the LED",
+///     "reboot" => reboot,
+/// ])
+/// .unwrap();
+///
+/// assert_eq!(command_processor.capacity(), 2);
+/// ```
+///
+#[macro_export]
+macro_rules! command_table {
+    ($($name:expr => $callback:path $(: $help:expr)?),* $(,)?) => {
+        [$($crate::CommandDef {
+            name: $name,
+            callback: $callback,
+            help: None $(.or(Some($help)))?,
+        }),*]
+    };
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Creates a processor holding exactly the commands of a table
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The commands, usually built with `command_table!`
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(CommandProcessor)` - The processor with every command registered
+    /// * `Err(CommandProcessorError::CommandAlreadyExists)` - If a name appears twice
+    /// * `Err(CommandProcessorError::HelpTooLong)` - If a help string doesn't fit `HELP_STR_SIZE`
+    ///
+    pub fn from_table(
+        table: [CommandDef<'a, R>; NUM_COMMANDS],
+    ) -> Result<Self, CommandProcessorError> {
+        let mut command_processor = Self::new();
+
+        for def in table {
+            command_processor.add_command(def.name, def.callback, help_string(def.help)?)?;
+        }
+
+        Ok(command_processor)
+    }
+
+    /// Adds several commands, either all of them or none
+    ///
+    /// Every entry is checked before the first one is added, so a failure
+    /// leaves the command table untouched. Existing commands are handled
+    /// according to the duplicate policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `defs` - The commands to add
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If every command was added
+    /// * `Err((index, error))` - The index of the first entry that can't be
+    ///   added and why, `CommandAlreadyExists`, `CommandListFull` or `HelpTooLong`
+    ///
+    pub fn add_commands(
+        &mut self,
+        defs: &[CommandDef<'a, R>],
+    ) -> Result<(), (usize, CommandProcessorError)> {
+        let mut added: usize = 0;
+
+        for (index, def) in defs.iter().enumerate() {
+            help_string::<HELP_STR_SIZE>(def.help).map_err(|e| (index, e))?;
+
+            let registered = self.commands.iter().find(|cmd| cmd.command == def.name);
+            let exists =
+                registered.is_some() || defs.iter().take(index).any(|other| other.name == def.name);
+            let overrides_default = registered.is_some_and(|cmd| cmd.default);
+
+            if !exists {
+                added = added.saturating_add(1);
+                if self.commands.len().saturating_add(added) > NUM_COMMANDS {
+                    return Err((index, CommandProcessorError::CommandListFull));
+                }
+            } else if self.duplicate_policy == DuplicatePolicy::Error && !overrides_default {
+                return Err((index, CommandProcessorError::CommandAlreadyExists));
+            }
+        }
+
+        for (index, def) in defs.iter().enumerate() {
+            self.add_command(
+                def.name,
+                def.callback,
+                help_string(def.help).map_err(|e| (index, e))?,
+            )
+            .map_err(|e| (index, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the maximum number of commands
+    pub fn capacity(&self) -> usize {
+        NUM_COMMANDS
+    }
+}
+
+/// Copies a static help string into the command table's storage
+pub(crate) fn help_string<const HELP_STR_SIZE: usize>(
+    help: Option<&str>,
+) -> Result<Option<String<HELP_STR_SIZE>>, CommandProcessorError> {
+    help.map(|help| help.parse().map_err(|_| CommandProcessorError::HelpTooLong))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::printer_demo;
+    use crate::{CommandProcessor, CommandProcessorError};
+
+    #[test]
+    fn test_from_table() {
+        let command_processor: CommandProcessor<_, 8> =
+            CommandProcessor::from_table(command_table![
+                "a" => printer_demo: "first",
+                "b" => printer_demo,
+                "c" => printer_demo,
+            ])
+            .unwrap();
+
+        assert_eq!(command_processor.capacity(), 3);
+        assert_eq!(command_processor.commands.len(), 3);
+
+        let result: Result<CommandProcessor<_, 8>, _> =
+            CommandProcessor::from_table(command_table![
+                "a" => printer_demo: "longer than eight",
+            ]);
+        assert!(matches!(result, Err(CommandProcessorError::HelpTooLong)));
+
+        let result: Result<CommandProcessor<_, 8>, _> =
+            CommandProcessor::from_table(command_table![
+                "a" => printer_demo,
+                "a" => printer_demo,
+            ]);
+        assert!(matches!(
+            result,
+            Err(CommandProcessorError::CommandAlreadyExists)
+        ));
+    }
+
+    #[test]
+    fn test_add_commands_all_or_nothing() {
+        let mut command_processor: CommandProcessor<3, 8> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("a", printer_demo, None)
+            .is_ok());
+
+        let result = command_processor.add_commands(&command_table![
+            "b" => printer_demo,
+            "c" => printer_demo,
+            "d" => printer_demo,
+        ]);
+        assert!(matches!(
+            result,
+            Err((2, CommandProcessorError::CommandListFull))
+        ));
+        assert_eq!(command_processor.commands.len(), 1);
+
+        let result = command_processor.add_commands(&command_table![
+            "b" => printer_demo,
+            "a" => printer_demo,
+        ]);
+        assert!(matches!(
+            result,
+            Err((1, CommandProcessorError::CommandAlreadyExists))
+        ));
+        assert_eq!(command_processor.commands.len(), 1);
+
+        assert!(command_processor
+            .add_commands(&command_table![
+                "b" => printer_demo,
+                "c" => printer_demo: "third",
+            ])
+            .is_ok());
+        assert_eq!(command_processor.commands.len(), 3);
+    }
+}
@@ -0,0 +1,76 @@
+// This module contains the fixtures shared by the unit tests
+use crate::{Args, Clock, CommandCallbackReturn, CommandProcessorError, ReturnCode};
+
+use core::fmt::Write;
+#[cfg(any(feature = "async", feature = "embassy"))]
+use core::future::Future;
+#[cfg(any(feature = "async", feature = "embassy"))]
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "async", feature = "embassy"))]
+use core::task::{Context, Poll, Waker};
+
+/// Polls a future to completion, spinning while it is pending
+#[cfg(any(feature = "async", feature = "embassy"))]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+/// Returns pending once before completing, like a transfer in progress
+#[cfg(any(feature = "async", feature = "embassy"))]
+pub(crate) struct Transfer(pub(crate) bool);
+
+#[cfg(any(feature = "async", feature = "embassy"))]
+impl Future for Transfer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+        match core::mem::replace(&mut self.0, true) {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// A clock the tests move by hand
+pub(crate) struct TestClock(AtomicU64);
+
+impl TestClock {
+    pub(crate) fn new(now_ms: u64) -> Self {
+        Self(AtomicU64::new(now_ms))
+    }
+
+    pub(crate) fn set(&self, now_ms: u64) {
+        self.0.store(now_ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for TestClock {
+    fn now_ms(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A command doing nothing
+pub(crate) fn printer_demo<'a>(
+    _: Args,
+    _: Option<&mut (dyn Write + 'a)>,
+) -> CommandCallbackReturn<'a> {
+    Ok(ReturnCode::Success)
+}
+
+/// A command writing `hi` on a line
+pub(crate) fn hello<'a>(
+    _: Args,
+    writer: Option<&mut (dyn Write + 'a)>,
+) -> CommandCallbackReturn<'a> {
+    let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+    writeln!(writer, "hi").map_err(|_| CommandProcessorError::WriteError)?;
+    Ok(ReturnCode::Success)
+}
@@ -0,0 +1,287 @@
+// This module contains the splitting of command lines into tokens
+use crate::scratch::check_capacity;
+use crate::CommandProcessorError;
+
+/// Splits a line into its tokens
+///
+/// Tokens are separated by any number of delimiters, whitespace when no
+/// delimiter set is given. Quotes and escapes are not interpreted, this is
+/// meant for names in the configuration, see `Tokens` for received lines.
+pub(crate) fn tokenize<'l>(
+    line: &'l str,
+    delimiters: Option<&'l [char]>,
+) -> impl Iterator<Item = &'l str> + Clone + 'l {
+    line.split(move |c: char| is_delimiter(delimiters, c))
+        .filter(|token| !token.is_empty())
+}
+
+pub(crate) fn is_delimiter(delimiters: Option<&[char]>, c: char) -> bool {
+    match delimiters {
+        Some(delimiters) => delimiters.contains(&c),
+        None => c.is_whitespace(),
+    }
+}
+
+/// Scans a line, tracking quotes and escapes
+///
+/// Calls `stop` with every character outside quotes that isn't escaped,
+/// until it returns `true`. Returns the index it stopped at, or the
+/// length of the line, and whether a quote was left open.
+fn scan(line: &str, mut stop: impl FnMut(char) -> bool) -> (usize, bool) {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(open), _) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, _) if stop(c) => return (index, false),
+            (None, _) => {}
+        }
+    }
+
+    (line.len(), quote.is_some())
+}
+
+/// Returns the line without its trailing comment
+///
+/// Comment characters inside quotes or escaped with `\` are kept.
+pub(crate) fn strip_comment(line: &str, comment: char) -> &str {
+    let (end, _) = scan(line, |c| c == comment);
+    line.get(..end).unwrap_or(line)
+}
+
+/// The tokens of a received line
+///
+/// Tokens are separated by delimiters like with `tokenize`, except inside
+/// double or single quotes, so `"hello world"` is a single token. Quotes can
+/// be nested by using the other kind, as in `"say 'hi'"`. Outside single
+/// quotes, `\` escapes the next character, with `\n` and `\t` standing for a
+/// line feed and a tab. A trailing `\` is kept as is.
+///
+/// Tokens without quotes or escapes are slices of the line, the others are
+/// unescaped into the buffer, which never needs to be longer than the line.
+pub(crate) struct Tokens<'b> {
+    line: &'b str,
+    position: usize,
+    delimiters: Option<&'b [char]>,
+    buffer: &'b mut [u8],
+    unterminated: bool,
+    /// Set once a token didn't fit in the buffer and was returned raw
+    overflowed: bool,
+}
+
+impl<'b> Tokens<'b> {
+    pub(crate) fn new(line: &'b str, delimiters: Option<&'b [char]>, buffer: &'b mut [u8]) -> Self {
+        Self {
+            line,
+            position: 0,
+            delimiters,
+            buffer,
+            unterminated: false,
+            overflowed: false,
+        }
+    }
+
+    /// Checks that the quotes of every token returned so far were closed
+    ///
+    /// With the `strict-capacity` feature, also checks they were unescaped.
+    pub(crate) fn check(&self) -> Result<(), CommandProcessorError> {
+        check_capacity(!self.overflowed, CommandProcessorError::LineTooLong)?;
+        match self.unterminated {
+            true => Err(CommandProcessorError::UnterminatedQuote),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns the offset in the line of the next token, or its length
+    pub(crate) fn offset(&mut self) -> usize {
+        let rest = self.line.get(self.position..).unwrap_or("");
+        let skipped = rest
+            .find(|c: char| !is_delimiter(self.delimiters, c))
+            .unwrap_or(rest.len());
+        self.position = self.position.saturating_add(skipped);
+        self.position
+    }
+
+    /// Returns the next token as it is in the line, without consuming it
+    pub(crate) fn peek_raw(&mut self) -> Option<&'b str> {
+        let start = self.offset();
+        let rest = self.line.get(start..).filter(|rest| !rest.is_empty())?;
+        let delimiters = self.delimiters;
+        let (len, _) = scan(rest, |c| is_delimiter(delimiters, c));
+        rest.get(..len)
+    }
+
+    /// Unescapes a quoted or escaped token into the buffer
+    fn unescape(&mut self, raw: &'b str) -> Option<&'b str> {
+        let buffer = core::mem::take(&mut self.buffer);
+        let mut len = 0usize;
+        let mut quote = None;
+        let mut chars = raw.chars();
+
+        while let Some(c) = chars.next() {
+            let c = match (quote, c) {
+                (Some('\''), '\'') => {
+                    quote = None;
+                    continue;
+                }
+                (Some('\''), _) => c,
+                (_, '\\') => match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(escaped) => escaped,
+                    None => '\\',
+                },
+                (Some(open), _) if c == open => {
+                    quote = None;
+                    continue;
+                }
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    continue;
+                }
+                _ => c,
+            };
+
+            let end = len.checked_add(c.len_utf8())?;
+            c.encode_utf8(buffer.get_mut(len..end)?);
+            len = end;
+        }
+
+        let (token, rest) = buffer.split_at_mut(len);
+        self.buffer = rest;
+        core::str::from_utf8(token).ok()
+    }
+}
+
+impl<'b> Iterator for Tokens<'b> {
+    type Item = &'b str;
+
+    fn next(&mut self) -> Option<&'b str> {
+        let start = self.offset();
+        let rest = self.line.get(start..).filter(|rest| !rest.is_empty())?;
+
+        let delimiters = self.delimiters;
+        let (len, unterminated) = scan(rest, |c| is_delimiter(delimiters, c));
+        self.unterminated |= unterminated;
+        let raw = rest.get(..len).unwrap_or(rest);
+        self.position = start.saturating_add(len);
+
+        match !raw.contains(['"', '\'', '\\']) {
+            true => Some(raw),
+            // Without room in the buffer, the raw token is the best effort
+            false => Some(self.unescape(raw).unwrap_or_else(|| {
+                self.overflowed = true;
+                raw
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split<'b>(
+        line: &'b str,
+        delimiters: Option<&'b [char]>,
+        buffer: &'b mut [u8],
+    ) -> Vec<&'b str> {
+        Tokens::new(line, delimiters, buffer).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<&str> = tokenize("  set  key\tvalue ", None).collect();
+        assert_eq!(tokens, ["set", "key", "value"]);
+
+        let tokens: Vec<&str> = tokenize("set,key,,value", Some(&[','])).collect();
+        assert_eq!(tokens, ["set", "key", "value"]);
+
+        let tokens: Vec<&str> = tokenize("set key,value", Some(&[',', ' '])).collect();
+        assert_eq!(tokens, ["set", "key", "value"]);
+    }
+
+    #[test]
+    fn test_quoted_tokens() {
+        let mut buffer = [0u8; 64];
+
+        let line = r#"echo "hello world" 'say "hi"' "it's" a"b c"d"#;
+        assert_eq!(
+            split(line, None, &mut buffer),
+            ["echo", "hello world", "say \"hi\"", "it's", "ab cd"]
+        );
+
+        let line = r#"echo \"quoted\" a\\b "x\ny" '\n' "" \ end\"#;
+        assert_eq!(
+            split(line, None, &mut buffer),
+            ["echo", "\"quoted\"", "a\\b", "x\ny", "\\n", "", " end\\"]
+        );
+
+        let line = "set,\"a,b\",c";
+        assert_eq!(split(line, Some(&[',']), &mut buffer), ["set", "a,b", "c"]);
+
+        // Tokens without quotes don't use the buffer
+        assert_eq!(split("plain words", None, &mut []), ["plain", "words"]);
+        assert_eq!(split("\"no room\"", None, &mut []), ["\"no room\""]);
+    }
+
+    #[test]
+    fn test_malformed_quotes() {
+        let check = |line: &str| {
+            let mut buffer = [0u8; 32];
+            let mut tokens = Tokens::new(line, None, &mut buffer);
+            tokens.by_ref().for_each(drop);
+            tokens.check()
+        };
+
+        assert!(check(r#"echo "hello 'world'""#).is_ok());
+        assert!(check(r#"echo \"hello"#).is_ok());
+        assert!(matches!(
+            check(r#"echo "hello"#),
+            Err(CommandProcessorError::UnterminatedQuote)
+        ));
+        assert!(matches!(
+            check("echo 'it\\'s'"),
+            Err(CommandProcessorError::UnterminatedQuote)
+        ));
+    }
+
+    #[test]
+    fn test_unescape_overflow() {
+        let mut buffer = [0u8; 4];
+        let mut tokens = Tokens::new("say \"hello\"", None, &mut buffer);
+
+        // The raw token is returned, or rejected with the strict-capacity feature
+        assert_eq!(tokens.nth(1), Some("\"hello\""));
+        match cfg!(feature = "strict-capacity") {
+            true => assert!(matches!(
+                tokens.check(),
+                Err(CommandProcessorError::LineTooLong)
+            )),
+            false => assert!(tokens.check().is_ok()),
+        }
+    }
+
+    #[test]
+    fn test_strip_comment() {
+        assert_eq!(strip_comment("set a # b", '#'), "set a ");
+        assert_eq!(strip_comment("set \"a # b\" # c", '#'), "set \"a # b\" ");
+        assert_eq!(strip_comment("set \\# # c", '#'), "set \\# ");
+    }
+
+    #[test]
+    fn test_offset() {
+        let line = "publish topic  a \"b\"  # c ";
+        let mut tokens = Tokens::new(line, None, &mut []);
+        tokens.nth(1);
+
+        assert_eq!(line.get(tokens.offset()..), Some("a \"b\"  # c "));
+    }
+}
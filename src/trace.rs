@@ -0,0 +1,210 @@
+// This module contains the trace of the recent dispatches and the `trace` built-in
+use crate::tokenizer::Tokens;
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::Write;
+use heapless::String;
+
+/// The number of dispatches kept in the trace, older ones being dropped
+pub const TRACE_DEPTH: usize = 8;
+
+/// The maximum size of a command name in the trace, longer ones being cut
+const TRACE_NAME_SIZE: usize = 16;
+
+/// A dispatch kept in the trace
+pub(crate) struct TraceRecord {
+    /// When the command started, if a clock is set
+    at_ms: Option<u64>,
+    name: String<TRACE_NAME_SIZE>,
+    /// Whether the command succeeded, or the description of the processor's error
+    outcome: Result<bool, &'static str>,
+    duration_ms: Option<u64>,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Writes the trace of the last `TRACE_DEPTH` dispatches, oldest first
+    ///
+    /// Every line processed is traced with its command name, without its
+    /// arguments, and its result. With a clock, see `set_clock`, the start
+    /// time and duration are traced too. The trace doesn't depend on the
+    /// commands' output, so it can be written after a failure to see what
+    /// the console was asked to do, from a fault handler for instance. The
+    /// `trace` built-in writes it, and `trace clear` clears it. A command
+    /// registered as `trace` takes precedence over the built-in.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The writer to write the trace to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the trace was written successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the trace failed to write
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use core::fmt::Write;
+    /// use command_processor::{Args, CommandCallbackReturn, CommandProcessor, ReturnCode};
+    ///
+    /// fn led<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+    ///     Ok(ReturnCode::Success)
+    /// }
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// command_processor.add_command("led", led, None).unwrap();
+    /// command_processor.process_command("led on", None).unwrap();
+    /// let _ = command_processor.process_command("reboot", None);
+    ///
+    /// let mut writer = String::new();
+    /// command_processor.write_trace(&mut writer).unwrap();
+    /// assert_eq!(writer, "led: OK\nreboot: ERR: command not found\n");
+    /// ```
+    ///
+    pub fn write_trace(&self, writer: &mut dyn Write) -> Result<(), CommandProcessorError> {
+        for record in &self.trace {
+            if let Some(at_ms) = record.at_ms {
+                let duration_ms = record.duration_ms.unwrap_or(0);
+                self.output
+                    .write(writer, format_args!("@{} ({}ms) ", at_ms, duration_ms))?;
+            }
+            match record.outcome {
+                Ok(true) => self.output.write(
+                    writer,
+                    format_args!("{}: {}\n", record.name, self.messages.ok),
+                ),
+                Ok(false) => self.output.write(
+                    writer,
+                    format_args!("{}: {}\n", record.name, self.messages.error),
+                ),
+                Err(message) => self.output.write(
+                    writer,
+                    format_args!("{}: {}: {}\n", record.name, self.messages.error, message),
+                ),
+            }?;
+        }
+        Ok(())
+    }
+
+    /// Clears the trace, like `trace clear`
+    pub fn clear_trace(&mut self) {
+        self.trace.clear();
+    }
+
+    /// Adds a processed line to the trace
+    pub(crate) fn record_trace(
+        &mut self,
+        line: &str,
+        started_ms: Option<u64>,
+        result: &Result<R, CommandProcessorError>,
+    ) {
+        let mut path: String<LINE_SIZE> = String::new();
+        let mut buffer = [0u8; LINE_SIZE];
+        let mut tokens = Tokens::new(self.strip_comment(line), self.delimiters, &mut buffer);
+        let Some(name) = tokens.next() else {
+            return;
+        };
+        let name = match self.is_group(name) {
+            true => {
+                let _ = self.resolve_subcommand(name, &mut tokens, &mut path, None);
+                path.as_str()
+            }
+            false => name,
+        };
+
+        // Names that don't fit are cut, the start is enough to tell them apart
+        let mut copy = String::new();
+        let _ = name.chars().try_for_each(|c| copy.push(c));
+        let record = TraceRecord {
+            at_ms: started_ms,
+            name: copy,
+            outcome: match result {
+                Ok(code) => Ok(code.is_success()),
+                Err(e) => Err(e.message(self.messages)),
+            },
+            duration_ms: started_ms.and_then(|started| {
+                let now = self.clock?.now_ms();
+                Some(now.saturating_sub(started))
+            }),
+        };
+
+        if self.trace.is_full() {
+            self.trace.pop_front();
+        }
+        let _ = self.trace.push_back(record);
+    }
+
+    /// Runs the trace built-in, see `write_trace`
+    pub(crate) fn trace(
+        &mut self,
+        mut tokens: Tokens,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let subcommand = tokens.next();
+        if tokens.next().is_some() {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        tokens.check()?;
+
+        match subcommand {
+            None => self.write_trace(writer.ok_or(CommandProcessorError::NoWriter)?)?,
+            Some("clear") => self.clear_trace(),
+            Some(_) => return Err(CommandProcessorError::InvalidArguments),
+        }
+        Ok(ReturnCode::Success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestClock;
+    use crate::{Args, CommandCallbackReturn};
+
+    fn fail<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Failure)
+    }
+
+    #[test]
+    fn test_trace() {
+        let clock = TestClock::new(100);
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        command_processor.set_clock(&clock);
+        assert!(command_processor
+            .add_command("net ping", fail, None)
+            .is_ok());
+
+        assert!(command_processor
+            .process_command("net ping host", None)
+            .is_ok());
+        for _ in 0..TRACE_DEPTH {
+            assert!(command_processor.process_command("let x 1", None).is_ok());
+        }
+        let mut writer = std::string::String::new();
+        assert!(command_processor
+            .process_command("trace", Some(&mut writer))
+            .is_ok());
+        // The oldest dispatch was dropped
+        assert_eq!(writer.lines().count(), TRACE_DEPTH);
+        assert!(!writer.contains("net ping"));
+
+        assert!(command_processor
+            .process_command("trace clear", None)
+            .is_ok());
+        assert!(command_processor
+            .process_command("net ping host", None)
+            .is_ok());
+        writer.clear();
+        assert!(command_processor.write_trace(&mut writer).is_ok());
+        assert_eq!(writer, "@100 (0ms) trace: OK\n@100 (0ms) net ping: ERR\n");
+    }
+}
@@ -0,0 +1,282 @@
+// This module contains the guided tutorial mode
+use crate::tokenizer::tokenize;
+use crate::{CommandProcessor, CommandProcessorError, ReturnCode, Status};
+
+use core::fmt::Write;
+
+/// A step of a tutorial
+///
+/// The operator is shown the prompt and the step is complete once `command`
+/// is processed and returns `expected`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TutorialStep {
+    pub prompt: &'static str,
+    pub command: &'static str,
+    pub expected: ReturnCode,
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Starts a tutorial guiding the operator through a sequence of commands
+    ///
+    /// The prompt of the first step is written immediately. Each processed
+    /// command is then checked against the current step, advancing to the
+    /// next step when it returns the expected code. The `tutorial` built-in
+    /// repeats the current prompt and `tutorial stop` ends the tutorial.
+    ///
+    /// # Arguments
+    ///
+    /// * `steps` - The steps of the tutorial
+    /// * `writer` - The writer to write the first prompt to
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the tutorial was started successfully
+    /// * `Err(CommandProcessorError::WriteError)` - If the first prompt failed to write
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use command_processor::{CommandProcessor, ReturnCode, TutorialStep};
+    ///
+    /// static REWORK: [TutorialStep; 1] = [TutorialStep {
+    ///     prompt: "Run the self test",
+    ///     command: "selftest",
+    ///     expected: ReturnCode::Success,
+    /// }];
+    ///
+    /// let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+    /// let mut writer = String::new();
+    ///
+    /// command_processor.start_tutorial(&REWORK, &mut writer).unwrap();
+    ///
+    /// assert_eq!(writer, "step 1/1: Run the self test\n");
+    /// ```
+    ///
+    pub fn start_tutorial(
+        &mut self,
+        steps: &'a [TutorialStep],
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        self.tutorial = Some((steps, 0));
+        self.write_tutorial_prompt(writer)
+    }
+
+    /// Stops the running tutorial, if any
+    pub fn stop_tutorial(&mut self) {
+        self.tutorial = None;
+    }
+
+    /// Returns the index of the current tutorial step, if a tutorial is running
+    pub fn tutorial_step(&self) -> Option<usize> {
+        self.tutorial.map(|(_, step)| step)
+    }
+
+    pub(crate) fn tutorial(
+        &mut self,
+        argument: Option<&str>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        match argument {
+            Some("stop") => {
+                self.stop_tutorial();
+                Ok(ReturnCode::Success)
+            }
+            Some(_) => Err(CommandProcessorError::UnexpectedArgument),
+            None => {
+                let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+                match self.tutorial {
+                    Some(_) => self.write_tutorial_prompt(writer)?,
+                    None => self.output.write(
+                        writer,
+                        format_args!("{}\n", self.messages.tutorial_inactive),
+                    )?,
+                }
+                Ok(ReturnCode::Success)
+            }
+        }
+    }
+
+    pub(crate) fn advance_tutorial(
+        &mut self,
+        name: &str,
+        result: &Result<R, CommandProcessorError>,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<(), CommandProcessorError> {
+        let (steps, step) = match self.tutorial {
+            Some(tutorial) => tutorial,
+            None => return Ok(()),
+        };
+
+        let current = match steps.get(step) {
+            Some(current) => current,
+            None => {
+                self.tutorial = None;
+                return Ok(());
+            }
+        };
+
+        // Only the step's command is checked, so operators can look around
+        if tokenize(current.command, self.delimiters).next() != Some(name) {
+            return Ok(());
+        }
+
+        let expected = current.expected.is_success();
+        let passed = matches!(result, Ok(code) if code.is_success() == expected);
+        if passed {
+            self.tutorial = Some((steps, step.saturating_add(1)));
+        }
+
+        let writer = match writer {
+            Some(writer) => writer,
+            None => return Ok(()),
+        };
+
+        if !passed {
+            self.output
+                .write(writer, format_args!("{}: ", self.messages.tutorial_retry))?;
+        }
+
+        self.write_tutorial_prompt(writer)
+    }
+
+    fn write_tutorial_prompt(
+        &mut self,
+        writer: &mut dyn Write,
+    ) -> Result<(), CommandProcessorError> {
+        let (steps, step) = match self.tutorial {
+            Some(tutorial) => tutorial,
+            None => return Ok(()),
+        };
+
+        match steps.get(step) {
+            Some(current) => self.output.write(
+                writer,
+                format_args!(
+                    "{} {}/{}: {}\n",
+                    self.messages.tutorial_step,
+                    step.saturating_add(1),
+                    steps.len(),
+                    current.prompt
+                ),
+            ),
+            None => {
+                self.tutorial = None;
+                self.output.write(
+                    writer,
+                    format_args!("{}\n", self.messages.tutorial_complete),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Args, CommandCallbackReturn, CommandProcessor, CommandProcessorError, ReturnCode};
+    use core::fmt::Write;
+    use heapless::String;
+
+    use super::TutorialStep;
+
+    fn success<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Success)
+    }
+
+    fn failure<'a>(_: Args, _: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        Ok(ReturnCode::Failure)
+    }
+
+    static STEPS: [TutorialStep; 2] = [
+        TutorialStep {
+            prompt: "Unplug the probe and run `probe`",
+            command: "probe",
+            expected: ReturnCode::Failure,
+        },
+        TutorialStep {
+            prompt: "Run `selftest`",
+            command: "selftest",
+            expected: ReturnCode::Success,
+        },
+    ];
+
+    #[test]
+    fn test_tutorial() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("probe"), failure, None)
+            .is_ok());
+        assert!(command_processor
+            .add_command(String::from("selftest"), success, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .start_tutorial(&STEPS, &mut buffer)
+            .is_ok());
+        assert_eq!(buffer, "step 1/2: Unplug the probe and run `probe`\n");
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("probe", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "step 2/2: Run `selftest`\n");
+        assert_eq!(command_processor.tutorial_step(), Some(1));
+
+        // Unrelated commands don't affect the tutorial
+        buffer.clear();
+        assert!(command_processor
+            .process_command("probe", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "");
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("selftest", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(buffer, "tutorial complete\n");
+        assert_eq!(command_processor.tutorial_step(), None);
+    }
+
+    #[test]
+    fn test_tutorial_retry_and_stop() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+
+        assert!(command_processor
+            .add_command(String::from("probe"), success, None)
+            .is_ok());
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .start_tutorial(&STEPS, &mut buffer)
+            .is_ok());
+
+        buffer.clear();
+        assert!(command_processor
+            .process_command("probe", Some(&mut buffer))
+            .is_ok());
+        assert_eq!(
+            buffer,
+            "step failed, try again: step 1/2: Unplug the probe and run `probe`\n"
+        );
+
+        assert!(command_processor
+            .process_command("tutorial stop", None)
+            .is_ok());
+        assert_eq!(command_processor.tutorial_step(), None);
+
+        assert!(matches!(
+            command_processor.process_command("tutorial now", None),
+            Err(CommandProcessorError::UnexpectedArgument)
+        ));
+    }
+}
@@ -0,0 +1,272 @@
+// This module contains the session variables set with `let`
+use crate::{
+    output::Output, CommandProcessor, CommandProcessorError, ReturnCode, SliceWriter, Status,
+    Tokens,
+};
+
+use core::fmt::Write;
+use heapless::{String, Vec};
+
+/// The maximum number of session variables
+pub const MAX_VARIABLES: usize = 8;
+
+/// The maximum size of a session variable's name
+pub const VARIABLE_NAME_SIZE: usize = 16;
+
+/// The maximum size of a session variable's value
+pub const VARIABLE_VALUE_SIZE: usize = 32;
+
+/// The size of the buffer holding the values expanded in a line
+pub(crate) const EXPANSION_BUFFER_SIZE: usize = 64;
+
+/// The variables set during a session
+#[derive(Default)]
+pub(crate) struct Variables {
+    entries: Vec<(String<VARIABLE_NAME_SIZE>, String<VARIABLE_VALUE_SIZE>), MAX_VARIABLES>,
+}
+
+impl Variables {
+    /// Returns the variables, in no particular order
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets a variable, or removes it if there is no value
+    pub(crate) fn set(
+        &mut self,
+        name: &str,
+        value: Option<&str>,
+    ) -> Result<(), CommandProcessorError> {
+        let position = self.entries.iter().position(|(key, _)| key == name);
+
+        let Some(value) = value else {
+            if let Some(position) = position {
+                self.entries.swap_remove(position);
+            }
+            return Ok(());
+        };
+
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let name = copy(name).filter(|_| valid);
+        let (Some(name), Some(value)) = (name, copy(value)) else {
+            return Err(CommandProcessorError::InvalidArguments);
+        };
+
+        match position.and_then(|position| self.entries.get_mut(position)) {
+            Some(entry) => entry.1 = value,
+            None => self
+                .entries
+                .push((name, value))
+                .map_err(|_| CommandProcessorError::TooManyVariables)?,
+        }
+        Ok(())
+    }
+
+    /// Replaces a `$NAME` token by the variable's value
+    ///
+    /// The value is copied into the head of the buffer, which then points to
+    /// the rest, so the returned tokens don't borrow the variables.
+    pub(crate) fn expand<'b>(
+        &self,
+        token: &'b str,
+        buffer: &mut &'b mut [u8],
+    ) -> Result<&'b str, CommandProcessorError> {
+        let Some(name) = token.strip_prefix('$').filter(|name| !name.is_empty()) else {
+            return Ok(token);
+        };
+        let value = self
+            .get(name)
+            .ok_or(CommandProcessorError::UnknownVariable)?;
+
+        if value.len() > buffer.len() {
+            return Err(CommandProcessorError::LineTooLong);
+        }
+        let (head, rest) = core::mem::take(buffer).split_at_mut(value.len());
+        head.copy_from_slice(value.as_bytes());
+        *buffer = rest;
+
+        core::str::from_utf8(head).map_err(|_| CommandProcessorError::InvalidArguments)
+    }
+}
+
+impl<
+        'a,
+        const NUM_COMMANDS: usize,
+        const HELP_STR_SIZE: usize,
+        const NAME_SIZE: usize,
+        const LINE_SIZE: usize,
+        C,
+        R: Status,
+    > CommandProcessor<'a, NUM_COMMANDS, HELP_STR_SIZE, NAME_SIZE, LINE_SIZE, C, R>
+{
+    /// Returns the value of a session variable
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the variable
+    ///
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables.get(name)
+    }
+
+    /// Removes every session variable, when a new session starts
+    pub fn clear_variables(&mut self) {
+        self.variables = Variables::default();
+    }
+
+    /// Runs the command of a `NAME=$(command)` line, storing its output
+    ///
+    /// The output is stored without its trailing line break, and like other
+    /// values is limited to `VARIABLE_VALUE_SIZE` bytes. Output that doesn't
+    /// fit is truncated, or fails to write with the `strict-capacity`
+    /// feature. The variable is left untouched if the command fails.
+    pub(crate) fn capture(
+        &mut self,
+        name: &str,
+        command: &str,
+    ) -> Result<R, CommandProcessorError> {
+        let mut output = SliceWriter::new([0u8; VARIABLE_VALUE_SIZE]);
+        let code = self.dispatch(command, Some(&mut output))?;
+
+        let value = output.as_str().trim_end_matches(['\r', '\n']);
+        self.variables.set(name, Some(value))?;
+        Ok(code)
+    }
+
+    /// Sets, removes or lists session variables
+    ///
+    /// `let NAME value` sets a variable, `let NAME` removes it and `let`
+    /// alone lists them. The value can itself be a `$NAME` to copy.
+    pub(crate) fn let_variable(
+        &mut self,
+        mut tokens: Tokens,
+        writer: Option<&mut (dyn Write + 'a)>,
+    ) -> Result<ReturnCode, CommandProcessorError> {
+        let Some(name) = tokens.next() else {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            return list(&self.output, &self.variables, writer);
+        };
+
+        let mut buffer = [0u8; VARIABLE_VALUE_SIZE];
+        let value = match tokens.next() {
+            Some(value) => Some(self.variables.expand(value, &mut buffer.as_mut_slice())?),
+            None => None,
+        };
+        if tokens.next().is_some() {
+            return Err(CommandProcessorError::UnexpectedArgument);
+        }
+        tokens.check()?;
+
+        self.variables.set(name, value)?;
+        Ok(ReturnCode::Success)
+    }
+}
+
+/// Splits a `NAME=$(command)` line into the name and the command
+pub(crate) fn parse_capture(line: &str) -> Option<(&str, &str)> {
+    let (name, rest) = line.trim().split_once("=$(")?;
+    let command = rest.strip_suffix(')')?;
+    Some((name, command)).filter(|_| !name.contains(char::is_whitespace))
+}
+
+/// Copies a string, if it fits
+fn copy<const N: usize>(s: &str) -> Option<String<N>> {
+    let mut copy = String::new();
+    copy.push_str(s).ok()?;
+    Some(copy)
+}
+
+fn list(
+    output: &Output,
+    variables: &Variables,
+    writer: &mut dyn Write,
+) -> Result<ReturnCode, CommandProcessorError> {
+    for (name, value) in &variables.entries {
+        output.write(writer, format_args!("{}={}\n", name, value))?;
+    }
+    Ok(ReturnCode::Success)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Args, CommandCallbackReturn};
+
+    fn echo<'a>(args: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+        let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+        writeln!(writer, "{:?}", args.remaining())
+            .map_err(|_| CommandProcessorError::WriteError)?;
+        Ok(ReturnCode::Success)
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+
+        let mut buffer = std::string::String::new();
+        let mut run = |line: &str| command_processor.process_command(line, Some(&mut buffer));
+
+        assert!(run("let offset -12").is_ok());
+        assert!(run("let copy $offset").is_ok());
+        assert!(run("echo $offset \"$copy\" $").is_ok());
+        assert!(matches!(
+            run("echo $missing"),
+            Err(CommandProcessorError::UnknownVariable)
+        ));
+        assert!(matches!(
+            run("let 2x 1"),
+            Err(CommandProcessorError::InvalidArguments)
+        ));
+        assert!(run("let copy").is_ok());
+        assert!(run("let").is_ok());
+        assert_eq!(buffer, "[\"-12\", \"-12\", \"$\"]\noffset=-12\n");
+
+        command_processor.clear_variables();
+        assert_eq!(command_processor.variable("offset"), None);
+    }
+
+    #[test]
+    fn test_capture() {
+        fn readid<'a>(_: Args, writer: Option<&mut (dyn Write + 'a)>) -> CommandCallbackReturn<'a> {
+            let writer = writer.ok_or(CommandProcessorError::NoWriter)?;
+            writeln!(writer, "0x1234").map_err(|_| CommandProcessorError::WriteError)?;
+            Ok(ReturnCode::Success)
+        }
+
+        let mut command_processor: CommandProcessor<8, 32> = CommandProcessor::new();
+        assert!(command_processor
+            .add_command("readid", readid, None)
+            .is_ok());
+        assert!(command_processor.add_command("echo", echo, None).is_ok());
+
+        assert_eq!(parse_capture(" ID=$(readid) "), Some(("ID", "readid")));
+        assert_eq!(parse_capture("echo ID=$(readid)"), None);
+
+        let mut buffer = std::string::String::new();
+        assert!(command_processor
+            .process_command("ID=$(readid)", Some(&mut buffer))
+            .is_ok());
+        assert!(command_processor
+            .process_command("X=$(echo $ID 2)", Some(&mut buffer))
+            .is_ok());
+        assert!(matches!(
+            command_processor.process_command("ID=$(nothing)", None),
+            Err(CommandProcessorError::CommandNotFound)
+        ));
+
+        assert_eq!(buffer, "");
+        assert_eq!(command_processor.variable("ID"), Some("0x1234"));
+        assert_eq!(command_processor.variable("X"), Some("[\"0x1234\", \"2\"]"));
+    }
+}